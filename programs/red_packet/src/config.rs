@@ -1,16 +1,33 @@
+use crate::{BundlePacket, CreatorState, CreatorStateV0, ProtocolConfig, RedPacket, RedPacketV0};
 use anchor_lang::prelude::*;
 
+// 初始化 ProtocolConfig 时的默认费用和收款地址，之后由管理员修改
 pub const CLAIM_FEE: u64 = 1_000_000; // 0.001 SOL
 pub const CREATE_FEE: u64 = 5_000_000; // 0.005 SOL
 pub const MAX_PROOF_LENGTH: usize = 32;
 pub const DEFAULT_RED_PACKET_EXPIRY_DAYS: i64 = 7;
 pub const MAX_EXPIRY_TIME_CHANGES: u8 = 3;
-pub const FEE_RECEIVER_SEED: &[u8] = b"fee_receiver";
+pub const MAX_START_DELAY: i64 = 30 * 24 * 60 * 60; // 开始时间最多提前 30 天预告
+pub const MAX_CANCEL_GRACE_PERIOD: i64 = 60 * 60; // 取消后最多再给已发出的领取 1 小时
+pub const PROTOCOL_CONFIG_SEED: &[u8] = b"protocol_config";
 pub const CREATOR_STATE_SEED: &[u8] = b"creator_state";
-pub const RED_PACKET_SPACE: usize =
-    8 + 32 + 32 + 8 + 8 + 4 + 4 + 1 + 8 + 8 + 8 + 32 + 1 + 1 + 8 + 1;
+pub const CLAIM_BITMAP_SEED: &[u8] = b"claim_bitmap";
+pub const CLAIM_COMMIT_SEED: &[u8] = b"claim_commit";
+pub const BUNDLE_SEED: &[u8] = b"bundle";
+pub const MAX_CLAIM_CODE_LENGTH: usize = 64;
+pub const RED_PACKET_SPACE: usize = 8 + RedPacket::INIT_SPACE;
+pub const LEGACY_RED_PACKET_SPACE: usize = 8 + RedPacketV0::INIT_SPACE; // 版本号引入之前的账户大小
 pub const USER_STATE_SPACE: usize = 8 + 1;
-pub const FEE_VAULT_SPACE: usize = 8 + 8;
-pub const CREATOR_STATE_SPACE: usize = 8 + 8 + 1;
+pub const CLAIM_COMMIT_SPACE: usize = 8 + 32 + 8;
+pub const BUNDLE_PACKET_SPACE: usize = 8 + BundlePacket::INIT_SPACE;
+pub const PROTOCOL_CONFIG_SPACE: usize = 8 + ProtocolConfig::INIT_SPACE;
+pub const CREATOR_STATE_SPACE: usize = 8 + CreatorState::INIT_SPACE;
+pub const LEGACY_CREATOR_STATE_SPACE: usize = 8 + CreatorStateV0::INIT_SPACE; // 引入操作员和版本号之前的账户大小
+pub const TOKEN_METADATA_PROGRAM_ID: Pubkey =
+    pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
 pub const FEE_RECEIVER: Pubkey = pubkey!("15hPXzWgid1UWUKnp4KvtZEbaNUCWkPK79cb5uqHysf");
 pub const MAX_PACKET_COUNT: u32 = 100000;
+pub const MAX_USER_STATE_SWEEP: usize = 20;
+pub const MAX_OPERATORS: usize = 5;
+pub const MAX_PRIZE_TIERS: usize = 4;
+pub const MAX_BUNDLE_LEGS: usize = 4;
@@ -3,7 +3,7 @@ use anchor_lang::prelude::*;
 #[error_code]
 pub enum RedPacketError {
     /* 参数校验类错误 (4xx) */
-    #[msg("Invalid red packet type (must be 0-2)")]
+    #[msg("Invalid red packet type (must be 0-7)")]
     InvalidRedPacketType,
     #[msg("Claim amount out of valid range")]
     InvalidClaimAmount,
@@ -91,4 +91,127 @@ pub enum RedPacketError {
     ArithmeticOverflow,
     #[msg("Invalid fee calculation")]
     FeeCalculationError,
+
+    /* Anchor 按声明顺序给错误编号，已发布的错误码不能变，之后新增的错误一律追加在这里 */
+    /* 追加的参数校验类错误 */
+    #[msg("Start time must be within 30 days and before the expiry time")]
+    InvalidStartTime,
+    #[msg("Top-up amount must be greater than zero")]
+    InvalidTopUpAmount,
+    #[msg("Cancel grace period must be 0-3600 seconds")]
+    InvalidGracePeriod,
+    #[msg(
+        "Prize tiers must be 1-4 non-empty tiers that add up to the packet count and total amount"
+    )]
+    InvalidPrizeTiers,
+    #[msg("Bundle needs a SOL leg or 1-4 distinct token legs with non-zero amounts")]
+    InvalidBundleLegs,
+
+    /* 追加的状态校验类错误 */
+    #[msg("Red packet has not started yet")]
+    RedPacketNotStarted,
+    #[msg("Red packet still has unclaimed packets")]
+    RedPacketNotDrained,
+    #[msg("Red packet is still claimable")]
+    RedPacketStillActive,
+    #[msg("Account already uses the current layout")]
+    RedPacketAlreadyMigrated,
+    #[msg("Start time can only be changed before the red packet starts")]
+    RedPacketAlreadyStarted,
+    #[msg("Merkle and tiered red packets cannot be topped up")]
+    TopUpNotSupported,
+    #[msg("Fully claimed red packets cannot be topped up")]
+    RedPacketFullyClaimed,
+    #[msg("Red packet has been cancelled")]
+    RedPacketCancelled,
+
+    /* 追加的权限类错误 */
+    #[msg("Account is not a red packet")]
+    InvalidRedPacketAccount,
+    #[msg("Only the protocol admin can do this")]
+    NotProtocolAdmin,
+    #[msg("Only the pending admin can accept the admin role")]
+    NotPendingAdmin,
+    #[msg("Only the program upgrade authority can do this")]
+    NotUpgradeAuthority,
+    #[msg("Program is paused")]
+    ProgramPaused,
+    #[msg("Unknown pause flags")]
+    InvalidPauseFlags,
+    #[msg("Unknown mint risk policy flags")]
+    InvalidMintRiskPolicy,
+    #[msg("Invalid fee configuration")]
+    InvalidFeeConfig,
+    #[msg("Fee receiver does not match the protocol config")]
+    InvalidFeeReceiver,
+    #[msg("Invalid holder gate configuration")]
+    InvalidHolderGate,
+    #[msg("Operator permissions must be a non-empty subset of the known permissions")]
+    InvalidOperatorPermissions,
+    #[msg("Too many operators")]
+    TooManyOperators,
+    #[msg("Operator not found")]
+    OperatorNotFound,
+    #[msg("Holder gate account is missing")]
+    MissingGateAccount,
+    #[msg("Claimer does not meet the holder gate requirement")]
+    HolderGateNotMet,
+
+    /* 追加的加密验证类错误 */
+    #[msg("Invalid SlotHashes sysvar account")]
+    InvalidSlotHashes,
+    #[msg("Missing ed25519 signature instruction")]
+    MissingEd25519Instruction,
+    #[msg("Ed25519 signature does not authorize this claim")]
+    InvalidEd25519Instruction,
+    #[msg("Code hash is required for code-gated red packets")]
+    CodeHashRequired,
+    #[msg("Claim code is required")]
+    MissingClaimCode,
+    #[msg("Claim code is invalid or too long")]
+    InvalidClaimCode,
+    #[msg("A claim commitment from an earlier slot is required")]
+    MissingClaimCommit,
+    #[msg("Revealed code does not match the claim commitment")]
+    InvalidClaimCommit,
+    #[msg("Claim code must be revealed in a later slot than the commitment")]
+    ClaimCommitTooRecent,
+    #[msg("Random red packets accept only one claim commitment")]
+    ClaimCommitExists,
+    #[msg("Slot-hash random claims cannot be made through CPI")]
+    CpiClaimNotAllowed,
+
+    /* 追加的账户类错误 */
+    #[msg("Invalid user state account")]
+    InvalidUserState,
+    #[msg("Invalid creator state account")]
+    InvalidCreatorState,
+    #[msg("Invalid protocol config account")]
+    InvalidProtocolConfig,
+    #[msg("Invalid claim bitmap account")]
+    InvalidClaimBitmap,
+    #[msg("Claim bitmap is not fully allocated yet")]
+    ClaimBitmapNotReady,
+    #[msg("Claim index out of range")]
+    InvalidClaimIndex,
+    #[msg("Invalid remaining accounts")]
+    InvalidRemainingAccounts,
+    #[msg("Recipient account does not match recipient")]
+    InvalidRecipientAccount,
+    #[msg("SOL share is below the rent-exempt minimum of an empty recipient")]
+    ShareBelowRentExemption,
+    #[msg("Transfer fee is not supported")]
+    TransferFeeNotSupported,
+    #[msg("Token mint is paused")]
+    MintPaused,
+    #[msg("Mints that freeze new token accounts by default are not supported")]
+    DefaultFrozenNotSupported,
+    #[msg("Mints with a freeze authority are rejected by the protocol policy")]
+    FreezeAuthorityNotAllowed,
+    #[msg("Mints with a close authority are rejected by the protocol policy")]
+    CloseAuthorityNotAllowed,
+    #[msg("Claim commit slot is no longer in SlotHashes, close it and commit again")]
+    ClaimCommitExpired,
+    #[msg("Claim commit can still be revealed")]
+    ClaimCommitNotExpired,
 }
@@ -1,3 +1,4 @@
+use crate::{BundleLeg, FeeRule, HolderGate};
 use anchor_lang::prelude::*;
 
 #[event]
@@ -16,6 +17,10 @@ pub struct RedPacketCreated {
     pub has_transfer_hook: bool,
     pub has_permanent_delegate: bool,
     pub has_close_authority: bool,
+    // 以下字段追加在原有字段之后，旧的解析器按前缀读取仍然正确
+    pub start_time: i64,
+    pub holder_gate: HolderGate,
+    pub has_freeze_authority: bool,
 }
 
 #[event]
@@ -24,6 +29,9 @@ pub struct RedPacketClaimed {
     pub red_packet: Pubkey,
     pub amount: u64,
     pub red_packet_id: u64,
+    pub net_amount: u64,
+    pub transfer_fee: u64,
+    pub tier: Option<u8>, // 分档红包抽中的奖项下标
 }
 
 #[event]
@@ -32,6 +40,16 @@ pub struct RedPacketRefunded {
     pub red_packet: Pubkey,
     pub amount: u64,
     pub red_packet_id: u64,
+    pub net_amount: u64,
+    pub transfer_fee: u64,
+}
+
+#[event]
+pub struct RedPacketCancelled {
+    pub creator: Pubkey,
+    pub red_packet: Pubkey,
+    pub red_packet_id: u64,
+    pub claims_end_time: i64,
 }
 
 #[event]
@@ -40,3 +58,117 @@ pub struct ExpiryTimeUpdated {
     pub new_expiry_time: i64,
     pub red_packet_id: u64,
 }
+
+#[event]
+pub struct StartTimeUpdated {
+    pub red_packet: Pubkey,
+    pub new_start_time: i64,
+    pub red_packet_id: u64,
+}
+
+#[event]
+pub struct RedPacketClosed {
+    pub creator: Pubkey,
+    pub red_packet: Pubkey,
+    pub leftover_amount: u64,
+    pub red_packet_id: u64,
+}
+
+#[event]
+pub struct UserStatesClosed {
+    pub red_packet: Pubkey,
+    pub closed_count: u32,
+    pub reclaimed_lamports: u64,
+}
+
+#[event]
+pub struct ProtocolConfigUpdated {
+    pub admin: Pubkey,
+    pub fee_receiver: Pubkey,
+    pub create_fee: FeeRule,
+    pub claim_fee: FeeRule,
+    pub pause_flags: u8,
+    pub mint_risk_policy: u8,
+}
+
+#[event]
+pub struct PauseUpdated {
+    pub admin: Pubkey,
+    pub previous_flags: u8,
+    pub pause_flags: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminTransferProposed {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+}
+
+#[event]
+pub struct AdminTransferred {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+#[event]
+pub struct RedPacketMigrated {
+    pub red_packet: Pubkey,
+    pub red_packet_id: u64,
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+#[event]
+pub struct RedPacketToppedUp {
+    pub creator: Pubkey,
+    pub authority: Pubkey,
+    pub red_packet: Pubkey,
+    pub red_packet_id: u64,
+    pub amount: u64,
+    pub extra_packets: u32,
+    pub total_amount: u64,
+    pub remaining_amount: u64,
+    pub packet_count: u32,
+    pub share_amount: u64,
+    pub transfer_fee: u64,
+    pub top_up_fee: u64,
+}
+
+/// permissions 为 0 表示操作员已被移除
+#[event]
+pub struct OperatorUpdated {
+    pub creator: Pubkey,
+    pub operator: Pubkey,
+    pub permissions: u8,
+}
+
+#[event]
+pub struct BundleCreated {
+    pub creator: Pubkey,
+    pub bundle: Pubkey,
+    pub bundle_id: u64,
+    pub packet_count: u32,
+    pub sol_per_claim: u64,
+    pub legs: Vec<BundleLeg>,
+    pub expiry_time: i64,
+}
+
+/// token_amounts 与 legs 顺序一致，是每种代币从池子转出的数量（含转账费）
+#[event]
+pub struct BundleClaimed {
+    pub claimer: Pubkey,
+    pub bundle: Pubkey,
+    pub bundle_id: u64,
+    pub sol_amount: u64,
+    pub token_amounts: Vec<u64>,
+}
+
+#[event]
+pub struct BundleRefunded {
+    pub creator: Pubkey,
+    pub bundle: Pubkey,
+    pub bundle_id: u64,
+    pub sol_amount: u64,
+    pub token_amounts: Vec<u64>,
+}
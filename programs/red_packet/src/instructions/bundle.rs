@@ -0,0 +1,495 @@
+use crate::{
+    config::{
+        self, BUNDLE_PACKET_SPACE, BUNDLE_SEED, CREATOR_STATE_SEED, DEFAULT_RED_PACKET_EXPIRY_DAYS,
+        MAX_BUNDLE_LEGS, MAX_PACKET_COUNT, PROTOCOL_CONFIG_SEED,
+    },
+    events::{BundleClaimed, BundleCreated, BundleRefunded},
+    mint_info::{MintInfo, MintUsage},
+    BundleLeg, BundlePacket, CreatorState, ProtocolConfig, RedPacketError, UserState,
+};
+use anchor_lang::{prelude::*, system_program};
+use anchor_spl::{
+    associated_token::{get_associated_token_address_with_program_id, AssociatedToken},
+    token_interface::{self, TransferChecked},
+};
+use spl_token_2022::{extension::StateWithExtensions, state::Account as TokenAccount2022};
+
+/// 每种代币在 remaining_accounts 里占用的账户数
+const LEG_ACCOUNTS: usize = 4;
+
+/// 创建组合红包。remaining_accounts 按 token_amounts 的顺序，每种代币依次传入
+/// [mint, creator_ata, pool_ata, token_program]。代币的 mint 检测和普通红包一致，
+/// 但各个代币直接按每份金额转账，不支持转账费和 transfer hook
+pub fn create_bundle_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, CreateBundle<'info>>,
+    packet_count: u32,
+    sol_per_claim: u64,
+    token_amounts: Vec<u64>,
+    expiry_days: Option<i64>,
+) -> Result<()> {
+    require!(
+        !ctx.accounts
+            .protocol_config
+            .is_paused(ProtocolConfig::PAUSE_CREATE),
+        RedPacketError::ProgramPaused
+    );
+    require!(packet_count > 0, RedPacketError::InvalidPacketCount);
+    require!(
+        packet_count <= MAX_PACKET_COUNT,
+        RedPacketError::PacketCountTooLarge
+    );
+    let expiry_days = expiry_days.unwrap_or(DEFAULT_RED_PACKET_EXPIRY_DAYS);
+    require!(
+        (1..=30).contains(&expiry_days),
+        RedPacketError::InvalidExpiryDays
+    );
+    require!(
+        token_amounts.len() <= MAX_BUNDLE_LEGS && (sol_per_claim > 0 || !token_amounts.is_empty()),
+        RedPacketError::InvalidBundleLegs
+    );
+    require!(
+        ctx.remaining_accounts.len() == token_amounts.len() * LEG_ACCOUNTS,
+        RedPacketError::InvalidRemainingAccounts
+    );
+
+    let sol_total = sol_per_claim
+        .checked_mul(packet_count as u64)
+        .ok_or(RedPacketError::ArithmeticOverflow)?;
+    // 比例部分只按 SOL 计算，和普通代币红包一样
+    let create_fee = ctx
+        .accounts
+        .protocol_config
+        .create_fee
+        .fee(sol_total, true)
+        .ok_or(RedPacketError::ArithmeticOverflow)?;
+
+    let creator_state = &mut ctx.accounts.creator_state;
+    let bundle_id = creator_state.next_red_packet_id;
+    creator_state.next_red_packet_id = bundle_id
+        .checked_add(1)
+        .ok_or(RedPacketError::CounterOverflow)?;
+
+    let bundle_key = ctx.accounts.bundle.key();
+    let mut legs: Vec<BundleLeg> = Vec::with_capacity(token_amounts.len());
+    for (&amount_per_claim, accounts) in token_amounts
+        .iter()
+        .zip(ctx.remaining_accounts.chunks(LEG_ACCOUNTS))
+    {
+        let (mint, creator_ata, pool_ata, token_program) =
+            (&accounts[0], &accounts[1], &accounts[2], &accounts[3]);
+        require!(
+            amount_per_claim > 0 && legs.iter().all(|leg| leg.mint != mint.key()),
+            RedPacketError::InvalidBundleLegs
+        );
+        let mint_info = MintInfo::load(mint, &token_program.key())?;
+        mint_info.check_usage(MintUsage::Bundle)?;
+        mint_info.check_risk_policy(ctx.accounts.protocol_config.mint_risk_policy)?;
+        require_keys_eq!(
+            pool_ata.key(),
+            get_associated_token_address_with_program_id(
+                &bundle_key,
+                &mint.key(),
+                &token_program.key()
+            ),
+            RedPacketError::InvalidPoolAta
+        );
+        if pool_ata.data_is_empty() {
+            anchor_spl::associated_token::create(CpiContext::new(
+                ctx.accounts.associated_token_program.to_account_info(),
+                anchor_spl::associated_token::Create {
+                    payer: ctx.accounts.creator.to_account_info(),
+                    associated_token: pool_ata.clone(),
+                    authority: ctx.accounts.bundle.to_account_info(),
+                    mint: mint.clone(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: token_program.clone(),
+                },
+            ))?;
+        }
+
+        let total = amount_per_claim
+            .checked_mul(packet_count as u64)
+            .ok_or(RedPacketError::ArithmeticOverflow)?;
+        token_interface::transfer_checked(
+            CpiContext::new(
+                token_program.clone(),
+                TransferChecked {
+                    from: creator_ata.clone(),
+                    mint: mint.clone(),
+                    to: pool_ata.clone(),
+                    authority: ctx.accounts.creator.to_account_info(),
+                },
+            ),
+            total,
+            mint_info.decimals,
+        )?;
+
+        legs.push(BundleLeg {
+            mint: mint.key(),
+            token_program: token_program.key(),
+            amount_per_claim,
+            decimals: mint_info.decimals,
+        });
+    }
+
+    if sol_total > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.creator.to_account_info(),
+                    to: ctx.accounts.bundle.to_account_info(),
+                },
+            ),
+            sol_total,
+        )?;
+    }
+    if create_fee > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.creator.to_account_info(),
+                    to: ctx.accounts.fee_receiver.to_account_info(),
+                },
+            ),
+            create_fee,
+        )?;
+    }
+
+    let expiry_time = Clock::get()?.unix_timestamp + expiry_days * 24 * 60 * 60;
+    ctx.accounts.bundle.set_inner(BundlePacket {
+        creator: ctx.accounts.creator.key(),
+        bundle_id,
+        packet_count,
+        claimed_count: 0,
+        sol_per_claim,
+        expiry_time,
+        legs: legs.clone(),
+        bump: ctx.bumps.bundle,
+    });
+
+    emit!(BundleCreated {
+        creator: ctx.accounts.creator.key(),
+        bundle: bundle_key,
+        bundle_id,
+        packet_count,
+        sol_per_claim,
+        legs,
+        expiry_time,
+    });
+    Ok(())
+}
+
+/// 领取一份组合红包，所有代币和 SOL 在同一条指令里发放，任何一项失败整体回滚。
+/// remaining_accounts 按 legs 的顺序，每种代币依次传入 [mint, pool_ata, claimer_ata, token_program]
+pub fn claim_bundle_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, ClaimBundle<'info>>,
+    bundle_id: u64,
+) -> Result<()> {
+    require!(
+        !ctx.accounts
+            .protocol_config
+            .is_paused(ProtocolConfig::PAUSE_CLAIM),
+        RedPacketError::ProgramPaused
+    );
+    require!(
+        ctx.accounts.user_state.is_claimed == 0,
+        RedPacketError::AlreadyClaimed
+    );
+    let bundle = &ctx.accounts.bundle;
+    require!(
+        Clock::get()?.unix_timestamp < bundle.expiry_time,
+        RedPacketError::RedPacketExpired
+    );
+    require!(
+        bundle.claimed_count < bundle.packet_count,
+        RedPacketError::NoPacketsRemaining
+    );
+    require!(
+        ctx.remaining_accounts.len() == bundle.legs.len() * LEG_ACCOUNTS,
+        RedPacketError::InvalidRemainingAccounts
+    );
+
+    // 先收手续费再直接改 lamports：CPI 时运行时会检查调用方账户的 lamports 总和不变
+    let claim_fee = ctx
+        .accounts
+        .protocol_config
+        .claim_fee
+        .fee(bundle.sol_per_claim, true)
+        .ok_or(RedPacketError::ArithmeticOverflow)?;
+    if claim_fee > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.claimer.to_account_info(),
+                    to: ctx.accounts.fee_receiver.to_account_info(),
+                },
+            ),
+            claim_fee,
+        )?;
+    }
+
+    let bundle_key = bundle.key();
+    let signer_seeds: &[&[u8]] = &[
+        BUNDLE_SEED,
+        bundle.creator.as_ref(),
+        &bundle.bundle_id.to_le_bytes(),
+        &[bundle.bump],
+    ];
+    let mut token_amounts = Vec::with_capacity(bundle.legs.len());
+    for (leg, accounts) in bundle
+        .legs
+        .iter()
+        .zip(ctx.remaining_accounts.chunks(LEG_ACCOUNTS))
+    {
+        let (mint, pool_ata, claimer_ata, token_program) =
+            (&accounts[0], &accounts[1], &accounts[2], &accounts[3]);
+        check_leg_accounts(leg, &bundle_key, mint, pool_ata, token_program)?;
+        if claimer_ata.data_is_empty() {
+            anchor_spl::associated_token::create(CpiContext::new(
+                ctx.accounts.associated_token_program.to_account_info(),
+                anchor_spl::associated_token::Create {
+                    payer: ctx.accounts.claimer.to_account_info(),
+                    associated_token: claimer_ata.clone(),
+                    authority: ctx.accounts.claimer.to_account_info(),
+                    mint: mint.clone(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: token_program.clone(),
+                },
+            ))?;
+        }
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                token_program.clone(),
+                TransferChecked {
+                    from: pool_ata.clone(),
+                    mint: mint.clone(),
+                    to: claimer_ata.clone(),
+                    authority: bundle.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            leg.amount_per_claim,
+            leg.decimals,
+        )?;
+        token_amounts.push(leg.amount_per_claim);
+    }
+
+    let sol_amount = bundle.sol_per_claim;
+    if sol_amount > 0 {
+        **ctx
+            .accounts
+            .bundle
+            .to_account_info()
+            .try_borrow_mut_lamports()? -= sol_amount;
+        **ctx.accounts.claimer.try_borrow_mut_lamports()? += sol_amount;
+    }
+    ctx.accounts.bundle.claimed_count += 1;
+    ctx.accounts.user_state.is_claimed = 1;
+
+    emit!(BundleClaimed {
+        claimer: ctx.accounts.claimer.key(),
+        bundle: bundle_key,
+        bundle_id,
+        sol_amount,
+        token_amounts,
+    });
+    Ok(())
+}
+
+/// 过期或领完之后退回所有代币和剩余 SOL，关闭各个池子 ATA 和组合红包账户。
+/// remaining_accounts 按 legs 的顺序，每种代币依次传入 [mint, pool_ata, creator_ata, token_program]
+pub fn refund_bundle_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, RefundBundle<'info>>,
+    bundle_id: u64,
+) -> Result<()> {
+    let bundle = &ctx.accounts.bundle;
+    require!(
+        Clock::get()?.unix_timestamp >= bundle.expiry_time
+            || bundle.claimed_count >= bundle.packet_count,
+        RedPacketError::RedPacketNotExpired
+    );
+    require!(
+        ctx.remaining_accounts.len() == bundle.legs.len() * LEG_ACCOUNTS,
+        RedPacketError::InvalidRemainingAccounts
+    );
+
+    let bundle_key = bundle.key();
+    let signer_seeds: &[&[u8]] = &[
+        BUNDLE_SEED,
+        bundle.creator.as_ref(),
+        &bundle.bundle_id.to_le_bytes(),
+        &[bundle.bump],
+    ];
+    let mut token_amounts = Vec::with_capacity(bundle.legs.len());
+    for (leg, accounts) in bundle
+        .legs
+        .iter()
+        .zip(ctx.remaining_accounts.chunks(LEG_ACCOUNTS))
+    {
+        let (mint, pool_ata, creator_ata, token_program) =
+            (&accounts[0], &accounts[1], &accounts[2], &accounts[3]);
+        check_leg_accounts(leg, &bundle_key, mint, pool_ata, token_program)?;
+        let pool_balance = {
+            let pool_data = pool_ata.try_borrow_data()?;
+            StateWithExtensions::<TokenAccount2022>::unpack(&pool_data)
+                .map_err(|_| RedPacketError::InvalidPoolAta)?
+                .base
+                .amount
+        };
+        if pool_balance > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    token_program.clone(),
+                    TransferChecked {
+                        from: pool_ata.clone(),
+                        mint: mint.clone(),
+                        to: creator_ata.clone(),
+                        authority: bundle.to_account_info(),
+                    },
+                    &[signer_seeds],
+                ),
+                pool_balance,
+                leg.decimals,
+            )?;
+        }
+        token_interface::close_account(CpiContext::new_with_signer(
+            token_program.clone(),
+            token_interface::CloseAccount {
+                account: pool_ata.clone(),
+                destination: ctx.accounts.creator.to_account_info(),
+                authority: bundle.to_account_info(),
+            },
+            &[signer_seeds],
+        ))?;
+        token_amounts.push(pool_balance);
+    }
+
+    // 剩余 SOL 和账户租金通过 close = creator 一起退回
+    emit!(BundleRefunded {
+        creator: bundle.creator,
+        bundle: bundle_key,
+        bundle_id,
+        sol_amount: bundle.sol_per_claim * (bundle.packet_count - bundle.claimed_count) as u64,
+        token_amounts,
+    });
+    Ok(())
+}
+
+/// 校验 remaining_accounts 里的一组代币账户与创建时记录的一致
+fn check_leg_accounts(
+    leg: &BundleLeg,
+    bundle: &Pubkey,
+    mint: &AccountInfo,
+    pool_ata: &AccountInfo,
+    token_program: &AccountInfo,
+) -> Result<()> {
+    require_keys_eq!(mint.key(), leg.mint, RedPacketError::InvalidMint);
+    require_keys_eq!(
+        token_program.key(),
+        leg.token_program,
+        RedPacketError::InvalidTokenProgram
+    );
+    require_keys_eq!(
+        pool_ata.key(),
+        get_associated_token_address_with_program_id(bundle, &leg.mint, &leg.token_program),
+        RedPacketError::InvalidPoolAta
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CreateBundle<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [CREATOR_STATE_SEED, creator.key().as_ref()],
+        bump = creator_state.bump
+    )]
+    pub creator_state: Account<'info, CreatorState>,
+
+    #[account(
+        init,
+        payer = creator,
+        space = BUNDLE_PACKET_SPACE,
+        seeds = [
+            BUNDLE_SEED,
+            creator.key().as_ref(),
+            &creator_state.next_red_packet_id.to_le_bytes()
+        ],
+        bump
+    )]
+    pub bundle: Account<'info, BundlePacket>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        address = protocol_config.fee_receiver @ RedPacketError::InvalidFeeReceiver
+    )]
+    pub fee_receiver: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(bundle_id: u64)]
+pub struct ClaimBundle<'info> {
+    #[account(mut)]
+    pub claimer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [BUNDLE_SEED, bundle.creator.as_ref(), &bundle_id.to_le_bytes()],
+        bump = bundle.bump
+    )]
+    pub bundle: Account<'info, BundlePacket>,
+
+    #[account(
+        init_if_needed,
+        payer = claimer,
+        space = config::USER_STATE_SPACE,
+        seeds = [b"user_state", bundle.key().as_ref(), claimer.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        address = protocol_config.fee_receiver @ RedPacketError::InvalidFeeReceiver
+    )]
+    pub fee_receiver: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(bundle_id: u64)]
+pub struct RefundBundle<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    #[account(
+        mut,
+        close = creator,
+        seeds = [BUNDLE_SEED, creator.key().as_ref(), &bundle_id.to_le_bytes()],
+        bump = bundle.bump
+    )]
+    pub bundle: Account<'info, BundlePacket>,
+}
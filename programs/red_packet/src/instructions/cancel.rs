@@ -0,0 +1,128 @@
+use crate::{
+    config::{CREATOR_STATE_SEED, MAX_CANCEL_GRACE_PERIOD},
+    events::RedPacketCancelled,
+    instructions::refund::{self, RefundAccounts},
+    utils, CreatorState, RedPacket, RedPacketError,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id, token::spl_token,
+    token_interface::TokenInterface,
+};
+
+/// 创建者或持有 PERMISSION_CANCEL 的操作员提前取消红包。没有宽限期时立即退款并关闭账户，
+/// 和过期后的 refund 一样；有宽限期时把过期时间提前到宽限期结束并标记为已取消，让已经发出的领取
+/// （口令红包的揭示和类型 3 / 7 的领取）还能完成，之后再由创建者调用 refund。宽限期内只放行取消前
+/// 已经提交承诺的领取，新的领取者不能再进来。已取消的红包不能再修改时间或追加，
+/// 否则过期时间又会被延长。暂停期间同样可以取消
+pub fn cancel_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, CancelRedPacket<'info>>,
+    red_packet_id: u64,
+    grace_period: Option<i64>,
+) -> Result<()> {
+    let red_packet = &mut ctx.accounts.red_packet;
+    require!(
+        red_packet.red_packet_id == red_packet_id,
+        RedPacketError::InvalidRedPacketId
+    );
+    utils::check_manager(
+        red_packet,
+        &ctx.accounts.creator_state,
+        &ctx.accounts.authority.key(),
+        CreatorState::PERMISSION_CANCEL,
+    )?;
+    let grace_period = grace_period.unwrap_or(0);
+    require!(
+        (0..=MAX_CANCEL_GRACE_PERIOD).contains(&grace_period),
+        RedPacketError::InvalidGracePeriod
+    );
+
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    // 只会提前，不会延长已经设置的过期时间
+    let claims_end_time = (now + grace_period).min(red_packet.expiry_time);
+    red_packet.expiry_time = claims_end_time;
+    // 重复取消只缩短宽限期，放行的承诺仍以第一次取消为准
+    if !red_packet.cancelled {
+        red_packet.cancel_slot = clock.slot;
+    }
+    red_packet.cancelled = true;
+
+    emit!(RedPacketCancelled {
+        creator: red_packet.creator,
+        red_packet: red_packet.key(),
+        red_packet_id,
+        claims_end_time,
+    });
+
+    if claims_end_time > now {
+        return Ok(());
+    }
+
+    // 签名者可能是操作员，剩余代币只能退到创建者自己的 ATA
+    if !red_packet.is_sol {
+        require!(
+            ctx.accounts.creator_ata.key()
+                == get_associated_token_address_with_program_id(
+                    &red_packet.creator,
+                    &red_packet.mint,
+                    &ctx.accounts.token_program.key(),
+                ),
+            RedPacketError::InvalidATA
+        );
+    }
+    let accounts = RefundAccounts {
+        creator: ctx.accounts.creator.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+        creator_ata: ctx.accounts.creator_ata.to_account_info(),
+        pool_ata: ctx.accounts.pool_ata.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+    };
+    refund::refund_and_close(
+        &mut ctx.accounts.red_packet,
+        accounts,
+        ctx.remaining_accounts,
+        red_packet_id,
+    )
+}
+
+#[derive(Accounts)]
+#[instruction(red_packet_id: u64)]
+pub struct CancelRedPacket<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"red_packet", red_packet.creator.as_ref(), &red_packet_id.to_le_bytes()],
+        bump = red_packet.bump
+    )]
+    pub red_packet: Account<'info, RedPacket>,
+
+    #[account(
+        seeds = [CREATOR_STATE_SEED, red_packet.creator.as_ref()],
+        bump = creator_state.bump
+    )]
+    pub creator_state: Account<'info, CreatorState>,
+
+    /// 剩余金额和租金总是退给创建者
+    #[account(mut, address = red_packet.creator @ RedPacketError::Unauthorized)]
+    pub creator: SystemAccount<'info>,
+
+    /// CHECK: This can be SOL (Pubkey::default) or SPL token mint, must be writable for transfer-fee mints to harvest withheld fees
+    #[account()]
+    pub mint: UncheckedAccount<'info>,
+
+    /// CHECK: Creator's ATA for SPL tokens (checked in the handler), creator for SOL
+    #[account(mut)]
+    pub creator_ata: UncheckedAccount<'info>,
+
+    /// CHECK: Pool ATA for SPL tokens, red_packet account for SOL
+    #[account(mut)]
+    pub pool_ata: UncheckedAccount<'info>,
+
+    #[account(
+    constraint = token_program.key() == spl_token::id() || token_program.key() == spl_token_2022::id() @ RedPacketError::InvalidTokenProgram
+    )]
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
@@ -1,128 +1,367 @@
 #![allow(unexpected_cfgs)]
 
 use crate::{
-    config::{self, CLAIM_FEE, FEE_RECEIVER},
+    config::{self, CLAIM_COMMIT_SEED, PROTOCOL_CONFIG_SEED},
     events::RedPacketClaimed,
-    utils, RedPacket, RedPacketError, UserState,
+    mint_info::{MintInfo, MintUsage},
+    utils, ClaimCommit, ProtocolConfig, RedPacket, RedPacketError, UserState,
 };
 use anchor_lang::{
     prelude::*,
-    solana_program::{program_pack::Pack, pubkey::Pubkey},
+    solana_program::{
+        instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT},
+        pubkey::Pubkey,
+        sysvar::slot_hashes,
+    },
     system_program,
 };
 use anchor_spl::{
     associated_token::AssociatedToken,
     token::spl_token,
-    token_interface::{self, TokenInterface, TransferChecked},
+    token_interface::{TokenInterface, TransferChecked},
 };
-use spl_token::state::Mint as MintLegacy;
-use spl_token_2022::{extension::StateWithExtensions, state::Mint as Mint2022}; // 引入 SPL Token 的 Mint 结构
 
-pub fn claim_handler(
-    ctx: Context<ClaimRedPacket>,
+pub fn claim_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, ClaimRedPacket<'info>>,
     amount: Option<u64>, // 仅 Merkle 树红包需要提供 amount
     proof: Option<Vec<[u8; 32]>>,
     red_packet_id: u64,
+    code: Option<Vec<u8>>, // 仅口令红包需要提供口令
 ) -> Result<()> {
-    let red_packet = &mut ctx.accounts.red_packet;
-    let user_state = &mut ctx.accounts.user_state;
-    let red_packet_key = red_packet.key();
-    let clock = Clock::get()?;
+    let accounts = ClaimAccounts {
+        payer: ctx.accounts.claimer.to_account_info(),
+        recipient: ctx.accounts.claimer.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+        pool_ata: ctx.accounts.pool_ata.to_account_info(),
+        recipient_ata: ctx.accounts.user_ata.to_account_info(),
+        fee_receiver: ctx.accounts.fee_receiver.to_account_info(),
+        system_program: ctx.accounts.system_program.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
+        slot_hashes: Some(ctx.accounts.slot_hashes.to_account_info()),
+        claim_commit: ctx
+            .accounts
+            .claim_commit
+            .as_ref()
+            .map(|claim_commit| claim_commit.to_account_info()),
+        gate_token_account: ctx
+            .accounts
+            .gate_token_account
+            .as_ref()
+            .map(|account| account.to_account_info()),
+        gate_metadata: ctx
+            .accounts
+            .gate_metadata
+            .as_ref()
+            .map(|account| account.to_account_info()),
+        protocol_config: ctx.accounts.protocol_config.clone().into_inner(),
+    };
+    process_claim(
+        &mut ctx.accounts.red_packet,
+        &mut ctx.accounts.user_state,
+        accounts,
+        ctx.remaining_accounts,
+        amount,
+        proof,
+        code,
+        red_packet_id,
+    )
+}
+
+/// 领取流程用到的账户。普通领取时 payer 和 recipient 都是 claimer，
+/// 代领时 payer 是中继方，recipient 是红包的实际归属人。
+pub struct ClaimAccounts<'info> {
+    /// 支付领取手续费以及 user_state / ATA 的租金
+    pub payer: AccountInfo<'info>,
+    /// 随机、Merkle 等逻辑以 recipient 为准
+    pub recipient: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    pub pool_ata: AccountInfo<'info>,
+    /// SOL 红包为 recipient 本身，代币红包为 recipient 的 ATA
+    pub recipient_ata: AccountInfo<'info>,
+    pub fee_receiver: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+    pub associated_token_program: AccountInfo<'info>,
+    /// 只有类型 3 和 7 需要
+    pub slot_hashes: Option<AccountInfo<'info>>,
+    /// 只有口令红包和类型 3 / 7 需要，调用方负责校验是 recipient 的 claim_commit PDA
+    pub claim_commit: Option<AccountInfo<'info>>,
+    /// 持有者门槛：recipient 持有的代币账户，合集门槛还需要该 NFT 的 Metadata
+    pub gate_token_account: Option<AccountInfo<'info>>,
+    pub gate_metadata: Option<AccountInfo<'info>>,
+    /// 已由账户约束校验过的全局配置，提供暂停状态和领取手续费
+    pub protocol_config: ProtocolConfig,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn process_claim<'info>(
+    red_packet: &mut Account<'info, RedPacket>,
+    user_state: &mut Account<'info, UserState>,
+    accounts: ClaimAccounts<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: Option<u64>,
+    proof: Option<Vec<[u8; 32]>>,
+    code: Option<Vec<u8>>,
+    red_packet_id: u64,
+) -> Result<()> {
+    let recipient = accounts.recipient.key();
     require!(user_state.is_claimed == 0, RedPacketError::AlreadyClaimed);
-    require!(
-        red_packet.red_packet_id == red_packet_id,
-        RedPacketError::InvalidRedPacketId
-    );
-    require!(
-        clock.unix_timestamp < red_packet.expiry_time,
-        RedPacketError::RedPacketExpired
-    );
+    validate_claim(red_packet, red_packet_id)?;
+    utils::verify_holder_gate(
+        &red_packet.holder_gate,
+        &recipient,
+        accounts.gate_token_account.as_ref(),
+        accounts.gate_metadata.as_ref(),
+    )?;
 
+    // 口令红包和类型 3 / 7 都要先提交承诺，领取成功后关闭承诺账户
+    let code = if matches!(red_packet.red_packet_type, 5 | 6) {
+        Some(code.ok_or(RedPacketError::MissingClaimCode)?)
+    } else {
+        None
+    };
+    let claim_commit = if matches!(red_packet.red_packet_type, 3 | 5 | 6 | 7) {
+        let claim_commit_info = accounts
+            .claim_commit
+            .clone()
+            .ok_or(RedPacketError::MissingClaimCommit)?;
+        require_keys_eq!(
+            *claim_commit_info.owner,
+            crate::ID,
+            RedPacketError::MissingClaimCommit
+        );
+        let claim_commit = ClaimCommit::try_deserialize(&mut &claim_commit_info.data.borrow()[..])?;
+        match &code {
+            Some(code) => utils::verify_claim_code(
+                &red_packet.key(),
+                &recipient,
+                code,
+                &claim_commit,
+                &red_packet.code_hash,
+            )?,
+            None => {
+                // 包一层程序 CPI 进来可以看到抽中的金额再回滚，只允许交易顶层直接领取
+                require!(
+                    get_stack_height() == TRANSACTION_LEVEL_STACK_HEIGHT,
+                    RedPacketError::CpiClaimNotAllowed
+                );
+                require!(
+                    Clock::get()?.slot > claim_commit.slot,
+                    RedPacketError::ClaimCommitTooRecent
+                );
+            }
+        }
+        Some((claim_commit_info, claim_commit.slot))
+    } else {
+        None
+    };
+    let commit_slot = claim_commit.as_ref().map_or(0, |(_, slot)| *slot);
+    // 宽限期内只放行取消前就提交了承诺的领取
     require!(
-        red_packet.claimed_count < red_packet.packet_count,
-        RedPacketError::NoPacketsRemaining
+        !red_packet.cancelled || (claim_commit.is_some() && commit_slot < red_packet.cancel_slot),
+        RedPacketError::RedPacketCancelled
     );
 
+    let mut tier = None;
     let claim_amount = match red_packet.red_packet_type {
-        0 => red_packet.share_amount,
-        1 => utils::calculate_random_amount(red_packet, ctx.accounts.claimer.key())?,
+        0 | 5 => red_packet.share_amount,
+        1 | 6 => utils::calculate_random_amount(red_packet, recipient)?,
         2 => {
             let claim_amount = amount.ok_or(RedPacketError::InvalidClaimAmount)?;
             let proof_vec = proof.ok_or(RedPacketError::MerkleProofInvalid)?;
             utils::verify_merkle_proof(
-                ctx.accounts.claimer.key(),
+                recipient,
                 claim_amount,
                 &proof_vec,
                 &red_packet.merkle_root,
             )?;
             claim_amount
         }
+        3 => utils::calculate_slot_hash_random_amount(
+            red_packet,
+            recipient,
+            accounts
+                .slot_hashes
+                .as_ref()
+                .ok_or(RedPacketError::InvalidSlotHashes)?,
+            commit_slot,
+        )?,
+        7 => {
+            let (tier_amount, tier_index) = utils::draw_prize_tier(
+                red_packet,
+                recipient,
+                accounts
+                    .slot_hashes
+                    .as_ref()
+                    .ok_or(RedPacketError::InvalidSlotHashes)?,
+                commit_slot,
+            )?;
+            tier = Some(tier_index);
+            tier_amount
+        }
+        // 类型 4 走 claim_indexed_redpacket，用位图记录领取状态
         _ => return Err(RedPacketError::InvalidRedPacketType.into()),
     };
 
+    pay_claim(
+        red_packet,
+        &accounts,
+        remaining_accounts,
+        claim_amount,
+        tier,
+        red_packet_id,
+    )?;
+    user_state.is_claimed = 1;
+
+    if let Some((claim_commit_info, _)) = claim_commit {
+        let lamports = claim_commit_info.lamports();
+        **claim_commit_info.try_borrow_mut_lamports()? = 0;
+        **accounts.recipient.try_borrow_mut_lamports()? += lamports;
+        claim_commit_info.assign(&system_program::ID);
+        claim_commit_info.resize(0)?;
+    }
+
+    Ok(())
+}
+
+/// 各种领取方式共用的前置检查
+pub fn validate_claim(red_packet: &RedPacket, red_packet_id: u64) -> Result<()> {
+    let clock = Clock::get()?;
+    require!(
+        red_packet.red_packet_id == red_packet_id,
+        RedPacketError::InvalidRedPacketId
+    );
+    require!(
+        clock.unix_timestamp >= red_packet.start_time,
+        RedPacketError::RedPacketNotStarted
+    );
+    // 已取消的红包过了宽限期单独报错，便于和正常过期区分
+    require!(
+        !red_packet.cancelled || clock.unix_timestamp < red_packet.expiry_time,
+        RedPacketError::RedPacketCancelled
+    );
+    require!(
+        clock.unix_timestamp < red_packet.expiry_time,
+        RedPacketError::RedPacketExpired
+    );
+    // 没有承诺的领取方式在取消后直接拒绝，有承诺的由 process_claim 比较提交 slot 和取消 slot
+    require!(
+        !red_packet.cancelled || matches!(red_packet.red_packet_type, 3 | 5 | 6 | 7),
+        RedPacketError::RedPacketCancelled
+    );
+
+    require!(
+        red_packet.claimed_count < red_packet.packet_count,
+        RedPacketError::NoPacketsRemaining
+    );
+    Ok(())
+}
+
+/// 把 claim_amount 从池子转给 recipient，收取领取手续费并更新红包计数；
+/// tier 是分档红包抽中的奖项，只用于事件
+pub fn pay_claim<'info>(
+    red_packet: &mut Account<'info, RedPacket>,
+    accounts: &ClaimAccounts<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    claim_amount: u64,
+    tier: Option<u8>,
+    red_packet_id: u64,
+) -> Result<()> {
+    let red_packet_key = red_packet.key();
+    require!(
+        !accounts
+            .protocol_config
+            .is_paused(ProtocolConfig::PAUSE_CLAIM),
+        RedPacketError::ProgramPaused
+    );
     require!(claim_amount > 0, RedPacketError::InvalidClaimAmount);
     require!(
         red_packet.remaining_amount >= claim_amount,
         RedPacketError::InsufficientFunds
     );
 
+    // 先收手续费再直接改 lamports：CPI 时运行时会检查调用方账户的 lamports 总和不变
+    let claim_fee = accounts
+        .protocol_config
+        .claim_fee
+        .fee(claim_amount, red_packet.is_sol)
+        .ok_or(RedPacketError::ArithmeticOverflow)?;
+    if claim_fee > 0 {
+        require!(
+            accounts.payer.lamports() >= claim_fee,
+            RedPacketError::InsufficientClaimerFunds
+        );
+        // 领取手续费（从 payer 到 fee_receiver）
+        system_program::transfer(
+            CpiContext::new(
+                accounts.system_program.clone(),
+                system_program::Transfer {
+                    from: accounts.payer.clone(),
+                    to: accounts.fee_receiver.clone(),
+                },
+            ),
+            claim_fee,
+        )?;
+    }
+
+    let mut transfer_fee = 0;
     if red_packet.is_sol {
         let red_packet_lamports = red_packet.to_account_info().lamports();
         require!(
-            red_packet_lamports >= claim_amount + CLAIM_FEE,
+            red_packet_lamports >= claim_amount,
             RedPacketError::InsufficientFunds
         );
+        // 代领给没有 SOL 的新钱包时，到账后仍低于免租下限会被运行时拒绝，这里给出明确的错误
         require!(
-            ctx.accounts.claimer.lamports() >= CLAIM_FEE,
-            RedPacketError::InsufficientClaimerFunds
+            accounts
+                .recipient_ata
+                .lamports()
+                .saturating_add(claim_amount)
+                >= Rent::get()?.minimum_balance(0),
+            RedPacketError::ShareBelowRentExemption
         );
         **red_packet.to_account_info().try_borrow_mut_lamports()? -= claim_amount;
-        **ctx.accounts.user_ata.try_borrow_mut_lamports()? += claim_amount;
-
-        **ctx.accounts.claimer.try_borrow_mut_lamports()? -= CLAIM_FEE;
-        **ctx
-            .accounts
-            .fee_receiver
-            .to_account_info()
-            .try_borrow_mut_lamports()? += CLAIM_FEE;
+        **accounts.recipient_ata.try_borrow_mut_lamports()? += claim_amount;
     } else {
-        let mint_data = ctx.accounts.mint.data.borrow();
-        let decimals = if ctx.accounts.token_program.key() == spl_token::id() {
-            // 处理 SPL Token
-            let mint =
-                MintLegacy::unpack(&mint_data).map_err(|_| RedPacketError::InvalidMintAccount)?;
-            mint.decimals
-        } else if ctx.accounts.token_program.key() == spl_token_2022::id() {
-            // 处理 Token-2022
-            let mint = StateWithExtensions::<Mint2022>::unpack(&mint_data)
-                .map_err(|_| RedPacketError::InvalidMintAccount)?;
-            // 暂时移除铸币权限检查，后续在第二步添加扩展检查
-            mint.base.decimals
-        } else {
-            return Err(RedPacketError::InvalidTokenProgram.into());
-        };
-
-        if ctx.accounts.user_ata.data_is_empty() {
+        let mint_info = MintInfo::load_pool(
+            &accounts.mint,
+            &accounts.pool_ata,
+            &red_packet_key,
+            &red_packet.mint,
+            &accounts.token_program.key(),
+        )?;
+        mint_info.check_usage(MintUsage::Claim)?;
+
+        if accounts.recipient_ata.data_is_empty() {
+            // 新建的 ATA 默认冻结时无法收款，直接给出明确的错误
+            require!(
+                !mint_info.is_default_frozen,
+                RedPacketError::DefaultFrozenNotSupported
+            );
             anchor_spl::associated_token::create(CpiContext::new(
-                ctx.accounts.associated_token_program.to_account_info(),
+                accounts.associated_token_program.clone(),
                 anchor_spl::associated_token::Create {
-                    payer: ctx.accounts.claimer.to_account_info(),
-                    associated_token: ctx.accounts.user_ata.to_account_info(),
-                    authority: ctx.accounts.claimer.to_account_info(),
-                    mint: ctx.accounts.mint.to_account_info(),
-                    system_program: ctx.accounts.system_program.to_account_info(),
-                    token_program: ctx.accounts.token_program.to_account_info(),
+                    payer: accounts.payer.clone(),
+                    associated_token: accounts.recipient_ata.clone(),
+                    authority: accounts.recipient.clone(),
+                    mint: accounts.mint.clone(),
+                    system_program: accounts.system_program.clone(),
+                    token_program: accounts.token_program.clone(),
                 },
             ))?;
         }
 
-        token_interface::transfer_checked(
+        // 代币从池子转出时扣除转账费，领取者实际到账 claim_amount - transfer_fee
+        transfer_fee = utils::calculate_transfer_fee(&accounts.mint, claim_amount)?;
+
+        utils::transfer_checked_with_hook(
             CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
+                accounts.token_program.clone(),
                 TransferChecked {
-                    from: ctx.accounts.pool_ata.to_account_info(),
-                    mint: ctx.accounts.mint.to_account_info(),
-                    to: ctx.accounts.user_ata.to_account_info(),
+                    from: accounts.pool_ata.clone(),
+                    mint: accounts.mint.clone(),
+                    to: accounts.recipient_ata.clone(),
                     authority: red_packet.to_account_info(),
                 },
                 &[&[
@@ -131,37 +370,24 @@ pub fn claim_handler(
                     &red_packet.red_packet_id.to_le_bytes(),
                     &[red_packet.bump],
                 ]],
-            ),
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
             claim_amount,
-            decimals,
-        )?;
-
-        require!(
-            ctx.accounts.claimer.lamports() >= CLAIM_FEE,
-            RedPacketError::InsufficientClaimerFunds
-        );
-        //  CLAIM_FEE 转移（从 claimer 到 fee_vault）
-        system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                system_program::Transfer {
-                    from: ctx.accounts.claimer.to_account_info(),
-                    to: ctx.accounts.fee_receiver.to_account_info(),
-                },
-            ),
-            CLAIM_FEE,
+            mint_info.decimals,
         )?;
     }
 
-    user_state.is_claimed = 1;
     red_packet.remaining_amount -= claim_amount;
     red_packet.claimed_count += 1;
 
     emit!(RedPacketClaimed {
-        claimer: ctx.accounts.claimer.key(),
+        claimer: accounts.recipient.key(),
         red_packet: red_packet_key,
         amount: claim_amount,
-        red_packet_id
+        net_amount: claim_amount - transfer_fee,
+        transfer_fee,
+        red_packet_id,
+        tier,
     });
 
     Ok(())
@@ -197,9 +423,15 @@ pub struct ClaimRedPacket<'info> {
     #[account(mut)]
     pub user_ata: UncheckedAccount<'info>,
 
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
     #[account(
         mut,
-        address = FEE_RECEIVER
+        address = protocol_config.fee_receiver @ RedPacketError::InvalidFeeReceiver
     )]
     pub fee_receiver: SystemAccount<'info>,
 
@@ -209,4 +441,21 @@ pub struct ClaimRedPacket<'info> {
     )]
     pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// CHECK: SlotHashes sysvar, only read by slot-hash random and tiered red packets
+    #[account(address = slot_hashes::ID @ RedPacketError::InvalidSlotHashes)]
+    pub slot_hashes: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [CLAIM_COMMIT_SEED, red_packet.key().as_ref(), claimer.key().as_ref()],
+        bump
+    )]
+    pub claim_commit: Option<Account<'info, ClaimCommit>>,
+
+    /// CHECK: Claimer's token account, only required by holder-gated red packets
+    pub gate_token_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Metaplex metadata of the held NFT, only required by collection-gated red packets
+    pub gate_metadata: Option<UncheckedAccount<'info>>,
 }
@@ -0,0 +1,129 @@
+use crate::{config::CLAIM_BITMAP_SEED, ClaimBitmap, RedPacket, RedPacketError};
+use anchor_lang::{
+    prelude::*, solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE, system_program,
+};
+
+/// 为类型 4 红包创建位图。CPI 创建账户最多 10KB，超过的部分用 extend_claim_bitmap 补齐
+pub fn init_bitmap_handler(ctx: Context<InitClaimBitmap>, red_packet_id: u64) -> Result<()> {
+    let red_packet = &ctx.accounts.red_packet;
+    require!(
+        red_packet.red_packet_id == red_packet_id,
+        RedPacketError::InvalidRedPacketId
+    );
+    require!(
+        ctx.accounts.creator.key() == red_packet.creator,
+        RedPacketError::Unauthorized
+    );
+    require!(
+        red_packet.red_packet_type == 4,
+        RedPacketError::InvalidRedPacketType
+    );
+
+    let mut claim_bitmap = ctx.accounts.claim_bitmap.load_init()?;
+    claim_bitmap.red_packet = red_packet.key();
+    claim_bitmap.creator = red_packet.creator;
+    claim_bitmap.bit_count = red_packet.packet_count;
+    Ok(())
+}
+
+/// 每次最多扩容 MAX_PERMITTED_DATA_INCREASE 字节，直到容纳 bit_count 位，任何人都可以代付租金
+pub fn extend_bitmap_handler(ctx: Context<ExtendClaimBitmap>) -> Result<()> {
+    let bit_count = ctx.accounts.claim_bitmap.load()?.bit_count;
+    let bitmap_info = ctx.accounts.claim_bitmap.to_account_info();
+    let target_len = ClaimBitmap::space(bit_count);
+    let current_len = bitmap_info.data_len();
+    require!(current_len < target_len, RedPacketError::InvalidClaimBitmap);
+
+    let new_len = target_len.min(current_len + MAX_PERMITTED_DATA_INCREASE);
+    let rent_shortfall = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(bitmap_info.lamports());
+    if rent_shortfall > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: bitmap_info.clone(),
+                },
+            ),
+            rent_shortfall,
+        )?;
+    }
+    bitmap_info.resize(new_len)?;
+    Ok(())
+}
+
+/// 红包已关闭或已领完后，位图不再需要，任何人都可以关闭，租金退给创建者
+pub fn close_bitmap_handler(ctx: Context<CloseClaimBitmap>) -> Result<()> {
+    let red_packet_info = ctx.accounts.red_packet.to_account_info();
+    {
+        let claim_bitmap = ctx.accounts.claim_bitmap.load()?;
+        require!(
+            claim_bitmap.creator == ctx.accounts.creator.key(),
+            RedPacketError::Unauthorized
+        );
+    }
+
+    let is_closed = red_packet_info.owner != &crate::ID || red_packet_info.data_is_empty();
+    if !is_closed {
+        let red_packet = RedPacket::try_deserialize(&mut &red_packet_info.data.borrow()[..])?;
+        require!(
+            red_packet.claimed_count >= red_packet.packet_count,
+            RedPacketError::RedPacketStillActive
+        );
+    }
+
+    ctx.accounts
+        .claim_bitmap
+        .close(ctx.accounts.creator.to_account_info())
+}
+
+#[derive(Accounts)]
+#[instruction(red_packet_id: u64)]
+pub struct InitClaimBitmap<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    pub red_packet: Account<'info, RedPacket>,
+
+    #[account(
+        init,
+        payer = creator,
+        space = ClaimBitmap::space(red_packet.packet_count).min(MAX_PERMITTED_DATA_INCREASE),
+        seeds = [CLAIM_BITMAP_SEED, red_packet.key().as_ref()],
+        bump
+    )]
+    pub claim_bitmap: AccountLoader<'info, ClaimBitmap>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendClaimBitmap<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(mut)]
+    pub claim_bitmap: AccountLoader<'info, ClaimBitmap>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseClaimBitmap<'info> {
+    /// CHECK: The red packet may already be closed, its state is checked in the handler
+    #[account()]
+    pub red_packet: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [CLAIM_BITMAP_SEED, red_packet.key().as_ref()],
+        bump
+    )]
+    pub claim_bitmap: AccountLoader<'info, ClaimBitmap>,
+
+    /// CHECK: Rent destination, must match the creator recorded in the bitmap
+    #[account(mut)]
+    pub creator: UncheckedAccount<'info>,
+}
@@ -0,0 +1,117 @@
+use crate::{
+    config::{CLAIM_BITMAP_SEED, PROTOCOL_CONFIG_SEED},
+    instructions::claim::{pay_claim, validate_claim, ClaimAccounts},
+    utils, ClaimBitmap, ProtocolConfig, RedPacket, RedPacketError,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken, token::spl_token, token_interface::TokenInterface,
+};
+
+/// 类型 4 的领取：按叶子序号在位图里翻转一位，不再为每个领取者创建 UserState
+pub fn claim_indexed_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, ClaimIndexedRedPacket<'info>>,
+    index: u32,
+    amount: u64,
+    proof: Vec<[u8; 32]>,
+    red_packet_id: u64,
+) -> Result<()> {
+    let red_packet = &ctx.accounts.red_packet;
+    let claimer = ctx.accounts.claimer.key();
+    require!(
+        red_packet.red_packet_type == 4,
+        RedPacketError::InvalidRedPacketType
+    );
+    validate_claim(red_packet, red_packet_id)?;
+    require!(
+        index < red_packet.packet_count,
+        RedPacketError::InvalidClaimIndex
+    );
+    utils::verify_indexed_merkle_proof(index, claimer, amount, &proof, &red_packet.merkle_root)?;
+
+    {
+        let bitmap_info = ctx.accounts.claim_bitmap.to_account_info();
+        let bit_count = ctx.accounts.claim_bitmap.load()?.bit_count;
+        require!(
+            bitmap_info.data_len() >= ClaimBitmap::space(bit_count),
+            RedPacketError::ClaimBitmapNotReady
+        );
+        let mut data = bitmap_info.try_borrow_mut_data()?;
+        let byte = &mut data[ClaimBitmap::HEADER_SPACE + index as usize / 8];
+        let mask = 1u8 << (index % 8);
+        require!(*byte & mask == 0, RedPacketError::AlreadyClaimed);
+        *byte |= mask;
+    }
+
+    let accounts = ClaimAccounts {
+        payer: ctx.accounts.claimer.to_account_info(),
+        recipient: ctx.accounts.claimer.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+        pool_ata: ctx.accounts.pool_ata.to_account_info(),
+        recipient_ata: ctx.accounts.user_ata.to_account_info(),
+        fee_receiver: ctx.accounts.fee_receiver.to_account_info(),
+        system_program: ctx.accounts.system_program.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
+        slot_hashes: None,
+        claim_commit: None,
+        gate_token_account: None,
+        gate_metadata: None,
+        protocol_config: ctx.accounts.protocol_config.clone().into_inner(),
+    };
+    pay_claim(
+        &mut ctx.accounts.red_packet,
+        &accounts,
+        ctx.remaining_accounts,
+        amount,
+        None,
+        red_packet_id,
+    )
+}
+
+#[derive(Accounts)]
+pub struct ClaimIndexedRedPacket<'info> {
+    #[account(mut)]
+    pub claimer: Signer<'info>,
+
+    #[account(mut)]
+    pub red_packet: Account<'info, RedPacket>,
+
+    #[account(
+        mut,
+        seeds = [CLAIM_BITMAP_SEED, red_packet.key().as_ref()],
+        bump
+    )]
+    pub claim_bitmap: AccountLoader<'info, ClaimBitmap>,
+
+    /// CHECK: This can be SOL (Pubkey::default) or SPL token mint
+    #[account()]
+    pub mint: UncheckedAccount<'info>,
+
+    /// CHECK: Pool ATA for SPL tokens, red_packet account for SOL
+    #[account(mut)]
+    pub pool_ata: UncheckedAccount<'info>,
+
+    /// CHECK: User's ATA for SPL tokens, user for SOL
+    #[account(mut)]
+    pub user_ata: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        address = protocol_config.fee_receiver @ RedPacketError::InvalidFeeReceiver
+    )]
+    pub fee_receiver: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+    #[account(
+    constraint = token_program.key() == spl_token::id() || token_program.key() == spl_token_2022::id() @ RedPacketError::InvalidTokenProgram
+    )]
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
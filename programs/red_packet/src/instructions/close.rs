@@ -0,0 +1,137 @@
+use crate::{
+    events::RedPacketClosed,
+    mint_info::{MintInfo, MintUsage},
+    utils, RedPacket, RedPacketError,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_interface::{self, TokenInterface, TransferChecked};
+use spl_token_2022::extension::StateWithExtensions;
+use spl_token_2022::state::Account as TokenAccount2022;
+
+pub fn close_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, CloseRedPacket<'info>>,
+    red_packet_id: u64,
+) -> Result<()> {
+    let red_packet = &ctx.accounts.red_packet;
+    let red_packet_key = red_packet.key();
+
+    require!(
+        red_packet.red_packet_id == red_packet_id,
+        RedPacketError::InvalidRedPacketId
+    );
+    require!(
+        ctx.accounts.creator.key() == red_packet.creator,
+        RedPacketError::Unauthorized
+    );
+    // 只有全部领完（或余额为 0）的红包才能关闭，未领完的红包走 refund
+    require!(
+        red_packet.claimed_count >= red_packet.packet_count || red_packet.remaining_amount == 0,
+        RedPacketError::RedPacketNotDrained
+    );
+
+    // 平分红包可能留下除不尽的零头，随账户一起退还给创建者
+    let mut leftover_amount = red_packet.remaining_amount;
+
+    if !red_packet.is_sol {
+        let mint_info = MintInfo::load_pool(
+            &ctx.accounts.mint,
+            &ctx.accounts.pool_ata,
+            &red_packet_key,
+            &red_packet.mint,
+            &ctx.accounts.token_program.key(),
+        )?;
+
+        let pool_balance = {
+            let pool_data = ctx.accounts.pool_ata.data.borrow();
+            StateWithExtensions::<TokenAccount2022>::unpack(&pool_data)
+                .map_err(|_| RedPacketError::InvalidPoolAta)?
+                .base
+                .amount
+        };
+
+        let signer_seeds: &[&[u8]] = &[
+            b"red_packet",
+            red_packet.creator.as_ref(),
+            &red_packet.red_packet_id.to_le_bytes(),
+            &[red_packet.bump],
+        ];
+
+        // 池子里剩余的代币必须先转走，否则无法关闭 ATA
+        if pool_balance > 0 {
+            mint_info.check_usage(MintUsage::Refund)?;
+            utils::transfer_checked_with_hook(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.pool_ata.to_account_info(),
+                        mint: ctx.accounts.mint.to_account_info(),
+                        to: ctx.accounts.creator_ata.to_account_info(),
+                        authority: red_packet.to_account_info(),
+                    },
+                    &[signer_seeds],
+                )
+                .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+                pool_balance,
+                mint_info.decimals,
+            )?;
+            leftover_amount = pool_balance;
+        }
+
+        utils::harvest_pool_fees(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.mint.to_account_info(),
+            &ctx.accounts.pool_ata.to_account_info(),
+        )?;
+
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token_interface::CloseAccount {
+                account: ctx.accounts.pool_ata.to_account_info(),
+                destination: ctx.accounts.creator.to_account_info(),
+                authority: red_packet.to_account_info(),
+            },
+            &[signer_seeds],
+        ))?;
+    }
+
+    ctx.accounts.red_packet.remaining_amount = 0;
+
+    emit!(RedPacketClosed {
+        creator: ctx.accounts.creator.key(),
+        red_packet: red_packet_key,
+        leftover_amount,
+        red_packet_id,
+    });
+
+    // 红包账户本身由 `close = creator` 在指令结束时关闭
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(red_packet_id: u64)]
+pub struct CloseRedPacket<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    #[account(mut, close = creator)]
+    pub red_packet: Account<'info, RedPacket>,
+
+    /// CHECK: This can be SOL (Pubkey::default) or SPL token mint, must be writable for transfer-fee mints to harvest withheld fees
+    #[account()]
+    pub mint: UncheckedAccount<'info>,
+
+    /// CHECK: Creator's ATA for SPL tokens, creator for SOL
+    #[account(mut)]
+    pub creator_ata: UncheckedAccount<'info>,
+
+    /// CHECK: Pool ATA for SPL tokens, red_packet account for SOL
+    #[account(mut)]
+    pub pool_ata: UncheckedAccount<'info>,
+
+    #[account(
+    constraint = token_program.key() == spl_token::id() || token_program.key() == spl_token_2022::id() @ RedPacketError::InvalidTokenProgram
+    )]
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,91 @@
+use crate::{
+    config::{CLAIM_COMMIT_SEED, CLAIM_COMMIT_SPACE},
+    utils, ClaimCommit, RedPacket, RedPacketError,
+};
+use anchor_lang::{prelude::*, solana_program::sysvar::slot_hashes};
+
+/// 口令红包和类型 3 / 7 领取的第一步，之后的 slot 才能领取。
+///
+/// 口令红包提交 sha256(red_packet ‖ claimer ‖ code)，重复提交会覆盖之前的承诺并重新计时。
+/// 类型 3 / 7 不校验 commitment，只记录提交时的 slot，领取时混入该 slot 的 hash；
+/// 每个领取者只能提交一次，否则可以反复重新提交直到抽中满意的金额。
+pub fn commit_handler(
+    ctx: Context<CommitClaim>,
+    red_packet_id: u64,
+    commitment: [u8; 32],
+) -> Result<()> {
+    let red_packet = &ctx.accounts.red_packet;
+    let clock = Clock::get()?;
+    require!(
+        red_packet.red_packet_id == red_packet_id,
+        RedPacketError::InvalidRedPacketId
+    );
+    require!(
+        matches!(red_packet.red_packet_type, 3 | 5 | 6 | 7),
+        RedPacketError::InvalidRedPacketType
+    );
+    // 取消后的宽限期只留给已经提交过承诺的领取者
+    require!(!red_packet.cancelled, RedPacketError::RedPacketCancelled);
+    require!(
+        clock.unix_timestamp < red_packet.expiry_time,
+        RedPacketError::RedPacketExpired
+    );
+
+    let claim_commit = &mut ctx.accounts.claim_commit;
+    if matches!(red_packet.red_packet_type, 3 | 7) {
+        require!(claim_commit.slot == 0, RedPacketError::ClaimCommitExists);
+    }
+    claim_commit.commitment = commitment;
+    claim_commit.slot = clock.slot;
+    Ok(())
+}
+
+/// 承诺的 slot 滑出 SlotHashes 后类型 3 / 7 无法再揭示，也不能重新提交。
+/// 领取者关闭这个承诺拿回租金，之后可以重新提交。红包已经退款关闭时同样可以关闭
+pub fn close_commit_handler(ctx: Context<CloseClaimCommit>) -> Result<()> {
+    require!(
+        utils::claim_commit_expired(&ctx.accounts.slot_hashes, ctx.accounts.claim_commit.slot)?,
+        RedPacketError::ClaimCommitNotExpired
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CommitClaim<'info> {
+    #[account(mut)]
+    pub claimer: Signer<'info>,
+
+    pub red_packet: Account<'info, RedPacket>,
+
+    #[account(
+        init_if_needed,
+        payer = claimer,
+        space = CLAIM_COMMIT_SPACE,
+        seeds = [CLAIM_COMMIT_SEED, red_packet.key().as_ref(), claimer.key().as_ref()],
+        bump
+    )]
+    pub claim_commit: Account<'info, ClaimCommit>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseClaimCommit<'info> {
+    #[account(mut)]
+    pub claimer: Signer<'info>,
+
+    /// CHECK: Only used to derive the claim_commit PDA, the red packet may already be closed
+    pub red_packet: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = claimer,
+        seeds = [CLAIM_COMMIT_SEED, red_packet.key().as_ref(), claimer.key().as_ref()],
+        bump
+    )]
+    pub claim_commit: Account<'info, ClaimCommit>,
+
+    /// CHECK: SlotHashes sysvar, used to tell whether the commit slot can still be revealed
+    #[account(address = slot_hashes::ID @ RedPacketError::InvalidSlotHashes)]
+    pub slot_hashes: UncheckedAccount<'info>,
+}
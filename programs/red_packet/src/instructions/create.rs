@@ -2,34 +2,23 @@
 
 use crate::{
     config::{
-        self, CREATE_FEE, DEFAULT_RED_PACKET_EXPIRY_DAYS, FEE_RECEIVER, MAX_PACKET_COUNT,
-        RED_PACKET_SPACE,
+        self, DEFAULT_RED_PACKET_EXPIRY_DAYS, MAX_PACKET_COUNT, MAX_PRIZE_TIERS, MAX_START_DELAY,
+        PROTOCOL_CONFIG_SEED, RED_PACKET_SPACE,
     },
     errors::RedPacketError,
     events::RedPacketCreated,
-    CreatorState, RedPacket,
+    mint_info::{MintInfo, MintUsage},
+    utils, CreatorState, HolderGate, PrizeTier, ProtocolConfig, RedPacket,
 };
-use anchor_lang::solana_program::program_option::COption as SolanaCOption;
-use anchor_lang::{prelude::*, solana_program::program_pack::Pack, system_program};
+use anchor_lang::{prelude::*, system_program};
 use anchor_spl::{
-    associated_token::AssociatedToken,
-    token::spl_token,
-    token_2022::TransferChecked,
-    token_interface::{self, TokenInterface},
+    associated_token::AssociatedToken, token::spl_token, token_2022::TransferChecked,
+    token_interface::TokenInterface,
 };
-use spl_token::state::Mint as MintLegacy;
-use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
-use spl_token_2022::{
-    extension::{
-        confidential_transfer::ConfidentialTransferMint, non_transferable::NonTransferable,
-        permanent_delegate::PermanentDelegate, transfer_hook::TransferHook,
-        BaseStateWithExtensions, StateWithExtensions,
-    },
-    state::Mint as Mint2022,
-}; // 引入 SPL Token 的 Mint 结构
 
-pub fn create_handler(
-    ctx: Context<CreateRedPacket>,
+#[allow(clippy::too_many_arguments)]
+pub fn create_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, CreateRedPacket<'info>>,
     total_amount: u64,
     packet_count: u32,
     red_packet_type: u8,
@@ -37,17 +26,37 @@ pub fn create_handler(
     is_sol: bool,
     expiry_days: Option<i64>,
     random_seed: Option<u64>,
+    start_time: Option<i64>,
+    code_hash: Option<[u8; 32]>,
+    holder_gate: Option<HolderGate>,
+    prize_tiers: Option<Vec<PrizeTier>>,
 ) -> Result<()> {
+    require!(
+        !ctx.accounts
+            .protocol_config
+            .is_paused(ProtocolConfig::PAUSE_CREATE),
+        RedPacketError::ProgramPaused
+    );
     validate_common_parameters(packet_count, total_amount, expiry_days)?;
+    let create_fee = ctx
+        .accounts
+        .protocol_config
+        .create_fee
+        .fee(total_amount, is_sol)
+        .ok_or(RedPacketError::ArithmeticOverflow)?;
 
     let red_packet_id = initialize_red_packet(
         &mut ctx.accounts.creator_state,
         &mut ctx.accounts.red_packet,
         &ctx.bumps.red_packet,
         expiry_days,
+        start_time,
         red_packet_type,
         random_seed,
         merkle_root,
+        code_hash,
+        holder_gate,
+        prize_tiers,
         ctx.accounts.creator.key(),
         ctx.accounts.mint.key(),
         total_amount,
@@ -55,11 +64,11 @@ pub fn create_handler(
         is_sol,
     )?;
 
-    let (has_transfer_fee, has_transfer_hook, has_permanent_delegate, has_close_authority);
+    let mut mint_info = None;
     if is_sol {
         let rent_exempt = Rent::get()?.minimum_balance(RED_PACKET_SPACE);
         require!(
-            ctx.accounts.creator.lamports() >= total_amount + rent_exempt + CREATE_FEE,
+            ctx.accounts.creator.lamports() >= total_amount + rent_exempt + create_fee,
             RedPacketError::InsufficientFunds
         );
 
@@ -73,11 +82,6 @@ pub fn create_handler(
             ),
             total_amount,
         )?;
-
-        has_transfer_fee = false;
-        has_transfer_hook = false;
-        has_permanent_delegate = false;
-        has_close_authority = false;
     } else {
         if ctx.accounts.pool_ata.data_is_empty() {
             anchor_spl::associated_token::create(CpiContext::new(
@@ -93,77 +97,21 @@ pub fn create_handler(
             ))?;
         }
 
-        let mint_data = ctx.accounts.mint.data.borrow();
-
-        let decimals;
-        let actual_amount;
-        if ctx.accounts.token_program.key() == spl_token::id() {
-            // 处理 SPL Token
-            let mint =
-                MintLegacy::unpack(&mint_data).map_err(|_| RedPacketError::InvalidMintAccount)?;
-            decimals = mint.decimals;
-            actual_amount = total_amount; // SPL Token 无转账费用
-            has_transfer_fee = false;
-            has_transfer_hook = false;
-            has_permanent_delegate = false;
-            has_close_authority = false;
-        } else if ctx.accounts.token_program.key() == spl_token_2022::id() {
-            // 处理 Token-2022
-            let mint = StateWithExtensions::<Mint2022>::unpack(&mint_data)
-                .map_err(|_| RedPacketError::InvalidMintAccount)?;
-            decimals = mint.base.decimals;
-
-            // 检查扩展特性
-            let transfer_fee = mint.get_extension::<TransferFeeConfig>().is_ok();
-            let transfer_hook = mint.get_extension::<TransferHook>().is_ok();
-            let permanent_delegate = mint.get_extension::<PermanentDelegate>().is_ok();
-            let confidential_transfer = mint.get_extension::<ConfidentialTransferMint>().is_ok();
-            let non_transferable = mint.get_extension::<NonTransferable>().is_ok();
-            let close_authority = mint.base.freeze_authority != SolanaCOption::None;
-
-            // 禁用不支持的扩展
-            require!(!transfer_hook, RedPacketError::TransferHookNotSupported);
-            require!(
-                !permanent_delegate,
-                RedPacketError::PermanentDelegateNotSupported
-            );
-            require!(
-                !confidential_transfer,
-                RedPacketError::ConfidentialTransferNotSupported
-            );
-            require!(
-                !non_transferable,
-                RedPacketError::NonTransferableNotSupported
-            );
-
-            actual_amount = if transfer_fee {
-                let fee_config = mint
-                    .get_extension::<TransferFeeConfig>()
-                    .map_err(|_| RedPacketError::InvalidExtension)?;
-                // Properly convert PodU64 to u64
-                let epoch = u64::from(fee_config.newer_transfer_fee.epoch);
-                let post_fee_amount = total_amount;
-
-                let fee = fee_config
-                    .calculate_inverse_epoch_fee(epoch, post_fee_amount)
-                    .ok_or(RedPacketError::FeeCalculationError)?;
-                total_amount
-                    .checked_add(fee)
-                    .ok_or(RedPacketError::ArithmeticOverflow)?
-            } else {
-                total_amount
-            };
-
-            has_transfer_fee = transfer_fee;
-            has_transfer_hook = transfer_hook;
-            has_permanent_delegate = permanent_delegate;
-            has_close_authority = close_authority;
-        } else {
-            return Err(RedPacketError::InvalidTokenProgram.into());
-        };
+        let info = MintInfo::load(&ctx.accounts.mint, &ctx.accounts.token_program.key())?;
+        info.check_usage(MintUsage::Create)?;
+        info.check_risk_policy(ctx.accounts.protocol_config.mint_risk_policy)?;
+        // 转账费从转出金额中扣除，多转入一部分让池子实际收到 total_amount
+        let actual_amount = total_amount
+            .checked_add(utils::calculate_inverse_transfer_fee(
+                &ctx.accounts.mint,
+                total_amount,
+            )?)
+            .ok_or(RedPacketError::ArithmeticOverflow)?;
+        let decimals = info.decimals;
+        mint_info = Some(info);
 
         // 转移SPL代币到pool_ata
-        token_interface::transfer_checked(
+        utils::transfer_checked_with_hook(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 TransferChecked {
@@ -172,32 +120,31 @@ pub fn create_handler(
                     to: ctx.accounts.pool_ata.to_account_info(),
                     authority: ctx.accounts.creator.to_account_info(),
                 },
-            ),
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
             actual_amount,
             decimals,
         )?;
     }
 
     // 更新事件
-    finalize_creation(
-        &ctx,
-        red_packet_id,
-        has_transfer_fee,
-        has_transfer_hook,
-        has_permanent_delegate,
-        has_close_authority,
-    )
+    finalize_creation(&ctx, red_packet_id, create_fee, mint_info)
 }
 
 /// 初始化红包账户数据
+#[allow(clippy::too_many_arguments)]
 fn initialize_red_packet(
     creator_state: &mut Account<'_, CreatorState>,
     red_packet: &mut Account<'_, RedPacket>,
     bump: &u8,
     expiry_days: Option<i64>,
+    start_time: Option<i64>,
     red_packet_type: u8,
     random_seed: Option<u64>,
     merkle_root: Option<[u8; 32]>,
+    code_hash: Option<[u8; 32]>,
+    holder_gate: Option<HolderGate>,
+    prize_tiers: Option<Vec<PrizeTier>>,
     creator_key: Pubkey,
     mint_key: Pubkey,
     total_amount: u64,
@@ -205,8 +152,16 @@ fn initialize_red_packet(
     is_sol: bool,
 ) -> Result<u64> {
     let clock = Clock::get()?;
-    let expiry_time = clock.unix_timestamp
-        + (expiry_days.unwrap_or(DEFAULT_RED_PACKET_EXPIRY_DAYS) * 24 * 60 * 60);
+    // 未指定或已过去的开始时间按创建时刻处理，有效期从开始时间算起
+    let start_time = start_time
+        .unwrap_or(clock.unix_timestamp)
+        .max(clock.unix_timestamp);
+    require!(
+        start_time <= clock.unix_timestamp + MAX_START_DELAY,
+        RedPacketError::InvalidStartTime
+    );
+    let expiry_time =
+        start_time + (expiry_days.unwrap_or(DEFAULT_RED_PACKET_EXPIRY_DAYS) * 24 * 60 * 60);
     let red_packet_id = creator_state.next_red_packet_id;
 
     creator_state.next_red_packet_id = red_packet_id
@@ -214,21 +169,37 @@ fn initialize_red_packet(
         .ok_or(RedPacketError::CounterOverflow)?;
 
     let (share_amount, random_seed_val, merkle_root_val) = match red_packet_type {
-        0 => {
+        0 | 5 => {
             let share = total_amount / packet_count as u64;
             require!(share > 0, RedPacketError::InvalidShareAmount);
             (share, 0, [0; 32])
         }
-        1 => (
+        1 | 3 | 6 | 7 => (
             0,
             random_seed.ok_or(RedPacketError::RandomSeedRequired)?,
             [0; 32],
         ),
-        2 => (0, 0, merkle_root.ok_or(RedPacketError::InvalidMerkleRoot)?),
+        2 | 4 => (0, 0, merkle_root.ok_or(RedPacketError::InvalidMerkleRoot)?),
         _ => return Err(RedPacketError::InvalidRedPacketType.into()),
     };
+    // 口令红包在平分/随机之上再要求口令
+    let code_hash_val = match red_packet_type {
+        5 | 6 => code_hash.ok_or(RedPacketError::CodeHashRequired)?,
+        _ => [0; 32],
+    };
+    // 分档红包的金额全部由奖项决定，其他类型不能带奖项
+    let tiers = match (red_packet_type, prize_tiers) {
+        (7, Some(prize_tiers)) => {
+            utils::validate_prize_tiers(&prize_tiers, packet_count, total_amount)?
+        }
+        (7, None) | (_, Some(_)) => return Err(RedPacketError::InvalidPrizeTiers.into()),
+        (_, None) => [PrizeTier::default(); MAX_PRIZE_TIERS],
+    };
+    let holder_gate = holder_gate.unwrap_or_default();
+    validate_holder_gate(&holder_gate, red_packet_type)?;
 
     red_packet.set_inner(RedPacket {
+        version: RedPacket::CURRENT_VERSION,
         creator: creator_key,
         mint: mint_key,
         total_amount,
@@ -237,6 +208,7 @@ fn initialize_red_packet(
         claimed_count: 0,
         red_packet_type,
         expiry_time,
+        start_time,
         is_sol,
         expiry_time_changes: 0,
         red_packet_id,
@@ -244,6 +216,12 @@ fn initialize_red_packet(
         share_amount,
         random_seed: random_seed_val,
         merkle_root: merkle_root_val,
+        code_hash: code_hash_val,
+        holder_gate,
+        tiers,
+        cancelled: false,
+        cancel_slot: 0,
+        reserved: [0; 7],
     });
 
     Ok(red_packet_id)
@@ -272,25 +250,47 @@ fn validate_common_parameters(
     Ok(())
 }
 
+/// Merkle 名单本身已经限定了领取者，不再叠加持有者门槛
+fn validate_holder_gate(holder_gate: &HolderGate, red_packet_type: u8) -> Result<()> {
+    match holder_gate.kind {
+        0 => return Ok(()),
+        1 => require!(
+            holder_gate.min_amount > 0,
+            RedPacketError::InvalidHolderGate
+        ),
+        2 => {}
+        _ => return Err(RedPacketError::InvalidHolderGate.into()),
+    }
+    require!(
+        holder_gate.mint != Pubkey::default(),
+        RedPacketError::InvalidHolderGate
+    );
+    require!(
+        !matches!(red_packet_type, 2 | 4),
+        RedPacketError::InvalidHolderGate
+    );
+    Ok(())
+}
+
 /// 最终处理：支付费用并发送事件
 fn finalize_creation(
     ctx: &Context<CreateRedPacket>,
     red_packet_id: u64,
-    has_transfer_fee: bool,
-    has_transfer_hook: bool,
-    has_permanent_delegate: bool,
-    has_close_authority: bool,
+    create_fee: u64,
+    mint_info: Option<MintInfo>,
 ) -> Result<()> {
-    system_program::transfer(
-        CpiContext::new(
-            ctx.accounts.system_program.to_account_info(),
-            system_program::Transfer {
-                from: ctx.accounts.creator.to_account_info(),
-                to: ctx.accounts.fee_receiver.to_account_info(),
-            },
-        ),
-        CREATE_FEE,
-    )?;
+    if create_fee > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.creator.to_account_info(),
+                    to: ctx.accounts.fee_receiver.to_account_info(),
+                },
+            ),
+            create_fee,
+        )?;
+    }
 
     emit!(RedPacketCreated {
         creator: ctx.accounts.creator.key(),
@@ -299,21 +299,25 @@ fn finalize_creation(
         packet_count: ctx.accounts.red_packet.packet_count,
         red_packet_type: ctx.accounts.red_packet.red_packet_type,
         expiry_time: ctx.accounts.red_packet.expiry_time,
+        start_time: ctx.accounts.red_packet.start_time,
+        holder_gate: ctx.accounts.red_packet.holder_gate,
         is_sol: ctx.accounts.red_packet.is_sol,
         red_packet_id,
         bump: ctx.accounts.red_packet.bump,
         mint: ctx.accounts.red_packet.mint,
-        has_transfer_fee,
-        has_transfer_hook,
-        has_permanent_delegate,
-        has_close_authority,
+        // SOL 红包没有 mint，所有标记都是 false
+        has_transfer_fee: mint_info.is_some_and(|info| info.has_transfer_fee),
+        has_transfer_hook: mint_info.is_some_and(|info| info.has_transfer_hook),
+        has_permanent_delegate: mint_info.is_some_and(|info| info.has_permanent_delegate),
+        has_close_authority: mint_info.is_some_and(|info| info.has_close_authority),
+        has_freeze_authority: mint_info.is_some_and(|info| info.has_freeze_authority),
     });
 
     Ok(())
 }
 
 #[derive(Accounts)]
-#[instruction(total_amount: u64, packet_count: u32, red_packet_type: u8, merkle_root: Option<[u8; 32]>, is_sol: bool, expiry_days: Option<i64>, random_seed: Option<u64>)]
+#[instruction(total_amount: u64, packet_count: u32, red_packet_type: u8, merkle_root: Option<[u8; 32]>, is_sol: bool, expiry_days: Option<i64>, random_seed: Option<u64>, start_time: Option<i64>, code_hash: Option<[u8; 32]>, holder_gate: Option<HolderGate>, prize_tiers: Option<Vec<PrizeTier>>)]
 pub struct CreateRedPacket<'info> {
     #[account(mut)]
     pub creator: Signer<'info>,
@@ -352,9 +356,15 @@ pub struct CreateRedPacket<'info> {
     #[account(mut)]
     pub pool_ata: UncheckedAccount<'info>,
 
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
     #[account(
         mut,
-        address = FEE_RECEIVER
+        address = protocol_config.fee_receiver @ RedPacketError::InvalidFeeReceiver
     )]
     pub fee_receiver: SystemAccount<'info>,
 
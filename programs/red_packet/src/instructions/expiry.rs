@@ -2,26 +2,41 @@
 
 use anchor_lang::prelude::*;
 
-use crate::{config, events::ExpiryTimeUpdated, RedPacket, RedPacketError};
+use crate::{
+    config::{self, CREATOR_STATE_SEED, PROTOCOL_CONFIG_SEED},
+    events::{ExpiryTimeUpdated, StartTimeUpdated},
+    utils, CreatorState, ProtocolConfig, RedPacket, RedPacketError,
+};
 
 pub fn expiry_handler(ctx: Context<SetExpiryTime>, expiry_time: i64) -> Result<()> {
     let red_packet = &mut ctx.accounts.red_packet;
     let red_packet_key = red_packet.key();
     let current_time = Clock::get()?.unix_timestamp;
+    utils::check_manager(
+        red_packet,
+        &ctx.accounts.creator_state,
+        &ctx.accounts.authority.key(),
+        CreatorState::PERMISSION_EXPIRY,
+    )?;
     require!(
-        ctx.accounts.authority.key() == red_packet.creator,
-        RedPacketError::Unauthorized
+        !ctx.accounts
+            .protocol_config
+            .is_paused(ProtocolConfig::PAUSE_EXPIRY),
+        RedPacketError::ProgramPaused
     );
+    require!(!red_packet.cancelled, RedPacketError::RedPacketCancelled);
     require!(
         red_packet.expiry_time_changes < config::MAX_EXPIRY_TIME_CHANGES,
         RedPacketError::TooManyExpiryChanges
     );
+    // 尚未开始的红包，有效期从开始时间起算
+    let window_start = current_time.max(red_packet.start_time);
     require!(
-        expiry_time > current_time,
+        expiry_time > window_start,
         RedPacketError::InvalidExpiryTime
     );
     require!(
-        expiry_time <= current_time + (30 * 24 * 60 * 60),
+        expiry_time <= window_start + (30 * 24 * 60 * 60),
         RedPacketError::InvalidExpiryTime
     );
     red_packet.expiry_time = expiry_time;
@@ -36,9 +51,65 @@ pub fn expiry_handler(ctx: Context<SetExpiryTime>, expiry_time: i64) -> Result<(
     Ok(())
 }
 
+/// 调整开始时间，只能在红包开始之前修改，不计入 expiry_time_changes
+pub fn start_time_handler(ctx: Context<SetExpiryTime>, start_time: i64) -> Result<()> {
+    let red_packet = &mut ctx.accounts.red_packet;
+    let red_packet_key = red_packet.key();
+    let current_time = Clock::get()?.unix_timestamp;
+    utils::check_manager(
+        red_packet,
+        &ctx.accounts.creator_state,
+        &ctx.accounts.authority.key(),
+        CreatorState::PERMISSION_EXPIRY,
+    )?;
+    require!(
+        !ctx.accounts
+            .protocol_config
+            .is_paused(ProtocolConfig::PAUSE_EXPIRY),
+        RedPacketError::ProgramPaused
+    );
+    require!(!red_packet.cancelled, RedPacketError::RedPacketCancelled);
+    require!(
+        current_time < red_packet.start_time,
+        RedPacketError::RedPacketAlreadyStarted
+    );
+    // 允许传入过去的时间表示立即开始
+    let start_time = start_time.max(current_time);
+    require!(
+        start_time <= current_time + config::MAX_START_DELAY,
+        RedPacketError::InvalidStartTime
+    );
+    require!(
+        start_time < red_packet.expiry_time,
+        RedPacketError::InvalidStartTime
+    );
+    red_packet.start_time = start_time;
+
+    emit!(StartTimeUpdated {
+        red_packet: red_packet_key,
+        new_start_time: start_time,
+        red_packet_id: red_packet.red_packet_id,
+    });
+
+    Ok(())
+}
+
 #[derive(Accounts)]
 pub struct SetExpiryTime<'info> {
     #[account(mut)]
     pub red_packet: Account<'info, RedPacket>,
+    /// 创建者或持有 PERMISSION_EXPIRY 的操作员
     pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [CREATOR_STATE_SEED, red_packet.creator.as_ref()],
+        bump = creator_state.bump
+    )]
+    pub creator_state: Account<'info, CreatorState>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
 }
@@ -5,8 +5,9 @@ use crate::{
 use anchor_lang::prelude::*;
 
 pub fn handler_creator_state(ctx: Context<InitializeCreatorState>) -> Result<()> {
-    ctx.accounts.creator_state.next_red_packet_id = 0;
-    ctx.accounts.creator_state.bump = ctx.bumps.creator_state;
+    ctx.accounts
+        .creator_state
+        .set_inner(CreatorState::new(ctx.bumps.creator_state));
     Ok(())
 }
 
@@ -0,0 +1,138 @@
+use crate::{
+    config::{
+        CREATOR_STATE_SPACE, LEGACY_CREATOR_STATE_SPACE, LEGACY_RED_PACKET_SPACE, RED_PACKET_SPACE,
+    },
+    events::RedPacketMigrated,
+    CreatorState, CreatorStateV0, RedPacket, RedPacketError, RedPacketV0,
+};
+use anchor_lang::{prelude::*, system_program, Discriminator};
+
+/// 把旧布局的红包账户扩容并改写为当前布局。任何人都可以代付新增的租金，
+/// 迁移只补默认值，不会改变红包的金额和状态。
+pub fn migrate_handler(ctx: Context<MigrateRedPacket>) -> Result<()> {
+    let red_packet_info = ctx.accounts.red_packet.to_account_info();
+    require_keys_eq!(
+        *red_packet_info.owner,
+        crate::ID,
+        RedPacketError::InvalidAccountOwner
+    );
+
+    let legacy = {
+        let data = red_packet_info.try_borrow_data()?;
+        require!(
+            data.starts_with(RedPacket::DISCRIMINATOR),
+            RedPacketError::InvalidRedPacketAccount
+        );
+        // 当前布局的账户直接拒绝，避免重复迁移
+        require!(
+            data.len() == LEGACY_RED_PACKET_SPACE,
+            RedPacketError::RedPacketAlreadyMigrated
+        );
+        RedPacketV0::deserialize(&mut &data[RedPacket::DISCRIMINATOR.len()..])?
+    };
+
+    grow_account(
+        &red_packet_info,
+        &ctx.accounts.payer,
+        &ctx.accounts.system_program,
+        LEGACY_RED_PACKET_SPACE,
+        RED_PACKET_SPACE,
+    )?;
+    let red_packet = RedPacket::from_v0(legacy);
+    red_packet.try_serialize(&mut &mut red_packet_info.try_borrow_mut_data()?[..])?;
+
+    emit!(RedPacketMigrated {
+        red_packet: red_packet_info.key(),
+        red_packet_id: red_packet.red_packet_id,
+        from_version: 0,
+        to_version: RedPacket::CURRENT_VERSION,
+    });
+    Ok(())
+}
+
+/// 把引入操作员和版本号之前的 CreatorState 扩容到当前布局，操作员列表为空
+pub fn migrate_creator_state_handler(ctx: Context<MigrateCreatorState>) -> Result<()> {
+    let creator_state_info = ctx.accounts.creator_state.to_account_info();
+    require_keys_eq!(
+        *creator_state_info.owner,
+        crate::ID,
+        RedPacketError::InvalidAccountOwner
+    );
+
+    let legacy = {
+        let data = creator_state_info.try_borrow_data()?;
+        require!(
+            data.starts_with(CreatorState::DISCRIMINATOR),
+            RedPacketError::InvalidCreatorState
+        );
+        require!(
+            data.len() == LEGACY_CREATOR_STATE_SPACE,
+            RedPacketError::RedPacketAlreadyMigrated
+        );
+        CreatorStateV0::deserialize(&mut &data[CreatorState::DISCRIMINATOR.len()..])?
+    };
+
+    grow_account(
+        &creator_state_info,
+        &ctx.accounts.payer,
+        &ctx.accounts.system_program,
+        LEGACY_CREATOR_STATE_SPACE,
+        CREATOR_STATE_SPACE,
+    )?;
+    CreatorState::from_v0(legacy)
+        .try_serialize(&mut &mut creator_state_info.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+/// 由 payer 补齐新旧大小之间的租金差后扩容。SOL 红包的 lamports 里还有待领取的金额，
+/// 所以只补差额而不是按当前余额计算；转账 CPI 必须在改写账户数据之前完成
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    old_space: usize,
+    new_space: usize,
+) -> Result<()> {
+    let rent = Rent::get()?;
+    let rent_increase = rent
+        .minimum_balance(new_space)
+        .saturating_sub(rent.minimum_balance(old_space));
+    if rent_increase > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            rent_increase,
+        )?;
+    }
+    account.resize(new_space)?;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateRedPacket<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Old layouts cannot be deserialized as RedPacket, owner and discriminator are checked in the handler
+    #[account(mut)]
+    pub red_packet: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateCreatorState<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Old layouts cannot be deserialized as CreatorState, owner and discriminator are checked in the handler
+    #[account(mut)]
+    pub creator_state: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
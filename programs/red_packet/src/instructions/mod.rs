@@ -1,11 +1,35 @@
 // instructions/mod.rs
+pub mod bundle;
+pub use bundle::*;
 pub mod claim;
 pub use claim::*;
+pub mod claim_bitmap;
+pub use claim_bitmap::*;
+pub mod cancel;
+pub use cancel::*;
+pub mod claim_indexed;
+pub use claim_indexed::*;
+pub mod close;
+pub use close::*;
+pub mod commit;
+pub use commit::*;
 pub mod create;
 pub use create::*;
 pub mod expiry;
 pub use expiry::*;
 pub mod initialize;
 pub use initialize::*;
+pub mod migrate;
+pub use migrate::*;
+pub mod operator;
+pub use operator::*;
+pub mod protocol_config;
+pub use protocol_config::*;
 pub mod refund;
 pub use refund::*;
+pub mod relay_claim;
+pub use relay_claim::*;
+pub mod sweep;
+pub use sweep::*;
+pub mod top_up;
+pub use top_up::*;
@@ -0,0 +1,74 @@
+use crate::{
+    config::{CREATOR_STATE_SEED, MAX_OPERATORS},
+    events::OperatorUpdated,
+    CreatorState, Operator, RedPacketError,
+};
+use anchor_lang::prelude::*;
+
+/// 添加操作员，已存在时覆盖其权限
+pub fn add_operator_handler(
+    ctx: Context<ManageOperators>,
+    operator: Pubkey,
+    permissions: u8,
+) -> Result<()> {
+    require!(
+        permissions != 0 && permissions & !CreatorState::PERMISSION_ALL == 0,
+        RedPacketError::InvalidOperatorPermissions
+    );
+    let creator = ctx.accounts.creator.key();
+    require_keys_neq!(
+        operator,
+        creator,
+        RedPacketError::InvalidOperatorPermissions
+    );
+
+    let operators = &mut ctx.accounts.creator_state.operators;
+    match operators.iter_mut().find(|op| op.key == operator) {
+        Some(existing) => existing.permissions = permissions,
+        None => {
+            require!(
+                operators.len() < MAX_OPERATORS,
+                RedPacketError::TooManyOperators
+            );
+            operators.push(Operator {
+                key: operator,
+                permissions,
+            });
+        }
+    }
+
+    emit!(OperatorUpdated {
+        creator,
+        operator,
+        permissions,
+    });
+    Ok(())
+}
+
+pub fn remove_operator_handler(ctx: Context<ManageOperators>, operator: Pubkey) -> Result<()> {
+    let operators = &mut ctx.accounts.creator_state.operators;
+    let index = operators
+        .iter()
+        .position(|op| op.key == operator)
+        .ok_or(RedPacketError::OperatorNotFound)?;
+    operators.remove(index);
+
+    emit!(OperatorUpdated {
+        creator: ctx.accounts.creator.key(),
+        operator,
+        permissions: 0,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ManageOperators<'info> {
+    pub creator: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [CREATOR_STATE_SEED, creator.key().as_ref()],
+        bump = creator_state.bump
+    )]
+    pub creator_state: Account<'info, CreatorState>,
+}
@@ -0,0 +1,191 @@
+use crate::{
+    config::{CLAIM_FEE, CREATE_FEE, FEE_RECEIVER, PROTOCOL_CONFIG_SEED, PROTOCOL_CONFIG_SPACE},
+    events::{AdminTransferProposed, AdminTransferred, PauseUpdated, ProtocolConfigUpdated},
+    FeeRule, ProtocolConfig, RedPacketError,
+};
+use anchor_lang::prelude::*;
+
+/// 创建全局配置，只有程序的升级权限可以调用，签名者成为管理员，费用和收款地址沿用原来的常量
+pub fn initialize_handler(ctx: Context<InitializeProtocolConfig>) -> Result<()> {
+    let protocol_config = &mut ctx.accounts.protocol_config;
+    protocol_config.set_inner(ProtocolConfig {
+        version: ProtocolConfig::CURRENT_VERSION,
+        admin: ctx.accounts.admin.key(),
+        pending_admin: Pubkey::default(),
+        fee_receiver: FEE_RECEIVER,
+        create_fee: FeeRule {
+            lamports: CREATE_FEE,
+            bps: 0,
+        },
+        claim_fee: FeeRule {
+            lamports: CLAIM_FEE,
+            bps: 0,
+        },
+        pause_flags: 0,
+        mint_risk_policy: 0,
+        bump: ctx.bumps.protocol_config,
+        reserved: [0; 32],
+    });
+    emit_config_updated(protocol_config);
+    Ok(())
+}
+
+/// 修改费用或收款地址，传 None 的字段保持不变
+pub fn update_handler(
+    ctx: Context<UpdateProtocolConfig>,
+    create_fee: Option<FeeRule>,
+    claim_fee: Option<FeeRule>,
+    fee_receiver: Option<Pubkey>,
+) -> Result<()> {
+    let protocol_config = &mut ctx.accounts.protocol_config;
+    if let Some(create_fee) = create_fee {
+        require!(
+            create_fee.bps <= FeeRule::MAX_BPS,
+            RedPacketError::InvalidFeeConfig
+        );
+        protocol_config.create_fee = create_fee;
+    }
+    if let Some(claim_fee) = claim_fee {
+        require!(
+            claim_fee.bps <= FeeRule::MAX_BPS,
+            RedPacketError::InvalidFeeConfig
+        );
+        protocol_config.claim_fee = claim_fee;
+    }
+    if let Some(fee_receiver) = fee_receiver {
+        require!(
+            fee_receiver != Pubkey::default(),
+            RedPacketError::InvalidFeeConfig
+        );
+        protocol_config.fee_receiver = fee_receiver;
+    }
+    emit_config_updated(protocol_config);
+    Ok(())
+}
+
+/// 紧急暂停：按位设置暂停的指令，0 表示全部恢复。refund 不受影响，创建者总能取回资金
+pub fn set_pause_handler(ctx: Context<UpdateProtocolConfig>, pause_flags: u8) -> Result<()> {
+    require!(
+        pause_flags & !ProtocolConfig::PAUSE_ALL == 0,
+        RedPacketError::InvalidPauseFlags
+    );
+    let protocol_config = &mut ctx.accounts.protocol_config;
+    let previous_flags = protocol_config.pause_flags;
+    protocol_config.pause_flags = pause_flags;
+
+    emit!(PauseUpdated {
+        admin: ctx.accounts.admin.key(),
+        previous_flags,
+        pause_flags,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// 设置 mint 风险策略：按位拒绝有冻结权限 / 关闭权限的 mint，0 表示只在创建事件里标记。
+/// 只影响之后创建的红包，已有红包的领取和退款不受影响
+pub fn set_mint_risk_policy_handler(
+    ctx: Context<UpdateProtocolConfig>,
+    mint_risk_policy: u8,
+) -> Result<()> {
+    require!(
+        mint_risk_policy & !ProtocolConfig::REJECT_ALL_RISKS == 0,
+        RedPacketError::InvalidMintRiskPolicy
+    );
+    let protocol_config = &mut ctx.accounts.protocol_config;
+    protocol_config.mint_risk_policy = mint_risk_policy;
+    emit_config_updated(protocol_config);
+    Ok(())
+}
+
+/// 两步转移的第一步：当前管理员提名新管理员，传 Pubkey::default() 取消提名
+pub fn propose_admin_handler(ctx: Context<UpdateProtocolConfig>, new_admin: Pubkey) -> Result<()> {
+    let protocol_config = &mut ctx.accounts.protocol_config;
+    protocol_config.pending_admin = new_admin;
+    emit!(AdminTransferProposed {
+        admin: protocol_config.admin,
+        pending_admin: new_admin,
+    });
+    Ok(())
+}
+
+/// 第二步：被提名的地址签名接受，避免把管理员转给无法签名的地址
+pub fn accept_admin_handler(ctx: Context<AcceptAdmin>) -> Result<()> {
+    let protocol_config = &mut ctx.accounts.protocol_config;
+    require!(
+        protocol_config.pending_admin != Pubkey::default()
+            && ctx.accounts.pending_admin.key() == protocol_config.pending_admin,
+        RedPacketError::NotPendingAdmin
+    );
+    let previous_admin = protocol_config.admin;
+    protocol_config.admin = protocol_config.pending_admin;
+    protocol_config.pending_admin = Pubkey::default();
+    emit!(AdminTransferred {
+        previous_admin,
+        new_admin: protocol_config.admin,
+    });
+    Ok(())
+}
+
+fn emit_config_updated(protocol_config: &ProtocolConfig) {
+    emit!(ProtocolConfigUpdated {
+        admin: protocol_config.admin,
+        fee_receiver: protocol_config.fee_receiver,
+        create_fee: protocol_config.create_fee,
+        claim_fee: protocol_config.claim_fee,
+        pause_flags: protocol_config.pause_flags,
+        mint_risk_policy: protocol_config.mint_risk_policy,
+    });
+}
+
+#[derive(Accounts)]
+pub struct InitializeProtocolConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = PROTOCOL_CONFIG_SPACE,
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// 本程序的 ProgramData，配置只能由升级权限创建，防止部署后被抢先初始化
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        seeds::program = ProgramData::owner(),
+        bump,
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ RedPacketError::NotUpgradeAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateProtocolConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump,
+        has_one = admin @ RedPacketError::NotProtocolAdmin
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
@@ -1,13 +1,18 @@
-use crate::{config, events::RedPacketRefunded, RedPacket, RedPacketError};
+use crate::{
+    config,
+    events::RedPacketRefunded,
+    mint_info::{MintInfo, MintUsage},
+    utils, RedPacket, RedPacketError,
+};
 use anchor_lang::prelude::*;
 use anchor_spl::token::spl_token;
 use anchor_spl::token_interface::{self, TokenInterface, TransferChecked};
-use spl_token_2022::extension::StateWithExtensions;
-use spl_token_2022::state::Mint as Mint2022;
 
-pub fn refund_handler(ctx: Context<Refund>, red_packet_id: u64) -> Result<()> {
-    let red_packet = &mut ctx.accounts.red_packet;
-    let red_packet_key = red_packet.key();
+pub fn refund_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Refund<'info>>,
+    red_packet_id: u64,
+) -> Result<()> {
+    let red_packet = &ctx.accounts.red_packet;
 
     require!(
         red_packet.red_packet_id == red_packet_id,
@@ -23,12 +28,48 @@ pub fn refund_handler(ctx: Context<Refund>, red_packet_id: u64) -> Result<()> {
         RedPacketError::Unauthorized
     );
 
+    let accounts = RefundAccounts {
+        creator: ctx.accounts.creator.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+        creator_ata: ctx.accounts.creator_ata.to_account_info(),
+        pool_ata: ctx.accounts.pool_ata.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+    };
+    refund_and_close(
+        &mut ctx.accounts.red_packet,
+        accounts,
+        ctx.remaining_accounts,
+        red_packet_id,
+    )
+}
+
+/// 退款流程用到的账户，退款和提前取消共用
+pub struct RefundAccounts<'info> {
+    /// 接收剩余 SOL、池子 ATA 和红包账户的租金
+    pub creator: AccountInfo<'info>,
+    pub mint: AccountInfo<'info>,
+    /// SOL 红包为 creator 本身，代币红包为接收剩余代币的账户
+    pub creator_ata: AccountInfo<'info>,
+    pub pool_ata: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+}
+
+/// 把剩余金额退给创建者，关闭池子 ATA 和红包账户；调用方负责检查权限和时间
+pub(crate) fn refund_and_close<'info>(
+    red_packet: &mut Account<'info, RedPacket>,
+    accounts: RefundAccounts<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    red_packet_id: u64,
+) -> Result<()> {
+    let red_packet_key = red_packet.key();
+
     require!(
         red_packet.remaining_amount > 0,
         RedPacketError::NoFundsToRefund
     );
 
     let refund_amount = red_packet.remaining_amount;
+    let mut transfer_fee = 0;
 
     if red_packet.is_sol {
         let rent_exempt_lamports = Rent::get()?.minimum_balance(config::RED_PACKET_SPACE);
@@ -39,22 +80,25 @@ pub fn refund_handler(ctx: Context<Refund>, red_packet_id: u64) -> Result<()> {
         );
 
         **red_packet.to_account_info().try_borrow_mut_lamports()? -= refund_amount;
-        **ctx
-            .accounts
-            .creator
-            .to_account_info()
-            .try_borrow_mut_lamports()? += refund_amount;
+        **accounts.creator.try_borrow_mut_lamports()? += refund_amount;
     } else {
-        let mint_data = ctx.accounts.mint.data.borrow();
-        let mint_account = StateWithExtensions::<Mint2022>::unpack(&mint_data)?;
-        let decimals = mint_account.base.decimals;
-        token_interface::transfer_checked(
+        // 收取转账费时需要写 mint，借用必须在 CPI 之前释放
+        let mint_info = MintInfo::load_pool(
+            &accounts.mint,
+            &accounts.pool_ata,
+            &red_packet_key,
+            &red_packet.mint,
+            &accounts.token_program.key(),
+        )?;
+        mint_info.check_usage(MintUsage::Refund)?;
+        transfer_fee = utils::calculate_transfer_fee(&accounts.mint, refund_amount)?;
+        utils::transfer_checked_with_hook(
             CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
+                accounts.token_program.clone(),
                 TransferChecked {
-                    from: ctx.accounts.pool_ata.to_account_info(),
-                    mint: ctx.accounts.mint.to_account_info(),
-                    to: ctx.accounts.creator_ata.to_account_info(),
+                    from: accounts.pool_ata.clone(),
+                    mint: accounts.mint.clone(),
+                    to: accounts.creator_ata.clone(),
                     authority: red_packet.to_account_info(),
                 },
                 &[&[
@@ -63,16 +107,19 @@ pub fn refund_handler(ctx: Context<Refund>, red_packet_id: u64) -> Result<()> {
                     &red_packet.red_packet_id.to_le_bytes(),
                     &[red_packet.bump],
                 ]],
-            ),
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec()),
             refund_amount,
-            decimals,
+            mint_info.decimals,
         )?;
 
+        utils::harvest_pool_fees(&accounts.token_program, &accounts.mint, &accounts.pool_ata)?;
+
         token_interface::close_account(CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
+            accounts.token_program.clone(),
             token_interface::CloseAccount {
-                account: ctx.accounts.pool_ata.to_account_info(),
-                destination: ctx.accounts.creator.to_account_info(),
+                account: accounts.pool_ata.clone(),
+                destination: accounts.creator.clone(),
                 authority: red_packet.to_account_info(),
             },
             &[&[
@@ -88,15 +135,17 @@ pub fn refund_handler(ctx: Context<Refund>, red_packet_id: u64) -> Result<()> {
 
     // 记录事件 - 在关闭账户前
     emit!(RedPacketRefunded {
-        creator: ctx.accounts.creator.key(),
+        creator: accounts.creator.key(),
         red_packet: red_packet_key,
         amount: refund_amount,
+        net_amount: refund_amount - transfer_fee,
+        transfer_fee,
         red_packet_id
     });
 
     // 关闭红包账户
-    let red_packet_account_info = ctx.accounts.red_packet.to_account_info();
-    let dest = ctx.accounts.creator.to_account_info();
+    let red_packet_account_info = red_packet.to_account_info();
+    let dest = accounts.creator;
     // 转移剩余lamports
     let lamports = red_packet_account_info.lamports();
     if lamports > 0 {
@@ -119,7 +168,7 @@ pub struct Refund<'info> {
     #[account(mut)]
     pub red_packet: Account<'info, RedPacket>,
 
-    /// CHECK: This can be SOL (Pubkey::default) or SPL token mint
+    /// CHECK: This can be SOL (Pubkey::default) or SPL token mint, must be writable for transfer-fee mints to harvest withheld fees
     #[account()]
     pub mint: UncheckedAccount<'info>,
 
@@ -0,0 +1,152 @@
+use crate::{
+    config::{self, PROTOCOL_CONFIG_SEED},
+    instructions::claim::{process_claim, ClaimAccounts},
+    utils, ProtocolConfig, RedPacket, RedPacketError, UserState,
+};
+use anchor_lang::{
+    prelude::*,
+    solana_program::sysvar::{instructions, slot_hashes},
+};
+use anchor_spl::{
+    associated_token::{get_associated_token_address_with_program_id, AssociatedToken},
+    token::spl_token,
+    token_interface::TokenInterface,
+};
+
+/// 代领：fee_payer 签名并支付手续费和租金，recipient 通过前一条 ed25519 指令授权，
+/// 红包记到 recipient 名下。
+pub fn relay_claim_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, RelayClaimRedPacket<'info>>,
+    amount: Option<u64>,
+    proof: Option<Vec<[u8; 32]>>,
+    red_packet_id: u64,
+) -> Result<()> {
+    let red_packet = &ctx.accounts.red_packet;
+    let recipient = ctx.accounts.recipient.key();
+
+    utils::verify_ed25519_signature(
+        &ctx.accounts.instructions,
+        &recipient,
+        &utils::relay_claim_message(&red_packet.key(), red_packet_id, &recipient),
+    )?;
+
+    // 中继方不能把钱转去别处：SOL 直接打给 recipient，代币只能进 recipient 在红包 mint 下的 ATA。
+    // 传入的 mint 账户由 pay_claim 再核对，这里不能拿它来推导地址
+    let expected_recipient_ata = if red_packet.is_sol {
+        recipient
+    } else {
+        get_associated_token_address_with_program_id(
+            &recipient,
+            &red_packet.mint,
+            &ctx.accounts.token_program.key(),
+        )
+    };
+    require!(
+        ctx.accounts.recipient_ata.key() == expected_recipient_ata,
+        RedPacketError::InvalidRecipientAccount
+    );
+
+    let accounts = ClaimAccounts {
+        payer: ctx.accounts.fee_payer.to_account_info(),
+        recipient: ctx.accounts.recipient.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+        pool_ata: ctx.accounts.pool_ata.to_account_info(),
+        recipient_ata: ctx.accounts.recipient_ata.to_account_info(),
+        fee_receiver: ctx.accounts.fee_receiver.to_account_info(),
+        system_program: ctx.accounts.system_program.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        associated_token_program: ctx.accounts.associated_token_program.to_account_info(),
+        slot_hashes: Some(ctx.accounts.slot_hashes.to_account_info()),
+        // 口令红包的承诺必须由领取者本人签名提交，代领不支持
+        claim_commit: None,
+        gate_token_account: ctx
+            .accounts
+            .gate_token_account
+            .as_ref()
+            .map(|account| account.to_account_info()),
+        gate_metadata: ctx
+            .accounts
+            .gate_metadata
+            .as_ref()
+            .map(|account| account.to_account_info()),
+        protocol_config: ctx.accounts.protocol_config.clone().into_inner(),
+    };
+    process_claim(
+        &mut ctx.accounts.red_packet,
+        &mut ctx.accounts.user_state,
+        accounts,
+        ctx.remaining_accounts,
+        amount,
+        proof,
+        None,
+        red_packet_id,
+    )
+}
+
+#[derive(Accounts)]
+#[instruction(amount: Option<u64>, proof: Option<Vec<[u8; 32]>>, red_packet_id: u64)]
+pub struct RelayClaimRedPacket<'info> {
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    /// CHECK: Authorized through the ed25519 instruction checked in the handler
+    #[account(mut)]
+    pub recipient: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub red_packet: Account<'info, RedPacket>,
+
+    #[account(
+        init_if_needed,
+        payer = fee_payer,
+        space = config::USER_STATE_SPACE,
+        seeds = [b"user_state", red_packet.key().as_ref(), recipient.key().as_ref()],
+        bump
+    )]
+    pub user_state: Account<'info, UserState>,
+
+    /// CHECK: This can be SOL (Pubkey::default) or SPL token mint
+    #[account()]
+    pub mint: UncheckedAccount<'info>,
+
+    /// CHECK: Pool ATA for SPL tokens, red_packet account for SOL
+    #[account(mut)]
+    pub pool_ata: UncheckedAccount<'info>,
+
+    /// CHECK: Recipient's ATA for SPL tokens, recipient for SOL, checked in the handler
+    #[account(mut)]
+    pub recipient_ata: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        address = protocol_config.fee_receiver @ RedPacketError::InvalidFeeReceiver
+    )]
+    pub fee_receiver: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+    #[account(
+    constraint = token_program.key() == spl_token::id() || token_program.key() == spl_token_2022::id() @ RedPacketError::InvalidTokenProgram
+    )]
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+
+    /// CHECK: SlotHashes sysvar, only read by slot-hash random red packets
+    #[account(address = slot_hashes::ID @ RedPacketError::InvalidSlotHashes)]
+    pub slot_hashes: UncheckedAccount<'info>,
+
+    /// CHECK: Instructions sysvar, used to find the recipient's ed25519 signature
+    #[account(address = instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    /// CHECK: Recipient's token account, only required by holder-gated red packets
+    pub gate_token_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Metaplex metadata of the held NFT, only required by collection-gated red packets
+    pub gate_metadata: Option<UncheckedAccount<'info>>,
+}
@@ -0,0 +1,74 @@
+use crate::{config, events::UserStatesClosed, RedPacketError, UserState};
+use anchor_lang::prelude::*;
+
+/// 批量关闭已无作用的 UserState，租金退还给各自的领取者。
+///
+/// remaining_accounts 按 `[user_state, claimer]` 成对传入。任何人都可以调用，
+/// 但只有当红包账户已关闭（退款、取消或 close_redpacket）时才允许清理：
+/// 领完但仍然存在的红包还可能被追加或延期，删除 UserState 会让同一用户再次领取。
+pub fn sweep_handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CloseUserStates<'info>>,
+) -> Result<()> {
+    let red_packet_info = ctx.accounts.red_packet.to_account_info();
+    let red_packet_key = red_packet_info.key();
+
+    require!(
+        red_packet_info.owner != &crate::ID || red_packet_info.data_is_empty(),
+        RedPacketError::RedPacketStillActive
+    );
+
+    let remaining_accounts = ctx.remaining_accounts;
+    require!(
+        !remaining_accounts.is_empty() && remaining_accounts.chunks_exact(2).remainder().is_empty(),
+        RedPacketError::InvalidRemainingAccounts
+    );
+    require!(
+        remaining_accounts.len() / 2 <= config::MAX_USER_STATE_SWEEP,
+        RedPacketError::InvalidRemainingAccounts
+    );
+
+    let mut closed_count: u32 = 0;
+    let mut reclaimed_lamports: u64 = 0;
+    for pair in remaining_accounts.chunks_exact(2) {
+        let (user_state_info, claimer_info) = (&pair[0], &pair[1]);
+
+        let (expected_user_state, _) = Pubkey::find_program_address(
+            &[
+                b"user_state",
+                red_packet_key.as_ref(),
+                claimer_info.key().as_ref(),
+            ],
+            &crate::ID,
+        );
+        require!(
+            user_state_info.key() == expected_user_state,
+            RedPacketError::InvalidUserState
+        );
+        require!(
+            claimer_info.is_writable,
+            RedPacketError::InvalidRemainingAccounts
+        );
+
+        let user_state = Account::<UserState>::try_from(user_state_info)?;
+        reclaimed_lamports = reclaimed_lamports
+            .checked_add(user_state_info.lamports())
+            .ok_or(RedPacketError::ArithmeticOverflow)?;
+        user_state.close(claimer_info.clone())?;
+        closed_count += 1;
+    }
+
+    emit!(UserStatesClosed {
+        red_packet: red_packet_key,
+        closed_count,
+        reclaimed_lamports,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CloseUserStates<'info> {
+    /// CHECK: Must already be closed, checked in the handler
+    #[account()]
+    pub red_packet: UncheckedAccount<'info>,
+}
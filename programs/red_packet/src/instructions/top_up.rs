@@ -0,0 +1,170 @@
+use crate::{
+    config::{CREATOR_STATE_SEED, PROTOCOL_CONFIG_SEED},
+    events::RedPacketToppedUp,
+    mint_info::{MintInfo, MintUsage},
+    utils, CreatorState, FeeRule, ProtocolConfig, RedPacket, RedPacketError,
+};
+use anchor_lang::{prelude::*, system_program};
+use anchor_spl::{
+    token::spl_token,
+    token_interface::{TokenInterface, TransferChecked},
+};
+
+/// 创建者或持有 PERMISSION_TOP_UP 的操作员向未过期、未取消的红包追加金额和份数，资金来自签名者。
+/// `amount` 是池子实际增加的金额，Token-2022 的转账费由签名者额外承担
+pub fn top_up_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, TopUpRedPacket<'info>>,
+    red_packet_id: u64,
+    amount: u64,
+    extra_packets: u32,
+) -> Result<()> {
+    require!(
+        !ctx.accounts
+            .protocol_config
+            .is_paused(ProtocolConfig::PAUSE_CREATE),
+        RedPacketError::ProgramPaused
+    );
+    let red_packet = &mut ctx.accounts.red_packet;
+    utils::check_manager(
+        red_packet,
+        &ctx.accounts.creator_state,
+        &ctx.accounts.authority.key(),
+        CreatorState::PERMISSION_TOP_UP,
+    )?;
+    require!(!red_packet.cancelled, RedPacketError::RedPacketCancelled);
+    require!(
+        Clock::get()?.unix_timestamp < red_packet.expiry_time,
+        RedPacketError::RedPacketExpired
+    );
+    utils::apply_top_up(red_packet, amount, extra_packets)?;
+
+    // 固定创建费已在创建时收过，追加只按比例收取，避免先小额创建再追加来绕过比例费
+    let top_up_fee = FeeRule {
+        lamports: 0,
+        ..ctx.accounts.protocol_config.create_fee
+    }
+    .fee(amount, red_packet.is_sol)
+    .ok_or(RedPacketError::ArithmeticOverflow)?;
+
+    let mut transfer_fee = 0;
+    if red_packet.is_sol {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: red_packet.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+    } else {
+        // 只能转入红包自己的池子，否则 remaining_amount 会凭空增加
+        let mint_info = MintInfo::load_pool(
+            &ctx.accounts.mint,
+            &ctx.accounts.pool_ata,
+            &red_packet.key(),
+            &red_packet.mint,
+            &ctx.accounts.token_program.key(),
+        )?;
+        mint_info.check_usage(MintUsage::Create)?;
+        transfer_fee = utils::calculate_inverse_transfer_fee(&ctx.accounts.mint, amount)?;
+        utils::transfer_checked_with_hook(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.authority_ata.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.pool_ata.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            amount
+                .checked_add(transfer_fee)
+                .ok_or(RedPacketError::ArithmeticOverflow)?,
+            mint_info.decimals,
+        )?;
+    }
+
+    if top_up_fee > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: ctx.accounts.fee_receiver.to_account_info(),
+                },
+            ),
+            top_up_fee,
+        )?;
+    }
+
+    let red_packet = &ctx.accounts.red_packet;
+    emit!(RedPacketToppedUp {
+        creator: red_packet.creator,
+        authority: ctx.accounts.authority.key(),
+        red_packet: red_packet.key(),
+        red_packet_id,
+        amount,
+        extra_packets,
+        total_amount: red_packet.total_amount,
+        remaining_amount: red_packet.remaining_amount,
+        packet_count: red_packet.packet_count,
+        share_amount: red_packet.share_amount,
+        transfer_fee,
+        top_up_fee,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(red_packet_id: u64)]
+pub struct TopUpRedPacket<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"red_packet", red_packet.creator.as_ref(), &red_packet_id.to_le_bytes()],
+        bump = red_packet.bump
+    )]
+    pub red_packet: Account<'info, RedPacket>,
+
+    #[account(
+        seeds = [CREATOR_STATE_SEED, red_packet.creator.as_ref()],
+        bump = creator_state.bump
+    )]
+    pub creator_state: Account<'info, CreatorState>,
+
+    /// CHECK: This can be SOL (Pubkey::default) or SPL token mint, checked against red_packet.mint for tokens
+    #[account()]
+    pub mint: UncheckedAccount<'info>,
+
+    /// CHECK: Authority's token account for SPL tokens, authority for SOL
+    #[account(mut)]
+    pub authority_ata: UncheckedAccount<'info>,
+
+    /// CHECK: Pool ATA for SPL tokens, red_packet account for SOL
+    #[account(mut)]
+    pub pool_ata: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        address = protocol_config.fee_receiver @ RedPacketError::InvalidFeeReceiver
+    )]
+    pub fee_receiver: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+    #[account(
+    constraint = token_program.key() == spl_token::id() || token_program.key() == spl_token_2022::id() @ RedPacketError::InvalidTokenProgram
+    )]
+    pub token_program: Interface<'info, TokenInterface>,
+}
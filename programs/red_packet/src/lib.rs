@@ -8,6 +8,7 @@ pub mod config;
 pub mod errors;
 pub mod events;
 pub mod instructions;
+pub mod mint_info;
 pub mod states;
 pub mod utils;
 
@@ -15,50 +16,237 @@ use errors::*;
 use instructions::*;
 use states::*;
 
-#[program]
-pub mod red_packet {
+pub use program_module::*;
+
+// #[program] 在所在模块里生成 __private::__idl，其中的 IDL resize 指令调用了已弃用的
+// AccountInfo::realloc，生成的代码上加不了属性，所以放进单独的模块只对这一层放行
+#[allow(deprecated)]
+mod program_module {
     use super::*;
 
-    pub fn initialize_creator_state(ctx: Context<InitializeCreatorState>) -> Result<()> {
-        instructions::initialize::handler_creator_state(ctx)
-    }
+    #[program]
+    pub mod red_packet {
+        use super::*;
 
-    pub fn create_redpacket(
-        ctx: Context<CreateRedPacket>,
-        total_amount: u64,
-        packet_count: u32,
-        red_packet_type: u8,
-        merkle_root: Option<[u8; 32]>,
-        is_sol: bool,
-        expiry_days: Option<i64>,
-        random_seed: Option<u64>,
-    ) -> Result<()> {
-        instructions::create::create_handler(
-            ctx,
-            total_amount,
-            packet_count,
-            red_packet_type,
-            merkle_root,
-            is_sol,
-            expiry_days,
-            random_seed,
-        )
-    }
+        pub fn initialize_creator_state(ctx: Context<InitializeCreatorState>) -> Result<()> {
+            instructions::initialize::handler_creator_state(ctx)
+        }
 
-    pub fn claim_redpacket(
-        ctx: Context<ClaimRedPacket>,
-        amount: Option<u64>,
-        proof: Option<Vec<[u8; 32]>>,
-        red_packet_id: u64,
-    ) -> Result<()> {
-        instructions::claim::claim_handler(ctx, amount, proof, red_packet_id)
-    }
+        pub fn add_operator(
+            ctx: Context<ManageOperators>,
+            operator: Pubkey,
+            permissions: u8,
+        ) -> Result<()> {
+            instructions::operator::add_operator_handler(ctx, operator, permissions)
+        }
 
-    pub fn refund(ctx: Context<Refund>, red_packet_id: u64) -> Result<()> {
-        instructions::refund::refund_handler(ctx, red_packet_id)
-    }
+        pub fn remove_operator(ctx: Context<ManageOperators>, operator: Pubkey) -> Result<()> {
+            instructions::operator::remove_operator_handler(ctx, operator)
+        }
+
+        pub fn initialize_protocol_config(ctx: Context<InitializeProtocolConfig>) -> Result<()> {
+            instructions::protocol_config::initialize_handler(ctx)
+        }
+
+        pub fn update_protocol_config(
+            ctx: Context<UpdateProtocolConfig>,
+            create_fee: Option<FeeRule>,
+            claim_fee: Option<FeeRule>,
+            fee_receiver: Option<Pubkey>,
+        ) -> Result<()> {
+            instructions::protocol_config::update_handler(ctx, create_fee, claim_fee, fee_receiver)
+        }
+
+        pub fn set_pause(ctx: Context<UpdateProtocolConfig>, pause_flags: u8) -> Result<()> {
+            instructions::protocol_config::set_pause_handler(ctx, pause_flags)
+        }
+
+        pub fn set_mint_risk_policy(
+            ctx: Context<UpdateProtocolConfig>,
+            mint_risk_policy: u8,
+        ) -> Result<()> {
+            instructions::protocol_config::set_mint_risk_policy_handler(ctx, mint_risk_policy)
+        }
+
+        pub fn propose_admin(ctx: Context<UpdateProtocolConfig>, new_admin: Pubkey) -> Result<()> {
+            instructions::protocol_config::propose_admin_handler(ctx, new_admin)
+        }
+
+        pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+            instructions::protocol_config::accept_admin_handler(ctx)
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn create_redpacket<'info>(
+            ctx: Context<'_, '_, '_, 'info, CreateRedPacket<'info>>,
+            total_amount: u64,
+            packet_count: u32,
+            red_packet_type: u8,
+            merkle_root: Option<[u8; 32]>,
+            is_sol: bool,
+            expiry_days: Option<i64>,
+            random_seed: Option<u64>,
+            start_time: Option<i64>,
+            code_hash: Option<[u8; 32]>,
+            holder_gate: Option<HolderGate>,
+            prize_tiers: Option<Vec<PrizeTier>>,
+        ) -> Result<()> {
+            instructions::create::create_handler(
+                ctx,
+                total_amount,
+                packet_count,
+                red_packet_type,
+                merkle_root,
+                is_sol,
+                expiry_days,
+                random_seed,
+                start_time,
+                code_hash,
+                holder_gate,
+                prize_tiers,
+            )
+        }
+
+        pub fn create_bundle<'info>(
+            ctx: Context<'_, '_, '_, 'info, CreateBundle<'info>>,
+            packet_count: u32,
+            sol_per_claim: u64,
+            token_amounts: Vec<u64>,
+            expiry_days: Option<i64>,
+        ) -> Result<()> {
+            instructions::bundle::create_bundle_handler(
+                ctx,
+                packet_count,
+                sol_per_claim,
+                token_amounts,
+                expiry_days,
+            )
+        }
+
+        pub fn claim_bundle<'info>(
+            ctx: Context<'_, '_, '_, 'info, ClaimBundle<'info>>,
+            bundle_id: u64,
+        ) -> Result<()> {
+            instructions::bundle::claim_bundle_handler(ctx, bundle_id)
+        }
+
+        pub fn refund_bundle<'info>(
+            ctx: Context<'_, '_, '_, 'info, RefundBundle<'info>>,
+            bundle_id: u64,
+        ) -> Result<()> {
+            instructions::bundle::refund_bundle_handler(ctx, bundle_id)
+        }
+
+        pub fn claim_redpacket<'info>(
+            ctx: Context<'_, '_, '_, 'info, ClaimRedPacket<'info>>,
+            amount: Option<u64>,
+            proof: Option<Vec<[u8; 32]>>,
+            red_packet_id: u64,
+            code: Option<Vec<u8>>,
+        ) -> Result<()> {
+            instructions::claim::claim_handler(ctx, amount, proof, red_packet_id, code)
+        }
+
+        pub fn commit_claim(
+            ctx: Context<CommitClaim>,
+            red_packet_id: u64,
+            commitment: [u8; 32],
+        ) -> Result<()> {
+            instructions::commit::commit_handler(ctx, red_packet_id, commitment)
+        }
+
+        pub fn close_claim_commit(ctx: Context<CloseClaimCommit>) -> Result<()> {
+            instructions::commit::close_commit_handler(ctx)
+        }
+
+        pub fn init_claim_bitmap(ctx: Context<InitClaimBitmap>, red_packet_id: u64) -> Result<()> {
+            instructions::claim_bitmap::init_bitmap_handler(ctx, red_packet_id)
+        }
+
+        pub fn extend_claim_bitmap(ctx: Context<ExtendClaimBitmap>) -> Result<()> {
+            instructions::claim_bitmap::extend_bitmap_handler(ctx)
+        }
+
+        pub fn close_claim_bitmap(ctx: Context<CloseClaimBitmap>) -> Result<()> {
+            instructions::claim_bitmap::close_bitmap_handler(ctx)
+        }
+
+        pub fn claim_indexed_redpacket<'info>(
+            ctx: Context<'_, '_, '_, 'info, ClaimIndexedRedPacket<'info>>,
+            index: u32,
+            amount: u64,
+            proof: Vec<[u8; 32]>,
+            red_packet_id: u64,
+        ) -> Result<()> {
+            instructions::claim_indexed::claim_indexed_handler(
+                ctx,
+                index,
+                amount,
+                proof,
+                red_packet_id,
+            )
+        }
+
+        pub fn relay_claim_redpacket<'info>(
+            ctx: Context<'_, '_, '_, 'info, RelayClaimRedPacket<'info>>,
+            amount: Option<u64>,
+            proof: Option<Vec<[u8; 32]>>,
+            red_packet_id: u64,
+        ) -> Result<()> {
+            instructions::relay_claim::relay_claim_handler(ctx, amount, proof, red_packet_id)
+        }
+
+        pub fn refund<'info>(
+            ctx: Context<'_, '_, '_, 'info, Refund<'info>>,
+            red_packet_id: u64,
+        ) -> Result<()> {
+            instructions::refund::refund_handler(ctx, red_packet_id)
+        }
+
+        pub fn cancel_redpacket<'info>(
+            ctx: Context<'_, '_, '_, 'info, CancelRedPacket<'info>>,
+            red_packet_id: u64,
+            grace_period: Option<i64>,
+        ) -> Result<()> {
+            instructions::cancel::cancel_handler(ctx, red_packet_id, grace_period)
+        }
+
+        pub fn set_expiry_time(ctx: Context<SetExpiryTime>, expiry_time: i64) -> Result<()> {
+            instructions::expiry::expiry_handler(ctx, expiry_time)
+        }
+
+        pub fn set_start_time(ctx: Context<SetExpiryTime>, start_time: i64) -> Result<()> {
+            instructions::expiry::start_time_handler(ctx, start_time)
+        }
+
+        pub fn top_up_redpacket<'info>(
+            ctx: Context<'_, '_, '_, 'info, TopUpRedPacket<'info>>,
+            red_packet_id: u64,
+            amount: u64,
+            extra_packets: u32,
+        ) -> Result<()> {
+            instructions::top_up::top_up_handler(ctx, red_packet_id, amount, extra_packets)
+        }
+
+        pub fn migrate_redpacket(ctx: Context<MigrateRedPacket>) -> Result<()> {
+            instructions::migrate::migrate_handler(ctx)
+        }
+
+        pub fn migrate_creator_state(ctx: Context<MigrateCreatorState>) -> Result<()> {
+            instructions::migrate::migrate_creator_state_handler(ctx)
+        }
+
+        pub fn close_redpacket<'info>(
+            ctx: Context<'_, '_, '_, 'info, CloseRedPacket<'info>>,
+            red_packet_id: u64,
+        ) -> Result<()> {
+            instructions::close::close_handler(ctx, red_packet_id)
+        }
 
-    pub fn set_expiry_time(ctx: Context<SetExpiryTime>, expiry_time: i64) -> Result<()> {
-        instructions::expiry::expiry_handler(ctx, expiry_time)
+        pub fn close_user_states<'info>(
+            ctx: Context<'_, '_, 'info, 'info, CloseUserStates<'info>>,
+        ) -> Result<()> {
+            instructions::sweep::sweep_handler(ctx)
+        }
     }
 }
@@ -0,0 +1,438 @@
+//! mint 解析：统一区分旧版 SPL Token 和 Token-2022，读出精度和红包关心的扩展，
+//! 并按创建 / 领取 / 退款三种用途决定哪些扩展可以接受
+use crate::{ProtocolConfig, RedPacketError};
+use anchor_lang::{prelude::*, solana_program::program_pack::Pack};
+use anchor_spl::{
+    associated_token::get_associated_token_address_with_program_id, token::spl_token,
+};
+use spl_token_2022::{
+    extension::{
+        confidential_transfer::ConfidentialTransferMint,
+        default_account_state::DefaultAccountState, mint_close_authority::MintCloseAuthority,
+        non_transferable::NonTransferable, pausable::PausableConfig,
+        permanent_delegate::PermanentDelegate, transfer_fee::TransferFeeConfig,
+        transfer_hook::TransferHook, BaseStateWithExtensions, StateWithExtensions,
+    },
+    state::{AccountState, Mint as Mint2022},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenProgramKind {
+    Legacy,
+    Token2022,
+}
+
+/// 使用 mint 的场景，不同场景对扩展的要求不同
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MintUsage {
+    Create,
+    Claim,
+    Refund,
+    /// 组合红包的代币直接 transfer_checked，不补偿转账费，remaining_accounts 也没有位置传 hook 的额外账户
+    Bundle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MintInfo {
+    pub program: TokenProgramKind,
+    pub decimals: u8,
+    /// 冻结权限可以冻结池子 ATA，让所有领取和退款失败
+    pub has_freeze_authority: bool,
+    /// MintCloseAuthority 扩展设置了关闭权限，供应量归零后 mint 可以被关闭
+    pub has_close_authority: bool,
+    pub has_transfer_fee: bool,
+    pub has_transfer_hook: bool,
+    pub has_permanent_delegate: bool,
+    pub has_confidential_transfer: bool,
+    pub is_non_transferable: bool,
+    /// 带 Pausable 扩展且当前处于暂停状态，此时任何转账都会失败
+    pub is_paused: bool,
+    /// DefaultAccountState 为 Frozen，新建的 ATA 默认冻结
+    pub is_default_frozen: bool,
+}
+
+impl MintInfo {
+    /// 校验 mint 账户属于传入的 token program 后解析
+    pub fn load(mint: &AccountInfo, token_program: &Pubkey) -> Result<Self> {
+        require_keys_eq!(
+            *mint.owner,
+            *token_program,
+            RedPacketError::InvalidMintAccount
+        );
+        Self::parse(&mint.try_borrow_data()?, token_program)
+    }
+
+    /// 从红包池子转出或转入前解析 mint：mint 必须是红包记录的 mint，pool_ata 必须是红包 PDA 的 ATA。
+    /// 否则可以传入自建 mint 和它归红包所有的 ATA，领取只扣减 remaining_amount 而真正的池子不动
+    pub fn load_pool(
+        mint: &AccountInfo,
+        pool_ata: &AccountInfo,
+        red_packet: &Pubkey,
+        red_packet_mint: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Result<Self> {
+        require_keys_eq!(mint.key(), *red_packet_mint, RedPacketError::InvalidMint);
+        require_keys_eq!(
+            pool_ata.key(),
+            get_associated_token_address_with_program_id(
+                red_packet,
+                red_packet_mint,
+                token_program
+            ),
+            RedPacketError::InvalidPoolAta
+        );
+        Self::load(mint, token_program)
+    }
+
+    pub fn parse(data: &[u8], token_program: &Pubkey) -> Result<Self> {
+        if *token_program == spl_token::id() {
+            let mint = spl_token::state::Mint::unpack(data)
+                .map_err(|_| RedPacketError::InvalidMintAccount)?;
+            Ok(Self {
+                program: TokenProgramKind::Legacy,
+                decimals: mint.decimals,
+                has_freeze_authority: mint.freeze_authority.is_some(),
+                has_close_authority: false,
+                has_transfer_fee: false,
+                has_transfer_hook: false,
+                has_permanent_delegate: false,
+                has_confidential_transfer: false,
+                is_non_transferable: false,
+                is_paused: false,
+                is_default_frozen: false,
+            })
+        } else if *token_program == spl_token_2022::id() {
+            let mint = StateWithExtensions::<Mint2022>::unpack(data)
+                .map_err(|_| RedPacketError::InvalidMintAccount)?;
+            Ok(Self {
+                program: TokenProgramKind::Token2022,
+                decimals: mint.base.decimals,
+                has_freeze_authority: mint.base.freeze_authority.is_some(),
+                has_close_authority: mint
+                    .get_extension::<MintCloseAuthority>()
+                    .is_ok_and(|ext| Option::<Pubkey>::from(ext.close_authority).is_some()),
+                has_transfer_fee: mint.get_extension::<TransferFeeConfig>().is_ok(),
+                has_transfer_hook: mint.get_extension::<TransferHook>().is_ok(),
+                has_permanent_delegate: mint.get_extension::<PermanentDelegate>().is_ok(),
+                has_confidential_transfer: mint.get_extension::<ConfidentialTransferMint>().is_ok(),
+                is_non_transferable: mint.get_extension::<NonTransferable>().is_ok(),
+                is_paused: mint
+                    .get_extension::<PausableConfig>()
+                    .is_ok_and(|config| bool::from(config.paused)),
+                is_default_frozen: mint
+                    .get_extension::<DefaultAccountState>()
+                    .is_ok_and(|state| state.state == AccountState::Frozen as u8),
+            })
+        } else {
+            Err(RedPacketError::InvalidTokenProgram.into())
+        }
+    }
+
+    /// 创建时拒绝所有红包不支持的扩展；领取时同样拒绝，防止绕过创建校验的账户；
+    /// 退款只拒绝暂停中的 mint，保证资金总能退回创建者。
+    /// 普通红包在这三种场景下都支持 transfer fee 和 transfer hook（hook 的额外账户由调用方传入），
+    /// 组合红包在创建的基础上再拒绝这两种扩展
+    pub fn check_usage(&self, usage: MintUsage) -> Result<()> {
+        require!(!self.is_paused, RedPacketError::MintPaused);
+        if usage == MintUsage::Refund {
+            return Ok(());
+        }
+        require!(
+            !self.has_permanent_delegate,
+            RedPacketError::PermanentDelegateNotSupported
+        );
+        require!(
+            !self.has_confidential_transfer,
+            RedPacketError::ConfidentialTransferNotSupported
+        );
+        require!(
+            !self.is_non_transferable,
+            RedPacketError::NonTransferableNotSupported
+        );
+        if usage == MintUsage::Bundle {
+            require!(
+                !self.has_transfer_fee,
+                RedPacketError::TransferFeeNotSupported
+            );
+            require!(
+                !self.has_transfer_hook,
+                RedPacketError::TransferHookNotSupported
+            );
+        }
+        // 领取者已有未冻结的 ATA 时仍然可以领取，是否需要新建 ATA 由领取逻辑判断
+        if matches!(usage, MintUsage::Create | MintUsage::Bundle) {
+            require!(
+                !self.is_default_frozen,
+                RedPacketError::DefaultFrozenNotSupported
+            );
+        }
+        Ok(())
+    }
+
+    /// 创建时按协议配置的风险策略拒绝有冻结权限或关闭权限的 mint，
+    /// 没有被拒绝的风险由调用方在创建事件里标记出来
+    pub fn check_risk_policy(&self, policy: u8) -> Result<()> {
+        require!(
+            !self.has_freeze_authority || policy & ProtocolConfig::REJECT_FREEZE_AUTHORITY == 0,
+            RedPacketError::FreezeAuthorityNotAllowed
+        );
+        require!(
+            !self.has_close_authority || policy & ProtocolConfig::REJECT_CLOSE_AUTHORITY == 0,
+            RedPacketError::CloseAuthorityNotAllowed
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::program_option::COption;
+    use spl_token_2022::extension::{
+        BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+    };
+
+    fn error_code(error: RedPacketError) -> Error {
+        error.into()
+    }
+
+    fn legacy_mint() -> Vec<u8> {
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        data
+    }
+
+    /// 构造只带一个扩展的 Token-2022 mint，`init` 负责填写扩展内容
+    fn token_2022_mint(
+        extension: Option<ExtensionType>,
+        init: impl FnOnce(&mut StateWithExtensionsMut<Mint2022>),
+    ) -> Vec<u8> {
+        let extensions: Vec<ExtensionType> = extension.into_iter().collect();
+        let len = ExtensionType::try_calculate_account_len::<Mint2022>(&extensions).unwrap();
+        let mut data = vec![0; len];
+        let mut mint = StateWithExtensionsMut::<Mint2022>::unpack_uninitialized(&mut data).unwrap();
+        init(&mut mint);
+        mint.base = Mint2022 {
+            decimals: 9,
+            is_initialized: true,
+            ..Default::default()
+        };
+        mint.pack_base();
+        mint.init_account_type().unwrap();
+        data
+    }
+
+    fn parse_2022(data: &[u8]) -> MintInfo {
+        MintInfo::parse(data, &spl_token_2022::id()).unwrap()
+    }
+
+    /// 每种扩展在创建 / 领取 / 退款三种场景下的预期结果
+    fn assert_matrix(info: &MintInfo, expected: [Option<RedPacketError>; 3]) {
+        for (usage, expected) in [MintUsage::Create, MintUsage::Claim, MintUsage::Refund]
+            .into_iter()
+            .zip(expected)
+        {
+            let result = info.check_usage(usage);
+            match expected {
+                None => assert!(result.is_ok(), "{usage:?} should succeed"),
+                Some(error) => assert_eq!(result.unwrap_err(), error_code(error), "{usage:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_legacy_mint() {
+        let info = MintInfo::parse(&legacy_mint(), &spl_token::id()).unwrap();
+        assert_eq!(info.program, TokenProgramKind::Legacy);
+        assert_eq!(info.decimals, 6);
+        assert!(!info.has_freeze_authority);
+        assert_matrix(&info, [None, None, None]);
+    }
+
+    #[test]
+    fn test_legacy_mint_with_token_2022_program() {
+        // 旧版 mint 的数据同样能按 Token-2022 解析，程序归属由 `load` 校验
+        let info = parse_2022(&legacy_mint());
+        assert_eq!(info.program, TokenProgramKind::Token2022);
+        assert_eq!(info.decimals, 6);
+    }
+
+    #[test]
+    fn test_unknown_token_program() {
+        assert_eq!(
+            MintInfo::parse(&legacy_mint(), &Pubkey::new_unique()).unwrap_err(),
+            error_code(RedPacketError::InvalidTokenProgram)
+        );
+        assert_eq!(
+            MintInfo::parse(&[0; 10], &spl_token::id()).unwrap_err(),
+            error_code(RedPacketError::InvalidMintAccount)
+        );
+    }
+
+    #[test]
+    fn test_plain_token_2022_mint() {
+        let mut data = token_2022_mint(None, |_| {});
+        let info = parse_2022(&data);
+        assert_eq!(info.decimals, 9);
+        assert!(!info.has_freeze_authority);
+        assert_matrix(&info, [None, None, None]);
+
+        // 冻结权限只记录，不影响是否可用
+        let mut mint = Mint2022::unpack_from_slice(&data[..Mint2022::LEN]).unwrap();
+        mint.freeze_authority = COption::Some(Pubkey::new_unique());
+        mint.pack_into_slice(&mut data[..Mint2022::LEN]);
+        let info = parse_2022(&data);
+        assert!(info.has_freeze_authority);
+        assert_matrix(&info, [None, None, None]);
+    }
+
+    #[test]
+    fn test_close_authority_detection() {
+        let unset = token_2022_mint(Some(ExtensionType::MintCloseAuthority), |mint| {
+            mint.init_extension::<MintCloseAuthority>(true).unwrap();
+        });
+        assert!(!parse_2022(&unset).has_close_authority);
+
+        let set = token_2022_mint(Some(ExtensionType::MintCloseAuthority), |mint| {
+            mint.init_extension::<MintCloseAuthority>(true)
+                .unwrap()
+                .close_authority = Some(Pubkey::new_unique()).try_into().unwrap();
+        });
+        let info = parse_2022(&set);
+        assert!(info.has_close_authority);
+        // 关闭权限和冻结权限是两回事
+        assert!(!info.has_freeze_authority);
+        assert_matrix(&info, [None, None, None]);
+    }
+
+    #[test]
+    fn test_risk_policy() {
+        let mut info = parse_2022(&token_2022_mint(None, |_| {}));
+        for policy in 0..=ProtocolConfig::REJECT_ALL_RISKS {
+            assert!(info.check_risk_policy(policy).is_ok());
+        }
+
+        info.has_freeze_authority = true;
+        assert!(info.check_risk_policy(0).is_ok());
+        assert!(info
+            .check_risk_policy(ProtocolConfig::REJECT_CLOSE_AUTHORITY)
+            .is_ok());
+        assert_eq!(
+            info.check_risk_policy(ProtocolConfig::REJECT_FREEZE_AUTHORITY)
+                .unwrap_err(),
+            error_code(RedPacketError::FreezeAuthorityNotAllowed)
+        );
+
+        info.has_freeze_authority = false;
+        info.has_close_authority = true;
+        assert!(info
+            .check_risk_policy(ProtocolConfig::REJECT_FREEZE_AUTHORITY)
+            .is_ok());
+        assert_eq!(
+            info.check_risk_policy(ProtocolConfig::REJECT_ALL_RISKS)
+                .unwrap_err(),
+            error_code(RedPacketError::CloseAuthorityNotAllowed)
+        );
+    }
+
+    #[test]
+    fn test_transfer_fee_matrix() {
+        let data = token_2022_mint(Some(ExtensionType::TransferFeeConfig), |mint| {
+            mint.init_extension::<TransferFeeConfig>(true).unwrap();
+        });
+        let info = parse_2022(&data);
+        assert!(info.has_transfer_fee);
+        assert_matrix(&info, [None, None, None]);
+    }
+
+    #[test]
+    fn test_transfer_hook_matrix() {
+        let data = token_2022_mint(Some(ExtensionType::TransferHook), |mint| {
+            mint.init_extension::<TransferHook>(true).unwrap();
+        });
+        let info = parse_2022(&data);
+        assert!(info.has_transfer_hook);
+        assert_matrix(&info, [None, None, None]);
+    }
+
+    #[test]
+    fn test_permanent_delegate_matrix() {
+        let data = token_2022_mint(Some(ExtensionType::PermanentDelegate), |mint| {
+            mint.init_extension::<PermanentDelegate>(true).unwrap();
+        });
+        let info = parse_2022(&data);
+        assert!(info.has_permanent_delegate);
+        let error = Some(RedPacketError::PermanentDelegateNotSupported);
+        assert_matrix(&info, [error, error, None]);
+    }
+
+    #[test]
+    fn test_confidential_transfer_matrix() {
+        let data = token_2022_mint(Some(ExtensionType::ConfidentialTransferMint), |mint| {
+            mint.init_extension::<ConfidentialTransferMint>(true)
+                .unwrap();
+        });
+        let info = parse_2022(&data);
+        assert!(info.has_confidential_transfer);
+        let error = Some(RedPacketError::ConfidentialTransferNotSupported);
+        assert_matrix(&info, [error, error, None]);
+    }
+
+    #[test]
+    fn test_non_transferable_matrix() {
+        let data = token_2022_mint(Some(ExtensionType::NonTransferable), |mint| {
+            mint.init_extension::<NonTransferable>(true).unwrap();
+        });
+        let info = parse_2022(&data);
+        assert!(info.is_non_transferable);
+        let error = Some(RedPacketError::NonTransferableNotSupported);
+        assert_matrix(&info, [error, error, None]);
+    }
+
+    #[test]
+    fn test_pausable_matrix() {
+        let resumed = token_2022_mint(Some(ExtensionType::Pausable), |mint| {
+            mint.init_extension::<PausableConfig>(true).unwrap();
+        });
+        let info = parse_2022(&resumed);
+        assert!(!info.is_paused);
+        assert_matrix(&info, [None, None, None]);
+
+        let paused = token_2022_mint(Some(ExtensionType::Pausable), |mint| {
+            mint.init_extension::<PausableConfig>(true).unwrap().paused = true.into();
+        });
+        let info = parse_2022(&paused);
+        assert!(info.is_paused);
+        let error = Some(RedPacketError::MintPaused);
+        assert_matrix(&info, [error, error, error]);
+    }
+
+    #[test]
+    fn test_default_account_state_matrix() {
+        let initialized = token_2022_mint(Some(ExtensionType::DefaultAccountState), |mint| {
+            mint.init_extension::<DefaultAccountState>(true)
+                .unwrap()
+                .state = AccountState::Initialized as u8;
+        });
+        let info = parse_2022(&initialized);
+        assert!(!info.is_default_frozen);
+        assert_matrix(&info, [None, None, None]);
+
+        let frozen = token_2022_mint(Some(ExtensionType::DefaultAccountState), |mint| {
+            mint.init_extension::<DefaultAccountState>(true)
+                .unwrap()
+                .state = AccountState::Frozen as u8;
+        });
+        let info = parse_2022(&frozen);
+        assert!(info.is_default_frozen);
+        assert_matrix(
+            &info,
+            [Some(RedPacketError::DefaultFrozenNotSupported), None, None],
+        );
+    }
+}
@@ -1,7 +1,82 @@
+use crate::config::{MAX_BUNDLE_LEGS, MAX_OPERATORS, MAX_PRIZE_TIERS};
 use anchor_lang::prelude::*;
 
+/// 红包账户。新增字段从 reserved 中划出空间并提升 CURRENT_VERSION，
+/// 账户大小变化时旧账户需要先调用 migrate_redpacket
 #[account]
+#[derive(InitSpace)]
 pub struct RedPacket {
+    pub version: u8, // 固定在判别符之后，以后的迁移可以先读它再决定按哪个布局解析
+    pub creator: Pubkey,
+    pub mint: Pubkey,
+    pub total_amount: u64,
+    pub remaining_amount: u64,
+    pub packet_count: u32,
+    pub claimed_count: u32,
+    pub red_packet_type: u8, // 0 平分，1 随机，2 Merkle，3 随机（混入承诺 slot 的 hash），4 Merkle + 位图，5 口令平分，6 口令随机，7 分档
+    pub share_amount: u64,
+    pub random_seed: u64,
+    pub expiry_time: i64,
+    pub start_time: i64, // 早于该时间不能领取，与 expiry_time 构成领取窗口
+    pub merkle_root: [u8; 32],
+    pub code_hash: [u8; 32], // 口令红包：sha256(red_packet ‖ code)
+    pub holder_gate: HolderGate,
+    pub is_sol: bool,
+    pub expiry_time_changes: u8,
+    pub red_packet_id: u64,
+    pub bump: u8,
+    pub tiers: [PrizeTier; MAX_PRIZE_TIERS], // 分档红包的奖项，count 为该档剩余份数
+    pub cancelled: bool, // 带宽限期取消后置位，之后不能再改时间或追加，宽限期结束即停止领取
+    pub cancel_slot: u64, // 取消时的 slot，宽限期内只放行在它之前提交过承诺的领取
+    pub reserved: [u8; 7],
+}
+
+impl RedPacket {
+    /// 第一个带版本号的布局。唯一的迁移是从没有版本号的原始布局（RedPacketV0）升到这里
+    pub const CURRENT_VERSION: u8 = 1;
+
+    /// 从没有版本号的旧布局升级，新字段取不改变原有行为的默认值
+    pub fn from_v0(legacy: RedPacketV0) -> Self {
+        Self {
+            version: Self::CURRENT_VERSION,
+            creator: legacy.creator,
+            mint: legacy.mint,
+            total_amount: legacy.total_amount,
+            remaining_amount: legacy.remaining_amount,
+            packet_count: legacy.packet_count,
+            claimed_count: legacy.claimed_count,
+            red_packet_type: legacy.red_packet_type,
+            share_amount: legacy.share_amount,
+            random_seed: legacy.random_seed,
+            expiry_time: legacy.expiry_time,
+            start_time: 0,
+            merkle_root: legacy.merkle_root,
+            code_hash: [0; 32],
+            holder_gate: HolderGate::default(),
+            is_sol: legacy.is_sol,
+            expiry_time_changes: legacy.expiry_time_changes,
+            red_packet_id: legacy.red_packet_id,
+            bump: legacy.bump,
+            tiers: [PrizeTier::default(); MAX_PRIZE_TIERS],
+            cancelled: false,
+            cancel_slot: 0,
+            reserved: [0; 7],
+        }
+    }
+}
+
+/// 分档红包的一档奖项
+#[derive(
+    AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+pub struct PrizeTier {
+    pub amount: u64, // 该档每份的金额
+    pub count: u32,
+}
+
+/// 引入版本号之前的 RedPacket 布局（判别符相同），只用于 migrate_redpacket
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub struct RedPacketV0 {
     pub creator: Pubkey,
     pub mint: Pubkey,
     pub total_amount: u64,
@@ -19,13 +94,325 @@ pub struct RedPacket {
     pub bump: u8,
 }
 
+/// 持有者门槛，可以与平分、随机、口令类型组合
+#[derive(
+    AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+pub struct HolderGate {
+    pub kind: u8,        // 0 不限制，1 持有指定代币，2 持有 Metaplex 已验证合集中的 NFT
+    pub mint: Pubkey,    // kind 1 为代币 mint，kind 2 为合集 mint
+    pub min_amount: u64, // kind 1 的最低持有量
+}
+
+/// 组合红包：每份同时发放固定数量的 SOL 和最多 MAX_BUNDLE_LEGS 种代币，
+/// PDA 种子为 [BUNDLE_SEED, creator, bundle_id]，每种代币的池子是该 PDA 的 ATA
+#[account]
+#[derive(InitSpace)]
+pub struct BundlePacket {
+    pub creator: Pubkey,
+    pub bundle_id: u64, // 与普通红包共用 CreatorState 的编号
+    pub packet_count: u32,
+    pub claimed_count: u32,
+    pub sol_per_claim: u64, // 0 表示不发 SOL
+    pub expiry_time: i64,
+    #[max_len(MAX_BUNDLE_LEGS)]
+    pub legs: Vec<BundleLeg>,
+    pub bump: u8,
+}
+
+/// 组合红包里的一种代币，remaining_accounts 按 legs 的顺序传入
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BundleLeg {
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub amount_per_claim: u64,
+    pub decimals: u8,
+}
+
 #[account]
 pub struct UserState {
     pub is_claimed: u8, // 1 表示已领取，0 表示未领取
 }
 
+/// 领取承诺：口令红包的 commitment = sha256(red_packet ‖ claimer ‖ code)，
+/// 类型 3 / 7 只用 slot 选取随机数混入的 slot hash
+#[account]
+pub struct ClaimCommit {
+    pub commitment: [u8; 32],
+    pub slot: u64, // 提交承诺时的 slot，揭示必须在之后的 slot
+}
+
+/// 全局协议配置，PDA 种子为 PROTOCOL_CONFIG_SEED，全局只有一个。
+/// 与 RedPacket 一样，新增字段从 reserved 中划出空间并提升 CURRENT_VERSION
 #[account]
+#[derive(InitSpace)]
+pub struct ProtocolConfig {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey, // 两步转移管理员，Pubkey::default() 表示没有待接受的管理员
+    pub fee_receiver: Pubkey,
+    pub create_fee: FeeRule,
+    pub claim_fee: FeeRule,
+    pub pause_flags: u8, // 按位暂停创建（含追加）、领取、修改时间，退款始终可用
+    pub mint_risk_policy: u8, // 按位拒绝有冻结权限 / 关闭权限的 mint，未拒绝的只在创建事件里标记
+    pub bump: u8,
+    pub reserved: [u8; 32],
+}
+
+impl ProtocolConfig {
+    /// 首个发布的布局，没有更早的版本需要迁移
+    pub const CURRENT_VERSION: u8 = 1;
+
+    pub const PAUSE_CREATE: u8 = 1 << 0;
+    pub const PAUSE_CLAIM: u8 = 1 << 1;
+    pub const PAUSE_EXPIRY: u8 = 1 << 2;
+    pub const PAUSE_ALL: u8 = Self::PAUSE_CREATE | Self::PAUSE_CLAIM | Self::PAUSE_EXPIRY;
+
+    pub const REJECT_FREEZE_AUTHORITY: u8 = 1 << 0;
+    pub const REJECT_CLOSE_AUTHORITY: u8 = 1 << 1;
+    pub const REJECT_ALL_RISKS: u8 = Self::REJECT_FREEZE_AUTHORITY | Self::REJECT_CLOSE_AUTHORITY;
+
+    pub fn is_paused(&self, flag: u8) -> bool {
+        self.pause_flags & flag != 0
+    }
+}
+
+/// 手续费以 lamports 收取：固定部分加上按金额的比例部分
+#[derive(
+    AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+pub struct FeeRule {
+    pub lamports: u64,
+    pub bps: u16, // 万分之几，创建按 total_amount、领取按领取金额计算，只对 SOL 红包生效
+}
+
+impl FeeRule {
+    pub const MAX_BPS: u16 = 10_000;
+
+    pub fn fee(&self, amount: u64, is_sol: bool) -> Option<u64> {
+        let proportional = if is_sol {
+            u64::try_from(amount as u128 * self.bps as u128 / Self::MAX_BPS as u128).ok()?
+        } else {
+            0
+        };
+        self.lamports.checked_add(proportional)
+    }
+}
+
+/// 创建者状态，布局约定与 RedPacket 相同：新增字段从 reserved 中划出空间并提升 CURRENT_VERSION
+#[account]
+#[derive(InitSpace)]
 pub struct CreatorState {
+    pub version: u8,
     pub next_red_packet_id: u64,
     pub bump: u8,
+    #[max_len(MAX_OPERATORS)]
+    pub operators: Vec<Operator>, // 创建者授权的操作员，可以代为管理该创建者的所有红包
+    pub reserved: [u8; 32],
+}
+
+impl CreatorState {
+    pub const CURRENT_VERSION: u8 = 1;
+
+    pub const PERMISSION_EXPIRY: u8 = 1 << 0; // 修改过期时间和开始时间
+    pub const PERMISSION_TOP_UP: u8 = 1 << 1;
+    pub const PERMISSION_CANCEL: u8 = 1 << 2;
+    pub const PERMISSION_ALL: u8 =
+        Self::PERMISSION_EXPIRY | Self::PERMISSION_TOP_UP | Self::PERMISSION_CANCEL;
+
+    pub fn has_permission(&self, operator: &Pubkey, permission: u8) -> bool {
+        self.operators
+            .iter()
+            .any(|op| op.key == *operator && op.permissions & permission != 0)
+    }
+}
+
+/// 引入操作员和版本号之前的 CreatorState 布局（判别符相同），只用于 migrate_creator_state
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub struct CreatorStateV0 {
+    pub next_red_packet_id: u64,
+    pub bump: u8,
+}
+
+impl CreatorState {
+    pub fn new(bump: u8) -> Self {
+        Self {
+            version: Self::CURRENT_VERSION,
+            next_red_packet_id: 0,
+            bump,
+            operators: Vec::new(),
+            reserved: [0; 32],
+        }
+    }
+
+    pub fn from_v0(legacy: CreatorStateV0) -> Self {
+        Self {
+            next_red_packet_id: legacy.next_red_packet_id,
+            ..Self::new(legacy.bump)
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Operator {
+    pub key: Pubkey,
+    pub permissions: u8,
+}
+
+/// 类型 4 红包的领取位图，头部之后紧跟 ceil(bit_count / 8) 字节，第 i 位表示第 i 个叶子是否已领取
+#[account(zero_copy)]
+pub struct ClaimBitmap {
+    pub red_packet: Pubkey,
+    pub creator: Pubkey, // 关闭位图时租金退回的地址
+    pub bit_count: u32,
+}
+
+impl ClaimBitmap {
+    pub const HEADER_SPACE: usize = 8 + std::mem::size_of::<ClaimBitmap>();
+
+    pub fn space(bit_count: u32) -> usize {
+        Self::HEADER_SPACE + (bit_count as usize).div_ceil(8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_rule_fixed_and_bps() {
+        let fixed = FeeRule {
+            lamports: 5_000,
+            bps: 0,
+        };
+        assert_eq!(fixed.fee(1_000_000, true), Some(5_000));
+
+        let bps = FeeRule {
+            lamports: 5_000,
+            bps: 250,
+        };
+        assert_eq!(bps.fee(1_000_000, true), Some(30_000));
+        // 代币红包只收固定部分
+        assert_eq!(bps.fee(1_000_000, false), Some(5_000));
+
+        let max = FeeRule {
+            lamports: u64::MAX,
+            bps: FeeRule::MAX_BPS,
+        };
+        assert_eq!(max.fee(1, true), None);
+    }
+
+    fn legacy_red_packet() -> RedPacketV0 {
+        RedPacketV0 {
+            creator: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            total_amount: 1_000,
+            remaining_amount: 400,
+            packet_count: 10,
+            claimed_count: 6,
+            red_packet_type: 1,
+            share_amount: 0,
+            random_seed: 42,
+            expiry_time: 1_700_000_000,
+            merkle_root: [3; 32],
+            is_sol: true,
+            expiry_time_changes: 1,
+            red_packet_id: 7,
+            bump: 254,
+        }
+    }
+
+    #[test]
+    fn test_red_packet_space_matches_serialized_len() {
+        let red_packet = RedPacket::from_v0(legacy_red_packet());
+        let mut data = Vec::new();
+        red_packet.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), crate::config::RED_PACKET_SPACE);
+    }
+
+    #[test]
+    fn test_legacy_space_matches_serialized_len() {
+        let legacy = legacy_red_packet().try_to_vec().unwrap();
+        assert_eq!(
+            RedPacket::DISCRIMINATOR.len() + legacy.len(),
+            crate::config::LEGACY_RED_PACKET_SPACE
+        );
+        // 旧版本手写的 RED_PACKET_SPACE
+        assert_eq!(crate::config::LEGACY_RED_PACKET_SPACE, 164);
+    }
+
+    #[test]
+    fn test_protocol_config_space_matches_serialized_len() {
+        let protocol_config = ProtocolConfig {
+            version: ProtocolConfig::CURRENT_VERSION,
+            admin: Pubkey::new_unique(),
+            pending_admin: Pubkey::new_unique(),
+            fee_receiver: Pubkey::new_unique(),
+            create_fee: FeeRule::default(),
+            claim_fee: FeeRule::default(),
+            pause_flags: ProtocolConfig::PAUSE_ALL,
+            mint_risk_policy: ProtocolConfig::REJECT_ALL_RISKS,
+            bump: 253,
+            reserved: [0; 32],
+        };
+        let mut data = Vec::new();
+        protocol_config.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), crate::config::PROTOCOL_CONFIG_SPACE);
+    }
+
+    #[test]
+    fn test_creator_state_space_fits_max_operators() {
+        let creator_state = CreatorState {
+            next_red_packet_id: u64::MAX,
+            operators: vec![
+                Operator {
+                    key: Pubkey::new_unique(),
+                    permissions: CreatorState::PERMISSION_ALL,
+                };
+                MAX_OPERATORS
+            ],
+            ..CreatorState::new(255)
+        };
+        let mut data = Vec::new();
+        creator_state.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), crate::config::CREATOR_STATE_SPACE);
+
+        let legacy = CreatorStateV0 {
+            next_red_packet_id: 1,
+            bump: 255,
+        };
+        assert_eq!(
+            8 + legacy.try_to_vec().unwrap().len(),
+            crate::config::LEGACY_CREATOR_STATE_SPACE
+        );
+        let migrated = CreatorState::from_v0(legacy);
+        assert_eq!(migrated.version, CreatorState::CURRENT_VERSION);
+        assert_eq!(migrated.next_red_packet_id, 1);
+        assert!(migrated.operators.is_empty());
+        assert!(creator_state.has_permission(
+            &creator_state.operators[0].key,
+            CreatorState::PERMISSION_CANCEL
+        ));
+        assert!(!creator_state.has_permission(&Pubkey::new_unique(), CreatorState::PERMISSION_ALL));
+    }
+
+    #[test]
+    fn test_from_v0_keeps_state_and_defaults_new_fields() {
+        let legacy = legacy_red_packet();
+        let creator = legacy.creator;
+        let red_packet = RedPacket::from_v0(legacy);
+        assert_eq!(red_packet.version, RedPacket::CURRENT_VERSION);
+        assert_eq!(red_packet.creator, creator);
+        assert_eq!(red_packet.remaining_amount, 400);
+        assert_eq!(red_packet.claimed_count, 6);
+        assert_eq!(red_packet.random_seed, 42);
+        assert_eq!(red_packet.expiry_time, 1_700_000_000);
+        assert_eq!(red_packet.merkle_root, [3; 32]);
+        assert!(red_packet.is_sol);
+        assert_eq!(red_packet.bump, 254);
+        assert_eq!(red_packet.start_time, 0);
+        assert_eq!(red_packet.code_hash, [0; 32]);
+        assert_eq!(red_packet.holder_gate, HolderGate::default());
+        assert!(!red_packet.cancelled);
+    }
 }
@@ -1,32 +1,440 @@
-use crate::{config, RedPacket, RedPacketError};
-use anchor_lang::prelude::*;
+use crate::{config, ClaimCommit, CreatorState, HolderGate, PrizeTier, RedPacket, RedPacketError};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        ed25519_program,
+        program::invoke_signed,
+        sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+    },
+};
+use anchor_spl::{
+    token_2022_extensions::transfer_fee::{
+        harvest_withheld_tokens_to_mint, HarvestWithheldTokensToMint,
+    },
+    token_interface::TransferChecked,
+};
 use sha2::{Digest, Sha256};
+use spl_token_2022::{
+    extension::{
+        transfer_fee::TransferFeeConfig, transfer_hook, BaseStateWithExtensions,
+        StateWithExtensions,
+    },
+    state::{Account as TokenAccount2022, Mint as Mint2022},
+};
+use spl_transfer_hook_interface::onchain::add_extra_accounts_for_execute_cpi;
 
 pub fn calculate_random_amount(red_packet: &mut RedPacket, claimer: Pubkey) -> Result<u64> {
-    let remaining_amount = red_packet.remaining_amount;
-    let remaining_packets = red_packet.packet_count - red_packet.claimed_count;
+    let randomness = claim_randomness(red_packet, claimer)?;
+    Ok(split_random_amount(
+        red_packet.remaining_amount,
+        red_packet.packet_count - red_packet.claimed_count,
+        randomness,
+    ))
+}
+
+/// 类型 3：在种子之外混入领取者提交承诺那个 slot 的 hash。提交承诺时该 slot 还没有产出，
+/// 领取者无法预知结果，而承诺在还能揭示时不能重新提交，所以也没法反复重试
+pub fn calculate_slot_hash_random_amount(
+    red_packet: &RedPacket,
+    claimer: Pubkey,
+    slot_hashes: &AccountInfo,
+    commit_slot: u64,
+) -> Result<u64> {
+    let randomness = committed_randomness(red_packet, claimer, slot_hashes, commit_slot)?;
+    Ok(split_random_amount(
+        red_packet.remaining_amount,
+        red_packet.packet_count - red_packet.claimed_count,
+        randomness,
+    ))
+}
 
+/// 按剩余金额和剩余份数切出一份随机金额。
+///
+/// 单份上限为平均值的两倍，同时给后面的每一份至少预留 1，最后一份拿走全部剩余，
+/// 所以只要 `remaining_amount >= remaining_packets`，每份都 >= 1 且总和恰好等于总额。
+pub fn split_random_amount(remaining_amount: u64, remaining_packets: u32, randomness: u64) -> u64 {
     if remaining_packets == 0 {
-        return Ok(0);
+        return 0;
     }
 
     if remaining_packets == 1 {
-        return Ok(remaining_amount);
+        return remaining_amount;
+    }
+
+    let remaining_packets = remaining_packets as u64;
+    let reserved = remaining_packets - 1;
+    let max_amount = ((remaining_amount / remaining_packets) * 2)
+        .min(remaining_amount.saturating_sub(reserved))
+        .max(1);
+    (randomness % max_amount) + 1
+}
+
+/// 类型 7：按剩余份数等概率抽一份，落在哪一档就领该档金额并把该档份数减一。
+/// 抽取不放回，所以全部领完时每档恰好被抽中创建时设定的次数
+pub fn draw_prize_tier(
+    red_packet: &mut RedPacket,
+    claimer: Pubkey,
+    slot_hashes: &AccountInfo,
+    commit_slot: u64,
+) -> Result<(u64, u8)> {
+    let randomness = committed_randomness(red_packet, claimer, slot_hashes, commit_slot)?;
+    pick_prize_tier(&mut red_packet.tiers, randomness)
+}
+
+fn pick_prize_tier(tiers: &mut [PrizeTier], randomness: u64) -> Result<(u64, u8)> {
+    let remaining: u64 = tiers.iter().map(|tier| tier.count as u64).sum();
+    require!(remaining > 0, RedPacketError::NoPacketsRemaining);
+    let mut ticket = randomness % remaining;
+    for (index, tier) in tiers.iter_mut().enumerate() {
+        if ticket < tier.count as u64 {
+            tier.count -= 1;
+            return Ok((tier.amount, index as u8));
+        }
+        ticket -= tier.count as u64;
+    }
+    Err(RedPacketError::NoPacketsRemaining.into())
+}
+
+/// 校验分档配置并展开成账户里的定长数组：每档金额和份数都大于 0，
+/// 份数之和等于 packet_count，金额之和等于 total_amount
+pub fn validate_prize_tiers(
+    tiers: &[PrizeTier],
+    packet_count: u32,
+    total_amount: u64,
+) -> Result<[PrizeTier; config::MAX_PRIZE_TIERS]> {
+    require!(
+        !tiers.is_empty() && tiers.len() <= config::MAX_PRIZE_TIERS,
+        RedPacketError::InvalidPrizeTiers
+    );
+    let mut result = [PrizeTier::default(); config::MAX_PRIZE_TIERS];
+    let mut count_sum: u64 = 0;
+    let mut amount_sum: u64 = 0;
+    for (slot, tier) in result.iter_mut().zip(tiers) {
+        require!(
+            tier.amount > 0 && tier.count > 0,
+            RedPacketError::InvalidPrizeTiers
+        );
+        count_sum += tier.count as u64;
+        amount_sum = tier
+            .amount
+            .checked_mul(tier.count as u64)
+            .and_then(|amount| amount_sum.checked_add(amount))
+            .ok_or(RedPacketError::ArithmeticOverflow)?;
+        *slot = *tier;
+    }
+    require!(
+        count_sum == packet_count as u64 && amount_sum == total_amount,
+        RedPacketError::InvalidPrizeTiers
+    );
+    Ok(result)
+}
+
+/// 创建者本人或者持有对应权限的操作员才能管理红包，creator_state 必须是红包创建者的
+pub fn check_manager(
+    red_packet: &RedPacket,
+    creator_state: &CreatorState,
+    authority: &Pubkey,
+    permission: u8,
+) -> Result<()> {
+    require!(
+        *authority == red_packet.creator || creator_state.has_permission(authority, permission),
+        RedPacketError::Unauthorized
+    );
+    Ok(())
+}
+
+/// 追加金额和份数后更新红包的计数：平分红包按剩余金额重新均分剩余份数，
+/// 随机红包只需保证每个剩余份额至少 1，Merkle 红包的金额由名单决定，不能追加。
+/// 已领完的红包可以关闭并清理 UserState，之后再追加会让领过的人重复领取，所以直接拒绝
+pub fn apply_top_up(red_packet: &mut RedPacket, amount: u64, extra_packets: u32) -> Result<()> {
+    require!(amount > 0, RedPacketError::InvalidTopUpAmount);
+    require!(
+        red_packet.claimed_count < red_packet.packet_count,
+        RedPacketError::RedPacketFullyClaimed
+    );
+    let packet_count = red_packet
+        .packet_count
+        .checked_add(extra_packets)
+        .ok_or(RedPacketError::CounterOverflow)?;
+    require!(
+        packet_count <= config::MAX_PACKET_COUNT,
+        RedPacketError::PacketCountTooLarge
+    );
+    let remaining_packets = packet_count - red_packet.claimed_count;
+    require!(remaining_packets > 0, RedPacketError::InvalidPacketCount);
+    let remaining_amount = red_packet
+        .remaining_amount
+        .checked_add(amount)
+        .ok_or(RedPacketError::ArithmeticOverflow)?;
+    let total_amount = red_packet
+        .total_amount
+        .checked_add(amount)
+        .ok_or(RedPacketError::ArithmeticOverflow)?;
+
+    match red_packet.red_packet_type {
+        0 | 5 => {
+            let share = remaining_amount / remaining_packets as u64;
+            require!(share > 0, RedPacketError::InvalidShareAmount);
+            red_packet.share_amount = share;
+        }
+        1 | 3 | 6 => require!(
+            remaining_amount >= remaining_packets as u64,
+            RedPacketError::InsufficientTotalAmount
+        ),
+        _ => return Err(RedPacketError::TopUpNotSupported.into()),
     }
 
+    red_packet.packet_count = packet_count;
+    red_packet.remaining_amount = remaining_amount;
+    red_packet.total_amount = total_amount;
+    Ok(())
+}
+
+fn claim_randomness(red_packet: &RedPacket, claimer: Pubkey) -> Result<u64> {
     let mut hasher = Sha256::new();
     hasher.update(red_packet.random_seed.to_le_bytes());
     hasher.update(claimer.as_ref());
     hasher.update(red_packet.claimed_count.to_le_bytes());
-    let result = hasher.finalize();
+    randomness_from(hasher)
+}
+
+/// 类型 3 和 7 的随机数，只由种子、领取者和承诺 slot 的 hash 决定。不混入 claimed_count，
+/// 否则领取者可以等别人领过几份、挑一个对自己有利的时机再揭示。
+/// 承诺的 slot 滑出 SlotHashes（只保留最近 512 个）后直接报错，领取者先调用 close_claim_commit
+/// 关闭承诺再重新提交
+fn committed_randomness(
+    red_packet: &RedPacket,
+    claimer: Pubkey,
+    slot_hashes: &AccountInfo,
+    commit_slot: u64,
+) -> Result<u64> {
+    let slot_hash =
+        slot_hash_at(slot_hashes, commit_slot)?.ok_or(RedPacketError::ClaimCommitExpired)?;
+    slot_hash_randomness(red_packet, claimer, &slot_hash)
+}
+
+fn slot_hash_randomness(
+    red_packet: &RedPacket,
+    claimer: Pubkey,
+    slot_hash: &[u8; 32],
+) -> Result<u64> {
+    let mut hasher = Sha256::new();
+    hasher.update(red_packet.random_seed.to_le_bytes());
+    hasher.update(claimer.as_ref());
+    hasher.update(slot_hash);
+    randomness_from(hasher)
+}
 
-    let randomness = u64::from_le_bytes(
+fn randomness_from(hasher: Sha256) -> Result<u64> {
+    let result = hasher.finalize();
+    Ok(u64::from_le_bytes(
         result[0..8]
             .try_into()
             .map_err(|_| RedPacketError::RandomnessError)?,
+    ))
+}
+
+/// 承诺的 slot 已经过去且不在 SlotHashes 里，揭示时再也取不到它的 hash
+pub fn claim_commit_expired(slot_hashes: &AccountInfo, commit_slot: u64) -> Result<bool> {
+    Ok(Clock::get()?.slot > commit_slot && slot_hash_at(slot_hashes, commit_slot)?.is_none())
+}
+
+/// 直接读取 SlotHashes 的原始数据：`u64` 长度 + `(u64 slot, [u8; 32] hash)` 列表，最新的在最前面。
+/// 该 sysvar 体积太大，无法通过 `Sysvar::get` 整体反序列化。
+fn slot_hash_at(slot_hashes: &AccountInfo, slot: u64) -> Result<Option<[u8; 32]>> {
+    require!(
+        slot_hashes.key() == anchor_lang::solana_program::sysvar::slot_hashes::ID,
+        RedPacketError::InvalidSlotHashes
+    );
+    let data = slot_hashes.try_borrow_data()?;
+    Ok(find_slot_hash(&data, slot))
+}
+
+fn find_slot_hash(data: &[u8], slot: u64) -> Option<[u8; 32]> {
+    let len = u64::from_le_bytes(data.get(0..8)?.try_into().ok()?) as usize;
+    let entries = data.get(8..8 + len.checked_mul(40)?)?;
+    entries
+        .chunks_exact(40)
+        .find(|entry| entry[..8] == slot.to_le_bytes())
+        .and_then(|entry| entry[8..].try_into().ok())
+}
+
+/// 按当前 epoch 计算从池子转出 `amount` 时 Token-2022 扣留的转账费，无转账费扩展时为 0
+pub fn calculate_transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    if mint.owner != &spl_token_2022::id() {
+        return Ok(0);
+    }
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<Mint2022>::unpack(&mint_data)
+        .map_err(|_| RedPacketError::InvalidMintAccount)?;
+    match mint_state.get_extension::<TransferFeeConfig>() {
+        Ok(fee_config) => fee_config
+            .calculate_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(RedPacketError::FeeCalculationError.into()),
+        Err(_) => Ok(0),
+    }
+}
+
+/// 按当前 epoch 计算要让池子实际收到 `amount` 需要额外转入的 Token-2022 转账费，无转账费扩展时为 0
+pub fn calculate_inverse_transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    if mint.owner != &spl_token_2022::id() {
+        return Ok(0);
+    }
+    let mint_data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<Mint2022>::unpack(&mint_data)
+        .map_err(|_| RedPacketError::InvalidMintAccount)?;
+    match mint_state.get_extension::<TransferFeeConfig>() {
+        Ok(fee_config) => fee_config
+            .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(RedPacketError::FeeCalculationError.into()),
+        Err(_) => Ok(0),
+    }
+}
+
+/// transfer_checked 的 transfer hook 版本：hook 的 ExtraAccountMetaList 及其声明的额外账户
+/// 通过 `with_remaining_accounts` 传入，解析后追加到同一个 CPI 里。
+/// 对旧版 SPL Token 和不带 hook 的 mint 等价于普通的 transfer_checked。
+pub fn transfer_checked_with_hook<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8,
+) -> Result<()> {
+    let TransferChecked {
+        from,
+        mint,
+        to,
+        authority,
+    } = ctx.accounts;
+    let mut instruction = spl_token_2022::instruction::transfer_checked(
+        ctx.program.key,
+        from.key,
+        mint.key,
+        to.key,
+        authority.key,
+        &[],
+        amount,
+        decimals,
+    )?;
+    let mut account_infos = vec![from.clone(), mint.clone(), to.clone(), authority.clone()];
+
+    let hook_program_id = if mint.owner == &spl_token_2022::id() {
+        let mint_data = mint.try_borrow_data()?;
+        let mint_state = StateWithExtensions::<Mint2022>::unpack(&mint_data)
+            .map_err(|_| RedPacketError::InvalidMintAccount)?;
+        transfer_hook::get_program_id(&mint_state)
+    } else {
+        None
+    };
+    if let Some(hook_program_id) = hook_program_id {
+        add_extra_accounts_for_execute_cpi(
+            &mut instruction,
+            &mut account_infos,
+            &hook_program_id,
+            from,
+            mint,
+            to,
+            authority,
+            amount,
+            &ctx.remaining_accounts,
+        )?;
+    }
+
+    invoke_signed(&instruction, &account_infos, ctx.signer_seeds).map_err(Into::into)
+}
+
+/// 把池子 ATA 上扣留的转账费收归到 mint（无需权限），否则 ATA 无法关闭
+pub fn harvest_pool_fees<'info>(
+    token_program: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    pool_ata: &AccountInfo<'info>,
+) -> Result<()> {
+    if mint.owner != &spl_token_2022::id() {
+        return Ok(());
+    }
+    let has_transfer_fee = {
+        let mint_data = mint.try_borrow_data()?;
+        StateWithExtensions::<Mint2022>::unpack(&mint_data)
+            .map_err(|_| RedPacketError::InvalidMintAccount)?
+            .get_extension::<TransferFeeConfig>()
+            .is_ok()
+    };
+    if !has_transfer_fee {
+        return Ok(());
+    }
+    // SOL 红包传入的 mint 是系统程序，不能标记为可写，所以只在这里要求可写
+    require!(mint.is_writable, RedPacketError::InvalidMintAccount);
+    harvest_withheld_tokens_to_mint(
+        CpiContext::new(
+            token_program.clone(),
+            HarvestWithheldTokensToMint {
+                token_program_id: token_program.clone(),
+                mint: mint.clone(),
+            },
+        ),
+        vec![pool_ata.clone()],
+    )
+}
+
+/// 代领时收款人需要签名的消息：`red_packet ‖ red_packet_id_le ‖ recipient`
+pub fn relay_claim_message(red_packet: &Pubkey, red_packet_id: u64, recipient: &Pubkey) -> Vec<u8> {
+    let mut message = Vec::with_capacity(32 + 8 + 32);
+    message.extend_from_slice(red_packet.as_ref());
+    message.extend_from_slice(&red_packet_id.to_le_bytes());
+    message.extend_from_slice(recipient.as_ref());
+    message
+}
+
+/// 通过 instructions sysvar 检查紧挨在当前指令之前的 ed25519 验签指令，
+/// 确认 `signer` 对 `message` 签过名（签名本身由 ed25519 程序校验）。
+pub fn verify_ed25519_signature(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current_index = load_current_index_checked(instructions_sysvar)?;
+    require!(current_index > 0, RedPacketError::MissingEd25519Instruction);
+    let instruction = load_instruction_at_checked(current_index as usize - 1, instructions_sysvar)?;
+    require!(
+        instruction.program_id == ed25519_program::ID,
+        RedPacketError::MissingEd25519Instruction
+    );
+
+    // 布局：[签名数量 u8, 填充 u8] + 7 个 u16 偏移量，数据必须都在本条指令内
+    let data = &instruction.data;
+    require!(
+        data.len() >= 16 && data[0] == 1,
+        RedPacketError::InvalidEd25519Instruction
+    );
+    let read_u16 = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]) as usize;
+    let signature_instruction_index = read_u16(4);
+    let public_key_offset = read_u16(6);
+    let public_key_instruction_index = read_u16(8);
+    let message_offset = read_u16(10);
+    let message_size = read_u16(12);
+    let message_instruction_index = read_u16(14);
+    require!(
+        [
+            signature_instruction_index,
+            public_key_instruction_index,
+            message_instruction_index
+        ]
+        .iter()
+        .all(|index| *index == u16::MAX as usize),
+        RedPacketError::InvalidEd25519Instruction
     );
-    let max_amount = remaining_amount.min((remaining_amount / remaining_packets as u64) * 2);
-    Ok((randomness % max_amount) + 1)
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(RedPacketError::InvalidEd25519Instruction)?;
+    let signed_message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(RedPacketError::InvalidEd25519Instruction)?;
+    require!(
+        public_key == signer.as_ref() && signed_message == message,
+        RedPacketError::InvalidEd25519Instruction
+    );
+
+    Ok(())
 }
 
 pub fn verify_merkle_proof(
@@ -35,16 +443,197 @@ pub fn verify_merkle_proof(
     proof: &[[u8; 32]],
     merkle_root: &[u8; 32],
 ) -> Result<()> {
-    require!(
-        proof.len() <= config::MAX_PROOF_LENGTH,
-        RedPacketError::MerkleProofTooLong
-    );
+    let mut hasher = Sha256::new();
+    hasher.update(claimer.as_ref());
+    hasher.update(amount.to_le_bytes());
+    verify_merkle_leaf(hasher.finalize().into(), proof, merkle_root)
+}
 
+/// 类型 4 的叶子带上序号：`sha256(index_le ‖ claimer ‖ amount_le)`，序号对应位图中的位
+pub fn verify_indexed_merkle_proof(
+    index: u32,
+    claimer: Pubkey,
+    amount: u64,
+    proof: &[[u8; 32]],
+    merkle_root: &[u8; 32],
+) -> Result<()> {
     let mut hasher = Sha256::new();
+    hasher.update(index.to_le_bytes());
     hasher.update(claimer.as_ref());
     hasher.update(amount.to_le_bytes());
-    let mut computed_hash = hasher.finalize();
+    verify_merkle_leaf(hasher.finalize().into(), proof, merkle_root)
+}
+
+/// 口令红包存储的哈希，用红包地址加盐，同一口令在不同红包里的哈希不同
+pub fn claim_code_hash(red_packet: &Pubkey, code: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(red_packet.as_ref())
+        .chain_update(code)
+        .finalize()
+        .into()
+}
 
+/// 领取者提交的承诺，绑定领取者地址，别人抄走承诺也无法替自己领取
+pub fn claim_commitment(red_packet: &Pubkey, claimer: &Pubkey, code: &[u8]) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(red_packet.as_ref())
+        .chain_update(claimer.as_ref())
+        .chain_update(code)
+        .finalize()
+        .into()
+}
+
+/// 校验揭示的口令：必须与红包的 code_hash 一致，并且与更早 slot 提交的承诺匹配
+pub fn verify_claim_code(
+    red_packet: &Pubkey,
+    claimer: &Pubkey,
+    code: &[u8],
+    claim_commit: &ClaimCommit,
+    code_hash: &[u8; 32],
+) -> Result<()> {
+    require!(
+        code.len() <= config::MAX_CLAIM_CODE_LENGTH,
+        RedPacketError::InvalidClaimCode
+    );
+    require!(
+        claim_code_hash(red_packet, code) == *code_hash,
+        RedPacketError::InvalidClaimCode
+    );
+    require!(
+        claim_commitment(red_packet, claimer, code) == claim_commit.commitment,
+        RedPacketError::InvalidClaimCommit
+    );
+    require!(
+        Clock::get()?.slot > claim_commit.slot,
+        RedPacketError::ClaimCommitTooRecent
+    );
+    Ok(())
+}
+
+/// 校验领取者满足红包的持有者门槛。
+/// 只看领取时的余额，同一批代币转到别的钱包后还能再领一次，需要严格一人一份时请用 Merkle 名单。
+pub fn verify_holder_gate(
+    gate: &HolderGate,
+    holder: &Pubkey,
+    token_account: Option<&AccountInfo>,
+    metadata: Option<&AccountInfo>,
+) -> Result<()> {
+    if gate.kind == 0 {
+        return Ok(());
+    }
+
+    let token_account = token_account.ok_or(RedPacketError::MissingGateAccount)?;
+    require!(
+        *token_account.owner == anchor_spl::token::spl_token::ID
+            || *token_account.owner == spl_token_2022::ID,
+        RedPacketError::HolderGateNotMet
+    );
+    let (held_mint, amount) = {
+        let data = token_account.data.borrow();
+        let account = StateWithExtensions::<TokenAccount2022>::unpack(&data)
+            .map_err(|_| RedPacketError::HolderGateNotMet)?;
+        require!(
+            account.base.owner == *holder,
+            RedPacketError::HolderGateNotMet
+        );
+        (account.base.mint, account.base.amount)
+    };
+
+    match gate.kind {
+        1 => {
+            require!(held_mint == gate.mint, RedPacketError::HolderGateNotMet);
+            require!(amount >= gate.min_amount, RedPacketError::HolderGateNotMet);
+        }
+        2 => {
+            require!(amount >= 1, RedPacketError::HolderGateNotMet);
+            let metadata = metadata.ok_or(RedPacketError::MissingGateAccount)?;
+            require!(
+                *metadata.owner == config::TOKEN_METADATA_PROGRAM_ID,
+                RedPacketError::HolderGateNotMet
+            );
+            let (metadata_mint, collection) = parse_metadata_collection(&metadata.data.borrow())
+                .ok_or(RedPacketError::HolderGateNotMet)?;
+            require!(metadata_mint == held_mint, RedPacketError::HolderGateNotMet);
+            require!(
+                collection == Some((true, gate.mint)),
+                RedPacketError::HolderGateNotMet
+            );
+        }
+        _ => return Err(RedPacketError::InvalidHolderGate.into()),
+    }
+    Ok(())
+}
+
+/// 从 Metaplex `Metadata` 账户中取出 mint 和 collection（verified, key）。
+/// 只解析到 collection 字段为止，避免引入 mpl-token-metadata 依赖。
+fn parse_metadata_collection(data: &[u8]) -> Option<(Pubkey, Option<(bool, Pubkey)>)> {
+    const METADATA_V1_KEY: u8 = 4;
+
+    fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        if data.len() < len {
+            return None;
+        }
+        let (head, tail) = data.split_at(len);
+        *data = tail;
+        Some(head)
+    }
+    fn take_u8(data: &mut &[u8]) -> Option<u8> {
+        take(data, 1).map(|bytes| bytes[0])
+    }
+    fn take_pubkey(data: &mut &[u8]) -> Option<Pubkey> {
+        take(data, 32).map(|bytes| Pubkey::try_from(bytes).unwrap())
+    }
+    fn skip_string(data: &mut &[u8]) -> Option<()> {
+        let len = u32::from_le_bytes(take(data, 4)?.try_into().ok()?);
+        take(data, len as usize).map(|_| ())
+    }
+    fn take_option(data: &mut &[u8]) -> Option<bool> {
+        match take_u8(data)? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    let mut data = data;
+    if take_u8(&mut data)? != METADATA_V1_KEY {
+        return None;
+    }
+    let _update_authority = take_pubkey(&mut data)?;
+    let mint = take_pubkey(&mut data)?;
+    skip_string(&mut data)?; // name
+    skip_string(&mut data)?; // symbol
+    skip_string(&mut data)?; // uri
+    take(&mut data, 2)?; // seller_fee_basis_points
+    if take_option(&mut data)? {
+        // creators: Vec<Creator { address, verified, share }>
+        let count = u32::from_le_bytes(take(&mut data, 4)?.try_into().ok()?);
+        take(&mut data, (count as usize).checked_mul(32 + 1 + 1)?)?;
+    }
+    take(&mut data, 2)?; // primary_sale_happened, is_mutable
+    if take_option(&mut data)? {
+        take(&mut data, 1)?; // edition_nonce
+    }
+    if take_option(&mut data)? {
+        take(&mut data, 1)?; // token_standard
+    }
+    let collection = if take_option(&mut data)? {
+        let verified = take_u8(&mut data)? == 1;
+        Some((verified, take_pubkey(&mut data)?))
+    } else {
+        None
+    };
+    Some((mint, collection))
+}
+
+/// 排序后两两拼接做 SHA-256，逐层向上计算并与根比较
+fn verify_merkle_leaf(leaf: [u8; 32], proof: &[[u8; 32]], merkle_root: &[u8; 32]) -> Result<()> {
+    require!(
+        proof.len() <= config::MAX_PROOF_LENGTH,
+        RedPacketError::MerkleProofTooLong
+    );
+
+    let mut computed_hash = leaf;
     for proof_element in proof.iter() {
         let mut hasher = Sha256::new();
         if computed_hash.as_slice() <= proof_element.as_slice() {
@@ -54,7 +643,7 @@ pub fn verify_merkle_proof(
             hasher.update(proof_element.as_slice());
             hasher.update(computed_hash.as_slice());
         }
-        computed_hash = hasher.finalize();
+        computed_hash = hasher.finalize().into();
     }
 
     require!(
@@ -64,3 +653,423 @@ pub fn verify_merkle_proof(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn random_packet(total_amount: u64, packet_count: u32, random_seed: u64) -> RedPacket {
+        RedPacket {
+            version: RedPacket::CURRENT_VERSION,
+            creator: Pubkey::new_unique(),
+            mint: Pubkey::default(),
+            total_amount,
+            remaining_amount: total_amount,
+            packet_count,
+            claimed_count: 0,
+            red_packet_type: 3,
+            share_amount: 0,
+            random_seed,
+            expiry_time: 0,
+            start_time: 0,
+            merkle_root: [0; 32],
+            code_hash: [0; 32],
+            holder_gate: HolderGate::default(),
+            is_sol: true,
+            expiry_time_changes: 0,
+            red_packet_id: 0,
+            bump: 0,
+            tiers: [PrizeTier::default(); config::MAX_PRIZE_TIERS],
+            cancelled: false,
+            cancel_slot: 0,
+            reserved: [0; 7],
+        }
+    }
+
+    /// 模拟整轮领取，每次领取混入不同的 slot hash
+    fn claim_one(red_packet: &mut RedPacket, slot_hash_mode: bool) -> u64 {
+        let claimer = Pubkey::new_unique();
+        let slot_hash = Sha256::digest(red_packet.claimed_count.to_be_bytes()).into();
+        let randomness = if slot_hash_mode {
+            slot_hash_randomness(red_packet, claimer, &slot_hash).unwrap()
+        } else {
+            claim_randomness(red_packet, claimer).unwrap()
+        };
+        let amount = split_random_amount(
+            red_packet.remaining_amount,
+            red_packet.packet_count - red_packet.claimed_count,
+            randomness,
+        );
+        assert!(amount >= 1);
+        assert!(amount <= red_packet.remaining_amount);
+        red_packet.remaining_amount -= amount;
+        red_packet.claimed_count += 1;
+        amount
+    }
+
+    fn drain(red_packet: &mut RedPacket, slot_hash_mode: bool) -> Vec<u64> {
+        let mut shares = Vec::new();
+        while red_packet.claimed_count < red_packet.packet_count {
+            shares.push(claim_one(red_packet, slot_hash_mode));
+        }
+        shares
+    }
+
+    #[test]
+    fn test_slot_hash_random_sums_to_total() {
+        for (total_amount, packet_count) in [(1, 1), (3, 3), (10, 3), (1_000_000, 7), (101, 100)] {
+            for seed in 0..20 {
+                let mut red_packet = random_packet(total_amount, packet_count, seed);
+                let shares = drain(&mut red_packet, true);
+                assert_eq!(shares.len(), packet_count as usize);
+                assert_eq!(shares.iter().sum::<u64>(), total_amount);
+                assert_eq!(red_packet.remaining_amount, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_seed_random_sums_to_total() {
+        for (total_amount, packet_count) in [(4, 2), (5, 5), (999, 10)] {
+            let mut red_packet = random_packet(total_amount, packet_count, 42);
+            let shares = drain(&mut red_packet, false);
+            assert_eq!(shares.iter().sum::<u64>(), total_amount);
+        }
+    }
+
+    #[test]
+    fn test_slot_hash_changes_randomness() {
+        let red_packet = random_packet(1_000, 10, 7);
+        let claimer = Pubkey::new_unique();
+        let a = slot_hash_randomness(&red_packet, claimer, &[1; 32]).unwrap();
+        let b = slot_hash_randomness(&red_packet, claimer, &[2; 32]).unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_slot_hash_randomness_ignores_claimed_count() {
+        let mut red_packet = random_packet(1_000, 10, 7);
+        let claimer = Pubkey::new_unique();
+        let before = slot_hash_randomness(&red_packet, claimer, &[1; 32]).unwrap();
+        red_packet.claimed_count = 3;
+        assert_eq!(
+            slot_hash_randomness(&red_packet, claimer, &[1; 32]).unwrap(),
+            before
+        );
+    }
+
+    #[test]
+    fn test_find_slot_hash_by_slot() {
+        let mut data = 2u64.to_le_bytes().to_vec();
+        for slot in [9u64, 7] {
+            data.extend(slot.to_le_bytes());
+            data.extend([slot as u8; 32]);
+        }
+        assert_eq!(find_slot_hash(&data, 7), Some([7; 32]));
+        // 跳过的 slot 和已经移出列表的 slot 都没有 hash
+        assert_eq!(find_slot_hash(&data, 8), None);
+        assert_eq!(find_slot_hash(&data, 1), None);
+        // 声明的长度超出实际数据
+        data[0] = 3;
+        assert_eq!(find_slot_hash(&data, 9), None);
+    }
+
+    #[test]
+    fn test_split_random_amount_reserves_remaining_packets() {
+        // 旧实现在这里可能切走全部 4，导致最后一份为 0
+        for randomness in 0..16 {
+            assert!(split_random_amount(4, 2, randomness) <= 3);
+        }
+        assert_eq!(split_random_amount(2, 2, u64::MAX), 1);
+        assert_eq!(split_random_amount(9, 1, 0), 9);
+    }
+
+    /// 随机红包的生成参数：份数 1-200，总额从刚好每份 1 到 u64::MAX，
+    /// 一半用例集中在总额只比份数略多的边界附近
+    fn random_split_params() -> impl Strategy<Value = (u64, u32)> {
+        (1..=200u32).prop_flat_map(|packet_count| {
+            let minimum = packet_count as u64;
+            let total_amount = prop_oneof![minimum..=minimum * 3, minimum..=u64::MAX];
+            (total_amount, Just(packet_count))
+        })
+    }
+
+    proptest! {
+        /// 单次切分：至少 1，并给后面每一份至少留下 1
+        #[test]
+        fn prop_split_random_amount_bounds(
+            (remaining_amount, remaining_packets) in random_split_params(),
+            randomness in any::<u64>(),
+        ) {
+            let amount = split_random_amount(remaining_amount, remaining_packets, randomness);
+            prop_assert!(amount >= 1);
+            prop_assert!(amount <= remaining_amount - (remaining_packets as u64 - 1));
+        }
+
+        /// 模拟整轮领取，领取者、种子和 slot hash 都由 proptest 生成，失败时会收缩出最小用例
+        #[test]
+        fn prop_random_claims_conserve_total(
+            (total_amount, packet_count) in random_split_params(),
+            random_seed in any::<u64>(),
+            claimer_seed in any::<[u8; 32]>(),
+            slot_hash_mode in any::<bool>(),
+        ) {
+            let mut red_packet = random_packet(total_amount, packet_count, random_seed);
+            let mut claimed = 0u64;
+            while red_packet.claimed_count < red_packet.packet_count {
+                let claimer = Pubkey::new_from_array(
+                    Sha256::new()
+                        .chain_update(claimer_seed)
+                        .chain_update(red_packet.claimed_count.to_le_bytes())
+                        .finalize()
+                        .into(),
+                );
+                let amount = if slot_hash_mode {
+                    let slot_hash = Sha256::digest(claimer.as_ref()).into();
+                    let randomness = slot_hash_randomness(&red_packet, claimer, &slot_hash).unwrap();
+                    split_random_amount(
+                        red_packet.remaining_amount,
+                        red_packet.packet_count - red_packet.claimed_count,
+                        randomness,
+                    )
+                } else {
+                    calculate_random_amount(&mut red_packet, claimer).unwrap()
+                };
+
+                prop_assert!(amount >= 1);
+                prop_assert!(amount <= red_packet.remaining_amount);
+                red_packet.remaining_amount -= amount;
+                red_packet.claimed_count += 1;
+                claimed += amount;
+                // 剩下的每一份都还能领到至少 1
+                prop_assert!(
+                    red_packet.remaining_amount
+                        >= (red_packet.packet_count - red_packet.claimed_count) as u64
+                );
+            }
+            prop_assert_eq!(claimed, total_amount);
+            prop_assert_eq!(red_packet.remaining_amount, 0);
+        }
+    }
+
+    #[test]
+    fn test_prize_tiers_are_drawn_without_replacement() {
+        let config = [
+            PrizeTier {
+                amount: 1_000,
+                count: 1,
+            },
+            PrizeTier {
+                amount: 100,
+                count: 10,
+            },
+            PrizeTier {
+                amount: 1,
+                count: 89,
+            },
+        ];
+        let mut red_packet = random_packet(2_089, 100, 11);
+        red_packet.red_packet_type = 7;
+        red_packet.tiers = validate_prize_tiers(&config, 100, 2_089).unwrap();
+
+        let mut drawn = [0u32; config::MAX_PRIZE_TIERS];
+        let mut total = 0;
+        while red_packet.claimed_count < red_packet.packet_count {
+            let slot_hash = Sha256::digest(red_packet.claimed_count.to_le_bytes()).into();
+            let randomness =
+                slot_hash_randomness(&red_packet, Pubkey::new_unique(), &slot_hash).unwrap();
+            let (amount, index) = pick_prize_tier(&mut red_packet.tiers, randomness).unwrap();
+            assert_eq!(amount, config[index as usize].amount);
+            drawn[index as usize] += 1;
+            total += amount;
+            red_packet.claimed_count += 1;
+        }
+        assert_eq!(drawn, [1, 10, 89, 0]);
+        assert_eq!(total, 2_089);
+        assert!(pick_prize_tier(&mut red_packet.tiers, 0).is_err());
+    }
+
+    #[test]
+    fn test_validate_prize_tiers() {
+        let tier = |amount, count| PrizeTier { amount, count };
+        assert!(validate_prize_tiers(&[tier(10, 2), tier(1, 3)], 5, 23).is_ok());
+        // 份数或金额对不上
+        assert!(validate_prize_tiers(&[tier(10, 2), tier(1, 3)], 6, 23).is_err());
+        assert!(validate_prize_tiers(&[tier(10, 2), tier(1, 3)], 5, 24).is_err());
+        // 空档、空配置和超过上限
+        assert!(validate_prize_tiers(&[tier(10, 2), tier(0, 3)], 5, 20).is_err());
+        assert!(validate_prize_tiers(&[], 0, 0).is_err());
+        assert!(validate_prize_tiers(&[tier(1, 1); 5], 5, 5).is_err());
+        assert!(validate_prize_tiers(&[tier(u64::MAX, 2)], 2, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_top_up_resplits_equal_share() {
+        let mut red_packet = random_packet(1_000, 10, 0);
+        red_packet.red_packet_type = 0;
+        red_packet.share_amount = 100;
+        red_packet.claimed_count = 4;
+        red_packet.remaining_amount = 600;
+
+        apply_top_up(&mut red_packet, 401, 4).unwrap();
+        assert_eq!(red_packet.total_amount, 1_401);
+        assert_eq!(red_packet.remaining_amount, 1_001);
+        assert_eq!(red_packet.packet_count, 14);
+        // 剩余 10 份均分 1_001，零头留给退款
+        assert_eq!(red_packet.share_amount, 100);
+
+        apply_top_up(&mut red_packet, 999, 0).unwrap();
+        assert_eq!(red_packet.share_amount, 200);
+    }
+
+    #[test]
+    fn test_top_up_random_still_sums_to_total() {
+        let mut red_packet = random_packet(1_000, 3, 7);
+        let mut shares = vec![
+            claim_one(&mut red_packet, true),
+            claim_one(&mut red_packet, true),
+        ];
+
+        apply_top_up(&mut red_packet, 500, 2).unwrap();
+        shares.extend(drain(&mut red_packet, true));
+        assert_eq!(shares.len(), 5);
+        assert_eq!(shares.iter().sum::<u64>(), 1_500);
+        assert_eq!(red_packet.total_amount, 1_500);
+    }
+
+    #[test]
+    fn test_top_up_rejects_invalid_requests() {
+        let mut red_packet = random_packet(1_000, 5, 7);
+        assert!(apply_top_up(&mut red_packet, 0, 1).is_err());
+        // 随机红包每份至少 1
+        red_packet.remaining_amount = 0;
+        red_packet.claimed_count = 3;
+        assert!(apply_top_up(&mut red_packet, 2, 1).is_err());
+        red_packet.packet_count = config::MAX_PACKET_COUNT;
+        assert!(apply_top_up(&mut red_packet, 1_000, 1).is_err());
+        // 已领完的红包即使追加份数也不行
+        red_packet.packet_count = 3;
+        red_packet.claimed_count = 3;
+        assert!(apply_top_up(&mut red_packet, 1_000, 2).is_err());
+
+        let mut merkle = random_packet(1_000, 5, 0);
+        merkle.red_packet_type = 2;
+        assert!(apply_top_up(&mut merkle, 1_000, 0).is_err());
+        assert_eq!(merkle.total_amount, 1_000);
+        // 分档红包的金额由奖项决定
+        merkle.red_packet_type = 7;
+        assert!(apply_top_up(&mut merkle, 1_000, 0).is_err());
+    }
+
+    #[test]
+    fn test_indexed_merkle_proof_binds_index() {
+        let claimer = Pubkey::new_unique();
+        let leaf = |index: u32, amount: u64| -> [u8; 32] {
+            let mut hasher = Sha256::new();
+            hasher.update(index.to_le_bytes());
+            hasher.update(claimer.as_ref());
+            hasher.update(amount.to_le_bytes());
+            hasher.finalize().into()
+        };
+        let (a, b) = (leaf(0, 100), leaf(1, 200));
+        let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+        let root: [u8; 32] = Sha256::new()
+            .chain_update(lo)
+            .chain_update(hi)
+            .finalize()
+            .into();
+
+        assert!(verify_indexed_merkle_proof(0, claimer, 100, &[b], &root).is_ok());
+        assert!(verify_indexed_merkle_proof(1, claimer, 200, &[a], &root).is_ok());
+        // 同一个地址换一个序号不能重复领取
+        assert!(verify_indexed_merkle_proof(1, claimer, 100, &[b], &root).is_err());
+    }
+
+    #[test]
+    fn test_claim_bitmap_space() {
+        assert_eq!(crate::ClaimBitmap::HEADER_SPACE, 8 + 32 + 32 + 4);
+        assert_eq!(
+            crate::ClaimBitmap::space(1),
+            crate::ClaimBitmap::HEADER_SPACE + 1
+        );
+        assert_eq!(
+            crate::ClaimBitmap::space(8),
+            crate::ClaimBitmap::HEADER_SPACE + 1
+        );
+        assert_eq!(
+            crate::ClaimBitmap::space(9),
+            crate::ClaimBitmap::HEADER_SPACE + 2
+        );
+    }
+
+    #[test]
+    fn test_claim_code_hash_is_salted_and_commitment_binds_claimer() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let claimer = Pubkey::new_unique();
+        assert_ne!(claim_code_hash(&a, b"8888"), claim_code_hash(&b, b"8888"));
+        assert_ne!(
+            claim_commitment(&a, &claimer, b"8888"),
+            claim_commitment(&a, &Pubkey::new_unique(), b"8888")
+        );
+        assert_ne!(
+            claim_commitment(&a, &claimer, b"8888"),
+            claim_commitment(&a, &claimer, b"8889")
+        );
+    }
+
+    /// 按 Metaplex Metadata 的 borsh 布局拼出账户数据
+    fn metadata_data(mint: Pubkey, creators: u32, collection: Option<(bool, Pubkey)>) -> Vec<u8> {
+        let mut data = vec![4];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(mint.as_ref());
+        for field in ["Red Packet #1", "RP", "https://example.com/1.json"] {
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(field.as_bytes());
+        }
+        data.extend_from_slice(&500u16.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&creators.to_le_bytes());
+        for _ in 0..creators {
+            data.extend_from_slice(Pubkey::new_unique().as_ref());
+            data.extend_from_slice(&[1, 100 / creators as u8]);
+        }
+        data.extend_from_slice(&[1, 1]);
+        data.extend_from_slice(&[1, 255]);
+        data.push(0);
+        match collection {
+            Some((verified, key)) => {
+                data.extend_from_slice(&[1, verified as u8]);
+                data.extend_from_slice(key.as_ref());
+            }
+            None => data.push(0),
+        }
+        // 真实账户在 collection 之后还有 uses 等字段和填充
+        data.extend_from_slice(&[0; 64]);
+        data
+    }
+
+    #[test]
+    fn test_parse_metadata_collection() {
+        let mint = Pubkey::new_unique();
+        let collection = Pubkey::new_unique();
+        assert_eq!(
+            parse_metadata_collection(&metadata_data(mint, 2, Some((true, collection)))),
+            Some((mint, Some((true, collection))))
+        );
+        assert_eq!(
+            parse_metadata_collection(&metadata_data(mint, 1, Some((false, collection)))),
+            Some((mint, Some((false, collection))))
+        );
+        assert_eq!(
+            parse_metadata_collection(&metadata_data(mint, 0, None)),
+            Some((mint, None))
+        );
+
+        let data = metadata_data(mint, 2, Some((true, collection)));
+        assert_eq!(parse_metadata_collection(&data[..100]), None);
+        let mut wrong_key = data.clone();
+        wrong_key[0] = 6;
+        assert_eq!(parse_metadata_collection(&wrong_key), None);
+    }
+}
@@ -0,0 +1,333 @@
+//! 组合红包：一个 PDA 同时托管 SOL、旧版 SPL Token 和 Token-2022 代币，
+//! 每次领取原子地发放所有代币，领完后一次退款关闭所有池子
+
+mod common;
+
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use common::{
+    custom_error, error_code, fund, initialize_creator_state_ix, initialize_protocol_config_ix,
+    lamports, mint_to, protocol_config_address, send, set_upgrade_authority, token_balance,
+    user_state_address, DECIMALS,
+};
+use red_packet::{config::BUNDLE_SEED, errors::RedPacketError, states::BundlePacket};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+};
+use solana_system_interface::{instruction as system_instruction, program as system_program};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+const PACKET_COUNT: u32 = 2;
+const SOL_PER_CLAIM: u64 = 100_000_000;
+const TOKEN_AMOUNTS: [u64; 2] = [1_000, 2_500];
+
+struct Bundle {
+    context: ProgramTestContext,
+    creator: Keypair,
+    /// (mint, token program)，旧版 SPL Token 在前
+    legs: Vec<(Pubkey, Pubkey)>,
+}
+
+fn bundle_address(creator: &Pubkey, bundle_id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[BUNDLE_SEED, creator.as_ref(), &bundle_id.to_le_bytes()],
+        &red_packet::ID,
+    )
+    .0
+}
+
+fn ata(owner: &Pubkey, (mint, token_program): &(Pubkey, Pubkey)) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
+
+async fn create_mint(
+    context: &mut ProgramTestContext,
+    token_program: &Pubkey,
+    owner: &Pubkey,
+) -> Pubkey {
+    let mint = Keypair::new();
+    let payer = context.payer.pubkey();
+    let mint_len = spl_token_2022::state::Mint::LEN;
+    let owner_ata =
+        get_associated_token_address_with_program_id(owner, &mint.pubkey(), token_program);
+    let instructions = [
+        system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            Rent::default().minimum_balance(mint_len),
+            mint_len as u64,
+            token_program,
+        ),
+        spl_token_2022::instruction::initialize_mint2(
+            token_program,
+            &mint.pubkey(),
+            &payer,
+            None,
+            DECIMALS,
+        )
+        .unwrap(),
+        spl_associated_token_account::instruction::create_associated_token_account(
+            &payer,
+            owner,
+            &mint.pubkey(),
+            token_program,
+        ),
+        spl_token_2022::instruction::mint_to(
+            token_program,
+            &mint.pubkey(),
+            &owner_ata,
+            &payer,
+            &[],
+            1_000_000,
+        )
+        .unwrap(),
+    ];
+    send(context, &instructions, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+async fn setup() -> Bundle {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = Keypair::new();
+    fund(&mut context, &creator.pubkey(), 10_000_000_000).await;
+    let admin = context.payer.pubkey();
+    set_upgrade_authority(&mut context, &admin);
+    send(
+        &mut context,
+        &[
+            initialize_protocol_config_ix(&admin),
+            initialize_creator_state_ix(&creator.pubkey()),
+        ],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+
+    let mut legs = Vec::new();
+    for token_program in [anchor_spl::token::ID, spl_token_2022::id()] {
+        let mint = create_mint(&mut context, &token_program, &creator.pubkey()).await;
+        legs.push((mint, token_program));
+    }
+    Bundle {
+        context,
+        creator,
+        legs,
+    }
+}
+
+fn create_bundle_ix(bundle: &Bundle, token_amounts: Vec<u64>) -> Instruction {
+    let creator = bundle.creator.pubkey();
+    let bundle_address = bundle_address(&creator, 0);
+    let mut accounts = red_packet::accounts::CreateBundle {
+        creator,
+        creator_state: common::creator_state_address(&creator),
+        bundle: bundle_address,
+        protocol_config: protocol_config_address(),
+        fee_receiver: red_packet::config::FEE_RECEIVER,
+        system_program: system_program::ID,
+        associated_token_program: spl_associated_token_account::id(),
+    }
+    .to_account_metas(None);
+    for leg in bundle.legs.iter().cycle().take(token_amounts.len()) {
+        accounts.extend([
+            AccountMeta::new_readonly(leg.0, false),
+            AccountMeta::new(ata(&creator, leg), false),
+            AccountMeta::new(ata(&bundle_address, leg), false),
+            AccountMeta::new_readonly(leg.1, false),
+        ]);
+    }
+    Instruction {
+        program_id: red_packet::ID,
+        accounts,
+        data: red_packet::instruction::CreateBundle {
+            packet_count: PACKET_COUNT,
+            sol_per_claim: SOL_PER_CLAIM,
+            token_amounts,
+            expiry_days: Some(7),
+        }
+        .data(),
+    }
+}
+
+fn claim_bundle_ix(bundle: &Bundle, claimer: &Pubkey) -> Instruction {
+    let bundle_address = bundle_address(&bundle.creator.pubkey(), 0);
+    let mut accounts = red_packet::accounts::ClaimBundle {
+        claimer: *claimer,
+        bundle: bundle_address,
+        user_state: user_state_address(&bundle_address, claimer),
+        protocol_config: protocol_config_address(),
+        fee_receiver: red_packet::config::FEE_RECEIVER,
+        system_program: system_program::ID,
+        associated_token_program: spl_associated_token_account::id(),
+    }
+    .to_account_metas(None);
+    for leg in &bundle.legs {
+        accounts.extend([
+            AccountMeta::new_readonly(leg.0, false),
+            AccountMeta::new(ata(&bundle_address, leg), false),
+            AccountMeta::new(ata(claimer, leg), false),
+            AccountMeta::new_readonly(leg.1, false),
+        ]);
+    }
+    Instruction {
+        program_id: red_packet::ID,
+        accounts,
+        data: red_packet::instruction::ClaimBundle { bundle_id: 0 }.data(),
+    }
+}
+
+fn refund_bundle_ix(bundle: &Bundle) -> Instruction {
+    let creator = bundle.creator.pubkey();
+    let bundle_address = bundle_address(&creator, 0);
+    let mut accounts = red_packet::accounts::RefundBundle {
+        creator,
+        bundle: bundle_address,
+    }
+    .to_account_metas(None);
+    for leg in &bundle.legs {
+        accounts.extend([
+            AccountMeta::new_readonly(leg.0, false),
+            AccountMeta::new(ata(&bundle_address, leg), false),
+            AccountMeta::new(ata(&creator, leg), false),
+            AccountMeta::new_readonly(leg.1, false),
+        ]);
+    }
+    Instruction {
+        program_id: red_packet::ID,
+        accounts,
+        data: red_packet::instruction::RefundBundle { bundle_id: 0 }.data(),
+    }
+}
+
+#[tokio::test]
+async fn test_create_claim_refund_bundle() {
+    let mut bundle = setup().await;
+    let creator = bundle.creator.insecure_clone();
+    let bundle_address = bundle_address(&creator.pubkey(), 0);
+
+    let ix = create_bundle_ix(&bundle, TOKEN_AMOUNTS.to_vec());
+    send(&mut bundle.context, &[ix], &[&creator]).await.unwrap();
+    let account = bundle
+        .context
+        .banks_client
+        .get_account(bundle_address)
+        .await
+        .unwrap()
+        .unwrap();
+    let state = BundlePacket::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(state.legs.len(), TOKEN_AMOUNTS.len());
+    assert_eq!(state.sol_per_claim, SOL_PER_CLAIM);
+    for (leg, amount) in bundle.legs.clone().iter().zip(TOKEN_AMOUNTS) {
+        let pool = ata(&bundle_address, leg);
+        assert_eq!(
+            token_balance(&mut bundle.context, &pool).await,
+            Some(amount * PACKET_COUNT as u64)
+        );
+    }
+
+    let claimers: Vec<Keypair> = (0..=PACKET_COUNT).map(|_| Keypair::new()).collect();
+    for claimer in &claimers {
+        fund(&mut bundle.context, &claimer.pubkey(), 1_000_000_000).await;
+    }
+    for claimer in &claimers[..PACKET_COUNT as usize] {
+        let before = lamports(&mut bundle.context, &bundle_address).await;
+        let ix = claim_bundle_ix(&bundle, &claimer.pubkey());
+        send(&mut bundle.context, &[ix], &[claimer]).await.unwrap();
+        assert_eq!(
+            lamports(&mut bundle.context, &bundle_address).await,
+            before - SOL_PER_CLAIM
+        );
+        for (leg, amount) in bundle.legs.clone().iter().zip(TOKEN_AMOUNTS) {
+            let claimer_ata = ata(&claimer.pubkey(), leg);
+            assert_eq!(
+                token_balance(&mut bundle.context, &claimer_ata).await,
+                Some(amount)
+            );
+        }
+
+        // 同一个人不能领两次
+        let ix = claim_bundle_ix(&bundle, &claimer.pubkey());
+        assert_eq!(
+            custom_error(send(&mut bundle.context, &[ix], &[claimer]).await),
+            Some(error_code(RedPacketError::AlreadyClaimed))
+        );
+    }
+
+    let late = &claimers[PACKET_COUNT as usize];
+    let ix = claim_bundle_ix(&bundle, &late.pubkey());
+    assert_eq!(
+        custom_error(send(&mut bundle.context, &[ix], &[late]).await),
+        Some(error_code(RedPacketError::NoPacketsRemaining))
+    );
+
+    // 全部领完后不用等过期就能退款，池子 ATA 和组合红包账户都被关闭
+    let ix = refund_bundle_ix(&bundle);
+    send(&mut bundle.context, &[ix], &[&creator]).await.unwrap();
+    assert_eq!(lamports(&mut bundle.context, &bundle_address).await, 0);
+    for leg in bundle.legs.clone() {
+        let pool = ata(&bundle_address, &leg);
+        assert_eq!(token_balance(&mut bundle.context, &pool).await, None);
+    }
+}
+
+#[tokio::test]
+async fn test_refund_bundle_requires_expiry_or_drained() {
+    let mut bundle = setup().await;
+    let creator = bundle.creator.insecure_clone();
+    let ix = create_bundle_ix(&bundle, TOKEN_AMOUNTS.to_vec());
+    send(&mut bundle.context, &[ix], &[&creator]).await.unwrap();
+
+    let ix = refund_bundle_ix(&bundle);
+    assert_eq!(
+        custom_error(send(&mut bundle.context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::RedPacketNotExpired))
+    );
+
+    common::warp_seconds(&mut bundle.context, 8 * 24 * 60 * 60).await;
+    let ix = refund_bundle_ix(&bundle);
+    send(&mut bundle.context, &[ix], &[&creator]).await.unwrap();
+    // 无人领取，代币全部退回
+    for leg in bundle.legs.clone() {
+        let creator_ata = ata(&creator.pubkey(), &leg);
+        assert_eq!(
+            token_balance(&mut bundle.context, &creator_ata).await,
+            Some(1_000_000)
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_create_bundle_rejects_duplicate_mints() {
+    let mut bundle = setup().await;
+    let creator = bundle.creator.insecure_clone();
+    // 第三组账户会重新使用第一种代币
+    let ix = create_bundle_ix(&bundle, vec![1, 2, 3]);
+    assert_eq!(
+        custom_error(send(&mut bundle.context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::InvalidBundleLegs))
+    );
+}
+
+#[tokio::test]
+async fn test_create_bundle_rejects_transfer_fee_mint() {
+    let mut bundle = setup().await;
+    let creator = bundle.creator.insecure_clone();
+    // 组合红包按每份金额直接转账，带转账费的 Token-2022 代币会让池子少于应发数量
+    let asset = common::create_mint(
+        &mut bundle.context,
+        &spl_token_2022::id(),
+        Some((100, u64::MAX)),
+    )
+    .await;
+    mint_to(&mut bundle.context, &asset, &creator.pubkey(), 1_000_000).await;
+    bundle.legs[1] = (asset.mint(), asset.token_program());
+    let ix = create_bundle_ix(&bundle, TOKEN_AMOUNTS.to_vec());
+    assert_eq!(
+        custom_error(send(&mut bundle.context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::TransferFeeNotSupported))
+    );
+}
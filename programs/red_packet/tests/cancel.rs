@@ -0,0 +1,411 @@
+//! 提前取消：无宽限期立即退款关闭，有宽限期时只有取消前提交过承诺的领取者能在宽限期内领取，
+//! 期间不能再改时间或追加
+
+mod common;
+
+use anchor_lang::{solana_program::sysvar, InstructionData, ToAccountMetas};
+use common::{
+    account, claim_args, claim_commit_address, claim_ix_with, commit_ix, create_args, custom_error,
+    error_code, fund, initialize_creator_state_ix, initialize_protocol_config_ix, lamports,
+    new_claimer, next_slot, protocol_config_address, red_packet_address, send,
+    set_upgrade_authority, setup_creator, unix_timestamp, user_state_address, warp_seconds, Asset,
+};
+use red_packet::{config::MAX_CANCEL_GRACE_PERIOD, errors::RedPacketError, states::RedPacket};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use solana_system_interface::program as system_program;
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+
+async fn setup() -> (ProgramTestContext, Keypair) {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = Keypair::new();
+    fund(&mut context, &creator.pubkey(), 10_000_000_000).await;
+    let admin = context.payer.pubkey();
+    set_upgrade_authority(&mut context, &admin);
+    let create = create_ix(&creator.pubkey());
+    send(
+        &mut context,
+        &[
+            initialize_protocol_config_ix(&admin),
+            initialize_creator_state_ix(&creator.pubkey()),
+            create,
+        ],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    (context, creator)
+}
+
+fn create_ix(creator: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CreateRedPacket {
+            creator: *creator,
+            creator_state: common::creator_state_address(creator),
+            red_packet,
+            mint: Pubkey::default(),
+            creator_ata: *creator,
+            pool_ata: red_packet,
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: spl_token_2022::id(),
+            associated_token_program: spl_associated_token_account::id(),
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::CreateRedpacket {
+            total_amount: TOTAL_AMOUNT,
+            packet_count: 4,
+            red_packet_type: 0,
+            merkle_root: None,
+            is_sol: true,
+            expiry_days: Some(7),
+            random_seed: None,
+            start_time: None,
+            code_hash: None,
+            holder_gate: None,
+            prize_tiers: None,
+        }
+        .data(),
+    }
+}
+
+fn claim_ix(red_packet: &Pubkey, claimer: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::ClaimRedPacket {
+            claimer: *claimer,
+            red_packet: *red_packet,
+            user_state: user_state_address(red_packet, claimer),
+            mint: Pubkey::default(),
+            pool_ata: *red_packet,
+            user_ata: *claimer,
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: spl_token_2022::id(),
+            associated_token_program: spl_associated_token_account::id(),
+            slot_hashes: sysvar::slot_hashes::ID,
+            claim_commit: None,
+            gate_token_account: None,
+            gate_metadata: None,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::ClaimRedpacket {
+            amount: None,
+            proof: None,
+            red_packet_id: 0,
+            code: None,
+        }
+        .data(),
+    }
+}
+
+fn cancel_ix(creator: &Pubkey, authority: &Pubkey, grace_period: Option<i64>) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CancelRedPacket {
+            authority: *authority,
+            red_packet,
+            creator_state: common::creator_state_address(creator),
+            creator: *creator,
+            mint: Pubkey::default(),
+            creator_ata: *creator,
+            pool_ata: red_packet,
+            token_program: spl_token_2022::id(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::CancelRedpacket {
+            red_packet_id: 0,
+            grace_period,
+        }
+        .data(),
+    }
+}
+
+fn set_time_ix(creator: &Pubkey, data: Vec<u8>) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::SetExpiryTime {
+            red_packet: red_packet_address(creator, 0),
+            authority: *creator,
+            creator_state: common::creator_state_address(creator),
+            protocol_config: protocol_config_address(),
+        }
+        .to_account_metas(None),
+        data,
+    }
+}
+
+fn top_up_ix(creator: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::TopUpRedPacket {
+            authority: *creator,
+            red_packet,
+            creator_state: common::creator_state_address(creator),
+            mint: Pubkey::default(),
+            authority_ata: *creator,
+            pool_ata: red_packet,
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: spl_token_2022::id(),
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::TopUpRedpacket {
+            red_packet_id: 0,
+            amount: TOTAL_AMOUNT,
+            extra_packets: 4,
+        }
+        .data(),
+    }
+}
+
+fn refund_ix(creator: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::Refund {
+            creator: *creator,
+            red_packet,
+            mint: Pubkey::default(),
+            creator_ata: *creator,
+            pool_ata: red_packet,
+            token_program: spl_token_2022::id(),
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::Refund { red_packet_id: 0 }.data(),
+    }
+}
+
+async fn claim(context: &mut ProgramTestContext, red_packet: &Pubkey) -> Option<u32> {
+    let claimer = Keypair::new();
+    fund(context, &claimer.pubkey(), 100_000_000).await;
+    let result = send(
+        context,
+        &[claim_ix(red_packet, &claimer.pubkey())],
+        &[&claimer],
+    )
+    .await;
+    custom_error(result)
+}
+
+#[tokio::test]
+async fn test_cancel_without_grace_refunds_immediately() {
+    let (mut context, creator) = setup().await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+    assert_eq!(claim(&mut context, &red_packet).await, None);
+
+    let before = lamports(&mut context, &creator.pubkey()).await;
+    send(
+        &mut context,
+        &[cancel_ix(&creator.pubkey(), &creator.pubkey(), None)],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    // 剩余 3 份和账户租金全部退回（交易费由 payer 支付）
+    assert!(lamports(&mut context, &creator.pubkey()).await > before + TOTAL_AMOUNT / 4 * 3);
+    assert!(context
+        .banks_client
+        .get_account(red_packet)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_cancel_with_grace_period() {
+    let (mut context, creator) = setup().await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+
+    send(
+        &mut context,
+        &[cancel_ix(&creator.pubkey(), &creator.pubkey(), Some(600))],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    // 宽限期内不能再有新的领取，也还不能退款
+    assert_eq!(
+        claim(&mut context, &red_packet).await,
+        Some(error_code(RedPacketError::RedPacketCancelled))
+    );
+    let result = send(&mut context, &[refund_ix(&creator.pubkey())], &[&creator]).await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::RedPacketNotExpired))
+    );
+
+    warp_seconds(&mut context, 601).await;
+    assert_eq!(
+        claim(&mut context, &red_packet).await,
+        Some(error_code(RedPacketError::RedPacketCancelled))
+    );
+    send(&mut context, &[refund_ix(&creator.pubkey())], &[&creator])
+        .await
+        .unwrap();
+    assert_eq!(lamports(&mut context, &red_packet).await, 0);
+}
+
+#[tokio::test]
+async fn test_cancel_rejects_long_grace_and_other_signers() {
+    let (mut context, creator) = setup().await;
+    let result = send(
+        &mut context,
+        &[cancel_ix(
+            &creator.pubkey(),
+            &creator.pubkey(),
+            Some(MAX_CANCEL_GRACE_PERIOD + 1),
+        )],
+        &[&creator],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::InvalidGracePeriod))
+    );
+
+    let other = Keypair::new();
+    fund(&mut context, &other.pubkey(), 1_000_000_000).await;
+    let ix = cancel_ix(&creator.pubkey(), &other.pubkey(), None);
+    let result = send(&mut context, &[ix], &[&other]).await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::Unauthorized))
+    );
+}
+
+#[tokio::test]
+async fn test_cancelled_red_packet_cannot_be_extended_or_topped_up() {
+    let (mut context, creator) = setup().await;
+    let creator_key = creator.pubkey();
+    let red_packet = red_packet_address(&creator_key, 0);
+    send(
+        &mut context,
+        &[cancel_ix(&creator_key, &creator_key, Some(600))],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    let state: RedPacket = account(&mut context, &red_packet).await;
+    assert!(state.cancelled);
+    let expiry_time = state.expiry_time;
+
+    let now = unix_timestamp(&mut context).await;
+    let rejected = [
+        set_time_ix(
+            &creator_key,
+            red_packet::instruction::SetExpiryTime {
+                expiry_time: now + 24 * 60 * 60,
+            }
+            .data(),
+        ),
+        set_time_ix(
+            &creator_key,
+            red_packet::instruction::SetStartTime {
+                start_time: now + 60,
+            }
+            .data(),
+        ),
+        top_up_ix(&creator_key),
+    ];
+    for ix in rejected {
+        assert_eq!(
+            custom_error(send(&mut context, &[ix], &[&creator]).await),
+            Some(error_code(RedPacketError::RedPacketCancelled))
+        );
+    }
+    let state: RedPacket = account(&mut context, &red_packet).await;
+    assert_eq!(state.expiry_time, expiry_time);
+    assert_eq!(state.total_amount, TOTAL_AMOUNT);
+
+    // 再次取消只会继续提前，放行承诺的取消 slot 仍是第一次取消时的
+    let cancel_slot = state.cancel_slot;
+    next_slot(&mut context).await;
+    send(
+        &mut context,
+        &[cancel_ix(&creator_key, &creator_key, Some(60))],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    let state: RedPacket = account(&mut context, &red_packet).await;
+    assert!(state.expiry_time < expiry_time);
+    assert_eq!(state.cancel_slot, cancel_slot);
+    warp_seconds(&mut context, 61).await;
+    assert_eq!(
+        claim(&mut context, &red_packet).await,
+        Some(error_code(RedPacketError::RedPacketCancelled))
+    );
+}
+
+#[tokio::test]
+async fn test_grace_period_only_admits_commits_made_before_cancel() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = setup_creator(&mut context).await;
+    let creator_key = creator.pubkey();
+    let red_packet = red_packet_address(&creator_key, 0);
+    let args = red_packet::instruction::CreateRedpacket {
+        random_seed: Some(42),
+        ..create_args(TOTAL_AMOUNT, 4, 3)
+    };
+    let ix = common::create_ix(&creator_key, 0, &Asset::Sol, args);
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    let committed_claim_ix = |claimer: &Pubkey| {
+        claim_ix_with(
+            &creator_key,
+            claimer,
+            &Asset::Sol,
+            claim_args(0),
+            Some(claim_commit_address(&red_packet, claimer)),
+            None,
+        )
+    };
+
+    let early = new_claimer(&mut context).await;
+    let commit = commit_ix(&creator_key, &early.pubkey(), 0, &[]);
+    send(&mut context, &[commit], &[&early]).await.unwrap();
+    next_slot(&mut context).await;
+    send(
+        &mut context,
+        &[cancel_ix(&creator_key, &creator_key, Some(600))],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    next_slot(&mut context).await;
+
+    // 取消之后的新领取者既不能提交承诺，也不能直接领取
+    let late = new_claimer(&mut context).await;
+    let commit = commit_ix(&creator_key, &late.pubkey(), 0, &[]);
+    assert_eq!(
+        custom_error(send(&mut context, &[commit], &[&late]).await),
+        Some(error_code(RedPacketError::RedPacketCancelled))
+    );
+    let ix = common::claim_ix(&creator_key, &late.pubkey(), &Asset::Sol, claim_args(0));
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&late]).await),
+        Some(error_code(RedPacketError::MissingClaimCommit))
+    );
+
+    // 取消前已经提交承诺的领取者在宽限期内完成领取
+    let ix = committed_claim_ix(&early.pubkey());
+    send(&mut context, &[ix], &[&early]).await.unwrap();
+    let state: RedPacket = account(&mut context, &red_packet).await;
+    assert_eq!(state.claimed_count, 1);
+}
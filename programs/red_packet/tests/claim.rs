@@ -0,0 +1,498 @@
+//! 领取红包：各类型的金额与手续费、代币红包自动建 ATA，以及领取阶段的所有状态错误
+
+mod common;
+
+use anchor_lang::InstructionData;
+use common::{
+    account, account_exists, claim_args, claim_commit_address, claim_ix, claim_ix_with,
+    close_commit_ix, commit_ix, create_args, create_ix, create_mint, custom_error, error_code,
+    fund, lamports, mint_to, new_claimer, next_slot, red_packet_address, send, setup_creator,
+    token_balance, unix_timestamp, warp_seconds, Asset,
+};
+use red_packet::{
+    config::{CLAIM_FEE, FEE_RECEIVER, USER_STATE_SPACE},
+    errors::RedPacketError,
+    states::{HolderGate, PrizeTier, RedPacket},
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    slot_hashes::SlotHashes,
+};
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+
+fn user_state_rent() -> u64 {
+    Rent::default().minimum_balance(USER_STATE_SPACE)
+}
+
+/// 创建一个 SOL 红包并返回创建者
+async fn create_sol(
+    context: &mut ProgramTestContext,
+    args: red_packet::instruction::CreateRedpacket,
+) -> Keypair {
+    let creator = setup_creator(context).await;
+    let ix = create_ix(&creator.pubkey(), 0, &Asset::Sol, args);
+    send(context, &[ix], &[&creator]).await.unwrap();
+    creator
+}
+
+/// 领取 SOL 红包，返回领取者实际拿到的份额（扣除手续费和 user_state 租金之前）
+async fn claim_sol(context: &mut ProgramTestContext, creator: &Keypair) -> u64 {
+    let claimer = new_claimer(context).await;
+    let before = lamports(context, &claimer.pubkey()).await;
+    let ix = claim_ix(
+        &creator.pubkey(),
+        &claimer.pubkey(),
+        &Asset::Sol,
+        claim_args(0),
+    );
+    send(context, &[ix], &[&claimer]).await.unwrap();
+    lamports(context, &claimer.pubkey()).await + CLAIM_FEE + user_state_rent() - before
+}
+
+/// 类型 3 / 7：先提交承诺，下一个 slot 再领取，承诺账户的租金在领取时退回
+async fn commit_and_claim_sol(context: &mut ProgramTestContext, creator: &Keypair) -> u64 {
+    let claimer = new_claimer(context).await;
+    let before = lamports(context, &claimer.pubkey()).await;
+    let commit = commit_ix(&creator.pubkey(), &claimer.pubkey(), 0, &[]);
+    send(context, &[commit], &[&claimer]).await.unwrap();
+    next_slot(context).await;
+    let ix = committed_claim_ix(&creator.pubkey(), &claimer.pubkey());
+    send(context, &[ix], &[&claimer]).await.unwrap();
+    lamports(context, &claimer.pubkey()).await + CLAIM_FEE + user_state_rent() - before
+}
+
+fn committed_claim_ix(creator: &Pubkey, claimer: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    claim_ix_with(
+        creator,
+        claimer,
+        &Asset::Sol,
+        claim_args(0),
+        Some(claim_commit_address(&red_packet, claimer)),
+        None,
+    )
+}
+
+#[tokio::test]
+async fn test_claim_even_sol_red_packet() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = create_sol(&mut context, create_args(TOTAL_AMOUNT, 4, 0)).await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+
+    let fee_receiver_before = lamports(&mut context, &FEE_RECEIVER).await;
+    let pool_before = lamports(&mut context, &red_packet).await;
+    assert_eq!(claim_sol(&mut context, &creator).await, TOTAL_AMOUNT / 4);
+    assert_eq!(
+        lamports(&mut context, &FEE_RECEIVER).await,
+        fee_receiver_before + CLAIM_FEE
+    );
+    assert_eq!(
+        lamports(&mut context, &red_packet).await,
+        pool_before - TOTAL_AMOUNT / 4
+    );
+
+    let state: RedPacket = account(&mut context, &red_packet).await;
+    assert_eq!(state.claimed_count, 1);
+    assert_eq!(state.remaining_amount, TOTAL_AMOUNT - TOTAL_AMOUNT / 4);
+}
+
+#[tokio::test]
+async fn test_random_red_packets_distribute_total() {
+    for red_packet_type in [1, 3] {
+        let mut context = common::program_test().start_with_context().await;
+        let args = red_packet::instruction::CreateRedpacket {
+            random_seed: Some(42),
+            ..create_args(TOTAL_AMOUNT, 5, red_packet_type)
+        };
+        let creator = create_sol(&mut context, args).await;
+
+        let mut claimed = 0;
+        for _ in 0..5 {
+            let amount = if red_packet_type == 3 {
+                commit_and_claim_sol(&mut context, &creator).await
+            } else {
+                claim_sol(&mut context, &creator).await
+            };
+            assert!(amount > 0);
+            claimed += amount;
+        }
+        assert_eq!(claimed, TOTAL_AMOUNT, "type {red_packet_type}");
+        let state: RedPacket =
+            account(&mut context, &red_packet_address(&creator.pubkey(), 0)).await;
+        assert_eq!(state.remaining_amount, 0);
+    }
+}
+
+#[tokio::test]
+async fn test_tiered_red_packet_pays_each_tier() {
+    let mut context = common::program_test().start_with_context().await;
+    let tiers = vec![
+        PrizeTier {
+            amount: 500_000_000,
+            count: 1,
+        },
+        PrizeTier {
+            amount: 250_000_000,
+            count: 2,
+        },
+    ];
+    let args = red_packet::instruction::CreateRedpacket {
+        random_seed: Some(42),
+        prize_tiers: Some(tiers),
+        ..create_args(TOTAL_AMOUNT, 3, 7)
+    };
+    let creator = create_sol(&mut context, args).await;
+
+    let mut amounts = Vec::new();
+    for _ in 0..3 {
+        amounts.push(commit_and_claim_sol(&mut context, &creator).await);
+    }
+    amounts.sort_unstable();
+    assert_eq!(amounts, [250_000_000, 250_000_000, 500_000_000]);
+}
+
+#[tokio::test]
+async fn test_slot_hash_random_claim_requires_single_earlier_commit() {
+    let mut context = common::program_test().start_with_context().await;
+    let args = red_packet::instruction::CreateRedpacket {
+        random_seed: Some(42),
+        ..create_args(TOTAL_AMOUNT, 2, 3)
+    };
+    let creator = create_sol(&mut context, args).await;
+    let claimer = new_claimer(&mut context).await;
+    let (creator_key, claimer_key) = (creator.pubkey(), claimer.pubkey());
+
+    let ix = claim_ix(&creator_key, &claimer_key, &Asset::Sol, claim_args(0));
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::MissingClaimCommit))
+    );
+
+    // 承诺所在 slot 的 hash 要等这个 slot 结束才知道，同一个 slot 里不能领取
+    let commit = commit_ix(&creator_key, &claimer_key, 0, &[]);
+    let ix = committed_claim_ix(&creator_key, &claimer_key);
+    assert_eq!(
+        custom_error(send(&mut context, &[commit.clone(), ix.clone()], &[&claimer]).await),
+        Some(error_code(RedPacketError::ClaimCommitTooRecent))
+    );
+
+    // 承诺只能提交一次，不能换个 slot 重新抽
+    send(&mut context, std::slice::from_ref(&commit), &[&claimer])
+        .await
+        .unwrap();
+    next_slot(&mut context).await;
+    assert_eq!(
+        custom_error(send(&mut context, &[commit], &[&claimer]).await),
+        Some(error_code(RedPacketError::ClaimCommitExists))
+    );
+    send(&mut context, &[ix], &[&claimer]).await.unwrap();
+    let red_packet = red_packet_address(&creator_key, 0);
+    assert!(
+        !account_exists(
+            &mut context,
+            &claim_commit_address(&red_packet, &claimer_key)
+        )
+        .await
+    );
+}
+
+#[tokio::test]
+async fn test_expired_commit_must_be_closed_and_recommitted() {
+    let mut context = common::program_test().start_with_context().await;
+    let args = red_packet::instruction::CreateRedpacket {
+        random_seed: Some(42),
+        ..create_args(TOTAL_AMOUNT, 2, 3)
+    };
+    let creator = create_sol(&mut context, args).await;
+    let claimer = new_claimer(&mut context).await;
+    let (creator_key, claimer_key) = (creator.pubkey(), claimer.pubkey());
+    let commit_address = claim_commit_address(&red_packet_address(&creator_key, 0), &claimer_key);
+
+    let commit = commit_ix(&creator_key, &claimer_key, 0, &[]);
+    send(&mut context, std::slice::from_ref(&commit), &[&claimer])
+        .await
+        .unwrap();
+    next_slot(&mut context).await;
+    // 承诺还能揭示时不能关闭
+    let close = close_commit_ix(&creator_key, &claimer_key, 0);
+    assert_eq!(
+        custom_error(send(&mut context, std::slice::from_ref(&close), &[&claimer]).await),
+        Some(error_code(RedPacketError::ClaimCommitNotExpired))
+    );
+
+    // 模拟承诺的 slot 滑出 SlotHashes：揭示报错，不再退回只用种子的结果
+    context.set_sysvar(&SlotHashes::new(&[]));
+    let ix = committed_claim_ix(&creator_key, &claimer_key);
+    assert_eq!(
+        custom_error(send(&mut context, std::slice::from_ref(&ix), &[&claimer]).await),
+        Some(error_code(RedPacketError::ClaimCommitExpired))
+    );
+
+    let commit_rent = lamports(&mut context, &commit_address).await;
+    let before = lamports(&mut context, &claimer_key).await;
+    send(&mut context, &[close], &[&claimer]).await.unwrap();
+    assert!(!account_exists(&mut context, &commit_address).await);
+    assert_eq!(
+        lamports(&mut context, &claimer_key).await,
+        before + commit_rent
+    );
+
+    // 关闭后重新提交，下一个 slot 正常领取
+    send(&mut context, &[commit], &[&claimer]).await.unwrap();
+    next_slot(&mut context).await;
+    send(&mut context, &[ix], &[&claimer]).await.unwrap();
+    let state: RedPacket = account(&mut context, &red_packet_address(&creator_key, 0)).await;
+    assert_eq!(state.claimed_count, 1);
+}
+
+#[tokio::test]
+async fn test_claim_token_red_packets_create_ata() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = setup_creator(&mut context).await;
+    let assets = [
+        (
+            create_mint(&mut context, &anchor_spl::token::ID, None).await,
+            0,
+        ),
+        // 1% 转账费从池子转出时扣除，领取者到账金额相应减少
+        (
+            create_mint(&mut context, &spl_token_2022::id(), Some((100, u64::MAX))).await,
+            TOTAL_AMOUNT / 4 / 100,
+        ),
+    ];
+
+    for (red_packet_id, (asset, transfer_fee)) in assets.iter().enumerate() {
+        let red_packet_id = red_packet_id as u64;
+        mint_to(&mut context, asset, &creator.pubkey(), 2 * TOTAL_AMOUNT).await;
+        let ix = create_ix(
+            &creator.pubkey(),
+            red_packet_id,
+            asset,
+            create_args(TOTAL_AMOUNT, 4, 0),
+        );
+        send(&mut context, &[ix], &[&creator]).await.unwrap();
+
+        let claimer = new_claimer(&mut context).await;
+        let claimer_ata = asset.account(&claimer.pubkey());
+        assert_eq!(token_balance(&mut context, &claimer_ata).await, None);
+        let fee_receiver_before = lamports(&mut context, &FEE_RECEIVER).await;
+        let ix = claim_ix(
+            &creator.pubkey(),
+            &claimer.pubkey(),
+            asset,
+            claim_args(red_packet_id),
+        );
+        send(&mut context, &[ix], &[&claimer]).await.unwrap();
+
+        assert_eq!(
+            token_balance(&mut context, &claimer_ata).await,
+            Some(TOTAL_AMOUNT / 4 - transfer_fee)
+        );
+        let red_packet = red_packet_address(&creator.pubkey(), red_packet_id);
+        assert_eq!(
+            token_balance(&mut context, &asset.account(&red_packet)).await,
+            Some(TOTAL_AMOUNT - TOTAL_AMOUNT / 4)
+        );
+        assert_eq!(
+            lamports(&mut context, &FEE_RECEIVER).await,
+            fee_receiver_before + CLAIM_FEE
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_claim_rejects_foreign_mint_pool() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = setup_creator(&mut context).await;
+    let asset = create_mint(&mut context, &anchor_spl::token::ID, None).await;
+    mint_to(&mut context, &asset, &creator.pubkey(), TOTAL_AMOUNT).await;
+    let ix = create_ix(
+        &creator.pubkey(),
+        0,
+        &asset,
+        create_args(TOTAL_AMOUNT, 4, 0),
+    );
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+
+    // 自建 mint 并给红包 PDA 建一个该 mint 的 ATA 冒充池子
+    let junk = create_mint(&mut context, &anchor_spl::token::ID, None).await;
+    mint_to(&mut context, &junk, &red_packet, TOTAL_AMOUNT).await;
+    let claimer = new_claimer(&mut context).await;
+    let ix = claim_ix(&creator.pubkey(), &claimer.pubkey(), &junk, claim_args(0));
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::InvalidMint))
+    );
+
+    // mint 正确但池子换成别人的代币账户
+    let mut ix = claim_ix(&creator.pubkey(), &claimer.pubkey(), &asset, claim_args(0));
+    ix.accounts[4].pubkey = asset.account(&creator.pubkey());
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::InvalidPoolAta))
+    );
+
+    let state: RedPacket = account(&mut context, &red_packet).await;
+    assert_eq!(state.remaining_amount, TOTAL_AMOUNT);
+    assert_eq!(state.claimed_count, 0);
+    assert_eq!(
+        token_balance(&mut context, &asset.account(&red_packet)).await,
+        Some(TOTAL_AMOUNT)
+    );
+}
+
+#[tokio::test]
+async fn test_claim_rejects_second_claim_and_empty_packet() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = create_sol(&mut context, create_args(TOTAL_AMOUNT, 1, 0)).await;
+
+    let claimer = new_claimer(&mut context).await;
+    let ix = claim_ix(
+        &creator.pubkey(),
+        &claimer.pubkey(),
+        &Asset::Sol,
+        claim_args(0),
+    );
+    send(&mut context, std::slice::from_ref(&ix), &[&claimer])
+        .await
+        .unwrap();
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::AlreadyClaimed))
+    );
+
+    let late = new_claimer(&mut context).await;
+    let ix = claim_ix(
+        &creator.pubkey(),
+        &late.pubkey(),
+        &Asset::Sol,
+        claim_args(0),
+    );
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&late]).await),
+        Some(error_code(RedPacketError::NoPacketsRemaining))
+    );
+}
+
+#[tokio::test]
+async fn test_claim_respects_start_and_expiry_time() {
+    let mut context = common::program_test().start_with_context().await;
+    let now = unix_timestamp(&mut context).await;
+    let args = red_packet::instruction::CreateRedpacket {
+        start_time: Some(now + 3600),
+        expiry_days: Some(1),
+        ..create_args(TOTAL_AMOUNT, 4, 0)
+    };
+    let creator = create_sol(&mut context, args).await;
+    let claimer = new_claimer(&mut context).await;
+    let ix = claim_ix(
+        &creator.pubkey(),
+        &claimer.pubkey(),
+        &Asset::Sol,
+        claim_args(0),
+    );
+
+    assert_eq!(
+        custom_error(send(&mut context, std::slice::from_ref(&ix), &[&claimer]).await),
+        Some(error_code(RedPacketError::RedPacketNotStarted))
+    );
+
+    // 有效期从开始时间算起
+    warp_seconds(&mut context, 3600 + 24 * 60 * 60).await;
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::RedPacketExpired))
+    );
+}
+
+#[tokio::test]
+async fn test_claim_rejects_mismatched_red_packet_id() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = create_sol(&mut context, create_args(TOTAL_AMOUNT, 4, 0)).await;
+    let claimer = new_claimer(&mut context).await;
+    let mut ix = claim_ix(
+        &creator.pubkey(),
+        &claimer.pubkey(),
+        &Asset::Sol,
+        claim_args(0),
+    );
+    ix.data = claim_args(1).data();
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::InvalidRedPacketId))
+    );
+}
+
+#[tokio::test]
+async fn test_claim_requires_claim_fee() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = create_sol(&mut context, create_args(TOTAL_AMOUNT, 4, 0)).await;
+    // 只够支付 user_state 的租金，付不起领取手续费
+    let claimer = Keypair::new();
+    fund(&mut context, &claimer.pubkey(), user_state_rent()).await;
+    let ix = claim_ix(
+        &creator.pubkey(),
+        &claimer.pubkey(),
+        &Asset::Sol,
+        claim_args(0),
+    );
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::InsufficientClaimerFunds))
+    );
+}
+
+#[tokio::test]
+async fn test_claim_enforces_holder_gate() {
+    let mut context = common::program_test().start_with_context().await;
+    let gate_asset = create_mint(&mut context, &spl_token_2022::id(), None).await;
+    let args = red_packet::instruction::CreateRedpacket {
+        holder_gate: Some(HolderGate {
+            kind: 1,
+            mint: gate_asset.mint(),
+            min_amount: 10,
+        }),
+        ..create_args(TOTAL_AMOUNT, 4, 0)
+    };
+    let creator = create_sol(&mut context, args).await;
+    let claimer = new_claimer(&mut context).await;
+    let gate_account = gate_asset.account(&claimer.pubkey());
+
+    let ix = claim_ix(
+        &creator.pubkey(),
+        &claimer.pubkey(),
+        &Asset::Sol,
+        claim_args(0),
+    );
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::MissingGateAccount))
+    );
+
+    let gated_claim_ix = || {
+        claim_ix_with(
+            &creator.pubkey(),
+            &claimer.pubkey(),
+            &Asset::Sol,
+            claim_args(0),
+            None,
+            Some(gate_account),
+        )
+    };
+    mint_to(&mut context, &gate_asset, &claimer.pubkey(), 5).await;
+    assert_eq!(
+        custom_error(send(&mut context, &[gated_claim_ix()], &[&claimer]).await),
+        Some(error_code(RedPacketError::HolderGateNotMet))
+    );
+
+    mint_to(&mut context, &gate_asset, &claimer.pubkey(), 5).await;
+    send(&mut context, &[gated_claim_ix()], &[&claimer])
+        .await
+        .unwrap();
+}
@@ -0,0 +1,231 @@
+//! 领完后关闭红包、批量回收 user_state 租金
+
+mod common;
+
+use anchor_lang::{solana_program::sysvar, InstructionData, ToAccountMetas};
+use common::{
+    close_user_states_ix, creator_state_address, custom_error, error_code, fund,
+    initialize_creator_state_ix, initialize_protocol_config_ix, lamports, protocol_config_address,
+    send, set_upgrade_authority, user_state_address,
+};
+use red_packet::{
+    config::{RED_PACKET_SPACE, USER_STATE_SPACE},
+    errors::RedPacketError,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+};
+use solana_system_interface::program as system_program;
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+
+async fn start() -> ProgramTestContext {
+    let mut context = common::program_test().start_with_context().await;
+    let admin = context.payer.pubkey();
+    set_upgrade_authority(&mut context, &admin);
+    send(&mut context, &[initialize_protocol_config_ix(&admin)], &[])
+        .await
+        .unwrap();
+    context
+}
+
+async fn new_account(context: &mut ProgramTestContext, lamports: u64) -> Keypair {
+    let keypair = Keypair::new();
+    fund(context, &keypair.pubkey(), lamports).await;
+    keypair
+}
+
+async fn account_exists(context: &mut ProgramTestContext, address: &Pubkey) -> bool {
+    context
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .is_some()
+}
+
+/// 本文件的红包都是创建者的第 0 个
+fn red_packet_address(creator: &Pubkey) -> Pubkey {
+    common::red_packet_address(creator, 0)
+}
+
+/// 创建一个 SOL 平分红包，编号为 0
+async fn create(context: &mut ProgramTestContext, total_amount: u64, packet_count: u32) -> Keypair {
+    let creator = new_account(context, 10_000_000_000).await;
+    let creator_key = creator.pubkey();
+    let red_packet = red_packet_address(&creator_key);
+    let init = initialize_creator_state_ix(&creator_key);
+    let create = Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CreateRedPacket {
+            creator: creator_key,
+            creator_state: creator_state_address(&creator_key),
+            red_packet,
+            protocol_config: protocol_config_address(),
+            mint: Pubkey::default(),
+            creator_ata: creator_key,
+            pool_ata: red_packet,
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::CreateRedpacket {
+            total_amount,
+            packet_count,
+            red_packet_type: 0,
+            merkle_root: None,
+            is_sol: true,
+            expiry_days: None,
+            random_seed: None,
+            start_time: None,
+            code_hash: None,
+            holder_gate: None,
+            prize_tiers: None,
+        }
+        .data(),
+    };
+    send(context, &[init, create], &[&creator]).await.unwrap();
+    creator
+}
+
+async fn claim(context: &mut ProgramTestContext, creator: &Pubkey) -> Keypair {
+    let claimer = new_account(context, 100_000_000).await;
+    let red_packet = red_packet_address(creator);
+    let ix = Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::ClaimRedPacket {
+            claimer: claimer.pubkey(),
+            red_packet,
+            user_state: user_state_address(&red_packet, &claimer.pubkey()),
+            protocol_config: protocol_config_address(),
+            mint: Pubkey::default(),
+            pool_ata: red_packet,
+            user_ata: claimer.pubkey(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: anchor_spl::token::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+            slot_hashes: sysvar::slot_hashes::ID,
+            claim_commit: None,
+            gate_token_account: None,
+            gate_metadata: None,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::ClaimRedpacket {
+            amount: None,
+            proof: None,
+            red_packet_id: 0,
+            code: None,
+        }
+        .data(),
+    };
+    send(context, &[ix], &[&claimer]).await.unwrap();
+    claimer
+}
+
+fn close_ix(creator: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CloseRedPacket {
+            creator: *creator,
+            red_packet,
+            mint: Pubkey::default(),
+            creator_ata: *creator,
+            pool_ata: red_packet,
+            token_program: anchor_spl::token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::CloseRedpacket { red_packet_id: 0 }.data(),
+    }
+}
+
+#[tokio::test]
+async fn test_close_drained_red_packet_returns_leftover() {
+    let mut context = start().await;
+    // 平分后剩下 1 lamport 的零头
+    let creator = create(&mut context, TOTAL_AMOUNT + 1, 2).await;
+    let red_packet = red_packet_address(&creator.pubkey());
+    claim(&mut context, &creator.pubkey()).await;
+
+    let ix = close_ix(&creator.pubkey());
+    assert_eq!(
+        custom_error(send(&mut context, std::slice::from_ref(&ix), &[&creator]).await),
+        Some(error_code(RedPacketError::RedPacketNotDrained))
+    );
+
+    claim(&mut context, &creator.pubkey()).await;
+    let stranger = new_account(&mut context, 100_000_000).await;
+    let mut wrong_caller = ix.clone();
+    wrong_caller.accounts[0] = AccountMeta::new(stranger.pubkey(), true);
+    assert_eq!(
+        custom_error(send(&mut context, &[wrong_caller], &[&stranger]).await),
+        Some(error_code(RedPacketError::Unauthorized))
+    );
+
+    let before = lamports(&mut context, &creator.pubkey()).await;
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    let rent = Rent::default().minimum_balance(RED_PACKET_SPACE);
+    assert_eq!(
+        lamports(&mut context, &creator.pubkey()).await,
+        before + 1 + rent
+    );
+    assert!(!account_exists(&mut context, &red_packet).await);
+}
+
+#[tokio::test]
+async fn test_close_user_states_refunds_claimers() {
+    let mut context = start().await;
+    let creator = create(&mut context, TOTAL_AMOUNT, 2).await;
+    let red_packet = red_packet_address(&creator.pubkey());
+    let first = claim(&mut context, &creator.pubkey()).await;
+    let pair = |claimer: &Keypair| {
+        (
+            user_state_address(&red_packet, &claimer.pubkey()),
+            claimer.pubkey(),
+        )
+    };
+
+    // 还能领取时删除 user_state 会让同一用户再领一次
+    let ix = close_user_states_ix(&creator.pubkey(), &[pair(&first)]);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[]).await),
+        Some(error_code(RedPacketError::RedPacketStillActive))
+    );
+
+    // 领完但账户还在时同样不能删除：红包还可能被追加或延期
+    let second = claim(&mut context, &creator.pubkey()).await;
+    let ix = close_user_states_ix(&creator.pubkey(), &[pair(&first), pair(&second)]);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[]).await),
+        Some(error_code(RedPacketError::RedPacketStillActive))
+    );
+
+    send(&mut context, &[close_ix(&creator.pubkey())], &[&creator])
+        .await
+        .unwrap();
+    // user_state 和领取者必须对应
+    let ix = close_user_states_ix(&creator.pubkey(), &[(pair(&first).0, second.pubkey())]);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[]).await),
+        Some(error_code(RedPacketError::InvalidUserState))
+    );
+
+    let before = lamports(&mut context, &first.pubkey()).await;
+    let ix = close_user_states_ix(&creator.pubkey(), &[pair(&first), pair(&second)]);
+    send(&mut context, &[ix], &[]).await.unwrap();
+    assert_eq!(
+        lamports(&mut context, &first.pubkey()).await,
+        before + Rent::default().minimum_balance(USER_STATE_SPACE)
+    );
+    assert!(!account_exists(&mut context, &pair(&first).0).await);
+    assert!(!account_exists(&mut context, &pair(&second).0).await);
+}
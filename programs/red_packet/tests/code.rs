@@ -0,0 +1,163 @@
+//! 口令红包（类型 5/6）：先提交承诺，下一个 slot 再揭示口令领取
+
+mod common;
+
+use common::{
+    account, account_exists, claim_args, claim_commit_address, claim_ix_with, commit_ix,
+    create_args, create_ix, custom_error, error_code, lamports, new_claimer, next_slot,
+    red_packet_address, send, setup_creator, user_state_address, Asset,
+};
+use red_packet::{
+    config::CLAIM_FEE, errors::RedPacketError, states::RedPacket, utils::claim_code_hash,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+const CODE: &[u8] = b"gong xi fa cai";
+
+fn reveal_ix(creator: &Pubkey, claimer: &Pubkey, code: Option<&[u8]>, commit: bool) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    claim_ix_with(
+        creator,
+        claimer,
+        &Asset::Sol,
+        red_packet::instruction::ClaimRedpacket {
+            code: code.map(<[u8]>::to_vec),
+            ..claim_args(0)
+        },
+        commit.then(|| claim_commit_address(&red_packet, claimer)),
+        None,
+    )
+}
+
+async fn create_code_packet(context: &mut ProgramTestContext, red_packet_type: u8) -> Keypair {
+    let creator = setup_creator(context).await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+    let args = red_packet::instruction::CreateRedpacket {
+        random_seed: Some(42),
+        code_hash: Some(claim_code_hash(&red_packet, CODE)),
+        ..create_args(TOTAL_AMOUNT, 4, red_packet_type)
+    };
+    let ix = create_ix(&creator.pubkey(), 0, &Asset::Sol, args);
+    send(context, &[ix], &[&creator]).await.unwrap();
+    creator
+}
+
+#[tokio::test]
+async fn test_code_claim_commit_then_reveal() {
+    for red_packet_type in [5, 6] {
+        let mut context = common::program_test().start_with_context().await;
+        let creator = create_code_packet(&mut context, red_packet_type).await;
+        let claimer = new_claimer(&mut context).await;
+        let red_packet = red_packet_address(&creator.pubkey(), 0);
+        let claim_commit = claim_commit_address(&red_packet, &claimer.pubkey());
+
+        send(
+            &mut context,
+            &[commit_ix(&creator.pubkey(), &claimer.pubkey(), 0, CODE)],
+            &[&claimer],
+        )
+        .await
+        .unwrap();
+        assert!(account_exists(&mut context, &claim_commit).await);
+        next_slot(&mut context).await;
+
+        let before = lamports(&mut context, &claimer.pubkey()).await;
+        let commit_rent = lamports(&mut context, &claim_commit).await;
+        let ix = reveal_ix(&creator.pubkey(), &claimer.pubkey(), Some(CODE), true);
+        send(&mut context, &[ix], &[&claimer]).await.unwrap();
+        // 领取成功后承诺账户关闭，租金退回领取者。随机金额可能小于手续费加 UserState 租金，
+        // 所以按实际领取金额核对余额，而不是只看余额有没有增加
+        assert!(!account_exists(&mut context, &claim_commit).await);
+        let state: RedPacket = account(&mut context, &red_packet).await;
+        let user_state_rent = lamports(
+            &mut context,
+            &user_state_address(&red_packet, &claimer.pubkey()),
+        )
+        .await;
+        assert_eq!(
+            lamports(&mut context, &claimer.pubkey()).await,
+            before + commit_rent + (TOTAL_AMOUNT - state.remaining_amount)
+                - CLAIM_FEE
+                - user_state_rent
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_code_claim_rejects_missing_or_wrong_code() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = create_code_packet(&mut context, 5).await;
+    let claimer = new_claimer(&mut context).await;
+    let (creator_key, claimer_key) = (creator.pubkey(), claimer.pubkey());
+
+    let ix = reveal_ix(&creator_key, &claimer_key, None, false);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::MissingClaimCode))
+    );
+    let ix = reveal_ix(&creator_key, &claimer_key, Some(CODE), false);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::MissingClaimCommit))
+    );
+
+    // 承诺和揭示在同一个 slot 里无效
+    let ix = reveal_ix(&creator_key, &claimer_key, Some(CODE), true);
+    assert_eq!(
+        custom_error(
+            send(
+                &mut context,
+                &[commit_ix(&creator_key, &claimer_key, 0, CODE), ix],
+                &[&claimer]
+            )
+            .await
+        ),
+        Some(error_code(RedPacketError::ClaimCommitTooRecent))
+    );
+
+    // 承诺的是另一个口令
+    send(
+        &mut context,
+        &[commit_ix(&creator_key, &claimer_key, 0, b"wrong")],
+        &[&claimer],
+    )
+    .await
+    .unwrap();
+    next_slot(&mut context).await;
+    let ix = reveal_ix(&creator_key, &claimer_key, Some(b"wrong"), true);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::InvalidClaimCode))
+    );
+    let ix = reveal_ix(&creator_key, &claimer_key, Some(CODE), true);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::InvalidClaimCommit))
+    );
+}
+
+#[tokio::test]
+async fn test_commit_requires_code_red_packet() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = setup_creator(&mut context).await;
+    let ix = create_ix(
+        &creator.pubkey(),
+        0,
+        &Asset::Sol,
+        create_args(TOTAL_AMOUNT, 4, 0),
+    );
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+
+    let claimer = new_claimer(&mut context).await;
+    let ix = commit_ix(&creator.pubkey(), &claimer.pubkey(), 0, CODE);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&claimer]).await),
+        Some(error_code(RedPacketError::InvalidRedPacketType))
+    );
+}
@@ -0,0 +1,568 @@
+//! 集成测试共用的 program-test 工具：原生加载红包程序、发交易、推导 PDA 和解析错误码
+#![allow(dead_code)]
+
+use anchor_lang::{
+    prelude::{AccountInfo, Owner, ProgramData},
+    solana_program::{entrypoint::ProgramResult, sysvar},
+    AccountDeserialize, InstructionData, ToAccountMetas,
+};
+use solana_loader_v3_interface::{get_program_data_address, state::UpgradeableLoaderState};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solana_system_interface::{instruction as system_instruction, program as system_program};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token_2022::extension::{ExtensionType, StateWithExtensions};
+
+/// 测试 mint 统一使用的精度
+pub const DECIMALS: u8 = 6;
+
+pub fn process_red_packet(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Anchor 的 entry 要求 accounts 与 AccountInfo 同生命周期，测试里直接泄漏一份拷贝
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    red_packet::entry(program_id, accounts, data)
+}
+
+/// 加载红包程序，并预先创建默认的手续费收款账户
+pub fn program_test() -> ProgramTest {
+    let mut program_test =
+        ProgramTest::new("red_packet", red_packet::ID, processor!(process_red_packet));
+    program_test.add_account(
+        red_packet::config::FEE_RECEIVER,
+        Account {
+            lamports: 1_000_000_000,
+            ..Account::default()
+        },
+    );
+    program_test
+}
+
+pub async fn send(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    context.banks_client.process_transaction(tx).await
+}
+
+pub async fn fund(context: &mut ProgramTestContext, to: &Pubkey, lamports: u64) {
+    let ix = system_instruction::transfer(&context.payer.pubkey(), to, lamports);
+    send(context, &[ix], &[]).await.unwrap();
+}
+
+pub async fn lamports(context: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    context.banks_client.get_balance(*address).await.unwrap()
+}
+
+pub async fn warp_seconds(context: &mut ProgramTestContext, seconds: i64) {
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp += seconds;
+    context.set_sysvar(&clock);
+}
+
+/// 取出交易失败时的自定义错误码，用于和 `RedPacketError` 比较
+pub fn custom_error(result: Result<(), BanksClientError>) -> Option<u32> {
+    match result.err()?.unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => Some(code),
+        _ => None,
+    }
+}
+
+pub fn error_code(error: red_packet::errors::RedPacketError) -> u32 {
+    error.into()
+}
+
+pub fn protocol_config_address() -> Pubkey {
+    Pubkey::find_program_address(&[red_packet::config::PROTOCOL_CONFIG_SEED], &red_packet::ID).0
+}
+
+pub fn creator_state_address(creator: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[red_packet::config::CREATOR_STATE_SEED, creator.as_ref()],
+        &red_packet::ID,
+    )
+    .0
+}
+
+pub fn red_packet_address(creator: &Pubkey, red_packet_id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"red_packet",
+            creator.as_ref(),
+            &red_packet_id.to_le_bytes(),
+        ],
+        &red_packet::ID,
+    )
+    .0
+}
+
+pub fn user_state_address(red_packet: &Pubkey, claimer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"user_state", red_packet.as_ref(), claimer.as_ref()],
+        &red_packet::ID,
+    )
+    .0
+}
+
+pub fn claim_commit_address(red_packet: &Pubkey, claimer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            red_packet::config::CLAIM_COMMIT_SEED,
+            red_packet.as_ref(),
+            claimer.as_ref(),
+        ],
+        &red_packet::ID,
+    )
+    .0
+}
+
+/// 原生加载的程序没有 ProgramData，按升级加载器的布局补一个，升级权限为 authority
+pub fn set_upgrade_authority(context: &mut ProgramTestContext, authority: &Pubkey) {
+    let state = UpgradeableLoaderState::ProgramData {
+        slot: 0,
+        upgrade_authority_address: Some(*authority),
+    };
+    let lamports =
+        Rent::default().minimum_balance(UpgradeableLoaderState::size_of_programdata_metadata());
+    let account = Account::new_data(lamports, &state, &ProgramData::owner()).unwrap();
+    context.set_account(&get_program_data_address(&red_packet::ID), &account.into());
+}
+
+pub fn initialize_protocol_config_ix(admin: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::InitializeProtocolConfig {
+            admin: *admin,
+            protocol_config: protocol_config_address(),
+            program_data: get_program_data_address(&red_packet::ID),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::InitializeProtocolConfig {}.data(),
+    }
+}
+
+pub fn initialize_creator_state_ix(creator: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::InitializeCreatorState {
+            creator_state: creator_state_address(creator),
+            creator: *creator,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::InitializeCreatorState {}.data(),
+    }
+}
+
+/// 红包里的资产：SOL 或某个 token program 下的 mint
+#[derive(Clone, Copy, Debug)]
+pub enum Asset {
+    Sol,
+    Token { mint: Pubkey, token_program: Pubkey },
+}
+
+impl Asset {
+    pub fn is_sol(&self) -> bool {
+        matches!(self, Asset::Sol)
+    }
+
+    /// SOL 红包传 Pubkey::default()
+    pub fn mint(&self) -> Pubkey {
+        match self {
+            Asset::Sol => Pubkey::default(),
+            Asset::Token { mint, .. } => *mint,
+        }
+    }
+
+    pub fn token_program(&self) -> Pubkey {
+        match self {
+            Asset::Sol => spl_token_2022::id(),
+            Asset::Token { token_program, .. } => *token_program,
+        }
+    }
+
+    /// owner 收发该资产的账户：SOL 为 owner 本身，代币为 owner 的 ATA
+    pub fn account(&self, owner: &Pubkey) -> Pubkey {
+        match self {
+            Asset::Sol => *owner,
+            Asset::Token {
+                mint,
+                token_program,
+            } => get_associated_token_address_with_program_id(owner, mint, token_program),
+        }
+    }
+
+    /// 带转账费的 mint 在退款和关闭时需要可写
+    fn mint_meta(&self) -> AccountMeta {
+        match self {
+            Asset::Sol => AccountMeta::new_readonly(Pubkey::default(), false),
+            Asset::Token { mint, .. } => AccountMeta::new(*mint, false),
+        }
+    }
+}
+
+/// 创建 mint，付款人同时是铸币权限；`transfer_fee` 为 (bps, 单笔上限)，只用于 Token-2022
+pub async fn create_mint(
+    context: &mut ProgramTestContext,
+    token_program: &Pubkey,
+    transfer_fee: Option<(u16, u64)>,
+) -> Asset {
+    let mint = Keypair::new();
+    let payer = context.payer.pubkey();
+    let extensions: Vec<ExtensionType> = transfer_fee
+        .iter()
+        .map(|_| ExtensionType::TransferFeeConfig)
+        .collect();
+    let mint_len =
+        ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&extensions)
+            .unwrap();
+    let mut instructions = vec![system_instruction::create_account(
+        &payer,
+        &mint.pubkey(),
+        Rent::default().minimum_balance(mint_len),
+        mint_len as u64,
+        token_program,
+    )];
+    if let Some((bps, maximum_fee)) = transfer_fee {
+        instructions.push(
+            spl_token_2022::extension::transfer_fee::instruction::initialize_transfer_fee_config(
+                token_program,
+                &mint.pubkey(),
+                Some(&payer),
+                Some(&payer),
+                bps,
+                maximum_fee,
+            )
+            .unwrap(),
+        );
+    }
+    instructions.push(
+        spl_token_2022::instruction::initialize_mint2(
+            token_program,
+            &mint.pubkey(),
+            &payer,
+            None,
+            DECIMALS,
+        )
+        .unwrap(),
+    );
+    send(context, &instructions, &[&mint]).await.unwrap();
+    Asset::Token {
+        mint: mint.pubkey(),
+        token_program: *token_program,
+    }
+}
+
+/// 给 owner 铸币，ATA 不存在时先创建
+pub async fn mint_to(context: &mut ProgramTestContext, asset: &Asset, owner: &Pubkey, amount: u64) {
+    let payer = context.payer.pubkey();
+    let instructions = [
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+            &payer,
+            owner,
+            &asset.mint(),
+            &asset.token_program(),
+        ),
+        spl_token_2022::instruction::mint_to(
+            &asset.token_program(),
+            &asset.mint(),
+            &asset.account(owner),
+            &payer,
+            &[],
+            amount,
+        )
+        .unwrap(),
+    ];
+    send(context, &instructions, &[]).await.unwrap();
+}
+
+/// 代币账户余额，账户不存在时返回 None
+pub async fn token_balance(context: &mut ProgramTestContext, address: &Pubkey) -> Option<u64> {
+    let account = context.banks_client.get_account(*address).await.unwrap()?;
+    Some(
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+            .unwrap()
+            .base
+            .amount,
+    )
+}
+
+pub async fn account<T: AccountDeserialize>(
+    context: &mut ProgramTestContext,
+    address: &Pubkey,
+) -> T {
+    let account = context
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .unwrap();
+    T::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+pub async fn account_exists(context: &mut ProgramTestContext, address: &Pubkey) -> bool {
+    context
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .is_some_and(|account| account.lamports > 0)
+}
+
+pub async fn unix_timestamp(context: &mut ProgramTestContext) -> i64 {
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp
+}
+
+/// 创建参数的默认值：7 天有效期，没有种子、口令、门槛和奖项
+pub fn create_args(
+    total_amount: u64,
+    packet_count: u32,
+    red_packet_type: u8,
+) -> red_packet::instruction::CreateRedpacket {
+    red_packet::instruction::CreateRedpacket {
+        total_amount,
+        packet_count,
+        red_packet_type,
+        merkle_root: None,
+        is_sol: true,
+        expiry_days: Some(7),
+        random_seed: None,
+        start_time: None,
+        code_hash: None,
+        holder_gate: None,
+        prize_tiers: None,
+    }
+}
+
+/// `is_sol` 按资产类型覆盖
+pub fn create_ix(
+    creator: &Pubkey,
+    red_packet_id: u64,
+    asset: &Asset,
+    mut args: red_packet::instruction::CreateRedpacket,
+) -> Instruction {
+    let red_packet = red_packet_address(creator, red_packet_id);
+    args.is_sol = asset.is_sol();
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CreateRedPacket {
+            creator: *creator,
+            creator_state: creator_state_address(creator),
+            red_packet,
+            mint: asset.mint(),
+            creator_ata: asset.account(creator),
+            pool_ata: asset.account(&red_packet),
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: asset.token_program(),
+            associated_token_program: spl_associated_token_account::id(),
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: args.data(),
+    }
+}
+
+pub fn claim_args(red_packet_id: u64) -> red_packet::instruction::ClaimRedpacket {
+    red_packet::instruction::ClaimRedpacket {
+        amount: None,
+        proof: None,
+        red_packet_id,
+        code: None,
+    }
+}
+
+pub fn claim_ix(
+    creator: &Pubkey,
+    claimer: &Pubkey,
+    asset: &Asset,
+    args: red_packet::instruction::ClaimRedpacket,
+) -> Instruction {
+    claim_ix_with(creator, claimer, asset, args, None, None)
+}
+
+/// 口令红包传入 claim_commit，持有者门槛传入 gate_token_account
+pub fn claim_ix_with(
+    creator: &Pubkey,
+    claimer: &Pubkey,
+    asset: &Asset,
+    args: red_packet::instruction::ClaimRedpacket,
+    claim_commit: Option<Pubkey>,
+    gate_token_account: Option<Pubkey>,
+) -> Instruction {
+    let red_packet = red_packet_address(creator, args.red_packet_id);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::ClaimRedPacket {
+            claimer: *claimer,
+            red_packet,
+            user_state: user_state_address(&red_packet, claimer),
+            mint: asset.mint(),
+            pool_ata: asset.account(&red_packet),
+            user_ata: asset.account(claimer),
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: asset.token_program(),
+            associated_token_program: spl_associated_token_account::id(),
+            slot_hashes: sysvar::slot_hashes::ID,
+            claim_commit,
+            gate_token_account,
+            gate_metadata: None,
+        }
+        .to_account_metas(None),
+        data: args.data(),
+    }
+}
+
+/// 口令红包提交口令的承诺，类型 3 / 7 的 commitment 不参与校验，传空口令即可
+pub fn commit_ix(
+    creator: &Pubkey,
+    claimer: &Pubkey,
+    red_packet_id: u64,
+    code: &[u8],
+) -> Instruction {
+    let red_packet = red_packet_address(creator, red_packet_id);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CommitClaim {
+            claimer: *claimer,
+            red_packet,
+            claim_commit: claim_commit_address(&red_packet, claimer),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::CommitClaim {
+            red_packet_id,
+            commitment: red_packet::utils::claim_commitment(&red_packet, claimer, code),
+        }
+        .data(),
+    }
+}
+
+/// 关闭已经滑出 SlotHashes 的承诺，租金退给领取者
+pub fn close_commit_ix(creator: &Pubkey, claimer: &Pubkey, red_packet_id: u64) -> Instruction {
+    let red_packet = red_packet_address(creator, red_packet_id);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CloseClaimCommit {
+            claimer: *claimer,
+            red_packet,
+            claim_commit: claim_commit_address(&red_packet, claimer),
+            slot_hashes: sysvar::slot_hashes::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::CloseClaimCommit {}.data(),
+    }
+}
+
+pub async fn next_slot(context: &mut ProgramTestContext) {
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    context.warp_to_slot(clock.slot + 1).unwrap();
+}
+
+pub fn refund_ix(creator: &Pubkey, red_packet_id: u64, asset: &Asset) -> Instruction {
+    let red_packet = red_packet_address(creator, red_packet_id);
+    let mut accounts = red_packet::accounts::Refund {
+        creator: *creator,
+        red_packet,
+        mint: asset.mint(),
+        creator_ata: asset.account(creator),
+        pool_ata: asset.account(&red_packet),
+        token_program: asset.token_program(),
+        system_program: system_program::ID,
+        rent: sysvar::rent::ID,
+    }
+    .to_account_metas(None);
+    accounts[2] = asset.mint_meta();
+    Instruction {
+        program_id: red_packet::ID,
+        accounts,
+        data: red_packet::instruction::Refund { red_packet_id }.data(),
+    }
+}
+
+pub fn close_ix(creator: &Pubkey, red_packet_id: u64, asset: &Asset) -> Instruction {
+    let red_packet = red_packet_address(creator, red_packet_id);
+    let mut accounts = red_packet::accounts::CloseRedPacket {
+        creator: *creator,
+        red_packet,
+        mint: asset.mint(),
+        creator_ata: asset.account(creator),
+        pool_ata: asset.account(&red_packet),
+        token_program: asset.token_program(),
+        system_program: system_program::ID,
+    }
+    .to_account_metas(None);
+    accounts[2] = asset.mint_meta();
+    Instruction {
+        program_id: red_packet::ID,
+        accounts,
+        data: red_packet::instruction::CloseRedpacket { red_packet_id }.data(),
+    }
+}
+
+/// 清理编号为 0 的红包的 UserState，pairs 为 (UserState, 领取者)
+pub fn close_user_states_ix(creator: &Pubkey, pairs: &[(Pubkey, Pubkey)]) -> Instruction {
+    let mut accounts = red_packet::accounts::CloseUserStates {
+        red_packet: red_packet_address(creator, 0),
+    }
+    .to_account_metas(None);
+    for (user_state, claimer) in pairs {
+        accounts.push(AccountMeta::new(*user_state, false));
+        accounts.push(AccountMeta::new(*claimer, false));
+    }
+    Instruction {
+        program_id: red_packet::ID,
+        accounts,
+        data: red_packet::instruction::CloseUserStates {}.data(),
+    }
+}
+
+/// 初始化全局配置（付款人为升级权限和管理员）和创建者状态，并给创建者 10 SOL
+pub async fn setup_creator(context: &mut ProgramTestContext) -> Keypair {
+    let creator = Keypair::new();
+    fund(context, &creator.pubkey(), 10_000_000_000).await;
+    let admin = context.payer.pubkey();
+    set_upgrade_authority(context, &admin);
+    send(
+        context,
+        &[
+            initialize_protocol_config_ix(&admin),
+            initialize_creator_state_ix(&creator.pubkey()),
+        ],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    creator
+}
+
+pub async fn new_claimer(context: &mut ProgramTestContext) -> Keypair {
+    let claimer = Keypair::new();
+    fund(context, &claimer.pubkey(), 100_000_000).await;
+    claimer
+}
@@ -0,0 +1,266 @@
+//! 创建红包：参数校验的每个错误分支，以及 SOL、旧版 SPL Token、Token-2022 三种资产的资金流向
+
+mod common;
+
+use common::{
+    account, create_args, create_ix, create_mint, custom_error, error_code, lamports, mint_to,
+    red_packet_address, send, setup_creator, token_balance, unix_timestamp, Asset,
+};
+use red_packet::{
+    config::{CREATE_FEE, FEE_RECEIVER, MAX_PACKET_COUNT, RED_PACKET_SPACE},
+    errors::RedPacketError,
+    states::{CreatorState, HolderGate, PrizeTier, RedPacket},
+};
+use solana_sdk::{instruction::AccountMeta, pubkey::Pubkey, rent::Rent, signature::Signer};
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+
+#[tokio::test]
+async fn test_create_sol_red_packet_pays_create_fee() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = setup_creator(&mut context).await;
+    let fee_receiver_before = lamports(&mut context, &FEE_RECEIVER).await;
+    let creator_before = lamports(&mut context, &creator.pubkey()).await;
+
+    let ix = create_ix(
+        &creator.pubkey(),
+        0,
+        &Asset::Sol,
+        create_args(TOTAL_AMOUNT, 4, 0),
+    );
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+    let rent = Rent::default().minimum_balance(RED_PACKET_SPACE);
+    assert_eq!(
+        lamports(&mut context, &FEE_RECEIVER).await,
+        fee_receiver_before + CREATE_FEE
+    );
+    assert_eq!(
+        lamports(&mut context, &creator.pubkey()).await,
+        creator_before - TOTAL_AMOUNT - CREATE_FEE - rent
+    );
+    assert_eq!(
+        lamports(&mut context, &red_packet).await,
+        TOTAL_AMOUNT + rent
+    );
+
+    let state: RedPacket = account(&mut context, &red_packet).await;
+    assert!(state.is_sol);
+    assert_eq!(state.remaining_amount, TOTAL_AMOUNT);
+    assert_eq!(state.share_amount, TOTAL_AMOUNT / 4);
+    assert_eq!(state.expiry_time, state.start_time + 7 * 24 * 60 * 60);
+    let creator_state: CreatorState = account(
+        &mut context,
+        &common::creator_state_address(&creator.pubkey()),
+    )
+    .await;
+    assert_eq!(creator_state.next_red_packet_id, 1);
+}
+
+#[tokio::test]
+async fn test_create_token_red_packets_fund_pool_exactly() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = setup_creator(&mut context).await;
+    let assets = [
+        create_mint(&mut context, &anchor_spl::token::ID, None).await,
+        create_mint(&mut context, &spl_token_2022::id(), None).await,
+        // 1% 转账费：创建时多转入的部分由创建者承担，池子正好收到 total_amount
+        create_mint(&mut context, &spl_token_2022::id(), Some((100, u64::MAX))).await,
+    ];
+
+    for (red_packet_id, asset) in assets.iter().enumerate() {
+        mint_to(&mut context, asset, &creator.pubkey(), 2 * TOTAL_AMOUNT).await;
+        let fee_receiver_before = lamports(&mut context, &FEE_RECEIVER).await;
+        let ix = create_ix(
+            &creator.pubkey(),
+            red_packet_id as u64,
+            asset,
+            create_args(TOTAL_AMOUNT, 4, 0),
+        );
+        send(&mut context, &[ix], &[&creator]).await.unwrap();
+
+        let red_packet = red_packet_address(&creator.pubkey(), red_packet_id as u64);
+        assert_eq!(
+            token_balance(&mut context, &asset.account(&red_packet)).await,
+            Some(TOTAL_AMOUNT)
+        );
+        // 代币红包只收固定的 SOL 创建费
+        assert_eq!(
+            lamports(&mut context, &FEE_RECEIVER).await,
+            fee_receiver_before + CREATE_FEE
+        );
+        let state: RedPacket = account(&mut context, &red_packet).await;
+        assert!(!state.is_sol);
+        assert_eq!(state.mint, asset.mint());
+    }
+
+    // 带转账费的 mint 从创建者多扣了约 1%
+    let creator_ata = assets[2].account(&creator.pubkey());
+    let spent = 2 * TOTAL_AMOUNT - token_balance(&mut context, &creator_ata).await.unwrap();
+    assert!(spent > TOTAL_AMOUNT && spent <= TOTAL_AMOUNT + TOTAL_AMOUNT / 99 + 1);
+}
+
+#[tokio::test]
+async fn test_create_rejects_invalid_parameters() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = setup_creator(&mut context).await;
+    let now = unix_timestamp(&mut context).await;
+    let gate = HolderGate {
+        kind: 1,
+        mint: Pubkey::new_unique(),
+        min_amount: 1,
+    };
+
+    let cases = [
+        (
+            create_args(TOTAL_AMOUNT, 0, 0),
+            RedPacketError::InvalidPacketCount,
+        ),
+        (
+            create_args(3, 4, 0),
+            RedPacketError::InsufficientTotalAmount,
+        ),
+        (
+            create_args(TOTAL_AMOUNT, MAX_PACKET_COUNT + 1, 0),
+            RedPacketError::PacketCountTooLarge,
+        ),
+        (
+            red_packet::instruction::CreateRedpacket {
+                expiry_days: Some(0),
+                ..create_args(TOTAL_AMOUNT, 4, 0)
+            },
+            RedPacketError::InvalidExpiryDays,
+        ),
+        (
+            red_packet::instruction::CreateRedpacket {
+                expiry_days: Some(31),
+                ..create_args(TOTAL_AMOUNT, 4, 0)
+            },
+            RedPacketError::InvalidExpiryDays,
+        ),
+        (
+            create_args(TOTAL_AMOUNT, 4, 8),
+            RedPacketError::InvalidRedPacketType,
+        ),
+        (
+            create_args(TOTAL_AMOUNT, 4, 1),
+            RedPacketError::RandomSeedRequired,
+        ),
+        (
+            create_args(TOTAL_AMOUNT, 4, 2),
+            RedPacketError::InvalidMerkleRoot,
+        ),
+        (
+            red_packet::instruction::CreateRedpacket {
+                random_seed: Some(7),
+                ..create_args(TOTAL_AMOUNT, 4, 6)
+            },
+            RedPacketError::CodeHashRequired,
+        ),
+        (
+            red_packet::instruction::CreateRedpacket {
+                start_time: Some(now + 31 * 24 * 60 * 60),
+                ..create_args(TOTAL_AMOUNT, 4, 0)
+            },
+            RedPacketError::InvalidStartTime,
+        ),
+        (
+            red_packet::instruction::CreateRedpacket {
+                random_seed: Some(7),
+                ..create_args(TOTAL_AMOUNT, 4, 7)
+            },
+            RedPacketError::InvalidPrizeTiers,
+        ),
+        (
+            red_packet::instruction::CreateRedpacket {
+                random_seed: Some(7),
+                // 奖项份数加起来不等于 packet_count
+                prize_tiers: Some(vec![PrizeTier {
+                    amount: TOTAL_AMOUNT / 4,
+                    count: 3,
+                }]),
+                ..create_args(TOTAL_AMOUNT, 4, 7)
+            },
+            RedPacketError::InvalidPrizeTiers,
+        ),
+        (
+            red_packet::instruction::CreateRedpacket {
+                prize_tiers: Some(vec![PrizeTier {
+                    amount: TOTAL_AMOUNT / 4,
+                    count: 4,
+                }]),
+                ..create_args(TOTAL_AMOUNT, 4, 0)
+            },
+            RedPacketError::InvalidPrizeTiers,
+        ),
+        (
+            red_packet::instruction::CreateRedpacket {
+                holder_gate: Some(HolderGate { kind: 3, ..gate }),
+                ..create_args(TOTAL_AMOUNT, 4, 0)
+            },
+            RedPacketError::InvalidHolderGate,
+        ),
+        (
+            red_packet::instruction::CreateRedpacket {
+                holder_gate: Some(HolderGate {
+                    min_amount: 0,
+                    ..gate
+                }),
+                ..create_args(TOTAL_AMOUNT, 4, 0)
+            },
+            RedPacketError::InvalidHolderGate,
+        ),
+        (
+            red_packet::instruction::CreateRedpacket {
+                merkle_root: Some([1; 32]),
+                holder_gate: Some(gate),
+                ..create_args(TOTAL_AMOUNT, 4, 2)
+            },
+            RedPacketError::InvalidHolderGate,
+        ),
+    ];
+
+    for (args, error) in cases {
+        let ix = create_ix(&creator.pubkey(), 0, &Asset::Sol, args);
+        assert_eq!(
+            custom_error(send(&mut context, &[ix], &[&creator]).await),
+            Some(error_code(error)),
+            "{error:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_create_rejects_wrong_fee_receiver() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = setup_creator(&mut context).await;
+    let mut ix = create_ix(
+        &creator.pubkey(),
+        0,
+        &Asset::Sol,
+        create_args(TOTAL_AMOUNT, 4, 0),
+    );
+    ix.accounts[7] = AccountMeta::new(Pubkey::new_unique(), false);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::InvalidFeeReceiver))
+    );
+}
+
+#[tokio::test]
+async fn test_create_requires_enough_sol() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = setup_creator(&mut context).await;
+    let balance = lamports(&mut context, &creator.pubkey()).await;
+    let ix = create_ix(
+        &creator.pubkey(),
+        0,
+        &Asset::Sol,
+        create_args(balance, 4, 0),
+    );
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::InsufficientFunds))
+    );
+}
@@ -0,0 +1,139 @@
+//! 修改有效期和开始时间：时间窗口校验、修改次数上限和只有创建者能改
+
+mod common;
+
+use anchor_lang::{InstructionData, ToAccountMetas};
+use common::{
+    account, create_args, create_ix, custom_error, error_code, new_claimer,
+    protocol_config_address, red_packet_address, send, setup_creator, unix_timestamp, Asset,
+};
+use red_packet::{config::MAX_EXPIRY_TIME_CHANGES, errors::RedPacketError, states::RedPacket};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const DAY: i64 = 24 * 60 * 60;
+
+fn set_time_ix(authority: &Pubkey, creator: &Pubkey, data: Vec<u8>) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::SetExpiryTime {
+            red_packet: red_packet_address(creator, 0),
+            authority: *authority,
+            creator_state: common::creator_state_address(creator),
+            protocol_config: protocol_config_address(),
+        }
+        .to_account_metas(None),
+        data,
+    }
+}
+
+fn set_expiry_ix(authority: &Pubkey, creator: &Pubkey, expiry_time: i64) -> Instruction {
+    set_time_ix(
+        authority,
+        creator,
+        red_packet::instruction::SetExpiryTime { expiry_time }.data(),
+    )
+}
+
+fn set_start_ix(authority: &Pubkey, creator: &Pubkey, start_time: i64) -> Instruction {
+    set_time_ix(
+        authority,
+        creator,
+        red_packet::instruction::SetStartTime { start_time }.data(),
+    )
+}
+
+/// `start_delay` 为开始时间相对当前时刻的延后秒数
+async fn setup(start_delay: Option<i64>) -> (ProgramTestContext, Keypair) {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = setup_creator(&mut context).await;
+    let now = unix_timestamp(&mut context).await;
+    let args = red_packet::instruction::CreateRedpacket {
+        start_time: start_delay.map(|delay| now + delay),
+        ..create_args(1_000_000_000, 4, 0)
+    };
+    let ix = create_ix(&creator.pubkey(), 0, &Asset::Sol, args);
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    (context, creator)
+}
+
+#[tokio::test]
+async fn test_set_expiry_time_limits() {
+    let (mut context, creator) = setup(None).await;
+    let creator_key = creator.pubkey();
+    let now = unix_timestamp(&mut context).await;
+
+    for expiry_time in [now - 1, now + 31 * DAY] {
+        assert_eq!(
+            custom_error(
+                send(
+                    &mut context,
+                    &[set_expiry_ix(&creator_key, &creator_key, expiry_time)],
+                    &[&creator]
+                )
+                .await
+            ),
+            Some(error_code(RedPacketError::InvalidExpiryTime))
+        );
+    }
+
+    let stranger = new_claimer(&mut context).await;
+    assert_eq!(
+        custom_error(
+            send(
+                &mut context,
+                &[set_expiry_ix(&stranger.pubkey(), &creator_key, now + DAY)],
+                &[&stranger]
+            )
+            .await
+        ),
+        Some(error_code(RedPacketError::Unauthorized))
+    );
+
+    for change in 1..=MAX_EXPIRY_TIME_CHANGES as i64 {
+        let ix = set_expiry_ix(&creator_key, &creator_key, now + change * DAY);
+        send(&mut context, &[ix], &[&creator]).await.unwrap();
+    }
+    let state: RedPacket = account(&mut context, &red_packet_address(&creator_key, 0)).await;
+    assert_eq!(
+        state.expiry_time,
+        now + MAX_EXPIRY_TIME_CHANGES as i64 * DAY
+    );
+    assert_eq!(state.expiry_time_changes, MAX_EXPIRY_TIME_CHANGES);
+
+    let ix = set_expiry_ix(&creator_key, &creator_key, now + 10 * DAY);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::TooManyExpiryChanges))
+    );
+}
+
+#[tokio::test]
+async fn test_set_start_time_before_start() {
+    let (mut context, creator) = setup(Some(DAY)).await;
+    let creator_key = creator.pubkey();
+    let now = unix_timestamp(&mut context).await;
+
+    // 开始时间不能晚于有效期
+    let ix = set_start_ix(&creator_key, &creator_key, now + 20 * DAY);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::InvalidStartTime))
+    );
+
+    // 提前到当前时刻，红包立即开始
+    let ix = set_start_ix(&creator_key, &creator_key, 0);
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    let state: RedPacket = account(&mut context, &red_packet_address(&creator_key, 0)).await;
+    assert_eq!(state.start_time, now);
+
+    let ix = set_start_ix(&creator_key, &creator_key, now + DAY);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::RedPacketAlreadyStarted))
+    );
+}
@@ -0,0 +1,423 @@
+//! Merkle 名单红包：类型 2 用 user_state 记录领取，类型 4 用位图记录，
+//! 覆盖证明校验失败、位图未就绪、序号越界和位图关闭
+
+mod common;
+
+use anchor_lang::{InstructionData, ToAccountMetas};
+use common::{
+    claim_ix, create_args, create_ix, custom_error, error_code, lamports, new_claimer,
+    protocol_config_address, red_packet_address, send, setup_creator, Asset,
+};
+use red_packet::{
+    config::{CLAIM_BITMAP_SEED, MAX_PACKET_COUNT, MAX_PROOF_LENGTH},
+    errors::RedPacketError,
+};
+use sha2::{Digest, Sha256};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use solana_system_interface::program as system_program;
+
+const AMOUNTS: [u64; 3] = [100_000_000, 200_000_000, 300_000_000];
+
+/// 按排序后的哈希对两两合并，返回每一层，最后一层只有根
+fn merkle_layers(leaves: Vec<[u8; 32]>) -> Vec<Vec<[u8; 32]>> {
+    let mut layers = vec![leaves];
+    while layers.last().unwrap().len() > 1 {
+        let next = layers
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => {
+                    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+                    Sha256::new()
+                        .chain_update(low)
+                        .chain_update(high)
+                        .finalize()
+                        .into()
+                }
+                // 落单的节点直接上移
+                [a] => *a,
+                _ => unreachable!(),
+            })
+            .collect();
+        layers.push(next);
+    }
+    layers
+}
+
+fn merkle_proof(layers: &[Vec<[u8; 32]>], mut index: usize) -> Vec<[u8; 32]> {
+    let mut proof = Vec::new();
+    for layer in &layers[..layers.len() - 1] {
+        if let Some(sibling) = layer.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        index /= 2;
+    }
+    proof
+}
+
+fn leaf(claimer: &Pubkey, amount: u64) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(claimer)
+        .chain_update(amount.to_le_bytes())
+        .finalize()
+        .into()
+}
+
+fn indexed_leaf(index: u32, claimer: &Pubkey, amount: u64) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(index.to_le_bytes())
+        .chain_update(claimer)
+        .chain_update(amount.to_le_bytes())
+        .finalize()
+        .into()
+}
+
+struct Whitelist {
+    claimers: Vec<Keypair>,
+    layers: Vec<Vec<[u8; 32]>>,
+}
+
+impl Whitelist {
+    async fn new(context: &mut ProgramTestContext, indexed: bool) -> Self {
+        let mut claimers = Vec::new();
+        for _ in AMOUNTS {
+            claimers.push(new_claimer(context).await);
+        }
+        let leaves = claimers
+            .iter()
+            .zip(AMOUNTS)
+            .enumerate()
+            .map(|(index, (claimer, amount))| {
+                if indexed {
+                    indexed_leaf(index as u32, &claimer.pubkey(), amount)
+                } else {
+                    leaf(&claimer.pubkey(), amount)
+                }
+            })
+            .collect();
+        Self {
+            claimers,
+            layers: merkle_layers(leaves),
+        }
+    }
+
+    fn root(&self) -> [u8; 32] {
+        self.layers.last().unwrap()[0]
+    }
+
+    fn proof(&self, index: usize) -> Vec<[u8; 32]> {
+        merkle_proof(&self.layers, index)
+    }
+}
+
+fn claim_bitmap_address(red_packet: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[CLAIM_BITMAP_SEED, red_packet.as_ref()], &red_packet::ID).0
+}
+
+fn merkle_claim_ix(
+    creator: &Pubkey,
+    claimer: &Pubkey,
+    amount: Option<u64>,
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    claim_ix(
+        creator,
+        claimer,
+        &Asset::Sol,
+        red_packet::instruction::ClaimRedpacket {
+            amount,
+            proof: Some(proof),
+            red_packet_id: 0,
+            code: None,
+        },
+    )
+}
+
+fn init_bitmap_ix(creator: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::InitClaimBitmap {
+            creator: *creator,
+            red_packet,
+            claim_bitmap: claim_bitmap_address(&red_packet),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::InitClaimBitmap { red_packet_id: 0 }.data(),
+    }
+}
+
+fn extend_bitmap_ix(payer: &Pubkey, creator: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::ExtendClaimBitmap {
+            payer: *payer,
+            claim_bitmap: claim_bitmap_address(&red_packet),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::ExtendClaimBitmap {}.data(),
+    }
+}
+
+fn close_bitmap_ix(creator: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CloseClaimBitmap {
+            red_packet,
+            claim_bitmap: claim_bitmap_address(&red_packet),
+            creator: *creator,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::CloseClaimBitmap {}.data(),
+    }
+}
+
+fn claim_indexed_ix(
+    creator: &Pubkey,
+    claimer: &Pubkey,
+    index: u32,
+    amount: u64,
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::ClaimIndexedRedPacket {
+            claimer: *claimer,
+            red_packet,
+            claim_bitmap: claim_bitmap_address(&red_packet),
+            mint: Pubkey::default(),
+            pool_ata: red_packet,
+            user_ata: *claimer,
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: spl_token_2022::id(),
+            associated_token_program: spl_associated_token_account::id(),
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::ClaimIndexedRedpacket {
+            index,
+            amount,
+            proof,
+            red_packet_id: 0,
+        }
+        .data(),
+    }
+}
+
+async fn create_merkle(
+    context: &mut ProgramTestContext,
+    root: [u8; 32],
+    packet_count: u32,
+    red_packet_type: u8,
+) -> Keypair {
+    let creator = setup_creator(context).await;
+    let total_amount = AMOUNTS.iter().sum::<u64>().max(packet_count as u64);
+    let args = red_packet::instruction::CreateRedpacket {
+        merkle_root: Some(root),
+        ..create_args(total_amount, packet_count, red_packet_type)
+    };
+    let ix = create_ix(&creator.pubkey(), 0, &Asset::Sol, args);
+    send(context, &[ix], &[&creator]).await.unwrap();
+    creator
+}
+
+#[tokio::test]
+async fn test_merkle_claim_verifies_proof() {
+    let mut context = common::program_test().start_with_context().await;
+    let whitelist = Whitelist::new(&mut context, false).await;
+    let creator = create_merkle(&mut context, whitelist.root(), 3, 2).await;
+    let creator_key = creator.pubkey();
+
+    for (index, claimer) in whitelist.claimers.iter().enumerate() {
+        let claimer_key = claimer.pubkey();
+        let proof = whitelist.proof(index);
+        // 金额与叶子不符时证明无法还原出根
+        let ix = merkle_claim_ix(
+            &creator_key,
+            &claimer_key,
+            Some(AMOUNTS[index] + 1),
+            proof.clone(),
+        );
+        assert_eq!(
+            custom_error(send(&mut context, &[ix], &[claimer]).await),
+            Some(error_code(RedPacketError::MerkleProofInvalid))
+        );
+
+        let before = lamports(&mut context, &claimer_key).await;
+        let ix = merkle_claim_ix(&creator_key, &claimer_key, Some(AMOUNTS[index]), proof);
+        send(&mut context, &[ix], &[claimer]).await.unwrap();
+        assert!(lamports(&mut context, &claimer_key).await > before);
+    }
+
+    // 不在名单里的人拿别人的证明也领不到
+    let outsider = new_claimer(&mut context).await;
+    let ix = merkle_claim_ix(
+        &creator_key,
+        &outsider.pubkey(),
+        Some(AMOUNTS[0]),
+        whitelist.proof(0),
+    );
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&outsider]).await),
+        Some(error_code(RedPacketError::NoPacketsRemaining))
+    );
+}
+
+#[tokio::test]
+async fn test_merkle_claim_rejects_malformed_requests() {
+    let mut context = common::program_test().start_with_context().await;
+    let whitelist = Whitelist::new(&mut context, false).await;
+    let creator = create_merkle(&mut context, whitelist.root(), 3, 2).await;
+    let claimer = &whitelist.claimers[0];
+
+    let cases = [
+        (
+            merkle_claim_ix(
+                &creator.pubkey(),
+                &claimer.pubkey(),
+                None,
+                whitelist.proof(0),
+            ),
+            RedPacketError::InvalidClaimAmount,
+        ),
+        (
+            merkle_claim_ix(
+                &creator.pubkey(),
+                &claimer.pubkey(),
+                Some(AMOUNTS[0]),
+                vec![[0; 32]; MAX_PROOF_LENGTH + 1],
+            ),
+            RedPacketError::MerkleProofTooLong,
+        ),
+        (
+            merkle_claim_ix(
+                &creator.pubkey(),
+                &whitelist.claimers[1].pubkey(),
+                Some(AMOUNTS[0]),
+                whitelist.proof(0),
+            ),
+            RedPacketError::MerkleProofInvalid,
+        ),
+    ];
+    for (ix, error) in cases {
+        let signer = whitelist
+            .claimers
+            .iter()
+            .find(|claimer| ix.accounts[0].pubkey == claimer.pubkey())
+            .unwrap();
+        assert_eq!(
+            custom_error(send(&mut context, &[ix], &[signer]).await),
+            Some(error_code(error)),
+            "{error:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_indexed_claim_uses_bitmap() {
+    let mut context = common::program_test().start_with_context().await;
+    let whitelist = Whitelist::new(&mut context, true).await;
+    let creator = create_merkle(&mut context, whitelist.root(), 3, 4).await;
+    let creator_key = creator.pubkey();
+    send(&mut context, &[init_bitmap_ix(&creator_key)], &[&creator])
+        .await
+        .unwrap();
+
+    // 类型 4 不能走普通领取
+    let claimer = &whitelist.claimers[0];
+    let ix = merkle_claim_ix(
+        &creator_key,
+        &claimer.pubkey(),
+        Some(AMOUNTS[0]),
+        whitelist.proof(0),
+    );
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[claimer]).await),
+        Some(error_code(RedPacketError::InvalidRedPacketType))
+    );
+
+    let ix = claim_indexed_ix(&creator_key, &claimer.pubkey(), 3, AMOUNTS[0], vec![]);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[claimer]).await),
+        Some(error_code(RedPacketError::InvalidClaimIndex))
+    );
+
+    for (index, claimer) in whitelist.claimers.iter().enumerate() {
+        let ix = claim_indexed_ix(
+            &creator_key,
+            &claimer.pubkey(),
+            index as u32,
+            AMOUNTS[index],
+            whitelist.proof(index),
+        );
+        send(&mut context, std::slice::from_ref(&ix), &[claimer])
+            .await
+            .unwrap();
+        if index == 0 {
+            // 位图已经被置位，还没领完时位图不能关闭
+            assert_eq!(
+                custom_error(send(&mut context, &[ix], &[claimer]).await),
+                Some(error_code(RedPacketError::AlreadyClaimed))
+            );
+            assert_eq!(
+                custom_error(send(&mut context, &[close_bitmap_ix(&creator_key)], &[]).await),
+                Some(error_code(RedPacketError::RedPacketStillActive))
+            );
+        }
+    }
+
+    let before = lamports(&mut context, &creator_key).await;
+    send(&mut context, &[close_bitmap_ix(&creator_key)], &[])
+        .await
+        .unwrap();
+    assert!(lamports(&mut context, &creator_key).await > before);
+}
+
+#[tokio::test]
+async fn test_indexed_claim_waits_for_bitmap_extension() {
+    let mut context = common::program_test().start_with_context().await;
+    let whitelist = Whitelist::new(&mut context, true).await;
+    // 十万份的位图超过单次分配上限，需要 extend 之后才能领取
+    let creator = create_merkle(&mut context, whitelist.root(), MAX_PACKET_COUNT, 4).await;
+    let creator_key = creator.pubkey();
+    send(&mut context, &[init_bitmap_ix(&creator_key)], &[&creator])
+        .await
+        .unwrap();
+
+    let claimer = &whitelist.claimers[1];
+    let ix = claim_indexed_ix(
+        &creator_key,
+        &claimer.pubkey(),
+        1,
+        AMOUNTS[1],
+        whitelist.proof(1),
+    );
+    assert_eq!(
+        custom_error(send(&mut context, std::slice::from_ref(&ix), &[claimer]).await),
+        Some(error_code(RedPacketError::ClaimBitmapNotReady))
+    );
+
+    let payer = context.payer.pubkey();
+    send(&mut context, &[extend_bitmap_ix(&payer, &creator_key)], &[])
+        .await
+        .unwrap();
+    send(&mut context, &[ix], &[claimer]).await.unwrap();
+
+    // 位图已经完整分配，再扩容会被拒绝
+    assert_eq!(
+        custom_error(send(&mut context, &[extend_bitmap_ix(&payer, &creator_key)], &[]).await),
+        Some(error_code(RedPacketError::InvalidClaimBitmap))
+    );
+}
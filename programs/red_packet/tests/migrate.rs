@@ -0,0 +1,175 @@
+//! 旧布局账户的迁移：扩容、补租金、保留原有状态，重复迁移会被拒绝
+
+mod common;
+
+use anchor_lang::{
+    AccountDeserialize, AnchorSerialize, Discriminator, InstructionData, ToAccountMetas,
+};
+use common::{custom_error, error_code, red_packet_address, send};
+use red_packet::{
+    config::{
+        CREATOR_STATE_SPACE, LEGACY_CREATOR_STATE_SPACE, LEGACY_RED_PACKET_SPACE, RED_PACKET_SPACE,
+    },
+    errors::RedPacketError,
+    states::{CreatorState, CreatorStateV0, RedPacket, RedPacketV0},
+};
+use solana_sdk::{
+    account::Account, instruction::Instruction, pubkey::Pubkey, rent::Rent, signature::Signer,
+};
+use solana_system_interface::program as system_program;
+
+/// 旧红包里还剩下的待领取 SOL
+const REMAINING_AMOUNT: u64 = 400_000_000;
+
+fn legacy_account(creator: Pubkey) -> Account {
+    let legacy = RedPacketV0 {
+        creator,
+        mint: Pubkey::default(),
+        total_amount: 1_000_000_000,
+        remaining_amount: REMAINING_AMOUNT,
+        packet_count: 10,
+        claimed_count: 6,
+        red_packet_type: 0,
+        share_amount: 100_000_000,
+        random_seed: 0,
+        expiry_time: i64::MAX,
+        merkle_root: [0; 32],
+        is_sol: true,
+        expiry_time_changes: 0,
+        red_packet_id: 0,
+        bump: 255,
+    };
+    let mut data = RedPacket::DISCRIMINATOR.to_vec();
+    legacy.serialize(&mut data).unwrap();
+    assert_eq!(data.len(), LEGACY_RED_PACKET_SPACE);
+    Account {
+        lamports: Rent::default().minimum_balance(LEGACY_RED_PACKET_SPACE) + REMAINING_AMOUNT,
+        data,
+        owner: red_packet::ID,
+        ..Account::default()
+    }
+}
+
+fn migrate_ix(payer: &Pubkey, red_packet: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::MigrateRedPacket {
+            payer: *payer,
+            red_packet: *red_packet,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::MigrateRedpacket {}.data(),
+    }
+}
+
+#[tokio::test]
+async fn test_migrate_legacy_red_packet() {
+    let creator = Pubkey::new_unique();
+    let address = red_packet_address(&creator, 0);
+    let mut program_test = common::program_test();
+    program_test.add_account(address, legacy_account(creator));
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.pubkey();
+
+    send(&mut context, &[migrate_ix(&payer, &address)], &[])
+        .await
+        .unwrap();
+
+    let account = context
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.data.len(), RED_PACKET_SPACE);
+    // 补齐新布局的租金后，待领取的 SOL 一分不少
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(RED_PACKET_SPACE) + REMAINING_AMOUNT
+    );
+    let red_packet = RedPacket::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(red_packet.version, RedPacket::CURRENT_VERSION);
+    assert_eq!(red_packet.creator, creator);
+    assert_eq!(red_packet.remaining_amount, REMAINING_AMOUNT);
+    assert_eq!(red_packet.claimed_count, 6);
+    assert_eq!(red_packet.start_time, 0);
+
+    let result = send(&mut context, &[migrate_ix(&payer, &address)], &[]).await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::RedPacketAlreadyMigrated))
+    );
+}
+
+#[tokio::test]
+async fn test_migrate_rejects_foreign_account() {
+    let creator = Pubkey::new_unique();
+    let address = Pubkey::new_unique();
+    let mut account = legacy_account(creator);
+    account.owner = system_program::ID;
+    let mut program_test = common::program_test();
+    program_test.add_account(address, account);
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.pubkey();
+
+    let result = send(&mut context, &[migrate_ix(&payer, &address)], &[]).await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::InvalidAccountOwner))
+    );
+}
+
+#[tokio::test]
+async fn test_migrate_legacy_creator_state() {
+    let creator = Pubkey::new_unique();
+    let address = common::creator_state_address(&creator);
+    let mut data = CreatorState::DISCRIMINATOR.to_vec();
+    CreatorStateV0 {
+        next_red_packet_id: 3,
+        bump: 254,
+    }
+    .serialize(&mut data)
+    .unwrap();
+    assert_eq!(data.len(), LEGACY_CREATOR_STATE_SPACE);
+    let mut program_test = common::program_test();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: Rent::default().minimum_balance(LEGACY_CREATOR_STATE_SPACE),
+            data,
+            owner: red_packet::ID,
+            ..Account::default()
+        },
+    );
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.pubkey();
+    let ix = Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::MigrateCreatorState {
+            payer,
+            creator_state: address,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::MigrateCreatorState {}.data(),
+    };
+    send(&mut context, &[ix], &[]).await.unwrap();
+
+    let account = context
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.data.len(), CREATOR_STATE_SPACE);
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(CREATOR_STATE_SPACE)
+    );
+    let creator_state = CreatorState::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(creator_state.version, CreatorState::CURRENT_VERSION);
+    assert_eq!(creator_state.next_red_packet_id, 3);
+    assert_eq!(creator_state.bump, 254);
+    assert!(creator_state.operators.is_empty());
+}
@@ -0,0 +1,190 @@
+//! mint 风险策略：默认只在创建事件里标记冻结权限，管理员可以改为直接拒绝
+
+mod common;
+
+use anchor_lang::{solana_program::sysvar, InstructionData, ToAccountMetas};
+use common::{
+    custom_error, error_code, fund, initialize_creator_state_ix, initialize_protocol_config_ix,
+    protocol_config_address, red_packet_address, send, set_upgrade_authority,
+};
+use red_packet::{errors::RedPacketError, states::ProtocolConfig};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+};
+use solana_system_interface::{instruction as system_instruction, program as system_program};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+const TOKEN_PROGRAM: Pubkey = anchor_spl::token::ID;
+
+struct Setup {
+    context: ProgramTestContext,
+    creator: Keypair,
+    mint: Pubkey,
+}
+
+/// 创建一个保留了冻结权限的旧版 SPL Token mint，并给创建者铸币
+async fn setup() -> Setup {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = Keypair::new();
+    fund(&mut context, &creator.pubkey(), 10_000_000_000).await;
+    let payer = context.payer.pubkey();
+    set_upgrade_authority(&mut context, &payer);
+
+    let mint = Keypair::new();
+    let mint_len = spl_token_2022::state::Mint::LEN;
+    let creator_ata = get_associated_token_address_with_program_id(
+        &creator.pubkey(),
+        &mint.pubkey(),
+        &TOKEN_PROGRAM,
+    );
+    let instructions = [
+        system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            Rent::default().minimum_balance(mint_len),
+            mint_len as u64,
+            &TOKEN_PROGRAM,
+        ),
+        spl_token_2022::instruction::initialize_mint2(
+            &TOKEN_PROGRAM,
+            &mint.pubkey(),
+            &payer,
+            Some(&payer),
+            6,
+        )
+        .unwrap(),
+        spl_associated_token_account::instruction::create_associated_token_account(
+            &payer,
+            &creator.pubkey(),
+            &mint.pubkey(),
+            &TOKEN_PROGRAM,
+        ),
+        spl_token_2022::instruction::mint_to(
+            &TOKEN_PROGRAM,
+            &mint.pubkey(),
+            &creator_ata,
+            &payer,
+            &[],
+            1_000_000,
+        )
+        .unwrap(),
+        initialize_protocol_config_ix(&payer),
+        initialize_creator_state_ix(&creator.pubkey()),
+    ];
+    send(&mut context, &instructions, &[&mint, &creator])
+        .await
+        .unwrap();
+
+    Setup {
+        context,
+        creator,
+        mint: mint.pubkey(),
+    }
+}
+
+fn create_ix(creator: &Pubkey, mint: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CreateRedPacket {
+            creator: *creator,
+            creator_state: common::creator_state_address(creator),
+            red_packet,
+            mint: *mint,
+            creator_ata: get_associated_token_address_with_program_id(
+                creator,
+                mint,
+                &TOKEN_PROGRAM,
+            ),
+            pool_ata: get_associated_token_address_with_program_id(
+                &red_packet,
+                mint,
+                &TOKEN_PROGRAM,
+            ),
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: TOKEN_PROGRAM,
+            associated_token_program: spl_associated_token_account::id(),
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::CreateRedpacket {
+            total_amount: 1_000,
+            packet_count: 2,
+            red_packet_type: 0,
+            merkle_root: None,
+            is_sol: false,
+            expiry_days: Some(1),
+            random_seed: None,
+            start_time: None,
+            code_hash: None,
+            holder_gate: None,
+            prize_tiers: None,
+        }
+        .data(),
+    }
+}
+
+fn set_mint_risk_policy_ix(admin: &Pubkey, mint_risk_policy: u8) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::UpdateProtocolConfig {
+            admin: *admin,
+            protocol_config: protocol_config_address(),
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::SetMintRiskPolicy { mint_risk_policy }.data(),
+    }
+}
+
+#[tokio::test]
+async fn test_freeze_authority_rejected_only_by_policy() {
+    let Setup {
+        mut context,
+        creator,
+        mint,
+    } = setup().await;
+    let admin = context.payer.pubkey();
+
+    let ix = set_mint_risk_policy_ix(&admin, ProtocolConfig::REJECT_FREEZE_AUTHORITY);
+    send(&mut context, &[ix], &[]).await.unwrap();
+    let ix = create_ix(&creator.pubkey(), &mint);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::FreezeAuthorityNotAllowed))
+    );
+
+    // 只拒绝关闭权限时，有冻结权限的 mint 可以创建
+    let ix = set_mint_risk_policy_ix(&admin, ProtocolConfig::REJECT_CLOSE_AUTHORITY);
+    send(&mut context, &[ix], &[]).await.unwrap();
+    let ix = create_ix(&creator.pubkey(), &mint);
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_only_admin_sets_known_policy_flags() {
+    let Setup {
+        mut context,
+        creator,
+        ..
+    } = setup().await;
+    let admin = context.payer.pubkey();
+
+    let ix = set_mint_risk_policy_ix(&creator.pubkey(), ProtocolConfig::REJECT_ALL_RISKS);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::NotProtocolAdmin))
+    );
+
+    let ix = set_mint_risk_policy_ix(&admin, ProtocolConfig::REJECT_ALL_RISKS << 1);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[]).await),
+        Some(error_code(RedPacketError::InvalidMintRiskPolicy))
+    );
+}
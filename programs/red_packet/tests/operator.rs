@@ -0,0 +1,379 @@
+//! 操作员：创建者授权热钱包代为修改时间、追加和取消，权限按位独立
+
+mod common;
+
+use anchor_lang::{solana_program::sysvar, AccountDeserialize, InstructionData, ToAccountMetas};
+use common::{
+    creator_state_address, custom_error, error_code, fund, initialize_creator_state_ix,
+    initialize_protocol_config_ix, lamports, protocol_config_address, red_packet_address, send,
+    set_upgrade_authority,
+};
+use red_packet::{
+    errors::RedPacketError,
+    states::{CreatorState, RedPacket},
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use solana_system_interface::program as system_program;
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+
+struct Setup {
+    context: ProgramTestContext,
+    creator: Keypair,
+    operator: Keypair,
+}
+
+async fn setup(permissions: u8) -> Setup {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = Keypair::new();
+    let operator = Keypair::new();
+    fund(&mut context, &creator.pubkey(), 10_000_000_000).await;
+    fund(&mut context, &operator.pubkey(), 10_000_000_000).await;
+    let admin = context.payer.pubkey();
+    set_upgrade_authority(&mut context, &admin);
+    let instructions = [
+        initialize_protocol_config_ix(&admin),
+        initialize_creator_state_ix(&creator.pubkey()),
+        create_ix(&creator.pubkey()),
+        add_operator_ix(&creator.pubkey(), &operator.pubkey(), permissions),
+    ];
+    send(&mut context, &instructions, &[&creator])
+        .await
+        .unwrap();
+    Setup {
+        context,
+        creator,
+        operator,
+    }
+}
+
+fn create_ix(creator: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CreateRedPacket {
+            creator: *creator,
+            creator_state: creator_state_address(creator),
+            red_packet,
+            mint: Pubkey::default(),
+            creator_ata: *creator,
+            pool_ata: red_packet,
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: spl_token_2022::id(),
+            associated_token_program: spl_associated_token_account::id(),
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::CreateRedpacket {
+            total_amount: TOTAL_AMOUNT,
+            packet_count: 2,
+            red_packet_type: 0,
+            merkle_root: None,
+            is_sol: true,
+            expiry_days: Some(1),
+            random_seed: None,
+            start_time: None,
+            code_hash: None,
+            holder_gate: None,
+            prize_tiers: None,
+        }
+        .data(),
+    }
+}
+
+fn manage_operators_accounts(creator: &Pubkey) -> Vec<solana_sdk::instruction::AccountMeta> {
+    red_packet::accounts::ManageOperators {
+        creator: *creator,
+        creator_state: creator_state_address(creator),
+    }
+    .to_account_metas(None)
+}
+
+fn add_operator_ix(creator: &Pubkey, operator: &Pubkey, permissions: u8) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: manage_operators_accounts(creator),
+        data: red_packet::instruction::AddOperator {
+            operator: *operator,
+            permissions,
+        }
+        .data(),
+    }
+}
+
+fn remove_operator_ix(creator: &Pubkey, operator: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: manage_operators_accounts(creator),
+        data: red_packet::instruction::RemoveOperator {
+            operator: *operator,
+        }
+        .data(),
+    }
+}
+
+fn set_expiry_ix(creator: &Pubkey, authority: &Pubkey, expiry_time: i64) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::SetExpiryTime {
+            red_packet: red_packet_address(creator, 0),
+            authority: *authority,
+            creator_state: creator_state_address(creator),
+            protocol_config: protocol_config_address(),
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::SetExpiryTime { expiry_time }.data(),
+    }
+}
+
+fn top_up_ix(creator: &Pubkey, authority: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::TopUpRedPacket {
+            authority: *authority,
+            red_packet,
+            creator_state: creator_state_address(creator),
+            mint: Pubkey::default(),
+            authority_ata: *authority,
+            pool_ata: red_packet,
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: spl_token_2022::id(),
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::TopUpRedpacket {
+            red_packet_id: 0,
+            amount: TOTAL_AMOUNT,
+            extra_packets: 0,
+        }
+        .data(),
+    }
+}
+
+fn cancel_ix(creator: &Pubkey, authority: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CancelRedPacket {
+            authority: *authority,
+            red_packet,
+            creator_state: creator_state_address(creator),
+            creator: *creator,
+            mint: Pubkey::default(),
+            creator_ata: *creator,
+            pool_ata: red_packet,
+            token_program: spl_token_2022::id(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::CancelRedpacket {
+            red_packet_id: 0,
+            grace_period: None,
+        }
+        .data(),
+    }
+}
+
+async fn account<T: AccountDeserialize>(context: &mut ProgramTestContext, address: &Pubkey) -> T {
+    let account = context
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .unwrap();
+    T::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn test_operator_permissions_are_enforced() {
+    let Setup {
+        mut context,
+        creator,
+        operator,
+    } = setup(CreatorState::PERMISSION_EXPIRY).await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+    let state: RedPacket = account(&mut context, &red_packet).await;
+
+    let expiry_time = state.expiry_time + 3_600;
+    send(
+        &mut context,
+        &[set_expiry_ix(
+            &creator.pubkey(),
+            &operator.pubkey(),
+            expiry_time,
+        )],
+        &[&operator],
+    )
+    .await
+    .unwrap();
+    let state: RedPacket = account(&mut context, &red_packet).await;
+    assert_eq!(state.expiry_time, expiry_time);
+
+    // 没有授予的权限
+    let result = send(
+        &mut context,
+        &[top_up_ix(&creator.pubkey(), &operator.pubkey())],
+        &[&operator],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::Unauthorized))
+    );
+    let result = send(
+        &mut context,
+        &[cancel_ix(&creator.pubkey(), &operator.pubkey())],
+        &[&operator],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::Unauthorized))
+    );
+
+    // 移除后原有权限也失效
+    send(
+        &mut context,
+        &[remove_operator_ix(&creator.pubkey(), &operator.pubkey())],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    let result = send(
+        &mut context,
+        &[set_expiry_ix(
+            &creator.pubkey(),
+            &operator.pubkey(),
+            expiry_time + 60,
+        )],
+        &[&operator],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::Unauthorized))
+    );
+}
+
+#[tokio::test]
+async fn test_operator_top_up_and_cancel_refunds_creator() {
+    let Setup {
+        mut context,
+        creator,
+        operator,
+    } = setup(CreatorState::PERMISSION_TOP_UP | CreatorState::PERMISSION_CANCEL).await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+
+    // 追加的资金来自操作员
+    let operator_before = lamports(&mut context, &operator.pubkey()).await;
+    send(
+        &mut context,
+        &[top_up_ix(&creator.pubkey(), &operator.pubkey())],
+        &[&operator],
+    )
+    .await
+    .unwrap();
+    assert!(lamports(&mut context, &operator.pubkey()).await <= operator_before - TOTAL_AMOUNT);
+    let state: RedPacket = account(&mut context, &red_packet).await;
+    assert_eq!(state.remaining_amount, 2 * TOTAL_AMOUNT);
+
+    // 取消后剩余金额退给创建者而不是操作员
+    let creator_before = lamports(&mut context, &creator.pubkey()).await;
+    send(
+        &mut context,
+        &[cancel_ix(&creator.pubkey(), &operator.pubkey())],
+        &[&operator],
+    )
+    .await
+    .unwrap();
+    assert!(lamports(&mut context, &creator.pubkey()).await > creator_before + 2 * TOTAL_AMOUNT);
+    assert_eq!(lamports(&mut context, &red_packet).await, 0);
+}
+
+#[tokio::test]
+async fn test_operator_list_limits() {
+    let Setup {
+        mut context,
+        creator,
+        operator,
+    } = setup(CreatorState::PERMISSION_EXPIRY).await;
+
+    // 重复添加只更新权限
+    send(
+        &mut context,
+        &[add_operator_ix(
+            &creator.pubkey(),
+            &operator.pubkey(),
+            CreatorState::PERMISSION_ALL,
+        )],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    let state: CreatorState =
+        account(&mut context, &creator_state_address(&creator.pubkey())).await;
+    assert_eq!(state.operators.len(), 1);
+    assert_eq!(state.operators[0].permissions, CreatorState::PERMISSION_ALL);
+
+    let result = send(
+        &mut context,
+        &[add_operator_ix(
+            &creator.pubkey(),
+            &Pubkey::new_unique(),
+            1 << 7,
+        )],
+        &[&creator],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::InvalidOperatorPermissions))
+    );
+
+    let instructions: Vec<_> = (1..red_packet::config::MAX_OPERATORS)
+        .map(|_| {
+            add_operator_ix(
+                &creator.pubkey(),
+                &Pubkey::new_unique(),
+                CreatorState::PERMISSION_EXPIRY,
+            )
+        })
+        .collect();
+    send(&mut context, &instructions, &[&creator])
+        .await
+        .unwrap();
+    let result = send(
+        &mut context,
+        &[add_operator_ix(
+            &creator.pubkey(),
+            &Pubkey::new_unique(),
+            CreatorState::PERMISSION_EXPIRY,
+        )],
+        &[&creator],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::TooManyOperators))
+    );
+
+    let result = send(
+        &mut context,
+        &[remove_operator_ix(&creator.pubkey(), &Pubkey::new_unique())],
+        &[&creator],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::OperatorNotFound))
+    );
+}
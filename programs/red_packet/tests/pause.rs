@@ -0,0 +1,324 @@
+//! 紧急暂停：按位暂停创建、领取、修改时间，退款始终可用
+
+mod common;
+
+use anchor_lang::{solana_program::sysvar, InstructionData, ToAccountMetas};
+use common::{
+    custom_error, error_code, fund, initialize_creator_state_ix, initialize_protocol_config_ix,
+    lamports, protocol_config_address, red_packet_address, send, set_upgrade_authority,
+    user_state_address,
+};
+use red_packet::{errors::RedPacketError, states::ProtocolConfig};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use solana_system_interface::program as system_program;
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+
+struct Setup {
+    context: ProgramTestContext,
+    admin: Keypair,
+    creator: Keypair,
+}
+
+async fn setup() -> Setup {
+    let mut context = common::program_test().start_with_context().await;
+    let admin = Keypair::new();
+    let creator = Keypair::new();
+    fund(&mut context, &admin.pubkey(), 1_000_000_000).await;
+    fund(&mut context, &creator.pubkey(), 10_000_000_000).await;
+    set_upgrade_authority(&mut context, &admin.pubkey());
+    send(
+        &mut context,
+        &[
+            initialize_protocol_config_ix(&admin.pubkey()),
+            initialize_creator_state_ix(&creator.pubkey()),
+        ],
+        &[&admin, &creator],
+    )
+    .await
+    .unwrap();
+    Setup {
+        context,
+        admin,
+        creator,
+    }
+}
+
+fn create_ix(creator: &Pubkey, red_packet_id: u64) -> Instruction {
+    let red_packet = red_packet_address(creator, red_packet_id);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CreateRedPacket {
+            creator: *creator,
+            creator_state: common::creator_state_address(creator),
+            red_packet,
+            mint: Pubkey::default(),
+            creator_ata: *creator,
+            pool_ata: red_packet,
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: spl_token_2022::id(),
+            associated_token_program: spl_associated_token_account::id(),
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::CreateRedpacket {
+            total_amount: TOTAL_AMOUNT,
+            packet_count: 2,
+            red_packet_type: 0,
+            merkle_root: None,
+            is_sol: true,
+            expiry_days: Some(1),
+            random_seed: None,
+            start_time: None,
+            code_hash: None,
+            holder_gate: None,
+            prize_tiers: None,
+        }
+        .data(),
+    }
+}
+
+fn claim_ix(red_packet: &Pubkey, claimer: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::ClaimRedPacket {
+            claimer: *claimer,
+            red_packet: *red_packet,
+            user_state: user_state_address(red_packet, claimer),
+            mint: Pubkey::default(),
+            pool_ata: *red_packet,
+            user_ata: *claimer,
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: spl_token_2022::id(),
+            associated_token_program: spl_associated_token_account::id(),
+            slot_hashes: sysvar::slot_hashes::ID,
+            claim_commit: None,
+            gate_token_account: None,
+            gate_metadata: None,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::ClaimRedpacket {
+            amount: None,
+            proof: None,
+            red_packet_id: 0,
+            code: None,
+        }
+        .data(),
+    }
+}
+
+fn refund_ix(creator: &Pubkey, red_packet: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::Refund {
+            creator: *creator,
+            red_packet: *red_packet,
+            mint: Pubkey::default(),
+            creator_ata: *creator,
+            pool_ata: *red_packet,
+            token_program: spl_token_2022::id(),
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::Refund { red_packet_id: 0 }.data(),
+    }
+}
+
+fn set_expiry_ix(creator: &Pubkey, red_packet: &Pubkey, expiry_time: i64) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::SetExpiryTime {
+            red_packet: *red_packet,
+            authority: *creator,
+            creator_state: common::creator_state_address(creator),
+            protocol_config: protocol_config_address(),
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::SetExpiryTime { expiry_time }.data(),
+    }
+}
+
+fn set_pause_ix(admin: &Pubkey, pause_flags: u8) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::UpdateProtocolConfig {
+            admin: *admin,
+            protocol_config: protocol_config_address(),
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::SetPause { pause_flags }.data(),
+    }
+}
+
+async fn new_claimer(context: &mut ProgramTestContext) -> Keypair {
+    let claimer = Keypair::new();
+    fund(context, &claimer.pubkey(), 100_000_000).await;
+    claimer
+}
+
+#[tokio::test]
+async fn test_only_admin_can_pause() {
+    let Setup {
+        mut context,
+        creator,
+        ..
+    } = setup().await;
+    let result = send(
+        &mut context,
+        &[set_pause_ix(&creator.pubkey(), ProtocolConfig::PAUSE_ALL)],
+        &[&creator],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::NotProtocolAdmin))
+    );
+}
+
+#[tokio::test]
+async fn test_refund_succeeds_while_fully_paused() {
+    let Setup {
+        mut context,
+        admin,
+        creator,
+    } = setup().await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+    send(
+        &mut context,
+        &[create_ix(&creator.pubkey(), 0)],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+
+    send(
+        &mut context,
+        &[set_pause_ix(&admin.pubkey(), ProtocolConfig::PAUSE_ALL)],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+
+    // 创建、领取、修改时间都被拦住
+    let result = send(
+        &mut context,
+        &[create_ix(&creator.pubkey(), 1)],
+        &[&creator],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::ProgramPaused))
+    );
+    let claimer = new_claimer(&mut context).await;
+    let result = send(
+        &mut context,
+        &[claim_ix(&red_packet, &claimer.pubkey())],
+        &[&claimer],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::ProgramPaused))
+    );
+    let clock: solana_sdk::clock::Clock = context.banks_client.get_sysvar().await.unwrap();
+    let result = send(
+        &mut context,
+        &[set_expiry_ix(
+            &creator.pubkey(),
+            &red_packet,
+            clock.unix_timestamp + 3 * 24 * 60 * 60,
+        )],
+        &[&creator],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::ProgramPaused))
+    );
+
+    // 过期后退款不受暂停影响
+    common::warp_seconds(&mut context, 2 * 24 * 60 * 60).await;
+    let before = lamports(&mut context, &creator.pubkey()).await;
+    send(
+        &mut context,
+        &[refund_ix(&creator.pubkey(), &red_packet)],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    let after = lamports(&mut context, &creator.pubkey()).await;
+    assert!(after - before >= TOTAL_AMOUNT);
+}
+
+#[tokio::test]
+async fn test_pause_flags_are_independent() {
+    let Setup {
+        mut context,
+        admin,
+        creator,
+    } = setup().await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+
+    // 只暂停领取：创建照常
+    send(
+        &mut context,
+        &[set_pause_ix(&admin.pubkey(), ProtocolConfig::PAUSE_CLAIM)],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+    send(
+        &mut context,
+        &[create_ix(&creator.pubkey(), 0)],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    let claimer = new_claimer(&mut context).await;
+    let result = send(
+        &mut context,
+        &[claim_ix(&red_packet, &claimer.pubkey())],
+        &[&claimer],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::ProgramPaused))
+    );
+
+    // 恢复后可以领取
+    send(&mut context, &[set_pause_ix(&admin.pubkey(), 0)], &[&admin])
+        .await
+        .unwrap();
+    let before = lamports(&mut context, &claimer.pubkey()).await;
+    send(
+        &mut context,
+        &[claim_ix(&red_packet, &claimer.pubkey())],
+        &[&claimer],
+    )
+    .await
+    .unwrap();
+    assert!(lamports(&mut context, &claimer.pubkey()).await > before);
+
+    let result = send(
+        &mut context,
+        &[set_pause_ix(&admin.pubkey(), 0x80)],
+        &[&admin],
+    )
+    .await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::InvalidPauseFlags))
+    );
+}
@@ -0,0 +1,232 @@
+//! 全局配置：只有升级权限能初始化、费用修改生效、手续费收款地址校验和管理员两步交接
+
+mod common;
+
+use anchor_lang::{InstructionData, ToAccountMetas};
+use common::{
+    account, account_exists, claim_args, claim_ix, create_args, create_ix, custom_error,
+    error_code, fund, initialize_protocol_config_ix, lamports, new_claimer,
+    protocol_config_address, send, set_upgrade_authority, setup_creator, Asset,
+};
+use red_packet::{
+    errors::RedPacketError,
+    states::{FeeRule, ProtocolConfig},
+};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+
+fn update_ix(
+    admin: &Pubkey,
+    create_fee: Option<FeeRule>,
+    claim_fee: Option<FeeRule>,
+    fee_receiver: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::UpdateProtocolConfig {
+            admin: *admin,
+            protocol_config: protocol_config_address(),
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::UpdateProtocolConfig {
+            create_fee,
+            claim_fee,
+            fee_receiver,
+        }
+        .data(),
+    }
+}
+
+fn propose_admin_ix(admin: &Pubkey, new_admin: Pubkey) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::UpdateProtocolConfig {
+            admin: *admin,
+            protocol_config: protocol_config_address(),
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::ProposeAdmin { new_admin }.data(),
+    }
+}
+
+fn accept_admin_ix(pending_admin: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::AcceptAdmin {
+            pending_admin: *pending_admin,
+            protocol_config: protocol_config_address(),
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::AcceptAdmin {}.data(),
+    }
+}
+
+#[tokio::test]
+async fn test_initialize_requires_upgrade_authority() {
+    let mut context = common::program_test().start_with_context().await;
+    let deployer = context.payer.pubkey();
+    let attacker = Keypair::new();
+    fund(&mut context, &attacker.pubkey(), 1_000_000_000).await;
+
+    // 原生加载时还没有 ProgramData，账户校验直接失败
+    let ix = initialize_protocol_config_ix(&attacker.pubkey());
+    assert!(send(&mut context, &[ix], &[&attacker]).await.is_err());
+
+    set_upgrade_authority(&mut context, &deployer);
+    let ix = initialize_protocol_config_ix(&attacker.pubkey());
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&attacker]).await),
+        Some(error_code(RedPacketError::NotUpgradeAuthority))
+    );
+    assert!(!account_exists(&mut context, &protocol_config_address()).await);
+
+    send(
+        &mut context,
+        &[initialize_protocol_config_ix(&deployer)],
+        &[],
+    )
+    .await
+    .unwrap();
+    let config: ProtocolConfig = account(&mut context, &protocol_config_address()).await;
+    assert_eq!(config.admin, deployer);
+}
+
+#[tokio::test]
+async fn test_updated_fees_go_to_new_receiver() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = setup_creator(&mut context).await;
+    let admin = context.payer.pubkey();
+    let fee_receiver = Pubkey::new_unique();
+    fund(&mut context, &fee_receiver, 1_000_000_000).await;
+
+    // 创建按 1% 收取，领取固定 0.002 SOL
+    let ix = update_ix(
+        &admin,
+        Some(FeeRule {
+            lamports: 0,
+            bps: 100,
+        }),
+        Some(FeeRule {
+            lamports: 2_000_000,
+            bps: 0,
+        }),
+        Some(fee_receiver),
+    );
+    send(&mut context, &[ix], &[]).await.unwrap();
+
+    // 旧的收款地址不再被接受
+    let ix = create_ix(
+        &creator.pubkey(),
+        0,
+        &Asset::Sol,
+        create_args(TOTAL_AMOUNT, 4, 0),
+    );
+    assert_eq!(
+        custom_error(send(&mut context, std::slice::from_ref(&ix), &[&creator]).await),
+        Some(error_code(RedPacketError::InvalidFeeReceiver))
+    );
+
+    let mut ix = ix;
+    ix.accounts[7] = AccountMeta::new(fee_receiver, false);
+    let before = lamports(&mut context, &fee_receiver).await;
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    assert_eq!(
+        lamports(&mut context, &fee_receiver).await,
+        before + TOTAL_AMOUNT / 100
+    );
+
+    let claimer = new_claimer(&mut context).await;
+    let mut ix = claim_ix(
+        &creator.pubkey(),
+        &claimer.pubkey(),
+        &Asset::Sol,
+        claim_args(0),
+    );
+    ix.accounts[7] = AccountMeta::new(fee_receiver, false);
+    send(&mut context, &[ix], &[&claimer]).await.unwrap();
+    assert_eq!(
+        lamports(&mut context, &fee_receiver).await,
+        before + TOTAL_AMOUNT / 100 + 2_000_000
+    );
+}
+
+#[tokio::test]
+async fn test_update_rejects_invalid_config() {
+    let mut context = common::program_test().start_with_context().await;
+    setup_creator(&mut context).await;
+    let admin = context.payer.pubkey();
+    let too_high = FeeRule {
+        lamports: 0,
+        bps: FeeRule::MAX_BPS + 1,
+    };
+
+    let cases = [
+        update_ix(&admin, Some(too_high), None, None),
+        update_ix(&admin, None, Some(too_high), None),
+        update_ix(&admin, None, None, Some(Pubkey::default())),
+    ];
+    for ix in cases {
+        assert_eq!(
+            custom_error(send(&mut context, &[ix], &[]).await),
+            Some(error_code(RedPacketError::InvalidFeeConfig))
+        );
+    }
+
+    let stranger = Keypair::new();
+    let ix = update_ix(&stranger.pubkey(), None, None, None);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&stranger]).await),
+        Some(error_code(RedPacketError::NotProtocolAdmin))
+    );
+}
+
+#[tokio::test]
+async fn test_admin_transfer_requires_acceptance() {
+    let mut context = common::program_test().start_with_context().await;
+    setup_creator(&mut context).await;
+    let admin = context.payer.pubkey();
+    let new_admin = Keypair::new();
+    let stranger = Keypair::new();
+
+    send(
+        &mut context,
+        &[propose_admin_ix(&admin, new_admin.pubkey())],
+        &[],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        custom_error(
+            send(
+                &mut context,
+                &[accept_admin_ix(&stranger.pubkey())],
+                &[&stranger]
+            )
+            .await
+        ),
+        Some(error_code(RedPacketError::NotPendingAdmin))
+    );
+
+    send(
+        &mut context,
+        &[accept_admin_ix(&new_admin.pubkey())],
+        &[&new_admin],
+    )
+    .await
+    .unwrap();
+    let config: ProtocolConfig = account(&mut context, &protocol_config_address()).await;
+    assert_eq!(config.admin, new_admin.pubkey());
+    assert_eq!(config.pending_admin, Pubkey::default());
+
+    // 原管理员失去权限
+    let ix = update_ix(&admin, None, None, None);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[]).await),
+        Some(error_code(RedPacketError::NotProtocolAdmin))
+    );
+}
@@ -0,0 +1,230 @@
+//! 退款与关闭：过期退款、领完后关闭红包、回收 user_state 租金
+
+mod common;
+
+use anchor_lang::InstructionData;
+use common::{
+    account_exists, claim_args, claim_ix, close_ix, close_user_states_ix, create_args, create_ix,
+    create_mint, custom_error, error_code, lamports, mint_to, new_claimer, red_packet_address,
+    refund_ix, send, setup_creator, token_balance, user_state_address, warp_seconds, Asset,
+};
+use red_packet::{
+    config::{RED_PACKET_SPACE, USER_STATE_SPACE},
+    errors::RedPacketError,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::AccountMeta,
+    rent::Rent,
+    signature::{Keypair, Signer},
+};
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+const EXPIRY: i64 = 7 * 24 * 60 * 60;
+
+async fn create(
+    context: &mut ProgramTestContext,
+    asset: &Asset,
+    total_amount: u64,
+    packet_count: u32,
+) -> Keypair {
+    let creator = setup_creator(context).await;
+    if !asset.is_sol() {
+        mint_to(context, asset, &creator.pubkey(), total_amount).await;
+    }
+    let ix = create_ix(
+        &creator.pubkey(),
+        0,
+        asset,
+        create_args(total_amount, packet_count, 0),
+    );
+    send(context, &[ix], &[&creator]).await.unwrap();
+    creator
+}
+
+async fn claim(context: &mut ProgramTestContext, creator: &Keypair, asset: &Asset) -> Keypair {
+    let claimer = new_claimer(context).await;
+    let ix = claim_ix(&creator.pubkey(), &claimer.pubkey(), asset, claim_args(0));
+    send(context, &[ix], &[&claimer]).await.unwrap();
+    claimer
+}
+
+#[tokio::test]
+async fn test_refund_sol_after_expiry() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = create(&mut context, &Asset::Sol, TOTAL_AMOUNT, 4).await;
+    claim(&mut context, &creator, &Asset::Sol).await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+
+    let ix = refund_ix(&creator.pubkey(), 0, &Asset::Sol);
+    assert_eq!(
+        custom_error(send(&mut context, std::slice::from_ref(&ix), &[&creator]).await),
+        Some(error_code(RedPacketError::RedPacketNotExpired))
+    );
+
+    warp_seconds(&mut context, EXPIRY).await;
+    let before = lamports(&mut context, &creator.pubkey()).await;
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    // 未领取的金额连同账户租金一起退回
+    let rent = Rent::default().minimum_balance(RED_PACKET_SPACE);
+    assert_eq!(
+        lamports(&mut context, &creator.pubkey()).await,
+        before + TOTAL_AMOUNT * 3 / 4 + rent
+    );
+    assert!(!account_exists(&mut context, &red_packet).await);
+}
+
+#[tokio::test]
+async fn test_refund_token_closes_pool() {
+    for token_program in [anchor_spl::token::ID, spl_token_2022::id()] {
+        let mut context = common::program_test().start_with_context().await;
+        let asset = create_mint(&mut context, &token_program, None).await;
+        let creator = create(&mut context, &asset, TOTAL_AMOUNT, 4).await;
+        claim(&mut context, &creator, &asset).await;
+        let red_packet = red_packet_address(&creator.pubkey(), 0);
+
+        warp_seconds(&mut context, EXPIRY).await;
+        let ix = refund_ix(&creator.pubkey(), 0, &asset);
+        send(&mut context, &[ix], &[&creator]).await.unwrap();
+        assert_eq!(
+            token_balance(&mut context, &asset.account(&creator.pubkey())).await,
+            Some(TOTAL_AMOUNT * 3 / 4)
+        );
+        assert_eq!(
+            token_balance(&mut context, &asset.account(&red_packet)).await,
+            None
+        );
+        assert!(!account_exists(&mut context, &red_packet).await);
+    }
+}
+
+#[tokio::test]
+async fn test_refund_rejects_wrong_caller_and_id() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = create(&mut context, &Asset::Sol, TOTAL_AMOUNT, 4).await;
+    warp_seconds(&mut context, EXPIRY).await;
+
+    let mut ix = refund_ix(&creator.pubkey(), 0, &Asset::Sol);
+    ix.data = red_packet::instruction::Refund { red_packet_id: 1 }.data();
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::InvalidRedPacketId))
+    );
+
+    let stranger = new_claimer(&mut context).await;
+    let mut ix = refund_ix(&creator.pubkey(), 0, &Asset::Sol);
+    ix.accounts[0] = AccountMeta::new(stranger.pubkey(), true);
+    ix.accounts[3] = AccountMeta::new(stranger.pubkey(), false);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&stranger]).await),
+        Some(error_code(RedPacketError::Unauthorized))
+    );
+}
+
+#[tokio::test]
+async fn test_refund_drained_red_packet_has_nothing_to_return() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = create(&mut context, &Asset::Sol, TOTAL_AMOUNT, 2).await;
+    for _ in 0..2 {
+        claim(&mut context, &creator, &Asset::Sol).await;
+    }
+    warp_seconds(&mut context, EXPIRY).await;
+    let ix = refund_ix(&creator.pubkey(), 0, &Asset::Sol);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::NoFundsToRefund))
+    );
+}
+
+#[tokio::test]
+async fn test_close_drained_red_packet_returns_leftover() {
+    let mut context = common::program_test().start_with_context().await;
+    // 平分后剩下 1 lamport 的零头
+    let creator = create(&mut context, &Asset::Sol, TOTAL_AMOUNT + 1, 2).await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+    claim(&mut context, &creator, &Asset::Sol).await;
+
+    let ix = close_ix(&creator.pubkey(), 0, &Asset::Sol);
+    assert_eq!(
+        custom_error(send(&mut context, std::slice::from_ref(&ix), &[&creator]).await),
+        Some(error_code(RedPacketError::RedPacketNotDrained))
+    );
+
+    claim(&mut context, &creator, &Asset::Sol).await;
+    let before = lamports(&mut context, &creator.pubkey()).await;
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    let rent = Rent::default().minimum_balance(RED_PACKET_SPACE);
+    assert_eq!(
+        lamports(&mut context, &creator.pubkey()).await,
+        before + 1 + rent
+    );
+    assert!(!account_exists(&mut context, &red_packet).await);
+}
+
+#[tokio::test]
+async fn test_close_drained_token_red_packet() {
+    let mut context = common::program_test().start_with_context().await;
+    let asset = create_mint(&mut context, &spl_token_2022::id(), None).await;
+    let creator = create(&mut context, &asset, TOTAL_AMOUNT + 1, 2).await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+    for _ in 0..2 {
+        claim(&mut context, &creator, &asset).await;
+    }
+
+    let ix = close_ix(&creator.pubkey(), 0, &asset);
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    assert_eq!(
+        token_balance(&mut context, &asset.account(&creator.pubkey())).await,
+        Some(1)
+    );
+    assert_eq!(
+        token_balance(&mut context, &asset.account(&red_packet)).await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_close_user_states_refunds_claimers() {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = create(&mut context, &Asset::Sol, TOTAL_AMOUNT, 2).await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+    let first = claim(&mut context, &creator, &Asset::Sol).await;
+    let pair = |claimer: &Keypair| {
+        (
+            user_state_address(&red_packet, &claimer.pubkey()),
+            claimer.pubkey(),
+        )
+    };
+
+    let ix = close_user_states_ix(&creator.pubkey(), &[pair(&first)]);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[]).await),
+        Some(error_code(RedPacketError::RedPacketStillActive))
+    );
+
+    // 领完但账户还在时同样不能删除：红包还可能被追加或延期
+    let second = claim(&mut context, &creator, &Asset::Sol).await;
+    let ix = close_user_states_ix(&creator.pubkey(), &[pair(&first), pair(&second)]);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[]).await),
+        Some(error_code(RedPacketError::RedPacketStillActive))
+    );
+
+    let ix = close_ix(&creator.pubkey(), 0, &Asset::Sol);
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    // user_state 和领取者必须对应
+    let ix = close_user_states_ix(&creator.pubkey(), &[(pair(&first).0, second.pubkey())]);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[]).await),
+        Some(error_code(RedPacketError::InvalidUserState))
+    );
+
+    let before = lamports(&mut context, &first.pubkey()).await;
+    let ix = close_user_states_ix(&creator.pubkey(), &[pair(&first), pair(&second)]);
+    send(&mut context, &[ix], &[]).await.unwrap();
+    assert_eq!(
+        lamports(&mut context, &first.pubkey()).await,
+        before + Rent::default().minimum_balance(USER_STATE_SPACE)
+    );
+    assert!(!account_exists(&mut context, &pair(&second).0).await);
+}
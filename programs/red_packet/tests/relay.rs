@@ -0,0 +1,279 @@
+//! 代领：中继方付手续费和租金，收款人只需在前一条 ed25519 指令里签名授权
+
+mod common;
+
+use anchor_lang::{
+    solana_program::sysvar::{instructions, slot_hashes},
+    InstructionData, ToAccountMetas,
+};
+use common::{
+    create_args, create_ix, create_mint, custom_error, error_code, fund, lamports, mint_to,
+    protocol_config_address, red_packet_address, send, setup_creator, token_balance,
+    user_state_address, Asset,
+};
+use red_packet::{
+    config::{CLAIM_FEE, FEE_RECEIVER},
+    errors::RedPacketError,
+    utils::relay_claim_message,
+};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    ed25519_instruction::new_ed25519_instruction_with_signature,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+};
+use solana_system_interface::program as system_program;
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+
+fn relay_claim_ix(fee_payer: &Pubkey, creator: &Pubkey, recipient: &Pubkey) -> Instruction {
+    relay_claim_asset_ix(fee_payer, creator, recipient, &Asset::Sol)
+}
+
+fn relay_claim_asset_ix(
+    fee_payer: &Pubkey,
+    creator: &Pubkey,
+    recipient: &Pubkey,
+    asset: &Asset,
+) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::RelayClaimRedPacket {
+            fee_payer: *fee_payer,
+            recipient: *recipient,
+            red_packet,
+            user_state: user_state_address(&red_packet, recipient),
+            mint: asset.mint(),
+            pool_ata: asset.account(&red_packet),
+            recipient_ata: asset.account(recipient),
+            protocol_config: protocol_config_address(),
+            fee_receiver: FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: asset.token_program(),
+            associated_token_program: spl_associated_token_account::id(),
+            slot_hashes: slot_hashes::ID,
+            instructions: instructions::ID,
+            gate_token_account: None,
+            gate_metadata: None,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::RelayClaimRedpacket {
+            amount: None,
+            proof: None,
+            red_packet_id: 0,
+        }
+        .data(),
+    }
+}
+
+/// `signer` 对收款人 `recipient` 的代领消息签名
+fn signature_ix(signer: &Keypair, creator: &Pubkey, recipient: &Pubkey) -> Instruction {
+    let message = relay_claim_message(&red_packet_address(creator, 0), 0, recipient);
+    new_ed25519_instruction_with_signature(
+        &message,
+        &signer.sign_message(&message).into(),
+        &signer.pubkey().to_bytes(),
+    )
+}
+
+async fn setup() -> (ProgramTestContext, Keypair) {
+    setup_with(&Asset::Sol, TOTAL_AMOUNT).await
+}
+
+/// 创建一个 4 份的平分红包，代币红包会新建 mint，返回 context、创建者和实际使用的资产
+async fn setup_with(asset: &Asset, total_amount: u64) -> (ProgramTestContext, Keypair) {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = setup_creator(&mut context).await;
+    if !asset.is_sol() {
+        mint_to(&mut context, asset, &creator.pubkey(), total_amount).await;
+    }
+    let ix = create_ix(&creator.pubkey(), 0, asset, create_args(total_amount, 4, 0));
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    (context, creator)
+}
+
+#[tokio::test]
+async fn test_relay_claim_pays_recipient() {
+    let (mut context, creator) = setup().await;
+    // 收款人是没有任何 SOL 的新钱包
+    let recipient = Keypair::new();
+    let relayer = context.payer.pubkey();
+    let fee_receiver_before = lamports(&mut context, &FEE_RECEIVER).await;
+
+    let instructions = [
+        signature_ix(&recipient, &creator.pubkey(), &recipient.pubkey()),
+        relay_claim_ix(&relayer, &creator.pubkey(), &recipient.pubkey()),
+    ];
+    send(&mut context, &instructions, &[]).await.unwrap();
+    assert_eq!(
+        lamports(&mut context, &recipient.pubkey()).await,
+        TOTAL_AMOUNT / 4
+    );
+    assert_eq!(
+        lamports(&mut context, &FEE_RECEIVER).await,
+        fee_receiver_before + CLAIM_FEE
+    );
+}
+
+#[tokio::test]
+async fn test_relay_claim_requires_recipient_signature() {
+    let (mut context, creator) = setup().await;
+    let recipient = Keypair::new();
+    let relayer = context.payer.pubkey();
+    let claim = relay_claim_ix(&relayer, &creator.pubkey(), &recipient.pubkey());
+
+    assert_eq!(
+        custom_error(send(&mut context, std::slice::from_ref(&claim), &[]).await),
+        Some(error_code(RedPacketError::MissingEd25519Instruction))
+    );
+
+    // 签名本身有效，但签名者不是收款人
+    let impostor = Keypair::new();
+    let instructions = [
+        signature_ix(&impostor, &creator.pubkey(), &recipient.pubkey()),
+        claim,
+    ];
+    assert_eq!(
+        custom_error(send(&mut context, &instructions, &[]).await),
+        Some(error_code(RedPacketError::InvalidEd25519Instruction))
+    );
+}
+
+#[tokio::test]
+async fn test_relay_claim_rejects_foreign_recipient_account() {
+    let (mut context, creator) = setup().await;
+    let recipient = Keypair::new();
+    let relayer = context.payer.pubkey();
+    let mut claim = relay_claim_ix(&relayer, &creator.pubkey(), &recipient.pubkey());
+    // 中继方试图把 SOL 转到自己账户
+    claim.accounts[6] = AccountMeta::new(relayer, false);
+
+    let instructions = [
+        signature_ix(&recipient, &creator.pubkey(), &recipient.pubkey()),
+        claim,
+    ];
+    assert_eq!(
+        custom_error(send(&mut context, &instructions, &[]).await),
+        Some(error_code(RedPacketError::InvalidRecipientAccount))
+    );
+}
+
+#[tokio::test]
+async fn test_relay_claim_rejects_replayed_signature() {
+    let (mut context, creator) = setup().await;
+    let recipient = Keypair::new();
+    let relayer = context.payer.pubkey();
+    let instructions = [
+        signature_ix(&recipient, &creator.pubkey(), &recipient.pubkey()),
+        relay_claim_ix(&relayer, &creator.pubkey(), &recipient.pubkey()),
+    ];
+    send(&mut context, &instructions, &[]).await.unwrap();
+
+    // 同一个签名再提交一次（新的 blockhash），user_state 已经记下领取
+    assert_eq!(
+        custom_error(send(&mut context, &instructions, &[]).await),
+        Some(error_code(RedPacketError::AlreadyClaimed))
+    );
+    assert_eq!(
+        lamports(&mut context, &recipient.pubkey()).await,
+        TOTAL_AMOUNT / 4
+    );
+}
+
+#[tokio::test]
+async fn test_relay_claim_rejects_mismatched_recipient_ata() {
+    let mut context = common::program_test().start_with_context().await;
+    let asset = create_mint(&mut context, &spl_token_2022::id(), None).await;
+    let creator = setup_creator(&mut context).await;
+    mint_to(&mut context, &asset, &creator.pubkey(), TOTAL_AMOUNT).await;
+    let ix = create_ix(
+        &creator.pubkey(),
+        0,
+        &asset,
+        create_args(TOTAL_AMOUNT, 4, 0),
+    );
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+
+    let recipient = Keypair::new();
+    let relayer = context.payer.pubkey();
+    let signature = signature_ix(&recipient, &creator.pubkey(), &recipient.pubkey());
+    let mut claim = relay_claim_asset_ix(&relayer, &creator.pubkey(), &recipient.pubkey(), &asset);
+    // 中继方把代币导向自己的 ATA
+    claim.accounts[6] = AccountMeta::new(asset.account(&relayer), false);
+    assert_eq!(
+        custom_error(send(&mut context, &[signature.clone(), claim], &[]).await),
+        Some(error_code(RedPacketError::InvalidRecipientAccount))
+    );
+
+    // 收款人的 ATA 由中继方付租金创建
+    let claim = relay_claim_asset_ix(&relayer, &creator.pubkey(), &recipient.pubkey(), &asset);
+    send(&mut context, &[signature, claim], &[]).await.unwrap();
+    assert_eq!(
+        token_balance(&mut context, &asset.account(&recipient.pubkey())).await,
+        Some(TOTAL_AMOUNT / 4)
+    );
+}
+
+#[tokio::test]
+async fn test_relay_claim_rejects_mismatched_mint() {
+    let mut context = common::program_test().start_with_context().await;
+    let asset = create_mint(&mut context, &anchor_spl::token::ID, None).await;
+    let creator = setup_creator(&mut context).await;
+    mint_to(&mut context, &asset, &creator.pubkey(), TOTAL_AMOUNT).await;
+    let ix = create_ix(
+        &creator.pubkey(),
+        0,
+        &asset,
+        create_args(TOTAL_AMOUNT, 4, 0),
+    );
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+
+    // 中继方换成自建的 mint，收款账户是收款人在这个 mint 下的 ATA
+    let junk = create_mint(&mut context, &anchor_spl::token::ID, None).await;
+    let recipient = Keypair::new();
+    let relayer = context.payer.pubkey();
+    let instructions = [
+        signature_ix(&recipient, &creator.pubkey(), &recipient.pubkey()),
+        relay_claim_asset_ix(&relayer, &creator.pubkey(), &recipient.pubkey(), &junk),
+    ];
+    assert_eq!(
+        custom_error(send(&mut context, &instructions, &[]).await),
+        Some(error_code(RedPacketError::InvalidRecipientAccount))
+    );
+    assert_eq!(
+        token_balance(
+            &mut context,
+            &asset.account(&red_packet_address(&creator.pubkey(), 0))
+        )
+        .await,
+        Some(TOTAL_AMOUNT)
+    );
+}
+
+#[tokio::test]
+async fn test_relay_claim_rejects_share_below_rent_exemption() {
+    let total_amount = 4 * (Rent::default().minimum_balance(0) - 1);
+    let (mut context, creator) = setup_with(&Asset::Sol, total_amount).await;
+    let recipient = Keypair::new();
+    let relayer = context.payer.pubkey();
+    let instructions = [
+        signature_ix(&recipient, &creator.pubkey(), &recipient.pubkey()),
+        relay_claim_ix(&relayer, &creator.pubkey(), &recipient.pubkey()),
+    ];
+    assert_eq!(
+        custom_error(send(&mut context, &instructions, &[]).await),
+        Some(error_code(RedPacketError::ShareBelowRentExemption))
+    );
+
+    // 收款人已经有余额时可以领取
+    fund(
+        &mut context,
+        &recipient.pubkey(),
+        Rent::default().minimum_balance(0),
+    )
+    .await;
+    send(&mut context, &instructions, &[]).await.unwrap();
+}
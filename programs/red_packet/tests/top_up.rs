@@ -0,0 +1,282 @@
+//! 追加红包：SOL 平分红包领取一部分后追加金额和份数，份额按剩余金额重新均分；领完后不能再追加
+
+mod common;
+
+use anchor_lang::{solana_program::sysvar, AccountDeserialize, InstructionData, ToAccountMetas};
+use common::{
+    account_exists, close_ix, close_user_states_ix, custom_error, error_code, fund,
+    initialize_creator_state_ix, initialize_protocol_config_ix, lamports, protocol_config_address,
+    red_packet_address, send, set_upgrade_authority, user_state_address, Asset,
+};
+use red_packet::{errors::RedPacketError, states::RedPacket};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use solana_system_interface::program as system_program;
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+
+async fn setup() -> (ProgramTestContext, Keypair) {
+    let mut context = common::program_test().start_with_context().await;
+    let creator = Keypair::new();
+    fund(&mut context, &creator.pubkey(), 10_000_000_000).await;
+    let admin = context.payer.pubkey();
+    set_upgrade_authority(&mut context, &admin);
+    let create = create_ix(&creator.pubkey());
+    send(
+        &mut context,
+        &[
+            initialize_protocol_config_ix(&admin),
+            initialize_creator_state_ix(&creator.pubkey()),
+            create,
+        ],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    (context, creator)
+}
+
+fn create_ix(creator: &Pubkey) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::CreateRedPacket {
+            creator: *creator,
+            creator_state: common::creator_state_address(creator),
+            red_packet,
+            mint: Pubkey::default(),
+            creator_ata: *creator,
+            pool_ata: red_packet,
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: spl_token_2022::id(),
+            associated_token_program: spl_associated_token_account::id(),
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::CreateRedpacket {
+            total_amount: TOTAL_AMOUNT,
+            packet_count: 2,
+            red_packet_type: 0,
+            merkle_root: None,
+            is_sol: true,
+            expiry_days: Some(1),
+            random_seed: None,
+            start_time: None,
+            code_hash: None,
+            holder_gate: None,
+            prize_tiers: None,
+        }
+        .data(),
+    }
+}
+
+fn claim_ix(red_packet: &Pubkey, claimer: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::ClaimRedPacket {
+            claimer: *claimer,
+            red_packet: *red_packet,
+            user_state: user_state_address(red_packet, claimer),
+            mint: Pubkey::default(),
+            pool_ata: *red_packet,
+            user_ata: *claimer,
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: spl_token_2022::id(),
+            associated_token_program: spl_associated_token_account::id(),
+            slot_hashes: sysvar::slot_hashes::ID,
+            claim_commit: None,
+            gate_token_account: None,
+            gate_metadata: None,
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::ClaimRedpacket {
+            amount: None,
+            proof: None,
+            red_packet_id: 0,
+            code: None,
+        }
+        .data(),
+    }
+}
+
+fn top_up_ix(creator: &Pubkey, authority: &Pubkey, amount: u64, extra_packets: u32) -> Instruction {
+    let red_packet = red_packet_address(creator, 0);
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: red_packet::accounts::TopUpRedPacket {
+            authority: *authority,
+            red_packet,
+            creator_state: common::creator_state_address(creator),
+            mint: Pubkey::default(),
+            authority_ata: *authority,
+            pool_ata: red_packet,
+            protocol_config: protocol_config_address(),
+            fee_receiver: red_packet::config::FEE_RECEIVER,
+            system_program: system_program::ID,
+            token_program: spl_token_2022::id(),
+        }
+        .to_account_metas(None),
+        data: red_packet::instruction::TopUpRedpacket {
+            red_packet_id: 0,
+            amount,
+            extra_packets,
+        }
+        .data(),
+    }
+}
+
+async fn red_packet_state(context: &mut ProgramTestContext, address: &Pubkey) -> RedPacket {
+    let account = context
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .unwrap();
+    RedPacket::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn test_top_up_sol_red_packet() {
+    let (mut context, creator) = setup().await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+
+    let first = Keypair::new();
+    fund(&mut context, &first.pubkey(), 100_000_000).await;
+    send(
+        &mut context,
+        &[claim_ix(&red_packet, &first.pubkey())],
+        &[&first],
+    )
+    .await
+    .unwrap();
+
+    let pool_before = lamports(&mut context, &red_packet).await;
+    send(
+        &mut context,
+        &[top_up_ix(
+            &creator.pubkey(),
+            &creator.pubkey(),
+            TOTAL_AMOUNT,
+            2,
+        )],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        lamports(&mut context, &red_packet).await,
+        pool_before + TOTAL_AMOUNT
+    );
+
+    let state = red_packet_state(&mut context, &red_packet).await;
+    assert_eq!(state.total_amount, 2 * TOTAL_AMOUNT);
+    assert_eq!(state.remaining_amount, TOTAL_AMOUNT + TOTAL_AMOUNT / 2);
+    assert_eq!(state.packet_count, 4);
+    assert_eq!(state.claimed_count, 1);
+    assert_eq!(state.share_amount, TOTAL_AMOUNT / 2);
+
+    // 追加后的份数可以继续领完
+    for _ in 0..3 {
+        let claimer = Keypair::new();
+        fund(&mut context, &claimer.pubkey(), 100_000_000).await;
+        let before = lamports(&mut context, &claimer.pubkey()).await;
+        send(
+            &mut context,
+            &[claim_ix(&red_packet, &claimer.pubkey())],
+            &[&claimer],
+        )
+        .await
+        .unwrap();
+        assert!(lamports(&mut context, &claimer.pubkey()).await > before);
+    }
+    let state = red_packet_state(&mut context, &red_packet).await;
+    assert_eq!(state.claimed_count, 4);
+    assert_eq!(state.remaining_amount, 0);
+}
+
+#[tokio::test]
+async fn test_only_creator_can_top_up() {
+    let (mut context, creator) = setup().await;
+    let other = Keypair::new();
+    fund(&mut context, &other.pubkey(), 10_000_000_000).await;
+
+    let ix = top_up_ix(&creator.pubkey(), &other.pubkey(), TOTAL_AMOUNT, 1);
+    let result = send(&mut context, &[ix], &[&other]).await;
+    assert_eq!(
+        custom_error(result),
+        Some(error_code(RedPacketError::Unauthorized))
+    );
+
+    let state = red_packet_state(&mut context, &red_packet_address(&creator.pubkey(), 0)).await;
+    assert_eq!(state.total_amount, TOTAL_AMOUNT);
+    assert_eq!(state.packet_count, 2);
+}
+
+#[tokio::test]
+async fn test_fully_claimed_red_packet_cannot_be_topped_up() {
+    let (mut context, creator) = setup().await;
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+    let mut claimers = Vec::new();
+    for _ in 0..2 {
+        let claimer = Keypair::new();
+        fund(&mut context, &claimer.pubkey(), 100_000_000).await;
+        send(
+            &mut context,
+            &[claim_ix(&red_packet, &claimer.pubkey())],
+            &[&claimer],
+        )
+        .await
+        .unwrap();
+        claimers.push(claimer);
+    }
+
+    // 领完但还没关闭的红包不能清理 UserState
+    let pairs: Vec<_> = claimers
+        .iter()
+        .map(|claimer| {
+            (
+                user_state_address(&red_packet, &claimer.pubkey()),
+                claimer.pubkey(),
+            )
+        })
+        .collect();
+    let ix = close_user_states_ix(&creator.pubkey(), &pairs);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[]).await),
+        Some(error_code(RedPacketError::RedPacketStillActive))
+    );
+
+    let ix = top_up_ix(&creator.pubkey(), &creator.pubkey(), TOTAL_AMOUNT, 2);
+    assert_eq!(
+        custom_error(send(&mut context, &[ix], &[&creator]).await),
+        Some(error_code(RedPacketError::RedPacketFullyClaimed))
+    );
+    let state = red_packet_state(&mut context, &red_packet).await;
+    assert_eq!(state.total_amount, TOTAL_AMOUNT);
+    assert_eq!(state.packet_count, 2);
+
+    // 关闭之后才能清理 UserState，此时红包账户已经不存在，追加和重复领取都会失败
+    let ix = close_ix(&creator.pubkey(), 0, &Asset::Sol);
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    let ix = close_user_states_ix(&creator.pubkey(), &pairs);
+    send(&mut context, &[ix], &[]).await.unwrap();
+    let ix = top_up_ix(&creator.pubkey(), &creator.pubkey(), TOTAL_AMOUNT, 2);
+    assert!(send(&mut context, &[ix], &[&creator]).await.is_err());
+    let claimer = &claimers[0];
+    let result = send(
+        &mut context,
+        &[claim_ix(&red_packet, &claimer.pubkey())],
+        &[claimer],
+    )
+    .await;
+    assert!(result.is_err());
+    assert!(!account_exists(&mut context, &red_packet).await);
+}
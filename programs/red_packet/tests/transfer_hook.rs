@@ -0,0 +1,344 @@
+//! Token-2022 transfer hook 的端到端测试：在 program-test 里以原生方式加载红包程序
+//! 和一个最小的 hook 程序，验证 create / claim / refund 都能把 hook 需要的额外账户带上。
+
+mod common;
+
+use anchor_lang::{
+    prelude::{AccountInfo, ProgramError},
+    solana_program::{entrypoint::ProgramResult, sysvar},
+    InstructionData, ToAccountMetas,
+};
+use common::{
+    fund, initialize_creator_state_ix, initialize_protocol_config_ix, protocol_config_address,
+    red_packet_address, send, set_upgrade_authority, user_state_address,
+};
+use solana_program_test::{processor, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+};
+use solana_system_interface::{instruction as system_instruction, program as system_program};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, state::ExtraAccountMetaList};
+use spl_token_2022::extension::{ExtensionType, StateWithExtensions};
+use spl_transfer_hook_interface::{
+    get_extra_account_metas_address,
+    instruction::{ExecuteInstruction, TransferHookInstruction},
+};
+
+const HOOK_PROGRAM_ID: Pubkey = Pubkey::new_from_array([7; 32]);
+/// hook 在 ExtraAccountMetaList 里声明的唯一额外账户，模拟白名单之类的配置账户
+const ALLOWLIST: Pubkey = Pubkey::new_from_array([9; 32]);
+const DECIMALS: u8 = 6;
+
+/// 最小的 transfer hook：只检查 Token-2022 是否按 ExtraAccountMetaList 传入了额外账户
+fn process_hook(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    match TransferHookInstruction::unpack(data)? {
+        TransferHookInstruction::Execute { .. } => {
+            let mint = accounts.get(1).ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let extra_metas = accounts.get(4).ok_or(ProgramError::NotEnoughAccountKeys)?;
+            if extra_metas.key != &get_extra_account_metas_address(mint.key, program_id) {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            match accounts.get(5) {
+                Some(allowlist) if allowlist.key == &ALLOWLIST => Ok(()),
+                _ => Err(ProgramError::NotEnoughAccountKeys),
+            }
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+struct HookedMint {
+    context: ProgramTestContext,
+    mint: Pubkey,
+    creator: Keypair,
+    creator_ata: Pubkey,
+}
+
+async fn setup() -> HookedMint {
+    let mut program_test = common::program_test();
+    program_test.add_program("transfer_hook", HOOK_PROGRAM_ID, processor!(process_hook));
+
+    let mint = Keypair::new();
+    let extra_metas = [ExtraAccountMeta::new_with_pubkey(&ALLOWLIST, false, false).unwrap()];
+    let mut extra_metas_data = vec![0; ExtraAccountMetaList::size_of(extra_metas.len()).unwrap()];
+    ExtraAccountMetaList::init::<ExecuteInstruction>(&mut extra_metas_data, &extra_metas).unwrap();
+    program_test.add_account(
+        get_extra_account_metas_address(&mint.pubkey(), &HOOK_PROGRAM_ID),
+        Account {
+            lamports: Rent::default().minimum_balance(extra_metas_data.len()),
+            data: extra_metas_data,
+            owner: HOOK_PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+    program_test.add_account(
+        ALLOWLIST,
+        Account {
+            lamports: 1_000_000,
+            ..Account::default()
+        },
+    );
+
+    let context = program_test.start_with_context().await;
+    let creator = Keypair::new();
+    let mut hooked = HookedMint {
+        creator_ata: get_associated_token_address_with_program_id(
+            &creator.pubkey(),
+            &mint.pubkey(),
+            &spl_token_2022::id(),
+        ),
+        context,
+        mint: mint.pubkey(),
+        creator,
+    };
+    fund(
+        &mut hooked.context,
+        &hooked.creator.pubkey(),
+        10_000_000_000,
+    )
+    .await;
+
+    let payer = hooked.context.payer.pubkey();
+    set_upgrade_authority(&mut hooked.context, &payer);
+    let mint_len = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[
+        ExtensionType::TransferHook,
+    ])
+    .unwrap();
+    let instructions = vec![
+        system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            Rent::default().minimum_balance(mint_len),
+            mint_len as u64,
+            &spl_token_2022::id(),
+        ),
+        spl_token_2022::extension::transfer_hook::instruction::initialize(
+            &spl_token_2022::id(),
+            &mint.pubkey(),
+            None,
+            Some(HOOK_PROGRAM_ID),
+        )
+        .unwrap(),
+        spl_token_2022::instruction::initialize_mint2(
+            &spl_token_2022::id(),
+            &mint.pubkey(),
+            &payer,
+            None,
+            DECIMALS,
+        )
+        .unwrap(),
+        spl_associated_token_account::instruction::create_associated_token_account(
+            &payer,
+            &hooked.creator.pubkey(),
+            &mint.pubkey(),
+            &spl_token_2022::id(),
+        ),
+        spl_token_2022::instruction::mint_to(
+            &spl_token_2022::id(),
+            &mint.pubkey(),
+            &hooked.creator_ata,
+            &payer,
+            &[],
+            1_000_000,
+        )
+        .unwrap(),
+    ];
+    send(&mut hooked.context, &instructions, &[&mint])
+        .await
+        .unwrap();
+
+    let instructions = [
+        initialize_protocol_config_ix(&payer),
+        initialize_creator_state_ix(&hooked.creator.pubkey()),
+    ];
+    let creator = hooked.creator.insecure_clone();
+    send(&mut hooked.context, &instructions, &[&creator])
+        .await
+        .unwrap();
+
+    hooked
+}
+
+fn hook_accounts(mint: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(HOOK_PROGRAM_ID, false),
+        AccountMeta::new_readonly(
+            get_extra_account_metas_address(mint, &HOOK_PROGRAM_ID),
+            false,
+        ),
+        AccountMeta::new_readonly(ALLOWLIST, false),
+    ]
+}
+
+fn create_ix(hooked: &HookedMint, with_hook_accounts: bool) -> Instruction {
+    let creator = hooked.creator.pubkey();
+    let red_packet = red_packet_address(&creator, 0);
+    let mut accounts = red_packet::accounts::CreateRedPacket {
+        creator,
+        creator_state: common::creator_state_address(&creator),
+        red_packet,
+        mint: hooked.mint,
+        creator_ata: hooked.creator_ata,
+        pool_ata: get_associated_token_address_with_program_id(
+            &red_packet,
+            &hooked.mint,
+            &spl_token_2022::id(),
+        ),
+        protocol_config: protocol_config_address(),
+        fee_receiver: red_packet::config::FEE_RECEIVER,
+        system_program: system_program::ID,
+        token_program: spl_token_2022::id(),
+        associated_token_program: spl_associated_token_account::id(),
+        rent: sysvar::rent::ID,
+    }
+    .to_account_metas(None);
+    if with_hook_accounts {
+        accounts.extend(hook_accounts(&hooked.mint));
+    }
+    Instruction {
+        program_id: red_packet::ID,
+        accounts,
+        data: red_packet::instruction::CreateRedpacket {
+            total_amount: 1_000,
+            packet_count: 2,
+            red_packet_type: 0,
+            merkle_root: None,
+            is_sol: false,
+            expiry_days: Some(1),
+            random_seed: None,
+            start_time: None,
+            code_hash: None,
+            holder_gate: None,
+            prize_tiers: None,
+        }
+        .data(),
+    }
+}
+
+fn token_balance(account: &Account) -> u64 {
+    StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+        .unwrap()
+        .base
+        .amount
+}
+
+#[tokio::test]
+async fn test_create_requires_hook_accounts() {
+    let mut hooked = setup().await;
+    let creator = hooked.creator.insecure_clone();
+    let ix = create_ix(&hooked, false);
+    assert!(send(&mut hooked.context, &[ix], &[&creator]).await.is_err());
+}
+
+#[tokio::test]
+async fn test_create_claim_refund_with_transfer_hook() {
+    let mut hooked = setup().await;
+    let creator = hooked.creator.insecure_clone();
+    let red_packet = red_packet_address(&creator.pubkey(), 0);
+    let pool_ata = get_associated_token_address_with_program_id(
+        &red_packet,
+        &hooked.mint,
+        &spl_token_2022::id(),
+    );
+
+    let ix = create_ix(&hooked, true);
+    send(&mut hooked.context, &[ix], &[&creator]).await.unwrap();
+    let pool = hooked
+        .context
+        .banks_client
+        .get_account(pool_ata)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(token_balance(&pool), 1_000);
+
+    let claimer = Keypair::new();
+    fund(&mut hooked.context, &claimer.pubkey(), 1_000_000_000).await;
+    let user_ata = get_associated_token_address_with_program_id(
+        &claimer.pubkey(),
+        &hooked.mint,
+        &spl_token_2022::id(),
+    );
+    let mut accounts = red_packet::accounts::ClaimRedPacket {
+        claimer: claimer.pubkey(),
+        red_packet,
+        user_state: user_state_address(&red_packet, &claimer.pubkey()),
+        mint: hooked.mint,
+        pool_ata,
+        user_ata,
+        protocol_config: protocol_config_address(),
+        fee_receiver: red_packet::config::FEE_RECEIVER,
+        system_program: system_program::ID,
+        token_program: spl_token_2022::id(),
+        associated_token_program: spl_associated_token_account::id(),
+        slot_hashes: sysvar::slot_hashes::ID,
+        claim_commit: None,
+        gate_token_account: None,
+        gate_metadata: None,
+    }
+    .to_account_metas(None);
+    accounts.extend(hook_accounts(&hooked.mint));
+    let ix = Instruction {
+        program_id: red_packet::ID,
+        accounts,
+        data: red_packet::instruction::ClaimRedpacket {
+            amount: None,
+            proof: None,
+            red_packet_id: 0,
+            code: None,
+        }
+        .data(),
+    };
+    send(&mut hooked.context, &[ix], &[&claimer]).await.unwrap();
+    let user = hooked
+        .context
+        .banks_client
+        .get_account(user_ata)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(token_balance(&user), 500);
+
+    // 把时钟拨到过期之后再退款
+    common::warp_seconds(&mut hooked.context, 2 * 24 * 60 * 60).await;
+
+    let mut accounts = red_packet::accounts::Refund {
+        creator: creator.pubkey(),
+        red_packet,
+        mint: hooked.mint,
+        creator_ata: hooked.creator_ata,
+        pool_ata,
+        token_program: spl_token_2022::id(),
+        system_program: system_program::ID,
+        rent: sysvar::rent::ID,
+    }
+    .to_account_metas(None);
+    accounts.extend(hook_accounts(&hooked.mint));
+    let ix = Instruction {
+        program_id: red_packet::ID,
+        accounts,
+        data: red_packet::instruction::Refund { red_packet_id: 0 }.data(),
+    };
+    send(&mut hooked.context, &[ix], &[&creator]).await.unwrap();
+
+    let creator_ata = hooked
+        .context
+        .banks_client
+        .get_account(hooked.creator_ata)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(token_balance(&creator_ata), 1_000_000 - 500);
+    assert!(hooked
+        .context
+        .banks_client
+        .get_account(pool_ata)
+        .await
+        .unwrap()
+        .is_none());
+}
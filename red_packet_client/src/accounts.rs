@@ -0,0 +1,202 @@
+//! 账户解码。迁移之前的旧布局与新布局共用判别符，按账户大小区分后统一转换成当前布局，
+//! 调用方不需要关心账户是否已经迁移。
+
+use anchor_lang::{AccountDeserialize, AnchorDeserialize, Discriminator};
+use red_packet::{
+    config::{LEGACY_CREATOR_STATE_SPACE, LEGACY_RED_PACKET_SPACE},
+    states::{
+        BundlePacket, ClaimCommit, CreatorState, CreatorStateV0, ProtocolConfig, RedPacket,
+        RedPacketV0, UserState,
+    },
+};
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("account data is not a {0} account")]
+    WrongAccountType(&'static str),
+    #[error("failed to decode {account}: {reason}")]
+    InvalidData {
+        account: &'static str,
+        reason: String,
+    },
+}
+
+fn decode<T: AccountDeserialize>(name: &'static str, data: &[u8]) -> Result<T, DecodeError> {
+    T::try_deserialize(&mut &data[..]).map_err(|err| invalid_data(name, err))
+}
+
+/// 判别符匹配、大小等于旧布局时按 `V` 解析
+fn decode_legacy<V: AnchorDeserialize>(
+    name: &'static str,
+    discriminator: &[u8],
+    data: &[u8],
+) -> Result<V, DecodeError> {
+    V::deserialize(&mut &data[discriminator.len()..]).map_err(|err| invalid_data(name, err))
+}
+
+fn invalid_data(account: &'static str, err: impl std::fmt::Display) -> DecodeError {
+    DecodeError::InvalidData {
+        account,
+        reason: err.to_string(),
+    }
+}
+
+fn check_discriminator(
+    name: &'static str,
+    discriminator: &[u8],
+    data: &[u8],
+) -> Result<(), DecodeError> {
+    if data.starts_with(discriminator) {
+        Ok(())
+    } else {
+        Err(DecodeError::WrongAccountType(name))
+    }
+}
+
+pub fn decode_red_packet(data: &[u8]) -> Result<RedPacket, DecodeError> {
+    check_discriminator("RedPacket", RedPacket::DISCRIMINATOR, data)?;
+    if data.len() == LEGACY_RED_PACKET_SPACE {
+        let legacy: RedPacketV0 = decode_legacy("RedPacket", RedPacket::DISCRIMINATOR, data)?;
+        return Ok(RedPacket::from_v0(legacy));
+    }
+    decode("RedPacket", data)
+}
+
+pub fn decode_user_state(data: &[u8]) -> Result<UserState, DecodeError> {
+    check_discriminator("UserState", UserState::DISCRIMINATOR, data)?;
+    decode("UserState", data)
+}
+
+pub fn decode_creator_state(data: &[u8]) -> Result<CreatorState, DecodeError> {
+    check_discriminator("CreatorState", CreatorState::DISCRIMINATOR, data)?;
+    if data.len() == LEGACY_CREATOR_STATE_SPACE {
+        let legacy: CreatorStateV0 =
+            decode_legacy("CreatorState", CreatorState::DISCRIMINATOR, data)?;
+        return Ok(CreatorState::from_v0(legacy));
+    }
+    decode("CreatorState", data)
+}
+
+/// 手续费收款地址以这里为准，构建创建、领取和追加指令时需要传入
+pub fn decode_protocol_config(data: &[u8]) -> Result<ProtocolConfig, DecodeError> {
+    check_discriminator("ProtocolConfig", ProtocolConfig::DISCRIMINATOR, data)?;
+    decode("ProtocolConfig", data)
+}
+
+pub fn decode_bundle(data: &[u8]) -> Result<BundlePacket, DecodeError> {
+    check_discriminator("BundlePacket", BundlePacket::DISCRIMINATOR, data)?;
+    decode("BundlePacket", data)
+}
+
+pub fn decode_claim_commit(data: &[u8]) -> Result<ClaimCommit, DecodeError> {
+    check_discriminator("ClaimCommit", ClaimCommit::DISCRIMINATOR, data)?;
+    decode("ClaimCommit", data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{prelude::Pubkey, AccountSerialize, AnchorSerialize};
+    use red_packet::{
+        config::CREATOR_STATE_SPACE,
+        states::{HolderGate, PrizeTier},
+    };
+
+    fn serialize<T: AccountSerialize>(account: &T, space: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data.resize(space, 0);
+        data
+    }
+
+    fn legacy_red_packet() -> RedPacketV0 {
+        RedPacketV0 {
+            creator: Pubkey::new_unique(),
+            mint: Pubkey::default(),
+            total_amount: 1_000,
+            remaining_amount: 600,
+            packet_count: 5,
+            claimed_count: 2,
+            red_packet_type: 0,
+            share_amount: 200,
+            random_seed: 0,
+            expiry_time: 1_700_000_000,
+            merkle_root: [0; 32],
+            is_sol: true,
+            expiry_time_changes: 1,
+            red_packet_id: 3,
+            bump: 254,
+        }
+    }
+
+    #[test]
+    fn test_decode_current_red_packet() {
+        let mut red_packet = RedPacket::from_v0(legacy_red_packet());
+        red_packet.start_time = 1_600_000_000;
+        red_packet.holder_gate = HolderGate {
+            kind: 1,
+            mint: Pubkey::new_unique(),
+            min_amount: 10,
+        };
+        red_packet.tiers[0] = PrizeTier {
+            amount: 100,
+            count: 2,
+        };
+        let data = serialize(&red_packet, red_packet::config::RED_PACKET_SPACE);
+        let decoded = decode_red_packet(&data).unwrap();
+        assert_eq!(decoded.start_time, red_packet.start_time);
+        assert_eq!(decoded.holder_gate, red_packet.holder_gate);
+        assert_eq!(decoded.tiers, red_packet.tiers);
+        assert_eq!(decoded.remaining_amount, 600);
+    }
+
+    #[test]
+    fn test_decode_legacy_red_packet() {
+        let legacy = legacy_red_packet();
+        let mut data = RedPacket::DISCRIMINATOR.to_vec();
+        legacy.serialize(&mut data).unwrap();
+        assert_eq!(data.len(), LEGACY_RED_PACKET_SPACE);
+
+        let decoded = decode_red_packet(&data).unwrap();
+        assert_eq!(decoded.version, RedPacket::CURRENT_VERSION);
+        assert_eq!(decoded.creator, legacy.creator);
+        assert_eq!(decoded.claimed_count, 2);
+        assert_eq!(decoded.start_time, 0);
+    }
+
+    #[test]
+    fn test_decode_legacy_creator_state() {
+        let mut data = CreatorState::DISCRIMINATOR.to_vec();
+        CreatorStateV0 {
+            next_red_packet_id: 9,
+            bump: 250,
+        }
+        .serialize(&mut data)
+        .unwrap();
+        let decoded = decode_creator_state(&data).unwrap();
+        assert_eq!(decoded.version, CreatorState::CURRENT_VERSION);
+        assert_eq!(decoded.next_red_packet_id, 9);
+        assert!(decoded.operators.is_empty());
+
+        let data = serialize(&decoded, CREATOR_STATE_SPACE);
+        assert_eq!(decode_creator_state(&data).unwrap().next_red_packet_id, 9);
+    }
+
+    #[test]
+    fn test_decode_rejects_other_account_types() {
+        let data = serialize(&UserState { is_claimed: 1 }, 9);
+        assert_eq!(decode_user_state(&data).unwrap().is_claimed, 1);
+        assert_eq!(
+            decode_red_packet(&data).err(),
+            Some(DecodeError::WrongAccountType("RedPacket"))
+        );
+        assert!(matches!(
+            decode_red_packet(&RedPacket::DISCRIMINATOR[..4]),
+            Err(DecodeError::WrongAccountType(_))
+        ));
+        assert!(matches!(
+            decode_red_packet(RedPacket::DISCRIMINATOR),
+            Err(DecodeError::InvalidData { .. })
+        ));
+    }
+}
@@ -0,0 +1,583 @@
+//! `red-packet` 命令行。RPC 地址和签名密钥默认读取 Solana CLI 配置，签名者同时是付款人、
+//! 创建者或领取者。金额一律以最小单位计（lamports 或代币最小单位）。
+//!
+//! 命令行只覆盖常用的平分、随机和 Merkle 红包；口令、门槛合集和位图红包请直接使用 SDK。
+//! 带转账钩子的 mint 需要额外账户，同样不在命令行支持范围内。
+
+pub mod rpc;
+
+use crate::{
+    accounts::{decode_creator_state, decode_protocol_config, decode_red_packet},
+    instructions::{self, ClaimParams, CreateParams},
+    pda, Asset, TokenMint,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use red_packet::states::RedPacket;
+use red_packet_merkle::{
+    io::{ProofFile, TreeFile},
+    LeafKind,
+};
+use rpc::Rpc;
+use serde::Deserialize;
+use solana_sdk::{
+    account::from_account,
+    clock::Clock,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
+    sysvar,
+    transaction::Transaction,
+};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Parser)]
+#[command(name = "red-packet", about = "Create, claim and inspect red packets")]
+pub struct Cli {
+    /// RPC 地址，或 localhost / devnet / testnet / mainnet-beta，默认取 Solana CLI 配置
+    #[arg(short = 'u', long, global = true)]
+    pub url: Option<String>,
+    /// 签名者密钥文件，默认取 Solana CLI 配置
+    #[arg(short, long, global = true)]
+    pub keypair: Option<PathBuf>,
+    /// Solana CLI 配置文件，默认 ~/.config/solana/cli/config.yml
+    #[arg(short = 'C', long, global = true)]
+    pub config: Option<PathBuf>,
+    /// 只模拟交易并打印消耗的计算单元，不发送
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// 初始化签名者的创建者状态，第一次创建红包前执行一次
+    InitCreator,
+    /// 创建红包，编号取创建者当前的 next_red_packet_id
+    Create {
+        #[arg(long, value_enum, default_value_t = Kind::Equal)]
+        kind: Kind,
+        /// 总金额，merkle 红包取名单文件中的总额
+        #[arg(long, required_unless_present = "tree")]
+        amount: Option<u64>,
+        /// 份数，merkle 红包取名单人数
+        #[arg(long, required_unless_present = "tree")]
+        count: Option<u32>,
+        /// 发放的代币 mint，不传则发 SOL
+        #[arg(long)]
+        mint: Option<Pubkey>,
+        /// 有效天数，默认 7 天
+        #[arg(long)]
+        expiry_days: Option<i64>,
+        /// red_packet_merkle build 生成的 tree.json，merkle 红包必填
+        #[arg(long)]
+        tree: Option<PathBuf>,
+        /// 随机红包的种子，默认取当前时间
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// 以签名者身份领取
+    Claim {
+        id: u64,
+        #[arg(long)]
+        creator: Pubkey,
+        /// red_packet_merkle build 生成的证明文件，merkle 红包必填
+        #[arg(long)]
+        proof_file: Option<PathBuf>,
+    },
+    /// 过期后退回签名者创建的红包中剩余的资产
+    Refund { id: u64 },
+    /// 把过期时间顺延若干天，创建者或有权限的操作员可用
+    Extend {
+        id: u64,
+        #[arg(long)]
+        days: i64,
+        /// 默认为签名者
+        #[arg(long)]
+        creator: Option<Pubkey>,
+    },
+    /// 显示一个红包的状态
+    Show {
+        id: u64,
+        /// 默认为签名者
+        #[arg(long)]
+        creator: Option<Pubkey>,
+    },
+    /// 列出创建者的所有红包
+    List {
+        /// 默认为签名者
+        #[arg(long)]
+        creator: Option<Pubkey>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Kind {
+    /// 类型 0
+    Equal,
+    /// 类型 3：金额混入领取者承诺所在 slot 的 hash，领取时先提交承诺，下一个 slot 再领取。
+    /// 类型 1 的金额在同一 slot 内可以被预测，命令行不再创建
+    Random,
+    /// 类型 2
+    Merkle,
+}
+
+/// Solana CLI 配置文件中用到的字段，缺省值与 Solana CLI 一致
+#[derive(Deserialize)]
+#[serde(default)]
+struct SolanaConfig {
+    json_rpc_url: String,
+    keypair_path: String,
+}
+
+impl Default for SolanaConfig {
+    fn default() -> Self {
+        Self {
+            json_rpc_url: "https://api.mainnet-beta.solana.com".to_string(),
+            keypair_path: home_path(".config/solana/id.json"),
+        }
+    }
+}
+
+fn home_path(relative: &str) -> String {
+    let home = std::env::var("HOME").unwrap_or_default();
+    Path::new(&home)
+        .join(relative)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// 命令行参数优先，其次是 Solana CLI 配置文件，都没有时使用 Solana CLI 的默认值
+pub fn load_config(cli: &Cli) -> Result<(String, Keypair)> {
+    let default_path = PathBuf::from(home_path(".config/solana/cli/config.yml"));
+    let config = match &cli.config {
+        Some(path) => read_solana_config(path)?,
+        None if default_path.exists() => read_solana_config(&default_path)?,
+        None => SolanaConfig::default(),
+    };
+    let url = normalize_url(cli.url.as_deref().unwrap_or(&config.json_rpc_url));
+    let keypair_path = cli
+        .keypair
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(&config.keypair_path));
+    let payer = read_keypair_file(&keypair_path)
+        .map_err(|err| anyhow!("read keypair {}: {err}", keypair_path.display()))?;
+    Ok((url, payer))
+}
+
+fn read_solana_config(path: &Path) -> Result<SolanaConfig> {
+    let data = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    serde_yaml::from_slice(&data).with_context(|| format!("parse {}", path.display()))
+}
+
+fn normalize_url(url: &str) -> String {
+    match url {
+        "localhost" | "l" => "http://localhost:8899",
+        "devnet" | "d" => "https://api.devnet.solana.com",
+        "testnet" | "t" => "https://api.testnet.solana.com",
+        "mainnet-beta" | "m" => "https://api.mainnet-beta.solana.com",
+        url => url,
+    }
+    .to_string()
+}
+
+pub async fn run<R: Rpc>(
+    rpc: &mut R,
+    payer: &Keypair,
+    dry_run: bool,
+    command: Command,
+    out: &mut impl Write,
+) -> Result<()> {
+    let signer = payer.pubkey();
+    match command {
+        Command::InitCreator => {
+            let ix = instructions::initialize_creator_state(&signer);
+            submit(rpc, payer, dry_run, &[ix], out).await?;
+            writeln!(out, "creator state: {}", pda::creator_state(&signer))?;
+        }
+        Command::Create {
+            kind,
+            amount,
+            count,
+            mint,
+            expiry_days,
+            tree,
+            seed,
+        } => {
+            let params = create_params(kind, amount, count, tree.as_deref(), seed)?;
+            let params = CreateParams {
+                expiry_days,
+                ..params
+            };
+            let creator_state = fetch(rpc, &pda::creator_state(&signer))
+                .await
+                .context("creator state not found, run init-creator first")?;
+            let id = decode_creator_state(&creator_state)?.next_red_packet_id;
+            let asset = match mint {
+                Some(mint) => token_asset(rpc, &mint).await?,
+                None => Asset::Sol,
+            };
+            let fee_receiver = fee_receiver(rpc).await?;
+            let ix = instructions::create_redpacket(&signer, id, &asset, &fee_receiver, params);
+            submit(rpc, payer, dry_run, &[ix], out).await?;
+            writeln!(out, "red packet {id}: {}", pda::red_packet(&signer, id))?;
+        }
+        Command::Claim {
+            id,
+            creator,
+            proof_file,
+        } => {
+            let red_packet = red_packet_account(rpc, &creator, id).await?;
+            let params = claim_params(rpc, &signer, &red_packet, proof_file.as_deref()).await?;
+            let address = pda::red_packet(&creator, id);
+            let claim_commit = pda::claim_commit(&address, &signer);
+            // 已经领取过的直接提交领取，让模拟报出重复领取，而不是再提交一次承诺
+            if params.commit
+                && rpc.get_account(&claim_commit).await?.is_none()
+                && rpc
+                    .get_account(&pda::user_state(&address, &signer))
+                    .await?
+                    .is_none()
+            {
+                // 随机金额混入承诺所在 slot 的 hash，只能等承诺上链之后的 slot 再领取
+                let ix = instructions::commit_claim(&signer, &creator, id, &[]);
+                submit(rpc, payer, dry_run, &[ix], out).await?;
+                writeln!(out, "claim committed, run claim again in a later slot")?;
+                return Ok(());
+            }
+            let asset = red_packet_asset(rpc, &red_packet).await?;
+            let fee_receiver = fee_receiver(rpc).await?;
+            let ix =
+                instructions::claim_redpacket(&signer, &creator, id, &asset, &fee_receiver, params);
+            submit(rpc, payer, dry_run, &[ix], out).await?;
+        }
+        Command::Refund { id } => {
+            let red_packet = red_packet_account(rpc, &signer, id).await?;
+            let asset = red_packet_asset(rpc, &red_packet).await?;
+            let ix = instructions::refund(&signer, id, &asset);
+            submit(rpc, payer, dry_run, &[ix], out).await?;
+        }
+        Command::Extend { id, days, creator } => {
+            let creator = creator.unwrap_or(signer);
+            let red_packet = red_packet_account(rpc, &creator, id).await?;
+            let expiry_time = days
+                .checked_mul(SECONDS_PER_DAY)
+                .and_then(|seconds| red_packet.expiry_time.checked_add(seconds))
+                .ok_or_else(|| anyhow!("--days {days} is out of range"))?;
+            let ix = instructions::set_expiry_time(&signer, &creator, id, expiry_time);
+            submit(rpc, payer, dry_run, &[ix], out).await?;
+            writeln!(out, "expiry_time: {expiry_time}")?;
+        }
+        Command::Show { id, creator } => {
+            let creator = creator.unwrap_or(signer);
+            let red_packet = red_packet_account(rpc, &creator, id).await?;
+            let now = unix_timestamp(rpc).await?;
+            write_red_packet(out, &pda::red_packet(&creator, id), &red_packet, now)?;
+        }
+        Command::List { creator } => {
+            let creator = creator.unwrap_or(signer);
+            let creator_state = fetch(rpc, &pda::creator_state(&creator))
+                .await
+                .context("creator state not found")?;
+            let next_id = decode_creator_state(&creator_state)?.next_red_packet_id;
+            let now = unix_timestamp(rpc).await?;
+            for id in 0..next_id {
+                let address = pda::red_packet(&creator, id);
+                match rpc.get_account(&address).await? {
+                    Some(account) => {
+                        let red_packet = decode_red_packet(&account.data)?;
+                        writeln!(
+                            out,
+                            "{id:>4}  {address}  {:<14}  {}/{}  remaining {}  {}",
+                            type_name(red_packet.red_packet_type),
+                            red_packet.claimed_count,
+                            red_packet.packet_count,
+                            red_packet.remaining_amount,
+                            status(&red_packet, now),
+                        )?;
+                    }
+                    None => writeln!(out, "{id:>4}  {address}  closed")?,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn create_params(
+    kind: Kind,
+    amount: Option<u64>,
+    count: Option<u32>,
+    tree: Option<&Path>,
+    seed: Option<u64>,
+) -> Result<CreateParams> {
+    if kind == Kind::Merkle {
+        let tree = tree.ok_or_else(|| anyhow!("merkle red packets require --tree"))?;
+        let file: TreeFile = serde_json::from_slice(
+            &std::fs::read(tree).with_context(|| format!("read {}", tree.display()))?,
+        )?;
+        if file.leaf_kind != LeafKind::Plain {
+            bail!("only plain Merkle trees are supported, rebuild with --kind plain");
+        }
+        return Ok(CreateParams {
+            merkle_root: Some(red_packet_merkle::io::decode_hash(&file.merkle_root)?),
+            ..CreateParams::new(file.total_amount, file.packet_count, file.red_packet_type)
+        });
+    }
+    let (Some(amount), Some(count)) = (amount, count) else {
+        bail!("--amount and --count are required");
+    };
+    Ok(match kind {
+        Kind::Random => CreateParams {
+            random_seed: Some(seed.unwrap_or_else(default_seed)),
+            ..CreateParams::new(amount, count, 3)
+        },
+        _ => CreateParams::new(amount, count, 0),
+    })
+}
+
+fn default_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+async fn claim_params<R: Rpc>(
+    rpc: &mut R,
+    claimer: &Pubkey,
+    red_packet: &RedPacket,
+    proof_file: Option<&Path>,
+) -> Result<ClaimParams> {
+    let mut params = ClaimParams::default();
+    match red_packet.red_packet_type {
+        0 | 1 => {}
+        3 | 7 => params.commit = true,
+        2 => {
+            let path =
+                proof_file.ok_or_else(|| anyhow!("merkle red packets require --proof-file"))?;
+            let file: ProofFile = serde_json::from_slice(
+                &std::fs::read(path).with_context(|| format!("read {}", path.display()))?,
+            )?;
+            if file.pubkey != claimer.to_string() {
+                bail!(
+                    "proof file is for {}, not the signer {claimer}",
+                    file.pubkey
+                );
+            }
+            let claim = file
+                .claims
+                .first()
+                .ok_or_else(|| anyhow!("proof file has no claims"))?;
+            params.amount = Some(claim.amount);
+            params.proof = Some(claim.proof_bytes()?);
+        }
+        red_packet_type => bail!(
+            "{} red packets are not supported by the CLI",
+            type_name(red_packet_type)
+        ),
+    }
+    match red_packet.holder_gate.kind {
+        0 => {}
+        1 => {
+            let gate = token_asset(rpc, &red_packet.holder_gate.mint).await?;
+            params.gate_token_account = Some(gate.token_account(claimer));
+        }
+        _ => bail!("collection-gated red packets are not supported by the CLI"),
+    }
+    Ok(params)
+}
+
+async fn submit<R: Rpc>(
+    rpc: &mut R,
+    payer: &Keypair,
+    dry_run: bool,
+    instructions: &[Instruction],
+    out: &mut impl Write,
+) -> Result<()> {
+    let blockhash = rpc.latest_blockhash().await?;
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &[payer],
+        blockhash,
+    );
+    if !dry_run {
+        let signature = rpc.send_transaction(&tx).await?;
+        writeln!(out, "signature: {signature}")?;
+        return Ok(());
+    }
+
+    let simulation = rpc.simulate_transaction(&tx).await?;
+    writeln!(out, "compute units: {}", simulation.units_consumed)?;
+    if let Some(error) = simulation.error {
+        for log in &simulation.logs {
+            writeln!(out, "  {log}")?;
+        }
+        bail!("simulation failed: {error}");
+    }
+    Ok(())
+}
+
+async fn fetch<R: Rpc>(rpc: &mut R, address: &Pubkey) -> Result<Vec<u8>> {
+    let account = rpc
+        .get_account(address)
+        .await?
+        .ok_or_else(|| anyhow!("account {address} not found"))?;
+    Ok(account.data)
+}
+
+async fn red_packet_account<R: Rpc>(rpc: &mut R, creator: &Pubkey, id: u64) -> Result<RedPacket> {
+    let address = pda::red_packet(creator, id);
+    let data = fetch(rpc, &address)
+        .await
+        .with_context(|| format!("red packet {id} of {creator}"))?;
+    Ok(decode_red_packet(&data)?)
+}
+
+async fn fee_receiver<R: Rpc>(rpc: &mut R) -> Result<Pubkey> {
+    let data = fetch(rpc, &pda::protocol_config())
+        .await
+        .context("protocol config not initialized")?;
+    Ok(decode_protocol_config(&data)?.fee_receiver)
+}
+
+/// mint 账户的 owner 决定走旧版 SPL Token 还是 Token-2022
+async fn token_asset<R: Rpc>(rpc: &mut R, mint: &Pubkey) -> Result<Asset> {
+    let account = rpc
+        .get_account(mint)
+        .await?
+        .ok_or_else(|| anyhow!("mint {mint} not found"))?;
+    if account.owner != anchor_spl::token::ID && account.owner != anchor_spl::token_2022::ID {
+        bail!("{mint} is not a token mint");
+    }
+    Ok(Asset::Token(TokenMint {
+        mint: *mint,
+        token_program: account.owner,
+    }))
+}
+
+async fn red_packet_asset<R: Rpc>(rpc: &mut R, red_packet: &RedPacket) -> Result<Asset> {
+    if red_packet.is_sol {
+        Ok(Asset::Sol)
+    } else {
+        token_asset(rpc, &red_packet.mint).await
+    }
+}
+
+async fn unix_timestamp<R: Rpc>(rpc: &mut R) -> Result<i64> {
+    let account = rpc
+        .get_account(&sysvar::clock::ID)
+        .await?
+        .ok_or_else(|| anyhow!("clock sysvar not found"))?;
+    let clock: Clock = from_account(&account).ok_or_else(|| anyhow!("invalid clock sysvar"))?;
+    Ok(clock.unix_timestamp)
+}
+
+fn type_name(red_packet_type: u8) -> &'static str {
+    match red_packet_type {
+        0 => "equal",
+        1 => "random",
+        2 => "merkle",
+        3 => "random-slot",
+        4 => "merkle-indexed",
+        5 => "code-equal",
+        6 => "code-random",
+        7 => "tiered",
+        _ => "unknown",
+    }
+}
+
+fn status(red_packet: &RedPacket, now: i64) -> &'static str {
+    if red_packet.claimed_count >= red_packet.packet_count {
+        "finished"
+    } else if red_packet.cancelled && now >= red_packet.expiry_time {
+        "cancelled"
+    } else if now >= red_packet.expiry_time {
+        "expired"
+    } else if now < red_packet.start_time {
+        "not started"
+    } else {
+        "active"
+    }
+}
+
+fn write_red_packet(
+    out: &mut impl Write,
+    address: &Pubkey,
+    red_packet: &RedPacket,
+    now: i64,
+) -> Result<()> {
+    let asset = if red_packet.is_sol {
+        "SOL".to_string()
+    } else {
+        red_packet.mint.to_string()
+    };
+    writeln!(out, "address:     {address}")?;
+    writeln!(out, "creator:     {}", red_packet.creator)?;
+    writeln!(out, "id:          {}", red_packet.red_packet_id)?;
+    writeln!(
+        out,
+        "type:        {} ({})",
+        red_packet.red_packet_type,
+        type_name(red_packet.red_packet_type)
+    )?;
+    writeln!(out, "asset:       {asset}")?;
+    writeln!(out, "total:       {}", red_packet.total_amount)?;
+    writeln!(out, "remaining:   {}", red_packet.remaining_amount)?;
+    writeln!(
+        out,
+        "claimed:     {}/{}",
+        red_packet.claimed_count, red_packet.packet_count
+    )?;
+    writeln!(out, "start_time:  {}", red_packet.start_time)?;
+    writeln!(out, "expiry_time: {}", red_packet.expiry_time)?;
+    writeln!(out, "status:      {}", status(red_packet, now))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_create_requires_amount_without_tree() {
+        assert!(Cli::try_parse_from(["red-packet", "create", "--count", "2"]).is_err());
+        let cli = Cli::try_parse_from(["red-packet", "--dry-run", "create", "--tree", "tree.json"])
+            .unwrap();
+        assert!(cli.dry_run);
+        assert!(matches!(cli.command, Command::Create { tree: Some(_), .. }));
+    }
+
+    #[test]
+    fn test_random_red_packet_gets_seed() {
+        let params = create_params(Kind::Random, Some(100), Some(2), None, Some(7)).unwrap();
+        assert_eq!(params.red_packet_type, 3);
+        assert_eq!(params.random_seed, Some(7));
+        let params = create_params(Kind::Equal, Some(100), Some(2), None, None).unwrap();
+        assert_eq!(params.random_seed, None);
+        assert!(create_params(Kind::Merkle, Some(100), Some(2), None, None).is_err());
+    }
+
+    #[test]
+    fn test_solana_config_defaults_missing_fields() {
+        let config: SolanaConfig =
+            serde_yaml::from_str("json_rpc_url: http://localhost:8899\n").unwrap();
+        assert_eq!(config.json_rpc_url, "http://localhost:8899");
+        assert!(config.keypair_path.ends_with(".config/solana/id.json"));
+    }
+
+    #[test]
+    fn test_normalize_url_monikers() {
+        assert_eq!(normalize_url("localhost"), "http://localhost:8899");
+        assert_eq!(normalize_url("d"), "https://api.devnet.solana.com");
+        assert_eq!(normalize_url("http://example.com"), "http://example.com");
+    }
+}
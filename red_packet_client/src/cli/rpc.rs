@@ -0,0 +1,72 @@
+//! 命令行用到的链上读写。抽成 trait 以便测试时换成 program-test 的 BanksClient
+
+use anyhow::{Context, Result};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_sdk::{
+    account::Account, commitment_config::CommitmentConfig, hash::Hash, pubkey::Pubkey,
+    signature::Signature, transaction::Transaction,
+};
+use std::future::Future;
+
+/// 模拟执行的结果，`error` 为 `None` 表示交易会成功
+#[derive(Debug, Default)]
+pub struct Simulation {
+    pub units_consumed: u64,
+    pub logs: Vec<String>,
+    pub error: Option<String>,
+}
+
+pub trait Rpc {
+    /// 账户不存在时返回 `None`
+    fn get_account(&mut self, address: &Pubkey) -> impl Future<Output = Result<Option<Account>>>;
+
+    fn latest_blockhash(&mut self) -> impl Future<Output = Result<Hash>>;
+
+    /// 发送并等待确认
+    fn send_transaction(&mut self, tx: &Transaction) -> impl Future<Output = Result<Signature>>;
+
+    fn simulate_transaction(
+        &mut self,
+        tx: &Transaction,
+    ) -> impl Future<Output = Result<Simulation>>;
+}
+
+impl Rpc for RpcClient {
+    async fn get_account(&mut self, address: &Pubkey) -> Result<Option<Account>> {
+        let response = self
+            .get_account_with_commitment(address, self.commitment())
+            .await
+            .with_context(|| format!("get account {address}"))?;
+        Ok(response.value)
+    }
+
+    async fn latest_blockhash(&mut self) -> Result<Hash> {
+        Ok(self.get_latest_blockhash().await?)
+    }
+
+    async fn send_transaction(&mut self, tx: &Transaction) -> Result<Signature> {
+        Ok(self
+            .send_and_confirm_transaction_with_spinner_and_commitment(
+                tx,
+                CommitmentConfig::confirmed(),
+            )
+            .await?)
+    }
+
+    async fn simulate_transaction(&mut self, tx: &Transaction) -> Result<Simulation> {
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: true,
+            commitment: Some(self.commitment()),
+            ..RpcSimulateTransactionConfig::default()
+        };
+        let result = self
+            .simulate_transaction_with_config(tx, config)
+            .await?
+            .value;
+        Ok(Simulation {
+            units_consumed: result.units_consumed.unwrap_or_default(),
+            logs: result.logs.unwrap_or_default(),
+            error: result.err.map(|err| err.to_string()),
+        })
+    }
+}
@@ -0,0 +1,231 @@
+//! 从交易日志解码 red_packet 事件。Anchor 的 `emit!` 把 判别符 ‖ borsh 数据 以 base64 写成
+//! `Program data: …`；这里按调用栈只取红包程序自己输出的那几行，CPI 到其他程序时输出的数据会被跳过。
+//!
+//! 失败交易的日志里同样可能带有事件，但状态已经回滚，调用方只应传入成功交易的日志。
+//! 程序升级后新增的事件本客户端还不认识，`parse_logs` 把它们原样作为 `Unknown` 返回，不影响同一笔交易里的其他事件。
+
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use red_packet::events::*;
+
+const PROGRAM_PREFIX: &str = "Program ";
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+const DISCRIMINATOR_LEN: usize = 8;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum EventError {
+    #[error("invalid base64 in program data: {0}")]
+    InvalidBase64(String),
+    #[error("program data is shorter than an event discriminator")]
+    TooShort,
+    #[error("unknown event discriminator {0:?}")]
+    UnknownDiscriminator([u8; DISCRIMINATOR_LEN]),
+    #[error("failed to decode {event}: {reason}")]
+    InvalidData { event: &'static str, reason: String },
+}
+
+fn decode_body<T: AnchorDeserialize>(event: &'static str, body: &[u8]) -> Result<T, EventError> {
+    T::try_from_slice(body).map_err(|err| EventError::InvalidData {
+        event,
+        reason: err.to_string(),
+    })
+}
+
+macro_rules! red_packet_events {
+    ($($event:ident),* $(,)?) => {
+        /// 红包程序发出的所有事件，变体名与事件结构体同名
+        pub enum RedPacketEvent {
+            $($event($event),)*
+            /// 判别符不在上面列表中的事件，保留判别符之后的原始数据
+            Unknown {
+                discriminator: [u8; DISCRIMINATOR_LEN],
+                data: Vec<u8>,
+            },
+        }
+
+        impl RedPacketEvent {
+            /// 解码 `Program data:` 之后 base64 解开的字节
+            pub fn decode(data: &[u8]) -> Result<Self, EventError> {
+                if data.len() < DISCRIMINATOR_LEN {
+                    return Err(EventError::TooShort);
+                }
+                let (discriminator, body) = data.split_at(DISCRIMINATOR_LEN);
+                $(
+                    if discriminator == $event::DISCRIMINATOR {
+                        return decode_body(stringify!($event), body).map(Self::$event);
+                    }
+                )*
+                let mut unknown = [0; DISCRIMINATOR_LEN];
+                unknown.copy_from_slice(discriminator);
+                Err(EventError::UnknownDiscriminator(unknown))
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$event(_) => stringify!($event),)*
+                    Self::Unknown { .. } => "Unknown",
+                }
+            }
+        }
+    };
+}
+
+red_packet_events!(
+    RedPacketCreated,
+    RedPacketClaimed,
+    RedPacketRefunded,
+    RedPacketCancelled,
+    ExpiryTimeUpdated,
+    StartTimeUpdated,
+    RedPacketClosed,
+    UserStatesClosed,
+    ProtocolConfigUpdated,
+    PauseUpdated,
+    AdminTransferProposed,
+    AdminTransferred,
+    RedPacketMigrated,
+    RedPacketToppedUp,
+    OperatorUpdated,
+    BundleCreated,
+    BundleClaimed,
+    BundleRefunded,
+);
+
+/// 按日志顺序返回一笔交易里红包程序发出的事件
+pub fn parse_logs<S: AsRef<str>>(logs: &[S]) -> Result<Vec<RedPacketEvent>, EventError> {
+    let program_id = red_packet::ID.to_string();
+    // 每一层调用是否是红包程序
+    let mut stack: Vec<bool> = Vec::new();
+    let mut events = Vec::new();
+    for log in logs {
+        let log = log.as_ref();
+        if let Some(data) = log.strip_prefix(PROGRAM_DATA_PREFIX) {
+            if stack.last() == Some(&true) {
+                let bytes = STANDARD
+                    .decode(data.trim())
+                    .map_err(|err| EventError::InvalidBase64(err.to_string()))?;
+                let event = match RedPacketEvent::decode(&bytes) {
+                    Err(EventError::UnknownDiscriminator(discriminator)) => {
+                        RedPacketEvent::Unknown {
+                            discriminator,
+                            data: bytes[DISCRIMINATOR_LEN..].to_vec(),
+                        }
+                    }
+                    event => event?,
+                };
+                events.push(event);
+            }
+            continue;
+        }
+        let Some(rest) = log.strip_prefix(PROGRAM_PREFIX) else {
+            continue;
+        };
+        let mut words = rest.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some(id), Some("invoke")) => stack.push(id == program_id),
+            (Some(_), Some("success" | "failed:")) => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{prelude::Pubkey, AnchorSerialize};
+
+    fn program_data<T: AnchorSerialize + Discriminator>(event: &T) -> String {
+        let mut data = T::DISCRIMINATOR.to_vec();
+        event.serialize(&mut data).unwrap();
+        format!("{PROGRAM_DATA_PREFIX}{}", STANDARD.encode(data))
+    }
+
+    fn expiry_updated(new_expiry_time: i64) -> ExpiryTimeUpdated {
+        ExpiryTimeUpdated {
+            red_packet: Pubkey::new_unique(),
+            new_expiry_time,
+            red_packet_id: 1,
+        }
+    }
+
+    #[test]
+    fn test_parse_logs_only_reads_red_packet_frames() {
+        let other_program = Pubkey::new_unique();
+        let logs = vec![
+            format!("Program {} invoke [1]", red_packet::ID),
+            "Program log: Instruction: SetExpiryTime".to_string(),
+            program_data(&expiry_updated(10)),
+            // CPI 到别的程序时输出的 data 不属于红包程序
+            format!("Program {other_program} invoke [2]"),
+            program_data(&expiry_updated(20)),
+            format!("Program {other_program} success"),
+            program_data(&expiry_updated(30)),
+            format!("Program {} success", red_packet::ID),
+            program_data(&expiry_updated(40)),
+        ];
+        let times: Vec<i64> = parse_logs(&logs)
+            .unwrap()
+            .into_iter()
+            .map(|event| match event {
+                RedPacketEvent::ExpiryTimeUpdated(event) => event.new_expiry_time,
+                event => panic!("unexpected {}", event.name()),
+            })
+            .collect();
+        assert_eq!(times, [10, 30]);
+    }
+
+    #[test]
+    fn test_parse_logs_keeps_unknown_events() {
+        let mut unknown = vec![9; DISCRIMINATOR_LEN];
+        unknown.extend([1, 2, 3]);
+        let logs = [
+            format!("Program {} invoke [1]", red_packet::ID),
+            format!("{PROGRAM_DATA_PREFIX}{}", STANDARD.encode(&unknown)),
+            program_data(&expiry_updated(10)),
+            format!("Program {} success", red_packet::ID),
+        ];
+        match parse_logs(&logs).unwrap().as_slice() {
+            [RedPacketEvent::Unknown {
+                discriminator,
+                data,
+            }, RedPacketEvent::ExpiryTimeUpdated(event)] => {
+                assert_eq!(discriminator, &[9; DISCRIMINATOR_LEN]);
+                assert_eq!(data, &[1, 2, 3]);
+                assert_eq!(event.new_expiry_time, 10);
+            }
+            events => panic!("unexpected {} events", events.len()),
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_data() {
+        assert_eq!(
+            RedPacketEvent::decode(&[1, 2, 3]).err(),
+            Some(EventError::TooShort)
+        );
+        assert_eq!(
+            RedPacketEvent::decode(&[0; 8]).err(),
+            Some(EventError::UnknownDiscriminator([0; 8]))
+        );
+        let truncated = ExpiryTimeUpdated::DISCRIMINATOR.to_vec();
+        assert!(matches!(
+            RedPacketEvent::decode(&truncated),
+            Err(EventError::InvalidData {
+                event: "ExpiryTimeUpdated",
+                ..
+            })
+        ));
+
+        let logs = [
+            format!("Program {} invoke [1]", red_packet::ID),
+            format!("{PROGRAM_DATA_PREFIX}not base64!"),
+        ];
+        assert!(matches!(
+            parse_logs(&logs),
+            Err(EventError::InvalidBase64(_))
+        ));
+    }
+}
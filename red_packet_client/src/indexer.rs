@@ -0,0 +1,389 @@
+//! 把交易日志中的红包事件折叠成每个红包的当前状态并保存到 SQLite。
+//!
+//! 以交易签名去重，同一笔交易重复送入不会重复计数。只有 RedPacketCreated 之后的事件
+//! 会更新红包状态，从中途开始索引时之前创建的红包只记录领取明细。
+//! 金额以 SQLite 的 INTEGER 保存，超过 i64::MAX 的金额会返回错误。
+//! 不认识的事件（程序升级后新增）原样记进 unknown_events，升级客户端后可以据此补索引。
+
+use crate::events::{parse_logs, EventError, RedPacketEvent};
+use anchor_lang::prelude::Pubkey;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::{path::Path, str::FromStr};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS red_packets (
+    address          TEXT PRIMARY KEY,
+    creator          TEXT NOT NULL,
+    red_packet_id    INTEGER NOT NULL,
+    mint             TEXT NOT NULL,
+    is_sol           INTEGER NOT NULL,
+    red_packet_type  INTEGER NOT NULL,
+    total_amount     INTEGER NOT NULL,
+    remaining_amount INTEGER NOT NULL,
+    packet_count     INTEGER NOT NULL,
+    claimed_count    INTEGER NOT NULL,
+    start_time       INTEGER NOT NULL,
+    expiry_time      INTEGER NOT NULL,
+    refunded_amount  INTEGER,
+    status           TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS claims (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    red_packet   TEXT NOT NULL,
+    claimer      TEXT NOT NULL,
+    amount       INTEGER NOT NULL,
+    net_amount   INTEGER NOT NULL,
+    transfer_fee INTEGER NOT NULL,
+    tier         INTEGER,
+    signature    TEXT NOT NULL,
+    slot         INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS claims_by_red_packet ON claims (red_packet);
+CREATE TABLE IF NOT EXISTS unknown_events (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    discriminator BLOB NOT NULL,
+    data          BLOB NOT NULL,
+    signature     TEXT NOT NULL,
+    slot          INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS transactions (
+    signature TEXT PRIMARY KEY,
+    slot      INTEGER NOT NULL
+);
+";
+
+#[derive(Debug, thiserror::Error)]
+pub enum IndexerError {
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    Event(#[from] EventError),
+}
+
+/// 红包的生命周期，过期与否由调用方按 expiry_time 判断
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PacketStatus {
+    Active,
+    /// 已取消，claims_end_time 之后才能退款
+    Cancelled,
+    Refunded,
+    /// 领完后由 close_redpacket 关闭
+    Closed,
+}
+
+impl PacketStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PacketStatus::Active => "active",
+            PacketStatus::Cancelled => "cancelled",
+            PacketStatus::Refunded => "refunded",
+            PacketStatus::Closed => "closed",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "active" => PacketStatus::Active,
+            "cancelled" => PacketStatus::Cancelled,
+            "refunded" => PacketStatus::Refunded,
+            "closed" => PacketStatus::Closed,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PacketRecord {
+    pub address: Pubkey,
+    pub creator: Pubkey,
+    pub red_packet_id: u64,
+    pub mint: Pubkey,
+    pub is_sol: bool,
+    pub red_packet_type: u8,
+    pub total_amount: u64,
+    pub remaining_amount: u64,
+    pub packet_count: u32,
+    pub claimed_count: u32,
+    pub start_time: i64,
+    pub expiry_time: i64,
+    /// 退款或关闭时退回创建者的数量（含转账费）
+    pub refunded_amount: Option<u64>,
+    pub status: PacketStatus,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClaimRecord {
+    pub claimer: Pubkey,
+    pub amount: u64,
+    pub net_amount: u64,
+    pub transfer_fee: u64,
+    pub tier: Option<u8>,
+    pub signature: String,
+    pub slot: u64,
+}
+
+/// 本客户端版本不认识的事件
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownEventRecord {
+    pub discriminator: [u8; 8],
+    pub data: Vec<u8>,
+    pub signature: String,
+    pub slot: u64,
+}
+
+pub struct Indexer {
+    conn: Connection,
+}
+
+impl Indexer {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, IndexerError> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, IndexerError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, IndexerError> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// 索引一笔成功交易的日志，返回 false 表示该签名已经索引过
+    pub fn index_transaction<S: AsRef<str>>(
+        &mut self,
+        signature: &str,
+        slot: u64,
+        logs: &[S],
+    ) -> Result<bool, IndexerError> {
+        let events = parse_logs(logs)?;
+        let tx = self.conn.transaction()?;
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO transactions (signature, slot) VALUES (?1, ?2)",
+            params![signature, slot],
+        )?;
+        if inserted == 0 {
+            return Ok(false);
+        }
+        for event in &events {
+            apply_event(&tx, signature, slot, event)?;
+        }
+        tx.commit()?;
+        Ok(true)
+    }
+
+    pub fn red_packet(&self, address: &Pubkey) -> Result<Option<PacketRecord>, IndexerError> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT address, creator, red_packet_id, mint, is_sol, red_packet_type,
+                        total_amount, remaining_amount, packet_count, claimed_count,
+                        start_time, expiry_time, refunded_amount, status
+                 FROM red_packets WHERE address = ?1",
+                [address.to_string()],
+                packet_record,
+            )
+            .optional()?)
+    }
+
+    /// 按索引顺序返回红包的领取明细
+    pub fn claims(&self, red_packet: &Pubkey) -> Result<Vec<ClaimRecord>, IndexerError> {
+        let mut statement = self.conn.prepare(
+            "SELECT claimer, amount, net_amount, transfer_fee, tier, signature, slot
+             FROM claims WHERE red_packet = ?1 ORDER BY id",
+        )?;
+        let claims = statement
+            .query_map([red_packet.to_string()], |row| {
+                Ok(ClaimRecord {
+                    claimer: pubkey(row, 0)?,
+                    amount: row.get(1)?,
+                    net_amount: row.get(2)?,
+                    transfer_fee: row.get(3)?,
+                    tier: row.get(4)?,
+                    signature: row.get(5)?,
+                    slot: row.get(6)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(claims)
+    }
+
+    /// 按索引顺序返回所有不认识的事件
+    pub fn unknown_events(&self) -> Result<Vec<UnknownEventRecord>, IndexerError> {
+        let mut statement = self.conn.prepare(
+            "SELECT discriminator, data, signature, slot FROM unknown_events ORDER BY id",
+        )?;
+        let events = statement
+            .query_map([], |row| {
+                Ok(UnknownEventRecord {
+                    discriminator: row.get(0)?,
+                    data: row.get(1)?,
+                    signature: row.get(2)?,
+                    slot: row.get(3)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(events)
+    }
+}
+
+fn apply_event(
+    tx: &rusqlite::Transaction,
+    signature: &str,
+    slot: u64,
+    event: &RedPacketEvent,
+) -> Result<(), IndexerError> {
+    match event {
+        RedPacketEvent::RedPacketCreated(event) => {
+            tx.execute(
+                "INSERT OR IGNORE INTO red_packets (
+                    address, creator, red_packet_id, mint, is_sol, red_packet_type,
+                    total_amount, remaining_amount, packet_count, claimed_count,
+                    start_time, expiry_time, refunded_amount, status
+                 ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7, ?8, 0, ?9, ?10, NULL, ?11)",
+                params![
+                    event.red_packet.to_string(),
+                    event.creator.to_string(),
+                    event.red_packet_id,
+                    event.mint.to_string(),
+                    event.is_sol,
+                    event.red_packet_type,
+                    event.total_amount,
+                    event.packet_count,
+                    event.start_time,
+                    event.expiry_time,
+                    PacketStatus::Active.as_str(),
+                ],
+            )?;
+        }
+        RedPacketEvent::RedPacketClaimed(event) => {
+            tx.execute(
+                "INSERT INTO claims (
+                    red_packet, claimer, amount, net_amount, transfer_fee, tier, signature, slot
+                 ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    event.red_packet.to_string(),
+                    event.claimer.to_string(),
+                    event.amount,
+                    event.net_amount,
+                    event.transfer_fee,
+                    event.tier,
+                    signature,
+                    slot,
+                ],
+            )?;
+            tx.execute(
+                "UPDATE red_packets
+                 SET claimed_count = claimed_count + 1, remaining_amount = remaining_amount - ?2
+                 WHERE address = ?1",
+                params![event.red_packet.to_string(), event.amount],
+            )?;
+        }
+        RedPacketEvent::RedPacketRefunded(event) => {
+            finish(tx, &event.red_packet, event.amount, PacketStatus::Refunded)?;
+        }
+        RedPacketEvent::RedPacketClosed(event) => {
+            finish(
+                tx,
+                &event.red_packet,
+                event.leftover_amount,
+                PacketStatus::Closed,
+            )?;
+        }
+        RedPacketEvent::RedPacketCancelled(event) => {
+            tx.execute(
+                "UPDATE red_packets SET expiry_time = ?2, status = ?3 WHERE address = ?1",
+                params![
+                    event.red_packet.to_string(),
+                    event.claims_end_time,
+                    PacketStatus::Cancelled.as_str(),
+                ],
+            )?;
+        }
+        RedPacketEvent::ExpiryTimeUpdated(event) => {
+            tx.execute(
+                "UPDATE red_packets SET expiry_time = ?2 WHERE address = ?1",
+                params![event.red_packet.to_string(), event.new_expiry_time],
+            )?;
+        }
+        RedPacketEvent::StartTimeUpdated(event) => {
+            tx.execute(
+                "UPDATE red_packets SET start_time = ?2 WHERE address = ?1",
+                params![event.red_packet.to_string(), event.new_start_time],
+            )?;
+        }
+        RedPacketEvent::RedPacketToppedUp(event) => {
+            tx.execute(
+                "UPDATE red_packets
+                 SET total_amount = ?2, remaining_amount = ?3, packet_count = ?4
+                 WHERE address = ?1",
+                params![
+                    event.red_packet.to_string(),
+                    event.total_amount,
+                    event.remaining_amount,
+                    event.packet_count,
+                ],
+            )?;
+        }
+        RedPacketEvent::Unknown {
+            discriminator,
+            data,
+        } => {
+            tx.execute(
+                "INSERT INTO unknown_events (discriminator, data, signature, slot)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![discriminator.as_slice(), data, signature, slot],
+            )?;
+        }
+        // 组合红包、全局配置和操作员的事件不影响红包状态
+        _ => {}
+    }
+    Ok(())
+}
+
+fn finish(
+    tx: &rusqlite::Transaction,
+    red_packet: &Pubkey,
+    amount: u64,
+    status: PacketStatus,
+) -> Result<(), IndexerError> {
+    tx.execute(
+        "UPDATE red_packets SET remaining_amount = 0, refunded_amount = ?2, status = ?3
+         WHERE address = ?1",
+        params![red_packet.to_string(), amount, status.as_str()],
+    )?;
+    Ok(())
+}
+
+fn pubkey(row: &Row, index: usize) -> rusqlite::Result<Pubkey> {
+    let value: String = row.get(index)?;
+    Pubkey::from_str(&value).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, err.into())
+    })
+}
+
+fn packet_record(row: &Row) -> rusqlite::Result<PacketRecord> {
+    let status: String = row.get(13)?;
+    Ok(PacketRecord {
+        address: pubkey(row, 0)?,
+        creator: pubkey(row, 1)?,
+        red_packet_id: row.get(2)?,
+        mint: pubkey(row, 3)?,
+        is_sol: row.get(4)?,
+        red_packet_type: row.get(5)?,
+        total_amount: row.get(6)?,
+        remaining_amount: row.get(7)?,
+        packet_count: row.get(8)?,
+        claimed_count: row.get(9)?,
+        start_time: row.get(10)?,
+        expiry_time: row.get(11)?,
+        refunded_amount: row.get(12)?,
+        status: PacketStatus::parse(&status).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                13,
+                rusqlite::types::Type::Text,
+                format!("unknown status {status:?}").into(),
+            )
+        })?,
+    })
+}
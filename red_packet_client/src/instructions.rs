@@ -0,0 +1,849 @@
+//! 每条指令的构建函数。账户顺序与链上的 `#[derive(Accounts)]` 一致，PDA 和 ATA 都在这里推导，
+//! 调用方只需要提供签名者、红包编号和资产类型。
+//!
+//! 涉及手续费的指令需要传入 `fee_receiver`：管理员可以修改收款地址，
+//! 应先用 [`crate::accounts::decode_protocol_config`] 读取当前配置。
+
+use crate::{pda, Asset, TokenMint};
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        sysvar,
+    },
+    system_program, InstructionData, ToAccountMetas,
+};
+use red_packet::{
+    accounts as acc, instruction as ix,
+    states::{FeeRule, HolderGate, PrizeTier},
+};
+
+pub use red_packet::utils::relay_claim_message;
+
+/// 退款、关闭和取消指令里 mint 的位置：带转账费的 mint 需要可写以回收池子里扣留的手续费
+const REFUND_MINT_INDEX: usize = 2;
+const CANCEL_MINT_INDEX: usize = 4;
+
+fn instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: red_packet::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn mint_meta(asset: &Asset) -> AccountMeta {
+    match asset {
+        Asset::Sol => AccountMeta::new_readonly(Pubkey::default(), false),
+        Asset::Token(token) => AccountMeta::new(token.mint, false),
+    }
+}
+
+fn with_writable_mint(mut instruction: Instruction, index: usize, asset: &Asset) -> Instruction {
+    instruction.accounts[index] = mint_meta(asset);
+    instruction
+}
+
+// ---------------------------------------------------------------------------
+// 全局配置
+// ---------------------------------------------------------------------------
+
+/// 签名者成为协议管理员，只能调用一次，且签名者必须是程序的升级权限
+pub fn initialize_protocol_config(admin: &Pubkey) -> Instruction {
+    instruction(
+        acc::InitializeProtocolConfig {
+            admin: *admin,
+            protocol_config: pda::protocol_config(),
+            program_data: pda::program_data(),
+            system_program: system_program::ID,
+        },
+        ix::InitializeProtocolConfig {},
+    )
+}
+
+fn update_protocol_config_accounts(admin: &Pubkey) -> acc::UpdateProtocolConfig {
+    acc::UpdateProtocolConfig {
+        admin: *admin,
+        protocol_config: pda::protocol_config(),
+    }
+}
+
+/// 传 `None` 的字段保持不变
+pub fn update_protocol_config(
+    admin: &Pubkey,
+    create_fee: Option<FeeRule>,
+    claim_fee: Option<FeeRule>,
+    fee_receiver: Option<Pubkey>,
+) -> Instruction {
+    instruction(
+        update_protocol_config_accounts(admin),
+        ix::UpdateProtocolConfig {
+            create_fee,
+            claim_fee,
+            fee_receiver,
+        },
+    )
+}
+
+/// `pause_flags` 由 `ProtocolConfig::PAUSE_*` 组合而成
+pub fn set_pause(admin: &Pubkey, pause_flags: u8) -> Instruction {
+    instruction(
+        update_protocol_config_accounts(admin),
+        ix::SetPause { pause_flags },
+    )
+}
+
+pub fn set_mint_risk_policy(admin: &Pubkey, mint_risk_policy: u8) -> Instruction {
+    instruction(
+        update_protocol_config_accounts(admin),
+        ix::SetMintRiskPolicy { mint_risk_policy },
+    )
+}
+
+pub fn propose_admin(admin: &Pubkey, new_admin: &Pubkey) -> Instruction {
+    instruction(
+        update_protocol_config_accounts(admin),
+        ix::ProposeAdmin {
+            new_admin: *new_admin,
+        },
+    )
+}
+
+pub fn accept_admin(pending_admin: &Pubkey) -> Instruction {
+    instruction(
+        acc::AcceptAdmin {
+            pending_admin: *pending_admin,
+            protocol_config: pda::protocol_config(),
+        },
+        ix::AcceptAdmin {},
+    )
+}
+
+// ---------------------------------------------------------------------------
+// 创建者状态与操作员
+// ---------------------------------------------------------------------------
+
+pub fn initialize_creator_state(creator: &Pubkey) -> Instruction {
+    instruction(
+        acc::InitializeCreatorState {
+            creator_state: pda::creator_state(creator),
+            creator: *creator,
+            system_program: system_program::ID,
+        },
+        ix::InitializeCreatorState {},
+    )
+}
+
+fn manage_operators_accounts(creator: &Pubkey) -> acc::ManageOperators {
+    acc::ManageOperators {
+        creator: *creator,
+        creator_state: pda::creator_state(creator),
+    }
+}
+
+/// `permissions` 由 `Operator::PERMISSION_*` 组合而成，已存在的操作员会被覆盖权限
+pub fn add_operator(creator: &Pubkey, operator: &Pubkey, permissions: u8) -> Instruction {
+    instruction(
+        manage_operators_accounts(creator),
+        ix::AddOperator {
+            operator: *operator,
+            permissions,
+        },
+    )
+}
+
+pub fn remove_operator(creator: &Pubkey, operator: &Pubkey) -> Instruction {
+    instruction(
+        manage_operators_accounts(creator),
+        ix::RemoveOperator {
+            operator: *operator,
+        },
+    )
+}
+
+// ---------------------------------------------------------------------------
+// 创建与领取
+// ---------------------------------------------------------------------------
+
+/// create_redpacket 的参数，可选字段默认都为 `None`
+#[derive(Clone, Debug, Default)]
+pub struct CreateParams {
+    pub total_amount: u64,
+    pub packet_count: u32,
+    pub red_packet_type: u8,
+    pub merkle_root: Option<[u8; 32]>,
+    pub expiry_days: Option<i64>,
+    pub random_seed: Option<u64>,
+    pub start_time: Option<i64>,
+    pub code_hash: Option<[u8; 32]>,
+    pub holder_gate: Option<HolderGate>,
+    pub prize_tiers: Option<Vec<PrizeTier>>,
+}
+
+impl CreateParams {
+    pub fn new(total_amount: u64, packet_count: u32, red_packet_type: u8) -> Self {
+        Self {
+            total_amount,
+            packet_count,
+            red_packet_type,
+            ..Self::default()
+        }
+    }
+}
+
+/// `red_packet_id` 必须等于创建者当前的 `next_red_packet_id`
+pub fn create_redpacket(
+    creator: &Pubkey,
+    red_packet_id: u64,
+    asset: &Asset,
+    fee_receiver: &Pubkey,
+    params: CreateParams,
+) -> Instruction {
+    let red_packet = pda::red_packet(creator, red_packet_id);
+    instruction(
+        acc::CreateRedPacket {
+            creator: *creator,
+            creator_state: pda::creator_state(creator),
+            red_packet,
+            mint: asset.mint(),
+            creator_ata: asset.token_account(creator),
+            pool_ata: asset.token_account(&red_packet),
+            protocol_config: pda::protocol_config(),
+            fee_receiver: *fee_receiver,
+            system_program: system_program::ID,
+            token_program: asset.token_program(),
+            associated_token_program: anchor_spl::associated_token::ID,
+            rent: sysvar::rent::ID,
+        },
+        ix::CreateRedpacket {
+            total_amount: params.total_amount,
+            packet_count: params.packet_count,
+            red_packet_type: params.red_packet_type,
+            merkle_root: params.merkle_root,
+            is_sol: asset.is_sol(),
+            expiry_days: params.expiry_days,
+            random_seed: params.random_seed,
+            start_time: params.start_time,
+            code_hash: params.code_hash,
+            holder_gate: params.holder_gate,
+            prize_tiers: params.prize_tiers,
+        },
+    )
+}
+
+/// claim_redpacket 的可选参数
+#[derive(Clone, Debug, Default)]
+pub struct ClaimParams {
+    /// 白名单红包（类型 2）的领取金额
+    pub amount: Option<u64>,
+    /// 白名单红包的 Merkle 证明
+    pub proof: Option<Vec<[u8; 32]>>,
+    /// 口令红包（类型 5/6）的口令明文，需要先 [`commit_claim`] 并等到下一个 slot
+    pub code: Option<Vec<u8>>,
+    /// 类型 3/7 同样要先 [`commit_claim`]（口令传空）并等到下一个 slot，领取时带上 claim_commit
+    pub commit: bool,
+    /// 持有者门槛红包：领取者持有的代币账户
+    pub gate_token_account: Option<Pubkey>,
+    /// 合集门槛红包：所持 NFT 的 Metaplex Metadata
+    pub gate_metadata: Option<Pubkey>,
+}
+
+/// 领取者自己付费领取。带口令或 `commit` 时自动带上领取者的 claim_commit PDA
+pub fn claim_redpacket(
+    claimer: &Pubkey,
+    creator: &Pubkey,
+    red_packet_id: u64,
+    asset: &Asset,
+    fee_receiver: &Pubkey,
+    params: ClaimParams,
+) -> Instruction {
+    let red_packet = pda::red_packet(creator, red_packet_id);
+    instruction(
+        acc::ClaimRedPacket {
+            claimer: *claimer,
+            red_packet,
+            user_state: pda::user_state(&red_packet, claimer),
+            mint: asset.mint(),
+            pool_ata: asset.token_account(&red_packet),
+            user_ata: asset.token_account(claimer),
+            protocol_config: pda::protocol_config(),
+            fee_receiver: *fee_receiver,
+            system_program: system_program::ID,
+            token_program: asset.token_program(),
+            associated_token_program: anchor_spl::associated_token::ID,
+            slot_hashes: sysvar::slot_hashes::ID,
+            claim_commit: (params.code.is_some() || params.commit)
+                .then(|| pda::claim_commit(&red_packet, claimer)),
+            gate_token_account: params.gate_token_account,
+            gate_metadata: params.gate_metadata,
+        },
+        ix::ClaimRedpacket {
+            amount: params.amount,
+            proof: params.proof,
+            red_packet_id,
+            code: params.code,
+        },
+    )
+}
+
+/// 口令红包领取的第一步：提交绑定领取者的口令承诺，口令本身不上链。
+/// 类型 3/7 也要先提交一次，`code` 传空，链上只记录提交时的 slot
+pub fn commit_claim(
+    claimer: &Pubkey,
+    creator: &Pubkey,
+    red_packet_id: u64,
+    code: &[u8],
+) -> Instruction {
+    let red_packet = pda::red_packet(creator, red_packet_id);
+    instruction(
+        acc::CommitClaim {
+            claimer: *claimer,
+            red_packet,
+            claim_commit: pda::claim_commit(&red_packet, claimer),
+            system_program: system_program::ID,
+        },
+        ix::CommitClaim {
+            red_packet_id,
+            commitment: red_packet::utils::claim_commitment(&red_packet, claimer, code),
+        },
+    )
+}
+
+/// 承诺的 slot 滑出 SlotHashes 后领取会报 `ClaimCommitExpired`，关闭承诺退回租金，之后可以重新
+/// [`commit_claim`]
+pub fn close_claim_commit(claimer: &Pubkey, creator: &Pubkey, red_packet_id: u64) -> Instruction {
+    let red_packet = pda::red_packet(creator, red_packet_id);
+    instruction(
+        acc::CloseClaimCommit {
+            claimer: *claimer,
+            red_packet,
+            claim_commit: pda::claim_commit(&red_packet, claimer),
+            slot_hashes: sysvar::slot_hashes::ID,
+        },
+        ix::CloseClaimCommit {},
+    )
+}
+
+/// 代领：`fee_payer` 付手续费和租金，资产发给 `recipient`。
+///
+/// 这条指令之前必须紧跟一条 ed25519 验签指令，由 `recipient` 对
+/// [`relay_claim_message`] 签名；验签指令由调用方用 ed25519 程序的 SDK 构建。
+#[allow(clippy::too_many_arguments)]
+pub fn relay_claim_redpacket(
+    fee_payer: &Pubkey,
+    recipient: &Pubkey,
+    creator: &Pubkey,
+    red_packet_id: u64,
+    asset: &Asset,
+    fee_receiver: &Pubkey,
+    amount: Option<u64>,
+    proof: Option<Vec<[u8; 32]>>,
+) -> Instruction {
+    let red_packet = pda::red_packet(creator, red_packet_id);
+    instruction(
+        acc::RelayClaimRedPacket {
+            fee_payer: *fee_payer,
+            recipient: *recipient,
+            red_packet,
+            user_state: pda::user_state(&red_packet, recipient),
+            mint: asset.mint(),
+            pool_ata: asset.token_account(&red_packet),
+            recipient_ata: asset.token_account(recipient),
+            protocol_config: pda::protocol_config(),
+            fee_receiver: *fee_receiver,
+            system_program: system_program::ID,
+            token_program: asset.token_program(),
+            associated_token_program: anchor_spl::associated_token::ID,
+            slot_hashes: sysvar::slot_hashes::ID,
+            instructions: sysvar::instructions::ID,
+            gate_token_account: None,
+            gate_metadata: None,
+        },
+        ix::RelayClaimRedpacket {
+            amount,
+            proof,
+            red_packet_id,
+        },
+    )
+}
+
+// ---------------------------------------------------------------------------
+// 大名单红包（类型 4）的领取位图
+// ---------------------------------------------------------------------------
+
+pub fn init_claim_bitmap(creator: &Pubkey, red_packet_id: u64) -> Instruction {
+    let red_packet = pda::red_packet(creator, red_packet_id);
+    instruction(
+        acc::InitClaimBitmap {
+            creator: *creator,
+            red_packet,
+            claim_bitmap: pda::claim_bitmap(&red_packet),
+            system_program: system_program::ID,
+        },
+        ix::InitClaimBitmap { red_packet_id },
+    )
+}
+
+/// 位图超过单次 realloc 上限时需要多次调用，任何人都可以付费
+pub fn extend_claim_bitmap(payer: &Pubkey, red_packet: &Pubkey) -> Instruction {
+    instruction(
+        acc::ExtendClaimBitmap {
+            payer: *payer,
+            claim_bitmap: pda::claim_bitmap(red_packet),
+            system_program: system_program::ID,
+        },
+        ix::ExtendClaimBitmap {},
+    )
+}
+
+/// 红包关闭或领完后回收位图租金，租金退回创建者
+pub fn close_claim_bitmap(creator: &Pubkey, red_packet: &Pubkey) -> Instruction {
+    instruction(
+        acc::CloseClaimBitmap {
+            red_packet: *red_packet,
+            claim_bitmap: pda::claim_bitmap(red_packet),
+            creator: *creator,
+        },
+        ix::CloseClaimBitmap {},
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn claim_indexed_redpacket(
+    claimer: &Pubkey,
+    creator: &Pubkey,
+    red_packet_id: u64,
+    asset: &Asset,
+    fee_receiver: &Pubkey,
+    index: u32,
+    amount: u64,
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    let red_packet = pda::red_packet(creator, red_packet_id);
+    instruction(
+        acc::ClaimIndexedRedPacket {
+            claimer: *claimer,
+            red_packet,
+            claim_bitmap: pda::claim_bitmap(&red_packet),
+            mint: asset.mint(),
+            pool_ata: asset.token_account(&red_packet),
+            user_ata: asset.token_account(claimer),
+            protocol_config: pda::protocol_config(),
+            fee_receiver: *fee_receiver,
+            system_program: system_program::ID,
+            token_program: asset.token_program(),
+            associated_token_program: anchor_spl::associated_token::ID,
+        },
+        ix::ClaimIndexedRedpacket {
+            index,
+            amount,
+            proof,
+            red_packet_id,
+        },
+    )
+}
+
+// ---------------------------------------------------------------------------
+// 退款、取消与清理
+// ---------------------------------------------------------------------------
+
+/// 过期后退回剩余资产；代币红包同时关闭池子 ATA
+pub fn refund(creator: &Pubkey, red_packet_id: u64, asset: &Asset) -> Instruction {
+    let red_packet = pda::red_packet(creator, red_packet_id);
+    let refund = instruction(
+        acc::Refund {
+            creator: *creator,
+            red_packet,
+            mint: asset.mint(),
+            creator_ata: asset.token_account(creator),
+            pool_ata: asset.token_account(&red_packet),
+            token_program: asset.token_program(),
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        ix::Refund { red_packet_id },
+    );
+    with_writable_mint(refund, REFUND_MINT_INDEX, asset)
+}
+
+/// 创建者或有取消权限的操作员提前结束红包，`grace_period` 为 `None` 时立即退款
+pub fn cancel_redpacket(
+    authority: &Pubkey,
+    creator: &Pubkey,
+    red_packet_id: u64,
+    asset: &Asset,
+    grace_period: Option<i64>,
+) -> Instruction {
+    let red_packet = pda::red_packet(creator, red_packet_id);
+    let cancel = instruction(
+        acc::CancelRedPacket {
+            authority: *authority,
+            red_packet,
+            creator_state: pda::creator_state(creator),
+            creator: *creator,
+            mint: asset.mint(),
+            creator_ata: asset.token_account(creator),
+            pool_ata: asset.token_account(&red_packet),
+            token_program: asset.token_program(),
+            system_program: system_program::ID,
+        },
+        ix::CancelRedpacket {
+            red_packet_id,
+            grace_period,
+        },
+    );
+    with_writable_mint(cancel, CANCEL_MINT_INDEX, asset)
+}
+
+/// 领完后关闭红包账户和池子，取回租金
+pub fn close_redpacket(creator: &Pubkey, red_packet_id: u64, asset: &Asset) -> Instruction {
+    let red_packet = pda::red_packet(creator, red_packet_id);
+    let close = instruction(
+        acc::CloseRedPacket {
+            creator: *creator,
+            red_packet,
+            mint: asset.mint(),
+            creator_ata: asset.token_account(creator),
+            pool_ata: asset.token_account(&red_packet),
+            token_program: asset.token_program(),
+            system_program: system_program::ID,
+        },
+        ix::CloseRedpacket { red_packet_id },
+    );
+    with_writable_mint(close, REFUND_MINT_INDEX, asset)
+}
+
+/// 批量关闭 UserState，租金退还各自的领取者；单次最多 `MAX_USER_STATE_SWEEP` 个
+pub fn close_user_states(red_packet: &Pubkey, claimers: &[Pubkey]) -> Instruction {
+    let mut sweep = instruction(
+        acc::CloseUserStates {
+            red_packet: *red_packet,
+        },
+        ix::CloseUserStates {},
+    );
+    for claimer in claimers {
+        sweep.accounts.extend([
+            AccountMeta::new(pda::user_state(red_packet, claimer), false),
+            AccountMeta::new(*claimer, false),
+        ]);
+    }
+    sweep
+}
+
+// ---------------------------------------------------------------------------
+// 修改进行中的红包
+// ---------------------------------------------------------------------------
+
+fn set_time_accounts(
+    authority: &Pubkey,
+    creator: &Pubkey,
+    red_packet_id: u64,
+) -> acc::SetExpiryTime {
+    acc::SetExpiryTime {
+        red_packet: pda::red_packet(creator, red_packet_id),
+        authority: *authority,
+        creator_state: pda::creator_state(creator),
+        protocol_config: pda::protocol_config(),
+    }
+}
+
+pub fn set_expiry_time(
+    authority: &Pubkey,
+    creator: &Pubkey,
+    red_packet_id: u64,
+    expiry_time: i64,
+) -> Instruction {
+    instruction(
+        set_time_accounts(authority, creator, red_packet_id),
+        ix::SetExpiryTime { expiry_time },
+    )
+}
+
+pub fn set_start_time(
+    authority: &Pubkey,
+    creator: &Pubkey,
+    red_packet_id: u64,
+    start_time: i64,
+) -> Instruction {
+    instruction(
+        set_time_accounts(authority, creator, red_packet_id),
+        ix::SetStartTime { start_time },
+    )
+}
+
+/// 创建者或有追加权限的操作员追加资金，资金来自 `authority`
+#[allow(clippy::too_many_arguments)]
+pub fn top_up_redpacket(
+    authority: &Pubkey,
+    creator: &Pubkey,
+    red_packet_id: u64,
+    asset: &Asset,
+    fee_receiver: &Pubkey,
+    amount: u64,
+    extra_packets: u32,
+) -> Instruction {
+    let red_packet = pda::red_packet(creator, red_packet_id);
+    instruction(
+        acc::TopUpRedPacket {
+            authority: *authority,
+            red_packet,
+            creator_state: pda::creator_state(creator),
+            mint: asset.mint(),
+            authority_ata: asset.token_account(authority),
+            pool_ata: asset.token_account(&red_packet),
+            protocol_config: pda::protocol_config(),
+            fee_receiver: *fee_receiver,
+            system_program: system_program::ID,
+            token_program: asset.token_program(),
+        },
+        ix::TopUpRedpacket {
+            red_packet_id,
+            amount,
+            extra_packets,
+        },
+    )
+}
+
+// ---------------------------------------------------------------------------
+// 旧布局迁移，任何人都可以付费调用
+// ---------------------------------------------------------------------------
+
+pub fn migrate_redpacket(payer: &Pubkey, red_packet: &Pubkey) -> Instruction {
+    instruction(
+        acc::MigrateRedPacket {
+            payer: *payer,
+            red_packet: *red_packet,
+            system_program: system_program::ID,
+        },
+        ix::MigrateRedpacket {},
+    )
+}
+
+pub fn migrate_creator_state(payer: &Pubkey, creator: &Pubkey) -> Instruction {
+    instruction(
+        acc::MigrateCreatorState {
+            payer: *payer,
+            creator_state: pda::creator_state(creator),
+            system_program: system_program::ID,
+        },
+        ix::MigrateCreatorState {},
+    )
+}
+
+// ---------------------------------------------------------------------------
+// 组合红包
+// ---------------------------------------------------------------------------
+
+/// 每份发放 `sol_per_claim` lamports，以及 `legs` 中每种代币各自的数量
+pub fn create_bundle(
+    creator: &Pubkey,
+    bundle_id: u64,
+    fee_receiver: &Pubkey,
+    packet_count: u32,
+    sol_per_claim: u64,
+    legs: &[(TokenMint, u64)],
+    expiry_days: Option<i64>,
+) -> Instruction {
+    let bundle = pda::bundle(creator, bundle_id);
+    let mut create = instruction(
+        acc::CreateBundle {
+            creator: *creator,
+            creator_state: pda::creator_state(creator),
+            bundle,
+            protocol_config: pda::protocol_config(),
+            fee_receiver: *fee_receiver,
+            system_program: system_program::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+        },
+        ix::CreateBundle {
+            packet_count,
+            sol_per_claim,
+            token_amounts: legs.iter().map(|(_, amount)| *amount).collect(),
+            expiry_days,
+        },
+    );
+    for (token, _) in legs {
+        create.accounts.extend([
+            AccountMeta::new_readonly(token.mint, false),
+            AccountMeta::new(token.ata(creator), false),
+            AccountMeta::new(token.ata(&bundle), false),
+            AccountMeta::new_readonly(token.token_program, false),
+        ]);
+    }
+    create
+}
+
+/// `legs` 必须与创建时的顺序一致
+pub fn claim_bundle(
+    claimer: &Pubkey,
+    creator: &Pubkey,
+    bundle_id: u64,
+    fee_receiver: &Pubkey,
+    legs: &[TokenMint],
+) -> Instruction {
+    let bundle = pda::bundle(creator, bundle_id);
+    let mut claim = instruction(
+        acc::ClaimBundle {
+            claimer: *claimer,
+            bundle,
+            user_state: pda::user_state(&bundle, claimer),
+            protocol_config: pda::protocol_config(),
+            fee_receiver: *fee_receiver,
+            system_program: system_program::ID,
+            associated_token_program: anchor_spl::associated_token::ID,
+        },
+        ix::ClaimBundle { bundle_id },
+    );
+    for token in legs {
+        claim.accounts.extend([
+            AccountMeta::new_readonly(token.mint, false),
+            AccountMeta::new(token.ata(&bundle), false),
+            AccountMeta::new(token.ata(claimer), false),
+            AccountMeta::new_readonly(token.token_program, false),
+        ]);
+    }
+    claim
+}
+
+/// `legs` 必须与创建时的顺序一致
+pub fn refund_bundle(creator: &Pubkey, bundle_id: u64, legs: &[TokenMint]) -> Instruction {
+    let bundle = pda::bundle(creator, bundle_id);
+    let mut refund = instruction(
+        acc::RefundBundle {
+            creator: *creator,
+            bundle,
+        },
+        ix::RefundBundle { bundle_id },
+    );
+    for token in legs {
+        refund.accounts.extend([
+            AccountMeta::new_readonly(token.mint, false),
+            AccountMeta::new(token.ata(&bundle), false),
+            AccountMeta::new(token.ata(creator), false),
+            AccountMeta::new_readonly(token.token_program, false),
+        ]);
+    }
+    refund
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sol_accounts_use_wallets() {
+        let creator = Pubkey::new_unique();
+        let red_packet = pda::red_packet(&creator, 0);
+        let create = create_redpacket(
+            &creator,
+            0,
+            &Asset::Sol,
+            &Pubkey::new_unique(),
+            CreateParams::new(1_000, 2, 0),
+        );
+        assert_eq!(create.accounts[3].pubkey, Pubkey::default());
+        assert_eq!(create.accounts[4].pubkey, creator);
+        assert_eq!(create.accounts[5].pubkey, red_packet);
+        assert_eq!(create.accounts[9].pubkey, anchor_spl::token_2022::ID);
+    }
+
+    #[test]
+    fn test_refund_marks_token_mint_writable() {
+        let creator = Pubkey::new_unique();
+        let token = Asset::Token(TokenMint::token_2022(Pubkey::new_unique()));
+        for refund in [
+            refund(&creator, 0, &token),
+            close_redpacket(&creator, 0, &token),
+        ] {
+            assert!(refund.accounts[REFUND_MINT_INDEX].is_writable);
+            assert_eq!(refund.accounts[REFUND_MINT_INDEX].pubkey, token.mint());
+        }
+        let cancel = cancel_redpacket(&creator, &creator, 0, &token, None);
+        assert!(cancel.accounts[CANCEL_MINT_INDEX].is_writable);
+        assert_eq!(cancel.accounts[CANCEL_MINT_INDEX].pubkey, token.mint());
+
+        let sol = refund(&creator, 0, &Asset::Sol);
+        assert!(!sol.accounts[REFUND_MINT_INDEX].is_writable);
+    }
+
+    #[test]
+    fn test_claim_includes_commit_only_when_needed() {
+        let creator = Pubkey::new_unique();
+        let claimer = Pubkey::new_unique();
+        let red_packet = pda::red_packet(&creator, 0);
+        let fee_receiver = Pubkey::new_unique();
+        let without_code = claim_redpacket(
+            &claimer,
+            &creator,
+            0,
+            &Asset::Sol,
+            &fee_receiver,
+            ClaimParams::default(),
+        );
+        // 未传入的可选账户以程序 ID 占位
+        assert_eq!(without_code.accounts[12].pubkey, red_packet::ID);
+
+        let with_code = claim_redpacket(
+            &claimer,
+            &creator,
+            0,
+            &Asset::Sol,
+            &fee_receiver,
+            ClaimParams {
+                code: Some(b"code".to_vec()),
+                ..ClaimParams::default()
+            },
+        );
+        assert_eq!(
+            with_code.accounts[12].pubkey,
+            pda::claim_commit(&red_packet, &claimer)
+        );
+
+        let committed = claim_redpacket(
+            &claimer,
+            &creator,
+            0,
+            &Asset::Sol,
+            &fee_receiver,
+            ClaimParams {
+                commit: true,
+                ..ClaimParams::default()
+            },
+        );
+        assert_eq!(committed.accounts[12].pubkey, with_code.accounts[12].pubkey);
+        assert_eq!(committed.data, without_code.data);
+    }
+
+    #[test]
+    fn test_bundle_legs_append_four_accounts_each() {
+        let creator = Pubkey::new_unique();
+        let legs = [
+            TokenMint::spl_token(Pubkey::new_unique()),
+            TokenMint::token_2022(Pubkey::new_unique()),
+        ];
+        let create = create_bundle(
+            &creator,
+            0,
+            &Pubkey::new_unique(),
+            2,
+            0,
+            &[(legs[0], 10), (legs[1], 20)],
+            None,
+        );
+        assert_eq!(create.accounts.len(), 7 + 8);
+        assert_eq!(create.accounts[7 + 4].pubkey, legs[1].mint);
+        assert_eq!(
+            create.accounts[7 + 6].pubkey,
+            legs[1].ata(&pda::bundle(&creator, 0))
+        );
+
+        let refund = refund_bundle(&creator, 0, &legs);
+        assert_eq!(refund.accounts.len(), 2 + 8);
+        assert!(!refund.accounts[2].is_writable);
+        assert!(refund.accounts[3].is_writable);
+
+        let sweep = close_user_states(&pda::bundle(&creator, 0), &[creator]);
+        assert_eq!(sweep.accounts.len(), 3);
+        assert!(sweep.accounts[2].is_writable);
+    }
+}
@@ -0,0 +1,87 @@
+//! red_packet 程序的 Rust 客户端：PDA 推导、每条指令的构建函数和账户解码。
+//!
+//! 指令构建函数按资产类型自动填好账户：SOL 红包的 `mint` 传 `Pubkey::default()`，
+//! `creator_ata` / `pool_ata` 分别是创建者和红包地址本身；代币红包则使用对应 token program 下的 ATA。
+//! 带转账钩子的 mint 需要调用方在返回的指令末尾追加钩子程序要求的额外账户。
+//!
+//! `events` 从交易日志解码程序发出的事件；开启 `indexer` 特性后可以把事件折叠成红包状态存进 SQLite。
+//! 命令行工具 `red-packet` 需要开启 `cli` 特性：`cargo run --features cli -- <子命令>`。
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+
+pub mod accounts;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod events;
+#[cfg(feature = "indexer")]
+pub mod indexer;
+pub mod instructions;
+pub mod pda;
+
+pub use red_packet::ID as PROGRAM_ID;
+
+/// 代币红包的 mint 及其所属的 token program（旧版 SPL Token 或 Token-2022）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenMint {
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+}
+
+impl TokenMint {
+    pub fn spl_token(mint: Pubkey) -> Self {
+        Self {
+            mint,
+            token_program: anchor_spl::token::ID,
+        }
+    }
+
+    pub fn token_2022(mint: Pubkey) -> Self {
+        Self {
+            mint,
+            token_program: anchor_spl::token_2022::ID,
+        }
+    }
+
+    /// owner 持有该代币的 ATA
+    pub fn ata(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.mint, &self.token_program)
+    }
+}
+
+/// 红包里发放的资产
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Asset {
+    Sol,
+    Token(TokenMint),
+}
+
+impl Asset {
+    pub fn is_sol(&self) -> bool {
+        matches!(self, Asset::Sol)
+    }
+
+    /// 指令里的 mint 账户，SOL 红包为 `Pubkey::default()`
+    pub fn mint(&self) -> Pubkey {
+        match self {
+            Asset::Sol => Pubkey::default(),
+            Asset::Token(token) => token.mint,
+        }
+    }
+
+    /// SOL 红包同样要传一个合法的 token program，程序不会调用它
+    pub fn token_program(&self) -> Pubkey {
+        match self {
+            Asset::Sol => anchor_spl::token_2022::ID,
+            Asset::Token(token) => token.token_program,
+        }
+    }
+
+    /// owner 收发该资产的账户：SOL 为 owner 本身，代币为 owner 的 ATA
+    pub fn token_account(&self, owner: &Pubkey) -> Pubkey {
+        match self {
+            Asset::Sol => *owner,
+            Asset::Token(token) => token.ata(owner),
+        }
+    }
+}
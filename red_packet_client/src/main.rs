@@ -0,0 +1,20 @@
+use anyhow::Result;
+use clap::Parser;
+use red_packet_client::cli::{self, Cli};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let (url, payer) = cli::load_config(&cli)?;
+    let mut rpc = RpcClient::new_with_commitment(url, CommitmentConfig::confirmed());
+    cli::run(
+        &mut rpc,
+        &payer,
+        cli.dry_run,
+        cli.command,
+        &mut std::io::stdout(),
+    )
+    .await
+}
@@ -0,0 +1,107 @@
+//! 程序使用的所有 PDA，种子与链上账户约束一致
+
+use anchor_lang::prelude::{Owner, ProgramData, Pubkey};
+use red_packet::{
+    config::{
+        BUNDLE_SEED, CLAIM_BITMAP_SEED, CLAIM_COMMIT_SEED, CREATOR_STATE_SEED, PROTOCOL_CONFIG_SEED,
+    },
+    ID,
+};
+
+const RED_PACKET_SEED: &[u8] = b"red_packet";
+const USER_STATE_SEED: &[u8] = b"user_state";
+
+pub fn protocol_config() -> Pubkey {
+    Pubkey::find_program_address(&[PROTOCOL_CONFIG_SEED], &ID).0
+}
+
+/// 程序在升级加载器下的 ProgramData，初始化全局配置时校验其中的升级权限
+pub fn program_data() -> Pubkey {
+    Pubkey::find_program_address(&[ID.as_ref()], &ProgramData::owner()).0
+}
+
+pub fn creator_state(creator: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[CREATOR_STATE_SEED, creator.as_ref()], &ID).0
+}
+
+/// `red_packet_id` 来自创建时 CreatorState 的 next_red_packet_id
+pub fn red_packet(creator: &Pubkey, red_packet_id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            RED_PACKET_SEED,
+            creator.as_ref(),
+            &red_packet_id.to_le_bytes(),
+        ],
+        &ID,
+    )
+    .0
+}
+
+/// 组合红包与普通红包共用编号，但种子前缀不同
+pub fn bundle(creator: &Pubkey, bundle_id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[BUNDLE_SEED, creator.as_ref(), &bundle_id.to_le_bytes()],
+        &ID,
+    )
+    .0
+}
+
+/// 普通红包和组合红包都用这个 PDA 记录是否已领取，`red_packet` 传对应的账户地址
+pub fn user_state(red_packet: &Pubkey, claimer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[USER_STATE_SEED, red_packet.as_ref(), claimer.as_ref()],
+        &ID,
+    )
+    .0
+}
+
+pub fn claim_bitmap(red_packet: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[CLAIM_BITMAP_SEED, red_packet.as_ref()], &ID).0
+}
+
+pub fn claim_commit(red_packet: &Pubkey, claimer: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[CLAIM_COMMIT_SEED, red_packet.as_ref(), claimer.as_ref()],
+        &ID,
+    )
+    .0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distinct_seeds_give_distinct_addresses() {
+        let creator = Pubkey::new_unique();
+        let claimer = Pubkey::new_unique();
+        let red_packet = red_packet(&creator, 0);
+        let addresses = [
+            protocol_config(),
+            creator_state(&creator),
+            red_packet,
+            super::red_packet(&creator, 1),
+            bundle(&creator, 0),
+            user_state(&red_packet, &claimer),
+            claim_bitmap(&red_packet),
+            claim_commit(&red_packet, &claimer),
+        ];
+        for (i, a) in addresses.iter().enumerate() {
+            assert!(!a.is_on_curve());
+            for b in &addresses[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_red_packet_id_is_little_endian() {
+        let creator = Pubkey::new_unique();
+        let expected = Pubkey::find_program_address(
+            &[b"red_packet", creator.as_ref(), &[1, 0, 0, 0, 0, 0, 0, 0]],
+            &ID,
+        )
+        .0;
+        assert_eq!(red_packet(&creator, 1), expected);
+    }
+}
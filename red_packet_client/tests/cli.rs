@@ -0,0 +1,417 @@
+//! 命令行对接 program-test：用 BanksClient 实现 `Rpc`，充当本地测试验证器
+#![allow(deprecated)]
+
+use anchor_lang::{
+    prelude::AccountInfo,
+    solana_program::{entrypoint::ProgramResult, program_pack::Pack},
+};
+use anchor_spl::{associated_token::spl_associated_token_account, token_2022::spl_token_2022};
+use anyhow::Result;
+use clap::Parser;
+use red_packet::config::FEE_RECEIVER;
+use red_packet_client::{
+    accounts::{decode_creator_state, decode_red_packet},
+    cli::{
+        self,
+        rpc::{Rpc, Simulation},
+        Cli,
+    },
+    instructions, pda, TokenMint,
+};
+use red_packet_merkle::{io, LeafKind, MerkleTree, Recipient};
+use solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
+    hash::Hash,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signature, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+
+struct Banks(BanksClient);
+
+impl Rpc for Banks {
+    async fn get_account(&mut self, address: &Pubkey) -> Result<Option<Account>> {
+        Ok(self.0.get_account(*address).await?)
+    }
+
+    async fn latest_blockhash(&mut self) -> Result<Hash> {
+        Ok(self.0.get_latest_blockhash().await?)
+    }
+
+    async fn send_transaction(&mut self, tx: &Transaction) -> Result<Signature> {
+        self.0.process_transaction(tx.clone()).await?;
+        Ok(tx.signatures[0])
+    }
+
+    async fn simulate_transaction(&mut self, tx: &Transaction) -> Result<Simulation> {
+        let simulation = self.0.simulate_transaction(tx.clone()).await?;
+        let (units_consumed, logs) = simulation
+            .simulation_details
+            .map(|details| (details.units_consumed, details.logs))
+            .unwrap_or_default();
+        Ok(Simulation {
+            units_consumed,
+            logs,
+            error: simulation
+                .result
+                .and_then(|result| result.err())
+                .map(|err| err.to_string()),
+        })
+    }
+}
+
+fn process_red_packet(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor 的 entry 要求 accounts 与 AccountInfo 同生命周期，测试里直接泄漏一份拷贝
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    red_packet::entry(program_id, accounts, data)
+}
+
+/// 启动程序并由付款人初始化全局配置，返回付款人资助过的创建者
+async fn start() -> (ProgramTestContext, Keypair) {
+    let mut program_test =
+        ProgramTest::new("red_packet", red_packet::ID, processor!(process_red_packet));
+    program_test.add_account(
+        FEE_RECEIVER,
+        Account {
+            lamports: 1_000_000_000,
+            ..Account::default()
+        },
+    );
+    let mut context = program_test.start_with_context().await;
+    let creator = Keypair::new();
+    let admin = context.payer.pubkey();
+    set_upgrade_authority(&mut context, &admin);
+    let instructions = [
+        instructions::initialize_protocol_config(&admin),
+        system_instruction::transfer(&admin, &creator.pubkey(), 10_000_000_000),
+    ];
+    send(&mut context, &instructions, &[]).await;
+    (context, creator)
+}
+
+/// 原生加载的程序没有 ProgramData，按升级加载器的布局补一个，升级权限为 authority
+fn set_upgrade_authority(context: &mut ProgramTestContext, authority: &Pubkey) {
+    let state = UpgradeableLoaderState::ProgramData {
+        slot: 0,
+        upgrade_authority_address: Some(*authority),
+    };
+    let lamports =
+        Rent::default().minimum_balance(UpgradeableLoaderState::size_of_programdata_metadata());
+    let account = Account::new_data(lamports, &state, &bpf_loader_upgradeable::ID).unwrap();
+    context.set_account(&pda::program_data(), &account.into());
+}
+
+async fn send(
+    context: &mut ProgramTestContext,
+    instructions: &[solana_sdk::instruction::Instruction],
+    signers: &[&Keypair],
+) {
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    context.banks_client.process_transaction(tx).await.unwrap();
+}
+
+async fn new_claimer(context: &mut ProgramTestContext) -> Keypair {
+    let claimer = Keypair::new();
+    let ix = system_instruction::transfer(&context.payer.pubkey(), &claimer.pubkey(), 100_000_000);
+    send(context, &[ix], &[]).await;
+    claimer
+}
+
+/// 以 `signer` 的身份执行一条命令行，返回输出
+async fn red_packet(
+    context: &mut ProgramTestContext,
+    signer: &Keypair,
+    args: &[&str],
+) -> Result<String> {
+    // 每条命令换一个 blockhash，避免重复交易被去重
+    context.get_new_latest_blockhash().await?;
+    let cli = Cli::try_parse_from(std::iter::once("red-packet").chain(args.iter().copied()))?;
+    let mut rpc = Banks(context.banks_client.clone());
+    let mut out = Vec::new();
+    cli::run(&mut rpc, signer, cli.dry_run, cli.command, &mut out).await?;
+    Ok(String::from_utf8(out)?)
+}
+
+async fn account_data(context: &mut ProgramTestContext, address: &Pubkey) -> Option<Vec<u8>> {
+    let account = context.banks_client.get_account(*address).await.unwrap()?;
+    Some(account.data)
+}
+
+#[tokio::test]
+async fn test_cli_sol_lifecycle() {
+    let (mut context, creator) = start().await;
+    let creator_key = creator.pubkey().to_string();
+    let red_packet_address = pda::red_packet(&creator.pubkey(), 0);
+
+    red_packet(&mut context, &creator, &["init-creator"])
+        .await
+        .unwrap();
+
+    // 模拟不会改变链上状态
+    let amount = TOTAL_AMOUNT.to_string();
+    let create = [
+        "create", "--amount", &amount, "--count", "4", "--kind", "random",
+    ];
+    let output = red_packet(
+        &mut context,
+        &creator,
+        &[&["--dry-run"], &create[..]].concat(),
+    )
+    .await
+    .unwrap();
+    let units: u64 = output
+        .lines()
+        .find_map(|line| line.strip_prefix("compute units: "))
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(units > 0);
+    assert!(account_data(&mut context, &red_packet_address)
+        .await
+        .is_none());
+
+    let output = red_packet(&mut context, &creator, &create).await.unwrap();
+    assert!(output.contains(&format!("red packet 0: {red_packet_address}")));
+    let state = account_data(&mut context, &pda::creator_state(&creator.pubkey()))
+        .await
+        .unwrap();
+    assert_eq!(decode_creator_state(&state).unwrap().next_red_packet_id, 1);
+
+    let output = red_packet(&mut context, &creator, &["show", "0"])
+        .await
+        .unwrap();
+    assert!(output.contains("type:        3 (random-slot)"));
+    assert!(output.contains("claimed:     0/4"));
+    assert!(output.contains("status:      active"));
+
+    let claimer = new_claimer(&mut context).await;
+    let claim = ["claim", "0", "--creator", &creator_key];
+    // 第一次只提交承诺，下一个 slot 再领取
+    let output = red_packet(&mut context, &claimer, &claim).await.unwrap();
+    assert!(output.contains("claim committed"));
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    context.warp_to_slot(clock.slot + 1).unwrap();
+    red_packet(&mut context, &claimer, &claim).await.unwrap();
+    let claimed = decode_red_packet(
+        &account_data(&mut context, &red_packet_address)
+            .await
+            .unwrap(),
+    )
+    .unwrap()
+    .claimed_count;
+    assert_eq!(claimed, 1);
+    // 重复领取在模拟阶段就失败，并打印程序日志
+    let error = red_packet(
+        &mut context,
+        &claimer,
+        &[&["--dry-run"], &claim[..]].concat(),
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().starts_with("simulation failed"));
+
+    let before = decode_red_packet(
+        &account_data(&mut context, &red_packet_address)
+            .await
+            .unwrap(),
+    )
+    .unwrap()
+    .expiry_time;
+    red_packet(&mut context, &creator, &["extend", "0", "--days", "1"])
+        .await
+        .unwrap();
+    let red_packet_state = decode_red_packet(
+        &account_data(&mut context, &red_packet_address)
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(red_packet_state.expiry_time, before + 24 * 60 * 60);
+
+    let output = red_packet(&mut context, &claimer, &["list", "--creator", &creator_key])
+        .await
+        .unwrap();
+    assert!(output.contains(&red_packet_address.to_string()));
+    assert!(output.contains("1/4"));
+
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = red_packet_state.expiry_time + 1;
+    context.set_sysvar(&clock);
+    red_packet(&mut context, &creator, &["refund", "0"])
+        .await
+        .unwrap();
+    let output = red_packet(&mut context, &creator, &["list"]).await.unwrap();
+    assert!(output.contains("closed"));
+}
+
+#[tokio::test]
+async fn test_cli_token_red_packet() {
+    let (mut context, creator) = start().await;
+    red_packet(&mut context, &creator, &["init-creator"])
+        .await
+        .unwrap();
+
+    let mint = Keypair::new();
+    let token = TokenMint::token_2022(mint.pubkey());
+    let payer = context.payer.pubkey();
+    let mint_len = spl_token_2022::state::Mint::LEN;
+    let instructions = [
+        system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            Rent::default().minimum_balance(mint_len),
+            mint_len as u64,
+            &token.token_program,
+        ),
+        spl_token_2022::instruction::initialize_mint2(
+            &token.token_program,
+            &mint.pubkey(),
+            &payer,
+            None,
+            6,
+        )
+        .unwrap(),
+        spl_associated_token_account::instruction::create_associated_token_account(
+            &payer,
+            &creator.pubkey(),
+            &mint.pubkey(),
+            &token.token_program,
+        ),
+        spl_token_2022::instruction::mint_to(
+            &token.token_program,
+            &mint.pubkey(),
+            &token.ata(&creator.pubkey()),
+            &payer,
+            &[],
+            TOTAL_AMOUNT,
+        )
+        .unwrap(),
+    ];
+    send(&mut context, &instructions, &[&mint]).await;
+
+    let mint_key = mint.pubkey().to_string();
+    let amount = TOTAL_AMOUNT.to_string();
+    red_packet(
+        &mut context,
+        &creator,
+        &[
+            "create", "--amount", &amount, "--count", "2", "--mint", &mint_key,
+        ],
+    )
+    .await
+    .unwrap();
+    let output = red_packet(&mut context, &creator, &["show", "0"])
+        .await
+        .unwrap();
+    assert!(output.contains(&format!("asset:       {mint_key}")));
+
+    let claimer = new_claimer(&mut context).await;
+    let creator_key = creator.pubkey().to_string();
+    red_packet(
+        &mut context,
+        &claimer,
+        &["claim", "0", "--creator", &creator_key],
+    )
+    .await
+    .unwrap();
+    let ata = account_data(&mut context, &token.ata(&claimer.pubkey()))
+        .await
+        .unwrap();
+    let balance = spl_token_2022::state::Account::unpack_from_slice(
+        &ata[..spl_token_2022::state::Account::LEN],
+    )
+    .unwrap()
+    .amount;
+    assert_eq!(balance, TOTAL_AMOUNT / 2);
+}
+
+#[tokio::test]
+async fn test_cli_merkle_red_packet() {
+    let (mut context, creator) = start().await;
+    red_packet(&mut context, &creator, &["init-creator"])
+        .await
+        .unwrap();
+    let claimers = [
+        new_claimer(&mut context).await,
+        new_claimer(&mut context).await,
+    ];
+    let recipients = claimers
+        .iter()
+        .zip([300_000_000, 700_000_000])
+        .map(|(claimer, amount)| Recipient {
+            pubkey: claimer.pubkey(),
+            amount,
+        })
+        .collect();
+    let tree = MerkleTree::new(LeafKind::Plain, recipients).unwrap();
+
+    let dir = std::env::temp_dir().join(format!("red-packet-cli-{}", Pubkey::new_unique()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let tree_path = dir.join("tree.json");
+    std::fs::write(
+        &tree_path,
+        serde_json::to_string(&io::tree_file(&tree)).unwrap(),
+    )
+    .unwrap();
+    let tree_arg = tree_path.to_string_lossy().into_owned();
+    red_packet(
+        &mut context,
+        &creator,
+        &["create", "--kind", "merkle", "--tree", &tree_arg],
+    )
+    .await
+    .unwrap();
+
+    let creator_key = creator.pubkey().to_string();
+    let claim = ["claim", "0", "--creator", &creator_key];
+    assert!(red_packet(&mut context, &claimers[0], &claim)
+        .await
+        .unwrap_err()
+        .to_string()
+        .contains("--proof-file"));
+
+    let proof_files = io::proof_files(&tree).unwrap();
+    let proof_file = proof_files
+        .iter()
+        .find(|file| file.pubkey == claimers[1].pubkey().to_string())
+        .unwrap();
+    let proof_path = dir.join("proof.json");
+    std::fs::write(&proof_path, serde_json::to_string(proof_file).unwrap()).unwrap();
+    let proof_arg = proof_path.to_string_lossy().into_owned();
+    // 证明文件属于另一个领取者
+    assert!(red_packet(
+        &mut context,
+        &claimers[0],
+        &[&claim[..], &["--proof-file", &proof_arg]].concat()
+    )
+    .await
+    .is_err());
+    red_packet(
+        &mut context,
+        &claimers[1],
+        &[&claim[..], &["--proof-file", &proof_arg]].concat(),
+    )
+    .await
+    .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let output = red_packet(&mut context, &creator, &["show", "0"])
+        .await
+        .unwrap();
+    assert!(output.contains("remaining:   300000000"));
+}
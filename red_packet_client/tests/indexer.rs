@@ -0,0 +1,198 @@
+//! 用 tests/fixtures 下录制的交易日志驱动索引器，重新录制见 tests/record_fixtures.rs
+
+use anchor_lang::prelude::Pubkey;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use red_packet_client::{
+    events::{parse_logs, RedPacketEvent},
+    indexer::{Indexer, PacketStatus},
+};
+use serde_json::Value;
+use std::path::Path;
+
+struct Fixture {
+    signature: String,
+    slot: u64,
+    logs: Vec<String>,
+}
+
+fn load(name: &str) -> Vec<Fixture> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    let value: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    value
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tx| Fixture {
+            signature: tx["signature"].as_str().unwrap().to_string(),
+            slot: tx["slot"].as_u64().unwrap(),
+            logs: serde_json::from_value(tx["logs"].clone()).unwrap(),
+        })
+        .collect()
+}
+
+fn index_all(indexer: &mut Indexer, fixtures: &[Fixture]) -> Vec<bool> {
+    fixtures
+        .iter()
+        .map(|tx| {
+            indexer
+                .index_transaction(&tx.signature, tx.slot, &tx.logs)
+                .unwrap()
+        })
+        .collect()
+}
+
+fn event_names(fixtures: &[Fixture]) -> Vec<&'static str> {
+    fixtures
+        .iter()
+        .flat_map(|tx| parse_logs(&tx.logs).unwrap())
+        .map(|event| event.name())
+        .collect()
+}
+
+/// 录制的第一笔交易是创建
+fn created_red_packet(fixtures: &[Fixture]) -> Pubkey {
+    match parse_logs(&fixtures[0].logs).unwrap().as_slice() {
+        [RedPacketEvent::RedPacketCreated(event)] => event.red_packet,
+        events => panic!("unexpected {} events", events.len()),
+    }
+}
+
+#[test]
+fn test_index_sol_lifecycle() {
+    let fixtures = load("sol_lifecycle.json");
+    assert_eq!(
+        event_names(&fixtures),
+        [
+            "RedPacketCreated",
+            "RedPacketClaimed",
+            "RedPacketClaimed",
+            "ExpiryTimeUpdated",
+            "RedPacketRefunded",
+        ]
+    );
+    let address = created_red_packet(&fixtures);
+
+    let dir = std::env::temp_dir().join(format!("red-packet-indexer-{}", Pubkey::new_unique()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db_path = dir.join("index.sqlite");
+    let mut indexer = Indexer::open(&db_path).unwrap();
+    assert!(index_all(&mut indexer, &fixtures).iter().all(|&new| new));
+    drop(indexer);
+
+    // 重新打开后状态仍在，重复送入的交易会被跳过
+    let mut indexer = Indexer::open(&db_path).unwrap();
+    assert!(index_all(&mut indexer, &fixtures).iter().all(|&new| !new));
+
+    let record = indexer.red_packet(&address).unwrap().unwrap();
+    let claims = indexer.claims(&address).unwrap();
+    assert_eq!(claims.len(), 2);
+    assert_ne!(claims[0].claimer, claims[1].claimer);
+    assert_eq!(claims[0].signature, fixtures[1].signature);
+    let claimed: u64 = claims.iter().map(|claim| claim.amount).sum();
+    assert!(claims
+        .iter()
+        .all(|claim| claim.amount == record.total_amount / 4 && claim.transfer_fee == 0));
+
+    assert!(record.is_sol);
+    assert_eq!(record.red_packet_id, 0);
+    assert_eq!(record.packet_count, 4);
+    assert_eq!(record.claimed_count, 2);
+    assert_eq!(record.remaining_amount, 0);
+    assert_eq!(record.refunded_amount, Some(record.total_amount - claimed));
+    assert_eq!(record.status, PacketStatus::Refunded);
+    let expiry_updated = parse_logs(&fixtures[3].logs).unwrap();
+    let RedPacketEvent::ExpiryTimeUpdated(event) = &expiry_updated[0] else {
+        panic!("unexpected {}", expiry_updated[0].name());
+    };
+    assert_eq!(record.expiry_time, event.new_expiry_time);
+
+    drop(indexer);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_index_token_top_up_and_cancel() {
+    let fixtures = load("token_top_up_cancel.json");
+    // 领取和取消时 CPI 到 token 程序，只有红包程序自己的数据会被解码
+    assert_eq!(
+        event_names(&fixtures),
+        [
+            "RedPacketCreated",
+            "RedPacketClaimed",
+            "RedPacketToppedUp",
+            "RedPacketCancelled",
+            "RedPacketRefunded",
+        ]
+    );
+    let address = created_red_packet(&fixtures);
+
+    let mut indexer = Indexer::open_in_memory().unwrap();
+    // 先只送入创建和领取，检查中间状态
+    assert_eq!(index_all(&mut indexer, &fixtures[..2]), [true, true]);
+    let record = indexer.red_packet(&address).unwrap().unwrap();
+    let claim = indexer.claims(&address).unwrap().remove(0);
+    assert!(!record.is_sol);
+    assert_eq!(record.status, PacketStatus::Active);
+    assert_eq!(record.packet_count, 2);
+    assert_eq!(record.claimed_count, 1);
+    assert_eq!(record.remaining_amount, record.total_amount - claim.amount);
+    let first_total = record.total_amount;
+
+    assert_eq!(
+        index_all(&mut indexer, &fixtures),
+        [false, false, true, true]
+    );
+    let record = indexer.red_packet(&address).unwrap().unwrap();
+    assert_eq!(record.total_amount, 2 * first_total);
+    assert_eq!(record.packet_count, 4);
+    assert_eq!(record.claimed_count, 1);
+    assert_eq!(record.remaining_amount, 0);
+    assert_eq!(
+        record.refunded_amount,
+        Some(record.total_amount - claim.amount)
+    );
+    assert_eq!(record.status, PacketStatus::Refunded);
+    assert_eq!(indexer.claims(&address).unwrap().len(), 1);
+}
+
+#[test]
+fn test_unknown_event_does_not_block_transaction() {
+    let mut fixtures = load("sol_lifecycle.json");
+    let address = created_red_packet(&fixtures);
+    // 模拟升级后的程序在领取时多发一个本客户端不认识的事件
+    let claim = &mut fixtures[1];
+    let position = claim
+        .logs
+        .iter()
+        .position(|log| log.starts_with("Program data: "))
+        .unwrap();
+    let unknown = [[7; 8].as_slice(), &[1, 2, 3]].concat();
+    claim.logs.insert(
+        position + 1,
+        format!("Program data: {}", STANDARD.encode(&unknown)),
+    );
+    assert_eq!(
+        event_names(&fixtures[1..2]),
+        ["RedPacketClaimed", "Unknown"]
+    );
+
+    let mut indexer = Indexer::open_in_memory().unwrap();
+    assert!(index_all(&mut indexer, &fixtures).iter().all(|&new| new));
+    assert_eq!(indexer.claims(&address).unwrap().len(), 2);
+    let unknown_events = indexer.unknown_events().unwrap();
+    assert_eq!(unknown_events.len(), 1);
+    assert_eq!(unknown_events[0].discriminator, [7; 8]);
+    assert_eq!(unknown_events[0].data, [1, 2, 3]);
+    assert_eq!(unknown_events[0].signature, fixtures[1].signature);
+    assert_eq!(unknown_events[0].slot, fixtures[1].slot);
+}
+
+#[test]
+fn test_unknown_red_packet_is_absent() {
+    let indexer = Indexer::open_in_memory().unwrap();
+    let address = Pubkey::new_unique();
+    assert_eq!(indexer.red_packet(&address).unwrap(), None);
+    assert!(indexer.claims(&address).unwrap().is_empty());
+}
@@ -0,0 +1,282 @@
+//! 用客户端构建的指令驱动原生加载的红包程序，并用客户端解码器读取账户
+#![allow(deprecated)]
+
+use anchor_lang::{
+    prelude::AccountInfo,
+    solana_program::{entrypoint::ProgramResult, program_pack::Pack},
+};
+use anchor_spl::{associated_token::spl_associated_token_account, token_2022::spl_token_2022};
+use red_packet::config::FEE_RECEIVER;
+use red_packet_client::{
+    accounts::{
+        decode_creator_state, decode_protocol_config, decode_red_packet, decode_user_state,
+    },
+    instructions::{self, ClaimParams, CreateParams},
+    pda, Asset, TokenMint,
+};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+const PACKET_COUNT: u32 = 4;
+
+fn process_red_packet(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor 的 entry 要求 accounts 与 AccountInfo 同生命周期，测试里直接泄漏一份拷贝
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    red_packet::entry(program_id, accounts, data)
+}
+
+async fn start() -> ProgramTestContext {
+    let mut program_test =
+        ProgramTest::new("red_packet", red_packet::ID, processor!(process_red_packet));
+    program_test.add_account(
+        FEE_RECEIVER,
+        Account {
+            lamports: 1_000_000_000,
+            ..Account::default()
+        },
+    );
+    program_test.start_with_context().await
+}
+
+/// 原生加载的程序没有 ProgramData，按升级加载器的布局补一个，升级权限为 authority
+fn set_upgrade_authority(context: &mut ProgramTestContext, authority: &Pubkey) {
+    let state = UpgradeableLoaderState::ProgramData {
+        slot: 0,
+        upgrade_authority_address: Some(*authority),
+    };
+    let lamports =
+        Rent::default().minimum_balance(UpgradeableLoaderState::size_of_programdata_metadata());
+    let account = Account::new_data(lamports, &state, &bpf_loader_upgradeable::ID).unwrap();
+    context.set_account(&pda::program_data(), &account.into());
+}
+
+async fn send(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    context.banks_client.process_transaction(tx).await
+}
+
+async fn data(context: &mut ProgramTestContext, address: &Pubkey) -> Option<Vec<u8>> {
+    let account = context.banks_client.get_account(*address).await.unwrap()?;
+    Some(account.data)
+}
+
+async fn lamports(context: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    context.banks_client.get_balance(*address).await.unwrap()
+}
+
+async fn token_balance(context: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    let data = data(context, address).await.unwrap();
+    spl_token_2022::state::Account::unpack_from_slice(&data[..spl_token_2022::state::Account::LEN])
+        .unwrap()
+        .amount
+}
+
+/// 初始化全局配置（付款人为升级权限和管理员）和创建者状态，返回创建者和当前的手续费收款地址
+async fn setup_creator(context: &mut ProgramTestContext) -> (Keypair, Pubkey) {
+    let creator = Keypair::new();
+    let admin = context.payer.pubkey();
+    set_upgrade_authority(context, &admin);
+    send(
+        context,
+        &[
+            system_instruction::transfer(&admin, &creator.pubkey(), 10_000_000_000),
+            instructions::initialize_protocol_config(&admin),
+            instructions::initialize_creator_state(&creator.pubkey()),
+        ],
+        &[&creator],
+    )
+    .await
+    .unwrap();
+    let config = data(context, &pda::protocol_config()).await.unwrap();
+    let fee_receiver = decode_protocol_config(&config).unwrap().fee_receiver;
+    (creator, fee_receiver)
+}
+
+async fn new_claimer(context: &mut ProgramTestContext) -> Keypair {
+    let claimer = Keypair::new();
+    let ix = system_instruction::transfer(&context.payer.pubkey(), &claimer.pubkey(), 100_000_000);
+    send(context, &[ix], &[]).await.unwrap();
+    claimer
+}
+
+/// 创建 Token-2022 mint 并给 owner 铸造代币，付款人是铸币权限
+async fn create_mint(context: &mut ProgramTestContext, owner: &Pubkey) -> TokenMint {
+    let mint = Keypair::new();
+    let token = TokenMint::token_2022(mint.pubkey());
+    let payer = context.payer.pubkey();
+    let mint_len = spl_token_2022::state::Mint::LEN;
+    let instructions = [
+        system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            Rent::default().minimum_balance(mint_len),
+            mint_len as u64,
+            &token.token_program,
+        ),
+        spl_token_2022::instruction::initialize_mint2(
+            &token.token_program,
+            &mint.pubkey(),
+            &payer,
+            None,
+            6,
+        )
+        .unwrap(),
+        spl_associated_token_account::instruction::create_associated_token_account(
+            &payer,
+            owner,
+            &mint.pubkey(),
+            &token.token_program,
+        ),
+        spl_token_2022::instruction::mint_to(
+            &token.token_program,
+            &mint.pubkey(),
+            &token.ata(owner),
+            &payer,
+            &[],
+            TOTAL_AMOUNT,
+        )
+        .unwrap(),
+    ];
+    send(context, &instructions, &[&mint]).await.unwrap();
+    token
+}
+
+#[tokio::test]
+async fn test_sol_red_packet_lifecycle() {
+    let mut context = start().await;
+    let (creator, fee_receiver) = setup_creator(&mut context).await;
+    let creator_key = creator.pubkey();
+    let red_packet = pda::red_packet(&creator_key, 0);
+
+    let ix = instructions::create_redpacket(
+        &creator_key,
+        0,
+        &Asset::Sol,
+        &fee_receiver,
+        CreateParams::new(TOTAL_AMOUNT, PACKET_COUNT, 0),
+    );
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    let state = data(&mut context, &pda::creator_state(&creator_key))
+        .await
+        .unwrap();
+    assert_eq!(decode_creator_state(&state).unwrap().next_red_packet_id, 1);
+
+    let claimer = new_claimer(&mut context).await;
+    let before = lamports(&mut context, &claimer.pubkey()).await;
+    let ix = instructions::claim_redpacket(
+        &claimer.pubkey(),
+        &creator_key,
+        0,
+        &Asset::Sol,
+        &fee_receiver,
+        ClaimParams::default(),
+    );
+    send(&mut context, &[ix], &[&claimer]).await.unwrap();
+    assert!(lamports(&mut context, &claimer.pubkey()).await > before);
+
+    let user_state = data(
+        &mut context,
+        &pda::user_state(&red_packet, &claimer.pubkey()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(decode_user_state(&user_state).unwrap().is_claimed, 1);
+    let account = data(&mut context, &red_packet).await.unwrap();
+    let decoded = decode_red_packet(&account).unwrap();
+    assert_eq!(decoded.claimed_count, 1);
+    assert_eq!(
+        decoded.remaining_amount,
+        TOTAL_AMOUNT - TOTAL_AMOUNT / PACKET_COUNT as u64
+    );
+
+    // 过期后退款关闭红包，红包关闭之后才能清理领取记录
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = decoded.expiry_time + 1;
+    context.set_sysvar(&clock);
+    let ix = instructions::refund(&creator_key, 0, &Asset::Sol);
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    assert!(data(&mut context, &red_packet).await.is_none());
+    let ix = instructions::close_user_states(&red_packet, &[claimer.pubkey()]);
+    send(&mut context, &[ix], &[]).await.unwrap();
+    assert!(data(
+        &mut context,
+        &pda::user_state(&red_packet, &claimer.pubkey())
+    )
+    .await
+    .is_none());
+}
+
+#[tokio::test]
+async fn test_token_2022_red_packet_claim_and_cancel() {
+    let mut context = start().await;
+    let (creator, fee_receiver) = setup_creator(&mut context).await;
+    let creator_key = creator.pubkey();
+    let token = create_mint(&mut context, &creator_key).await;
+    let asset = Asset::Token(token);
+    let red_packet = pda::red_packet(&creator_key, 0);
+
+    let ix = instructions::create_redpacket(
+        &creator_key,
+        0,
+        &asset,
+        &fee_receiver,
+        CreateParams::new(TOTAL_AMOUNT, PACKET_COUNT, 0),
+    );
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    assert_eq!(
+        token_balance(&mut context, &token.ata(&red_packet)).await,
+        TOTAL_AMOUNT
+    );
+    let account = data(&mut context, &red_packet).await.unwrap();
+    let decoded = decode_red_packet(&account).unwrap();
+    assert!(!decoded.is_sol);
+    assert_eq!(decoded.mint, token.mint);
+
+    // 领取者还没有 ATA，由领取指令创建
+    let claimer = new_claimer(&mut context).await;
+    let ix = instructions::claim_redpacket(
+        &claimer.pubkey(),
+        &creator_key,
+        0,
+        &asset,
+        &fee_receiver,
+        ClaimParams::default(),
+    );
+    send(&mut context, &[ix], &[&claimer]).await.unwrap();
+    assert_eq!(
+        token_balance(&mut context, &token.ata(&claimer.pubkey())).await,
+        TOTAL_AMOUNT / PACKET_COUNT as u64
+    );
+
+    let ix = instructions::cancel_redpacket(&creator_key, &creator_key, 0, &asset, None);
+    send(&mut context, &[ix], &[&creator]).await.unwrap();
+    assert!(data(&mut context, &red_packet).await.is_none());
+    assert!(data(&mut context, &token.ata(&red_packet)).await.is_none());
+    assert_eq!(
+        token_balance(&mut context, &token.ata(&creator_key)).await,
+        TOTAL_AMOUNT - TOTAL_AMOUNT / PACKET_COUNT as u64
+    );
+}
@@ -0,0 +1,395 @@
+//! 重新录制 tests/fixtures 下的交易日志：
+//! `cargo test --test record_fixtures -- --ignored`
+//!
+//! 原生 processor 下 `sol_log_data` 会落到默认桩函数直接打印到 stdout，这里换上一层转发桩，
+//! 把事件数据写进 program-test 的日志收集器，再改写成验证节点的 `Program data: ` 格式。
+#![allow(deprecated)]
+
+use anchor_lang::{
+    prelude::AccountInfo,
+    solana_program::{
+        entrypoint::ProgramResult,
+        instruction::Instruction as ProgramInstruction,
+        program_pack::Pack,
+        program_stubs::{set_syscall_stubs, SyscallStubs},
+    },
+};
+use anchor_spl::{associated_token::spl_associated_token_account, token_2022::spl_token_2022};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use red_packet::config::FEE_RECEIVER;
+use red_packet_client::{
+    instructions::{self, ClaimParams, CreateParams},
+    pda, Asset, TokenMint,
+};
+use serde_json::json;
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use std::{path::Path, sync::Once};
+
+const TOTAL_AMOUNT: u64 = 1_000_000_000;
+
+/// 原生加载的程序没有 ProgramData，按升级加载器的布局补一个，升级权限为 authority
+fn set_upgrade_authority(context: &mut ProgramTestContext, authority: &Pubkey) {
+    let state = UpgradeableLoaderState::ProgramData {
+        slot: 0,
+        upgrade_authority_address: Some(*authority),
+    };
+    let lamports =
+        Rent::default().minimum_balance(UpgradeableLoaderState::size_of_programdata_metadata());
+    let account = Account::new_data(lamports, &state, &bpf_loader_upgradeable::ID).unwrap();
+    context.set_account(&pda::program_data(), &account.into());
+}
+
+fn process_red_packet(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor 的 entry 要求 accounts 与 AccountInfo 同生命周期，测试里直接泄漏一份拷贝
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    red_packet::entry(program_id, accounts, data)
+}
+
+/// 除 `sol_log_data` 外全部转发给 program-test 的桩函数
+struct LogDataStubs(Box<dyn SyscallStubs>);
+
+impl SyscallStubs for LogDataStubs {
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message)
+    }
+    fn sol_log_compute_units(&self) {
+        self.0.sol_log_compute_units()
+    }
+    fn sol_remaining_compute_units(&self) -> u64 {
+        self.0.sol_remaining_compute_units()
+    }
+    fn sol_invoke_signed(
+        &self,
+        instruction: &ProgramInstruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        self.0
+            .sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+    fn sol_get_sysvar(
+        &self,
+        sysvar_id_addr: *const u8,
+        var_addr: *mut u8,
+        offset: u64,
+        length: u64,
+    ) -> u64 {
+        self.0
+            .sol_get_sysvar(sysvar_id_addr, var_addr, offset, length)
+    }
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_rewards_sysvar(var_addr)
+    }
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_last_restart_slot(var_addr)
+    }
+    fn sol_get_epoch_stake(&self, vote_address: *const u8) -> u64 {
+        self.0.sol_get_epoch_stake(vote_address)
+    }
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.0.sol_set_return_data(data)
+    }
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let fields: Vec<String> = fields.iter().map(|field| STANDARD.encode(field)).collect();
+        self.0
+            .sol_log(&format!("{LOG_DATA_PREFIX}{}", fields.join(" ")));
+    }
+    fn sol_get_processed_sibling_instruction(&self, index: usize) -> Option<ProgramInstruction> {
+        self.0.sol_get_processed_sibling_instruction(index)
+    }
+    fn sol_get_stack_height(&self) -> u64 {
+        self.0.sol_get_stack_height()
+    }
+}
+
+/// 换桩时临时占位
+struct Placeholder;
+
+impl SyscallStubs for Placeholder {}
+
+/// 经 `sol_log` 写入收集器后事件数据的前缀
+const LOG_DATA_PREFIX: &str = "data: ";
+
+struct Recorder {
+    context: ProgramTestContext,
+    transactions: Vec<serde_json::Value>,
+}
+
+impl Recorder {
+    async fn start() -> Self {
+        let mut program_test =
+            ProgramTest::new("red_packet", red_packet::ID, processor!(process_red_packet));
+        program_test.add_account(
+            FEE_RECEIVER,
+            Account {
+                lamports: 1_000_000_000,
+                ..Account::default()
+            },
+        );
+        let context = program_test.start_with_context().await;
+        // program-test 在第一次启动时装好自己的桩函数，之后再包一层
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            let stubs = set_syscall_stubs(Box::new(Placeholder));
+            set_syscall_stubs(Box::new(LogDataStubs(stubs)));
+        });
+        Self {
+            context,
+            transactions: Vec::new(),
+        }
+    }
+
+    /// 不录制的准备交易
+    async fn setup(&mut self, instructions: &[Instruction], signers: &[&Keypair]) {
+        self.send(instructions, signers, false).await;
+    }
+
+    async fn record(&mut self, instructions: &[Instruction], signers: &[&Keypair]) {
+        self.send(instructions, signers, true).await;
+    }
+
+    async fn send(&mut self, instructions: &[Instruction], signers: &[&Keypair], record: bool) {
+        let blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        let signature = tx.signatures[0];
+        let result = self
+            .context
+            .banks_client
+            .process_transaction_with_metadata(tx)
+            .await
+            .unwrap();
+        result.result.unwrap();
+        if record {
+            let slot = self.context.banks_client.get_root_slot().await.unwrap();
+            let log_data = format!("Program log: {LOG_DATA_PREFIX}");
+            let logs: Vec<String> = result
+                .metadata
+                .unwrap()
+                .log_messages
+                .into_iter()
+                .map(|log| match log.strip_prefix(&log_data) {
+                    Some(data) => format!("Program data: {data}"),
+                    None => log,
+                })
+                .collect();
+            self.transactions.push(json!({
+                "signature": signature.to_string(),
+                "slot": slot,
+                "logs": logs,
+            }));
+        }
+    }
+
+    async fn creator(&mut self) -> Keypair {
+        let creator = Keypair::new();
+        let admin = self.context.payer.pubkey();
+        set_upgrade_authority(&mut self.context, &admin);
+        self.setup(
+            &[
+                instructions::initialize_protocol_config(&admin),
+                system_instruction::transfer(&admin, &creator.pubkey(), 10_000_000_000),
+                instructions::initialize_creator_state(&creator.pubkey()),
+            ],
+            &[&creator],
+        )
+        .await;
+        creator
+    }
+
+    async fn claimer(&mut self) -> Keypair {
+        let claimer = Keypair::new();
+        let ix = system_instruction::transfer(
+            &self.context.payer.pubkey(),
+            &claimer.pubkey(),
+            100_000_000,
+        );
+        self.setup(&[ix], &[]).await;
+        claimer
+    }
+
+    async fn warp_past(&mut self, unix_timestamp: i64) {
+        let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp = unix_timestamp + 1;
+        self.context.set_sysvar(&clock);
+    }
+
+    async fn expiry_time(&mut self, red_packet: &Pubkey) -> i64 {
+        let account = self
+            .context
+            .banks_client
+            .get_account(*red_packet)
+            .await
+            .unwrap()
+            .unwrap();
+        red_packet_client::accounts::decode_red_packet(&account.data)
+            .unwrap()
+            .expiry_time
+    }
+
+    fn save(&self, name: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            serde_json::to_string_pretty(&self.transactions).unwrap() + "\n",
+        )
+        .unwrap();
+    }
+}
+
+/// SOL 平分红包：创建、两次领取、延期、过期退款
+#[tokio::test]
+#[ignore]
+async fn record_sol_lifecycle() {
+    let mut recorder = Recorder::start().await;
+    let creator = recorder.creator().await;
+    let fee_receiver = FEE_RECEIVER;
+    let red_packet = pda::red_packet(&creator.pubkey(), 0);
+
+    let ix = instructions::create_redpacket(
+        &creator.pubkey(),
+        0,
+        &Asset::Sol,
+        &fee_receiver,
+        CreateParams::new(TOTAL_AMOUNT, 4, 0),
+    );
+    recorder.record(&[ix], &[&creator]).await;
+    for _ in 0..2 {
+        let claimer = recorder.claimer().await;
+        let ix = instructions::claim_redpacket(
+            &claimer.pubkey(),
+            &creator.pubkey(),
+            0,
+            &Asset::Sol,
+            &fee_receiver,
+            ClaimParams::default(),
+        );
+        recorder.record(&[ix], &[&claimer]).await;
+    }
+    let expiry_time = recorder.expiry_time(&red_packet).await + 24 * 60 * 60;
+    let ix = instructions::set_expiry_time(&creator.pubkey(), &creator.pubkey(), 0, expiry_time);
+    recorder.record(&[ix], &[&creator]).await;
+    recorder.warp_past(expiry_time).await;
+    let ix = instructions::refund(&creator.pubkey(), 0, &Asset::Sol);
+    recorder.record(&[ix], &[&creator]).await;
+    recorder.save("sol_lifecycle.json");
+}
+
+/// Token-2022 红包：领取时 CPI 到 ATA 和 token 程序，追加资金后取消并立即退款
+#[tokio::test]
+#[ignore]
+async fn record_token_top_up_cancel() {
+    let mut recorder = Recorder::start().await;
+    let creator = recorder.creator().await;
+    let fee_receiver = FEE_RECEIVER;
+
+    let mint = Keypair::new();
+    let token = TokenMint::token_2022(mint.pubkey());
+    let payer = recorder.context.payer.pubkey();
+    let mint_len = spl_token_2022::state::Mint::LEN;
+    recorder
+        .setup(
+            &[
+                system_instruction::create_account(
+                    &payer,
+                    &mint.pubkey(),
+                    Rent::default().minimum_balance(mint_len),
+                    mint_len as u64,
+                    &token.token_program,
+                ),
+                spl_token_2022::instruction::initialize_mint2(
+                    &token.token_program,
+                    &mint.pubkey(),
+                    &payer,
+                    None,
+                    6,
+                )
+                .unwrap(),
+                spl_associated_token_account::instruction::create_associated_token_account(
+                    &payer,
+                    &creator.pubkey(),
+                    &mint.pubkey(),
+                    &token.token_program,
+                ),
+                spl_token_2022::instruction::mint_to(
+                    &token.token_program,
+                    &mint.pubkey(),
+                    &token.ata(&creator.pubkey()),
+                    &payer,
+                    &[],
+                    2 * TOTAL_AMOUNT,
+                )
+                .unwrap(),
+            ],
+            &[&mint],
+        )
+        .await;
+
+    let asset = Asset::Token(token);
+    let ix = instructions::create_redpacket(
+        &creator.pubkey(),
+        0,
+        &asset,
+        &fee_receiver,
+        CreateParams::new(TOTAL_AMOUNT, 2, 0),
+    );
+    recorder.record(&[ix], &[&creator]).await;
+    let claimer = recorder.claimer().await;
+    let ix = instructions::claim_redpacket(
+        &claimer.pubkey(),
+        &creator.pubkey(),
+        0,
+        &asset,
+        &fee_receiver,
+        ClaimParams::default(),
+    );
+    recorder.record(&[ix], &[&claimer]).await;
+    let ix = instructions::top_up_redpacket(
+        &creator.pubkey(),
+        &creator.pubkey(),
+        0,
+        &asset,
+        &fee_receiver,
+        TOTAL_AMOUNT,
+        2,
+    );
+    recorder.record(&[ix], &[&creator]).await;
+    let ix = instructions::cancel_redpacket(&creator.pubkey(), &creator.pubkey(), 0, &asset, None);
+    recorder.record(&[ix], &[&creator]).await;
+    recorder.save("token_top_up_cancel.json");
+}
@@ -0,0 +1,202 @@
+//! 名单输入与证明文件输出的格式
+
+use crate::{LeafKind, MerkleTree, Recipient};
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path, str::FromStr};
+
+/// CSV（表头 `pubkey,amount`）和 JSON 数组共用的一行名单
+#[derive(Debug, Deserialize)]
+struct RecipientRecord {
+    pubkey: String,
+    amount: u64,
+}
+
+impl TryFrom<RecipientRecord> for Recipient {
+    type Error = anyhow::Error;
+
+    fn try_from(record: RecipientRecord) -> Result<Self> {
+        let pubkey = Pubkey::from_str(record.pubkey.trim())
+            .map_err(|_| anyhow!("invalid pubkey {:?}", record.pubkey))?;
+        Ok(Recipient {
+            pubkey,
+            amount: record.amount,
+        })
+    }
+}
+
+pub fn parse_csv(reader: impl std::io::Read) -> Result<Vec<Recipient>> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+        .deserialize::<RecipientRecord>()
+        .enumerate()
+        .map(|(line, record)| {
+            record
+                .map_err(anyhow::Error::from)
+                .and_then(Recipient::try_from)
+                .with_context(|| format!("csv record {}", line + 1))
+        })
+        .collect()
+}
+
+pub fn parse_json(reader: impl std::io::Read) -> Result<Vec<Recipient>> {
+    let records: Vec<RecipientRecord> = serde_json::from_reader(reader)?;
+    records.into_iter().map(Recipient::try_from).collect()
+}
+
+/// 按扩展名选择解析方式，`.json` 之外都当作 CSV
+pub fn read_recipients(path: &Path) -> Result<Vec<Recipient>> {
+    let file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        parse_json(file)
+    } else {
+        parse_csv(file)
+    }
+}
+
+/// 创建红包时需要的参数：merkle_root、total_amount、packet_count
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TreeFile {
+    pub leaf_kind: LeafKind,
+    pub red_packet_type: u8,
+    pub merkle_root: String,
+    pub total_amount: u64,
+    pub packet_count: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ClaimProof {
+    /// 类型 4 领取时传入的叶子序号
+    pub index: u32,
+    pub amount: u64,
+    pub proof: Vec<String>,
+}
+
+/// 每个领取者一个文件，类型 4 中同一地址可能有多个叶子
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProofFile {
+    pub pubkey: String,
+    pub leaf_kind: LeafKind,
+    pub merkle_root: String,
+    pub claims: Vec<ClaimProof>,
+}
+
+impl ClaimProof {
+    pub fn proof_bytes(&self) -> Result<Vec<[u8; 32]>> {
+        self.proof.iter().map(|node| decode_hash(node)).collect()
+    }
+}
+
+pub fn decode_hash(value: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(value.trim_start_matches("0x"))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("hash {value:?} is not 32 bytes"))
+}
+
+pub fn tree_file(tree: &MerkleTree) -> TreeFile {
+    TreeFile {
+        leaf_kind: tree.kind(),
+        red_packet_type: tree.kind().red_packet_type(),
+        merkle_root: hex::encode(tree.root()),
+        total_amount: tree.total_amount(),
+        packet_count: tree.recipients().len() as u32,
+    }
+}
+
+/// 生成所有证明，写出前逐个用链上校验函数验证
+pub fn proof_files(tree: &MerkleTree) -> Result<Vec<ProofFile>> {
+    let mut claims: BTreeMap<Pubkey, Vec<ClaimProof>> = BTreeMap::new();
+    for (index, recipient) in tree.recipients().iter().enumerate() {
+        let proof = tree.proof(index)?;
+        if !tree.verify(index, &proof) {
+            return Err(anyhow!(
+                "generated proof for leaf {index} failed verification"
+            ));
+        }
+        claims
+            .entry(recipient.pubkey)
+            .or_default()
+            .push(ClaimProof {
+                index: index as u32,
+                amount: recipient.amount,
+                proof: proof.iter().map(hex::encode).collect(),
+            });
+    }
+
+    let merkle_root = hex::encode(tree.root());
+    Ok(claims
+        .into_iter()
+        .map(|(pubkey, claims)| ProofFile {
+            pubkey: pubkey.to_string(),
+            leaf_kind: tree.kind(),
+            merkle_root: merkle_root.clone(),
+            claims,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_and_json_inputs_match() {
+        let a = Pubkey::new_unique();
+        let b = Pubkey::new_unique();
+        let csv = format!("pubkey,amount\n{a}, 10\n{b},20\n");
+        let json = format!(r#"[{{"pubkey":"{a}","amount":10}},{{"pubkey":"{b}","amount":20}}]"#);
+        let from_csv = parse_csv(csv.as_bytes()).unwrap();
+        assert_eq!(from_csv, parse_json(json.as_bytes()).unwrap());
+        assert_eq!(from_csv[1].amount, 20);
+
+        assert!(parse_csv("pubkey,amount\nnot-a-key,1\n".as_bytes()).is_err());
+        assert!(parse_csv(format!("pubkey,amount\n{a},-1\n").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_proof_files_round_trip() {
+        let a = Pubkey::new_unique();
+        let b = Pubkey::new_unique();
+        let recipients = vec![
+            Recipient {
+                pubkey: a,
+                amount: 5,
+            },
+            Recipient {
+                pubkey: b,
+                amount: 6,
+            },
+            Recipient {
+                pubkey: a,
+                amount: 7,
+            },
+        ];
+        let tree = MerkleTree::new(LeafKind::Indexed, recipients).unwrap();
+        let files = proof_files(&tree).unwrap();
+        assert_eq!(files.len(), 2);
+
+        let root = decode_hash(&tree_file(&tree).merkle_root).unwrap();
+        for file in &files {
+            let pubkey = Pubkey::from_str(&file.pubkey).unwrap();
+            for claim in &file.claims {
+                let recipient = Recipient {
+                    pubkey,
+                    amount: claim.amount,
+                };
+                let proof = claim.proof_bytes().unwrap();
+                assert!(crate::verify_proof(
+                    file.leaf_kind,
+                    claim.index,
+                    &recipient,
+                    &proof,
+                    &root
+                ));
+            }
+        }
+        let a_file = files.iter().find(|f| f.pubkey == a.to_string()).unwrap();
+        assert_eq!(a_file.claims.len(), 2);
+    }
+}
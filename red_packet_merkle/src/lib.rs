@@ -0,0 +1,344 @@
+//! 为 red_packet 的 Merkle 红包（类型 2 和类型 4）离线生成根和证明。
+//!
+//! 哈希规则与链上 `red_packet::utils` 保持一致：
+//! - 类型 2 的叶子：`sha256(claimer ‖ amount_le)`
+//! - 类型 4 的叶子：`sha256(index_le ‖ claimer ‖ amount_le)`
+//! - 父节点：两个子节点按字节序排序后拼接再做 SHA-256
+//!
+//! 某一层节点数为奇数时，最后一个节点直接提升到上一层，证明里不包含它的兄弟。
+
+use anchor_lang::prelude::Pubkey;
+use red_packet::config::{MAX_PACKET_COUNT, MAX_PROOF_LENGTH};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+pub mod io;
+
+/// 叶子格式，对应红包类型
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeafKind {
+    /// 类型 2，每个地址只能领取一次
+    Plain,
+    /// 类型 4，按序号记录在位图里，同一地址可以出现多次
+    Indexed,
+}
+
+impl LeafKind {
+    pub fn red_packet_type(self) -> u8 {
+        match self {
+            LeafKind::Plain => 2,
+            LeafKind::Indexed => 4,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recipient {
+    pub pubkey: Pubkey,
+    pub amount: u64,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum MerkleError {
+    #[error("recipient list is empty")]
+    Empty,
+    #[error("recipient {0} has a zero amount")]
+    ZeroAmount(Pubkey),
+    #[error("recipient {0} appears more than once, use the indexed leaf kind")]
+    DuplicateRecipient(Pubkey),
+    #[error("{count} recipients exceed MAX_PACKET_COUNT ({max})")]
+    TooManyRecipients { count: usize, max: u32 },
+    #[error("{count} recipients need proofs longer than MAX_PROOF_LENGTH ({max})")]
+    ProofTooLong { count: usize, max: usize },
+    #[error("total amount overflows u64")]
+    AmountOverflow,
+    #[error("leaf index {0} is out of range")]
+    IndexOutOfRange(usize),
+}
+
+pub fn leaf_hash(kind: LeafKind, index: u32, recipient: &Recipient) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    if kind == LeafKind::Indexed {
+        hasher.update(index.to_le_bytes());
+    }
+    hasher.update(recipient.pubkey.as_ref());
+    hasher.update(recipient.amount.to_le_bytes());
+    hasher.finalize().into()
+}
+
+pub fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+    Sha256::new()
+        .chain_update(lo)
+        .chain_update(hi)
+        .finalize()
+        .into()
+}
+
+/// 叶子数为 `leaf_count` 时最长证明的长度，即树的高度
+pub fn proof_depth(leaf_count: usize) -> usize {
+    leaf_count.next_power_of_two().trailing_zeros() as usize
+}
+
+pub struct MerkleTree {
+    kind: LeafKind,
+    recipients: Vec<Recipient>,
+    /// layers[0] 是叶子，最后一层只有根
+    layers: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    pub fn new(kind: LeafKind, recipients: Vec<Recipient>) -> Result<Self, MerkleError> {
+        if recipients.is_empty() {
+            return Err(MerkleError::Empty);
+        }
+        // 每个叶子对应一份红包，数量受链上 packet_count 上限约束
+        if recipients.len() > MAX_PACKET_COUNT as usize {
+            return Err(MerkleError::TooManyRecipients {
+                count: recipients.len(),
+                max: MAX_PACKET_COUNT,
+            });
+        }
+        if proof_depth(recipients.len()) > MAX_PROOF_LENGTH {
+            return Err(MerkleError::ProofTooLong {
+                count: recipients.len(),
+                max: MAX_PROOF_LENGTH,
+            });
+        }
+
+        let mut seen = HashSet::new();
+        let mut total: u64 = 0;
+        for recipient in &recipients {
+            if recipient.amount == 0 {
+                return Err(MerkleError::ZeroAmount(recipient.pubkey));
+            }
+            // 类型 2 用 UserState 防重复领取，同一地址的第二个叶子永远领不到
+            if kind == LeafKind::Plain && !seen.insert(recipient.pubkey) {
+                return Err(MerkleError::DuplicateRecipient(recipient.pubkey));
+            }
+            total = total
+                .checked_add(recipient.amount)
+                .ok_or(MerkleError::AmountOverflow)?;
+        }
+
+        let leaves: Vec<[u8; 32]> = recipients
+            .iter()
+            .enumerate()
+            .map(|(index, recipient)| leaf_hash(kind, index as u32, recipient))
+            .collect();
+        let mut layers = vec![leaves];
+        while layers.last().unwrap().len() > 1 {
+            let next = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => hash_pair(a, b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+            layers.push(next);
+        }
+
+        Ok(Self {
+            kind,
+            recipients,
+            layers,
+        })
+    }
+
+    pub fn kind(&self) -> LeafKind {
+        self.kind
+    }
+
+    pub fn recipients(&self) -> &[Recipient] {
+        &self.recipients
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.layers.last().unwrap()[0]
+    }
+
+    pub fn total_amount(&self) -> u64 {
+        // 构造时已检查不会溢出
+        self.recipients.iter().map(|r| r.amount).sum()
+    }
+
+    pub fn proof(&self, index: usize) -> Result<Vec<[u8; 32]>, MerkleError> {
+        if index >= self.recipients.len() {
+            return Err(MerkleError::IndexOutOfRange(index));
+        }
+        let mut proof = Vec::with_capacity(self.layers.len() - 1);
+        let mut position = index;
+        for layer in &self.layers[..self.layers.len() - 1] {
+            if let Some(sibling) = layer.get(position ^ 1) {
+                proof.push(*sibling);
+            }
+            position /= 2;
+        }
+        Ok(proof)
+    }
+
+    /// 用链上同一份校验代码验证证明
+    pub fn verify(&self, index: usize, proof: &[[u8; 32]]) -> bool {
+        let Some(recipient) = self.recipients.get(index) else {
+            return false;
+        };
+        verify_proof(self.kind, index as u32, recipient, proof, &self.root())
+    }
+}
+
+/// 直接调用链上程序的校验函数，离线生成的证明与链上结果不会出现偏差
+pub fn verify_proof(
+    kind: LeafKind,
+    index: u32,
+    recipient: &Recipient,
+    proof: &[[u8; 32]],
+    root: &[u8; 32],
+) -> bool {
+    match kind {
+        LeafKind::Plain => {
+            red_packet::utils::verify_merkle_proof(recipient.pubkey, recipient.amount, proof, root)
+        }
+        LeafKind::Indexed => red_packet::utils::verify_indexed_merkle_proof(
+            index,
+            recipient.pubkey,
+            recipient.amount,
+            proof,
+            root,
+        ),
+    }
+    .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipients(count: usize) -> Vec<Recipient> {
+        (0..count)
+            .map(|i| Recipient {
+                pubkey: Pubkey::new_unique(),
+                amount: 1_000 + i as u64,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_proofs_pass_onchain_verifier() {
+        for kind in [LeafKind::Plain, LeafKind::Indexed] {
+            for count in [1, 2, 3, 4, 5, 7, 8, 9, 16, 31, 33, 100] {
+                let tree = MerkleTree::new(kind, recipients(count)).unwrap();
+                for index in 0..count {
+                    let proof = tree.proof(index).unwrap();
+                    assert!(proof.len() <= proof_depth(count));
+                    assert!(tree.verify(index, &proof), "{kind:?} {count} {index}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrong_amount_or_index_is_rejected() {
+        let tree = MerkleTree::new(LeafKind::Indexed, recipients(10)).unwrap();
+        let proof = tree.proof(3).unwrap();
+        let mut recipient = tree.recipients()[3].clone();
+        assert!(verify_proof(
+            LeafKind::Indexed,
+            3,
+            &recipient,
+            &proof,
+            &tree.root()
+        ));
+        assert!(!verify_proof(
+            LeafKind::Indexed,
+            4,
+            &recipient,
+            &proof,
+            &tree.root()
+        ));
+        recipient.amount += 1;
+        assert!(!verify_proof(
+            LeafKind::Indexed,
+            3,
+            &recipient,
+            &proof,
+            &tree.root()
+        ));
+        // 类型 2 的叶子不带序号，两种叶子不能混用
+        let recipient = tree.recipients()[3].clone();
+        assert!(!verify_proof(
+            LeafKind::Plain,
+            3,
+            &recipient,
+            &proof,
+            &tree.root()
+        ));
+    }
+
+    #[test]
+    fn test_single_leaf_root_is_leaf() {
+        let list = recipients(1);
+        let tree = MerkleTree::new(LeafKind::Plain, list.clone()).unwrap();
+        assert_eq!(tree.root(), leaf_hash(LeafKind::Plain, 0, &list[0]));
+        assert!(tree.proof(0).unwrap().is_empty());
+        assert_eq!(tree.proof(1), Err(MerkleError::IndexOutOfRange(1)));
+    }
+
+    #[test]
+    fn test_invalid_recipient_lists() {
+        assert!(matches!(
+            MerkleTree::new(LeafKind::Plain, vec![]),
+            Err(MerkleError::Empty)
+        ));
+
+        let mut list = recipients(3);
+        list[1].amount = 0;
+        assert!(matches!(
+            MerkleTree::new(LeafKind::Plain, list),
+            Err(MerkleError::ZeroAmount(_))
+        ));
+
+        let mut list = recipients(3);
+        list[2].pubkey = list[0].pubkey;
+        assert!(matches!(
+            MerkleTree::new(LeafKind::Plain, list.clone()),
+            Err(MerkleError::DuplicateRecipient(_))
+        ));
+        assert!(MerkleTree::new(LeafKind::Indexed, list).is_ok());
+
+        let mut list = recipients(2);
+        list[0].amount = u64::MAX;
+        assert!(matches!(
+            MerkleTree::new(LeafKind::Plain, list),
+            Err(MerkleError::AmountOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_max_packet_count() {
+        let list = vec![
+            Recipient {
+                pubkey: Pubkey::new_unique(),
+                amount: 1,
+            };
+            MAX_PACKET_COUNT as usize + 1
+        ];
+        assert!(matches!(
+            MerkleTree::new(LeafKind::Indexed, list),
+            Err(MerkleError::TooManyRecipients { .. })
+        ));
+    }
+
+    #[test]
+    fn test_proof_depth_respects_max_proof_length() {
+        assert_eq!(proof_depth(1), 0);
+        assert_eq!(proof_depth(2), 1);
+        assert_eq!(proof_depth(3), 2);
+        assert_eq!(proof_depth(1 << MAX_PROOF_LENGTH), MAX_PROOF_LENGTH);
+        assert!(proof_depth((1 << MAX_PROOF_LENGTH) + 1) > MAX_PROOF_LENGTH);
+    }
+}
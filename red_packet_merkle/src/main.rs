@@ -0,0 +1,127 @@
+use anchor_lang::prelude::Pubkey;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use red_packet_merkle::{
+    io::{self, ProofFile},
+    verify_proof, LeafKind, MerkleTree, Recipient,
+};
+use std::{fs, path::PathBuf, str::FromStr};
+
+#[derive(Parser)]
+#[command(about = "Build Merkle roots and proofs for red_packet Merkle distributions")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 读取名单，输出 tree.json 和每个领取者的证明文件
+    Build {
+        /// CSV（表头 pubkey,amount）或 JSON 数组
+        #[arg(short, long)]
+        input: PathBuf,
+        #[arg(short, long, default_value = "merkle_out")]
+        out_dir: PathBuf,
+        #[arg(short, long, value_enum, default_value_t = Kind::Plain)]
+        kind: Kind,
+    },
+    /// 用链上校验函数检查证明文件
+    Verify {
+        #[arg(short, long)]
+        proof_file: PathBuf,
+        /// 十六进制的 Merkle 根，默认使用证明文件里记录的根
+        #[arg(short, long)]
+        root: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Kind {
+    /// 类型 2
+    Plain,
+    /// 类型 4
+    Indexed,
+}
+
+impl From<Kind> for LeafKind {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::Plain => LeafKind::Plain,
+            Kind::Indexed => LeafKind::Indexed,
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Build {
+            input,
+            out_dir,
+            kind,
+        } => build(input, out_dir, kind.into()),
+        Command::Verify { proof_file, root } => verify(proof_file, root),
+    }
+}
+
+fn build(input: PathBuf, out_dir: PathBuf, kind: LeafKind) -> Result<()> {
+    let recipients = io::read_recipients(&input)?;
+    let tree = MerkleTree::new(kind, recipients)?;
+
+    let proofs_dir = out_dir.join("proofs");
+    fs::create_dir_all(&proofs_dir).with_context(|| format!("create {}", proofs_dir.display()))?;
+
+    let tree_file = io::tree_file(&tree);
+    fs::write(
+        out_dir.join("tree.json"),
+        serde_json::to_string_pretty(&tree_file)?,
+    )?;
+    let files = io::proof_files(&tree)?;
+    for file in &files {
+        fs::write(
+            proofs_dir.join(format!("{}.json", file.pubkey)),
+            serde_json::to_string_pretty(file)?,
+        )?;
+    }
+
+    println!("merkle_root:     {}", tree_file.merkle_root);
+    println!("red_packet_type: {}", tree_file.red_packet_type);
+    println!("total_amount:    {}", tree_file.total_amount);
+    println!("packet_count:    {}", tree_file.packet_count);
+    println!(
+        "proof files:     {} in {}",
+        files.len(),
+        proofs_dir.display()
+    );
+    Ok(())
+}
+
+fn verify(proof_file: PathBuf, root: Option<String>) -> Result<()> {
+    let file: ProofFile = serde_json::from_slice(
+        &fs::read(&proof_file).with_context(|| format!("read {}", proof_file.display()))?,
+    )?;
+    let root = io::decode_hash(root.as_deref().unwrap_or(&file.merkle_root))?;
+    let pubkey = Pubkey::from_str(&file.pubkey).context("invalid pubkey in proof file")?;
+
+    for claim in &file.claims {
+        let recipient = Recipient {
+            pubkey,
+            amount: claim.amount,
+        };
+        if !verify_proof(
+            file.leaf_kind,
+            claim.index,
+            &recipient,
+            &claim.proof_bytes()?,
+            &root,
+        ) {
+            bail!(
+                "proof for leaf {} ({}) is invalid",
+                claim.index,
+                claim.amount
+            );
+        }
+        println!("leaf {} amount {}: ok", claim.index, claim.amount);
+    }
+    Ok(())
+}